@@ -0,0 +1,108 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+#![allow(non_snake_case)]
+
+extern crate proc_macro;
+
+use proc_macro::{TokenStream, TokenTree};
+use s_exp::{Exp, FormReader, ParseResult::*};
+
+fn compileError(msg: &str) -> TokenStream {
+    format!("compile_error!({:?})", msg).parse().unwrap()
+}
+
+fn lineColumn(src: &[u8], offset: usize) -> (usize, usize) {
+    let mut line = 1;
+    let mut col = 1;
+    for c in src.iter().take(offset) {
+        if *c == b'\n' {
+            line += 1;
+            col = 1;
+        } else {
+            col += 1;
+        }
+    }
+    (line, col)
+}
+
+// the one form of `src`, or the offset and message of the error
+fn singleForm(src: &[u8]) -> Result<Exp, (usize, String)> {
+    let mut forms = FormReader::new(src);
+    let e = match forms.next() {
+        Some(PROk(e)) => e,
+        Some(PRErr(err)) => return Err((err.offset(), String::from(err.message()))),
+        None => return Err((src.len(), String::from("no form"))),
+    };
+    let end = forms.offset();
+    match forms.next() {
+        None => Ok(e),
+        Some(PROk(_)) => Err((end, String::from("more than one form, wrap them in a list"))),
+        Some(PRErr(err)) => Err((err.offset(), String::from(err.message()))),
+    }
+}
+
+/// `sexp_file!("path")`: parse the file (relative to the crate root) at compile
+/// time and expand to the matching `s_exp::StaticExp` const expression, the file
+/// must hold exactly one form
+#[proc_macro]
+pub fn sexp_file(input: TokenStream) -> TokenStream {
+    let tokens : Vec<TokenTree> = input.into_iter().collect();
+    let lit = match tokens.as_slice() {
+        [TokenTree::Literal(l)] => l.to_string(),
+        _ => return compileError("sexp_file! expects a single string literal"),
+    };
+    if lit.len() < 2 || !lit.starts_with('"') || !lit.ends_with('"') {
+        return compileError("sexp_file! expects a single string literal")
+    }
+
+    let name = &lit[1..lit.len() - 1];
+    let root = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| String::from("."));
+    let path = std::path::Path::new(&root).join(name);
+    let src = match std::fs::read(&path) {
+        Ok(src) => src,
+        Err(e) => return compileError(&format!("{}: {}", path.display(), e)),
+    };
+
+    match singleForm(&src) {
+        Ok(e) => {
+            // include_bytes! makes cargo rebuild when the file changes
+            format!("{{ const _ : &[u8] = include_bytes!({:?}); {} }}", path.display().to_string(), e.toStaticRustTokens().toStr())
+                .parse()
+                .unwrap()
+        },
+        Err((offset, message)) => {
+            let (line, col) = lineColumn(&src, offset);
+            compileError(&format!("{}:{}:{}: {}", path.display(), line, col, message))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSingleForm() {
+        assert!(matches!(singleForm(b"; cfg\n(a (b 1)) ; end\n"), Ok(e) if e.toString() == "(a (b 1))"));
+        assert!(matches!(singleForm(b"(a)\n(b)"), Err((3, m)) if m.starts_with("more than one form")));
+        assert!(matches!(singleForm(b"(a) (b"), Err((6, _))));
+        assert!(matches!(singleForm(b"  ; nothing\n"), Err((_, m)) if m == "no form"));
+    }
+}
@@ -0,0 +1,12 @@
+#![allow(non_snake_case)]
+
+use s_exp::StaticExp;
+use s_exp_macros::sexp_file;
+
+const CONFIG : StaticExp = sexp_file!("tests/cfg.sexp");
+
+#[test]
+fn testSexpFile() {
+    let s = CONFIG.toExp().toString();
+    assert!(s == "(server (port 8080) (host \"localhost\"))");
+}
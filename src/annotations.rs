@@ -0,0 +1,140 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// annotations: user data attached to nodes by index path, kept on the right
+// nodes while the tree is edited through patches
+//
+use crate::*;
+
+pub struct Annotations<T> {
+    entries : std::vec::Vec<(Path, T)>,
+}
+
+impl<T> Annotations<T> {
+    pub fn new() -> Self { Self { entries: std::vec::Vec::new() } }
+
+    /// attaches `value` to the node at `path`, returns the value it replaced
+    pub fn insert(&mut self, path: Path, value: T) -> Option<T> {
+        match self.entries.iter().position(|(p, _)| *p == path) {
+            Some(i) => Some(core::mem::replace(&mut self.entries[i].1, value)),
+            None => { self.entries.push((path, value)); None }
+        }
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&T> {
+        self.entries.iter().find(|(p, _)| p == path).map(|(_, v)| v)
+    }
+
+    pub fn getMut(&mut self, path: &Path) -> Option<&mut T> {
+        self.entries.iter_mut().find(|(p, _)| p == path).map(|(_, v)| v)
+    }
+
+    pub fn remove(&mut self, path: &Path) -> Option<T> {
+        let i = self.entries.iter().position(|(p, _)| p == path)?;
+        Some(self.entries.remove(i).1)
+    }
+
+    /// annotation of the node with id `id` in `root`
+    pub fn getById(&self, root: &Exp, id: NodeId) -> Option<&T> {
+        self.entries.iter().find(|(p, _)| root.nodeId(p) == Some(id)).map(|(_, v)| v)
+    }
+
+    pub fn len(&self) -> usize { self.entries.len() }
+    pub fn is_empty(&self) -> bool { self.entries.is_empty() }
+    pub fn iter(&self) -> impl Iterator<Item = &(Path, T)> { self.entries.iter() }
+
+    /// moves the annotations along with `op`: siblings after an insert or a
+    /// remove shift, annotations on (or inside) a replaced or removed node are
+    /// dropped, ancestors of the edit keep theirs
+    pub fn applyOp(&mut self, op: &PatchOp) {
+        let at = match PatchOp::indices(op.path()) { Some(at) => at, None => return };
+        let entries = core::mem::take(&mut self.entries);
+        for (p, v) in entries.into_iter() {
+            let ancestor = match PatchOp::indices(&p) { Some(ix) => ix.len() < at.len() && at.starts_with(&ix), None => false };
+            if ancestor {
+                self.entries.push((p, v));
+            } else if let Some(p) = op.shift(&p, false) {
+                self.entries.push((p, v));
+            }
+        }
+    }
+
+    /// `applyOp` for every edit of the patch, in order
+    pub fn applyPatch(&mut self, patch: &Patch) {
+        for op in patch.ops().iter() { self.applyOp(op) }
+    }
+}
+
+impl<T> Default for Annotations<T> {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testAnnotations() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = parse("(module (def x 1) (def y 2) (def z (f 3)))");
+        let mut notes = Annotations::new();
+        notes.insert(Path::parse(""), "root");
+        notes.insert(Path::parse("1"), "x");
+        notes.insert(Path::parse("2.2"), "y value");
+        notes.insert(Path::parse("3.2.1"), "z arg");
+        assert!(notes.insert(Path::parse("1"), "x def") == Some("x"));
+
+        let b = parse("(module (def w 0) (def y 2) (def z (f 3)))");
+        let c = parse("(module (use io) (def w 0) (def z (f 3)))");
+        let mut patch = a.diff(&b);
+        for op in b.diff(&c).ops().iter() { patch.push(op.clone()) }
+        assert!(patch.apply(&a) == Some(c.clone()));
+        notes.applyPatch(&patch);
+
+        // (def x 1) was edited in place, (def y 2) removed, the rest shifted
+        assert!(notes.len() == 3);
+        assert!(notes.get(&Path::parse("")) == Some(&"root"));
+        assert!(notes.get(&Path::parse("2")) == Some(&"x def"));
+        assert!(notes.get(&Path::parse("3.2.1")) == Some(&"z arg"));
+        assert!(c.at(&Path::parse("3.2.1")) == Some(&Exp::Int(3)));
+        let id = c.nodeId(&Path::parse("3.2.1")).unwrap();
+        assert!(notes.getById(&c, id) == Some(&"z arg"));
+
+        // nodes inside vectors, maps and pairs
+        let mut opts = ReaderOptions::new();
+        opts.braces = true;
+        let d = match Exp::fromSExpWithOptions(b"(module #(1 (f 3)) {k (f 3)} ((f 3) . y))", &opts) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut notes = Annotations::new();
+        for (p, n) in [("1.1", "in vector"), ("2.1", "in map"), ("3.0", "in pair")].iter() {
+            notes.insert(Path::parse(p), *n);
+        }
+        for (p, id) in d.nodeIds().asArray().iter() {
+            assert!(notes.getById(&d, *id) == notes.get(p));
+        }
+        let id = d.nodeId(&Path::parse("2.1")).unwrap();
+        assert!(notes.getById(&d, id) == Some(&"in map"));
+    }
+}
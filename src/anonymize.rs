@@ -0,0 +1,257 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use alt_std::hash::murmurHash64A;
+use crate::*;
+
+#[derive(Clone)]
+pub struct AnonymizeOptions {
+    pub symbols     : bool,
+    // strings, spilled strings and byte vectors
+    pub strings     : bool,
+    pub keywords    : bool,
+    // symbols and keyword names left as they are (keywords of the dialect, ...)
+    pub keep        : Vec<String>,
+    // the same seed gives the same placeholders across files
+    pub seed        : u64,
+}
+
+impl AnonymizeOptions {
+    pub fn new() -> Self {
+        Self {
+            symbols     : true,
+            strings     : true,
+            keywords    : true,
+            keep        : Vec::new(),
+            seed        : 0,
+        }
+    }
+
+    pub fn keep(&mut self, s: &str) {
+        self.keep.pushBack(String::from(s));
+    }
+}
+
+impl Default for AnonymizeOptions {
+    fn default() -> Self { Self::new() }
+}
+
+// the hashes handed out so far by kind, with what they stand for
+type Placeholders = std::collections::HashMap<(u8, u64), std::vec::Vec<u8>>;
+
+impl Exp {
+    // 64 bit hash of `s` of the given kind, bumping the seed past the hashes
+    // already standing for something else so that two inputs never merge
+    fn placeholderHash(kind: u8, s: &[u8], seed: u64, taken: &mut Placeholders) -> u64 {
+        let mut salt = 0u64;
+        loop {
+            let h = murmurHash64A(s, seed.wrapping_add(salt));
+            match taken.get(&(kind, h)) {
+                Some(o) if o.as_slice() != s => salt += 1,
+                Some(_) => return h,
+                None => { taken.insert((kind, h), s.to_vec()); return h },
+            }
+        }
+    }
+
+    fn placeholder(prefix: &str, s: &[u8], seed: u64, taken: &mut Placeholders) -> String {
+        let h = Self::placeholderHash(prefix.as_bytes()[0], s, seed, taken);
+        let mut p = String::from(prefix);
+        p.append(&alt_std::format!("{:016x}", h));
+        p
+    }
+
+    /// copy with symbols, strings and keywords replaced by hash derived placeholders
+    /// (`s-<16 hex digits>`, `"str-..."`, `:k-...`) and byte vectors by the 8 bytes
+    /// of their hash. Distinct inputs always get distinct placeholders, the
+    /// structure and every other atom are kept
+    pub fn anonymize(&self, opts: &AnonymizeOptions) -> Exp {
+        self.anonymizeWith(opts, &mut Placeholders::new())
+    }
+
+    fn anonymizeWith(&self, opts: &AnonymizeOptions, taken: &mut Placeholders) -> Exp {
+        match self {
+            Self::Symbol(s) if opts.symbols && !opts.keep.asArray().iter().any(|k| k == s) => Self::Symbol(Self::placeholder("s-", s.asArray(), opts.seed, taken)),
+            Self::Keyword(k) if opts.keywords && !opts.keep.asArray().iter().any(|e| e == k) => Self::Keyword(Self::placeholder("k-", k.asArray(), opts.seed, taken)),
+            Self::String(s) if opts.strings => Self::String(Self::placeholder("str-", s.asArray(), opts.seed, taken)),
+            Self::Spilled(s) if opts.strings => Self::String(Self::placeholder("str-", &Self::spilledBytes(s), opts.seed, taken)),
+            Self::Bytes(b) if opts.strings => {
+                let mut v = Vec::new();
+                for c in Self::placeholderHash(b'b', b.asArray(), opts.seed, taken).to_be_bytes().iter() { v.pushBack(*c) }
+                Self::Bytes(v)
+            },
+            Self::List(l) => {
+                let mut v = Vec::new();
+                for e in l.asArray().iter() {
+                    v.pushBack(e.anonymizeWith(opts, taken));
+                }
+                Self::List(v)
+            },
+            Self::Map(m) => {
+                let mut v = Vec::new();
+                for (k, e) in m.asArray().iter() {
+                    v.pushBack((k.anonymizeWith(opts, taken), e.anonymizeWith(opts, taken)));
+                }
+                Self::Map(v)
+            },
+            Self::Vector(l) => {
+                let mut v = Vec::new();
+                for e in l.asArray().iter() {
+                    v.pushBack(e.anonymizeWith(opts, taken));
+                }
+                Self::Vector(v)
+            },
+            Self::Pair(car, cdr) => Self::Pair(std::boxed::Box::new(car.anonymizeWith(opts, taken)), std::boxed::Box::new(cdr.anonymizeWith(opts, taken))),
+            e => e.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testAnonymize() {
+        let sexp = String::from("(define (secret-fn x) (call \"acme-api\" x 42 \"acme-api\"))");
+        let e = match Exp::fromSExp(sexp.asArray()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+
+        let mut opts = AnonymizeOptions::new();
+        opts.keep("define");
+        let a = e.anonymize(&opts);
+        let l = match &a { Exp::List(l) => l, _ => panic!("expected a list") };
+        assert!(l[0] == Exp::Symbol(String::from("define")));
+        match (&l[1], &l[2]) {
+            (Exp::List(sig), Exp::List(body)) => {
+                // same input, same placeholder
+                assert!(sig[1] == body[2]);
+                assert!(body[1] == body[4]);
+                assert!(body[3] == Exp::Int(42));
+                assert!(sig[0] != Exp::Symbol(String::from("secret-fn")));
+            },
+            _ => panic!("structure changed")
+        }
+        assert!(a == e.anonymize(&opts));
+        assert!(!a.toString().asArray().windows(4).any(|w| w == b"acme"));
+
+        opts.seed = 1;
+        assert!(a != e.anonymize(&opts));
+    }
+
+    #[test]
+    fn testAnonymizeKeywords() {
+        let e = match Exp::fromSExp(b"{:password \"x\" :user \"y\" :id 1}") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut opts = AnonymizeOptions::new();
+        opts.keep("id");
+        let a = e.anonymize(&opts);
+        assert!(!a.toString().asArray().windows(4).any(|w| w == b"pass" || w == b"user"));
+        match &a {
+            Exp::Map(m) => {
+                assert!(matches!(&m[0].0, Exp::Keyword(k) if k.toStr().starts_with("k-")));
+                assert!(m[2].0 == Exp::Keyword(String::from("id")));
+            },
+            _ => panic!("structure changed")
+        }
+        assert!(a.lookup(&Exp::Keyword(Exp::placeholder("k-", b"user", 0, &mut Placeholders::new()))).is_some());
+    }
+
+    #[test]
+    fn testAnonymizeMap() {
+        let e = match Exp::fromSExp(b"{host \"acme.internal\" port 80 auth {user \"root\"}}") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = e.anonymize(&AnonymizeOptions::new());
+        let s = a.toString();
+        assert!(!s.asArray().windows(4).any(|w| w == b"acme" || w == b"root" || w == b"host"));
+        match &a {
+            Exp::Map(m) => {
+                assert!(m.len() == 3 && m[1].1 == Exp::Int(80));
+                assert!(matches!(&m[2].1, Exp::Map(inner) if inner.len() == 1));
+            },
+            _ => panic!("structure changed")
+        }
+    }
+
+    #[test]
+    fn testAnonymizeVector() {
+        let e = match Exp::fromSExp(b"#(acme \"acme\" 1 #(acme))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = e.anonymize(&AnonymizeOptions::new());
+        assert!(!a.toString().asArray().windows(4).any(|w| w == b"acme"));
+        match &a {
+            Exp::Vector(v) => {
+                assert!(v.len() == 4 && v[2] == Exp::Int(1));
+                assert!(matches!(&v[3], Exp::Vector(inner) if inner[0] == v[0]));
+            },
+            _ => panic!("structure changed")
+        }
+    }
+
+    #[test]
+    fn testAnonymizePair() {
+        let e = match Exp::fromSExp(b"((acme . \"acme\") (secret . 7))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = e.anonymize(&AnonymizeOptions::new());
+        let s = a.toString();
+        assert!(!s.asArray().windows(4).any(|w| w == b"acme" || w == b"secr"));
+        match &a {
+            Exp::List(l) => {
+                assert!(matches!(&l[0], Exp::Pair(car, cdr) if matches!(**car, Exp::Symbol(_)) && matches!(**cdr, Exp::String(_))));
+                assert!(matches!(&l[1], Exp::Pair(_, cdr) if **cdr == Exp::Int(7)));
+            },
+            _ => panic!("structure changed")
+        }
+    }
+
+    #[test]
+    fn testAnonymizeCollisions() {
+        // a hash already standing for another input moves on to the next seed
+        let mut taken = Placeholders::new();
+        let h = murmurHash64A(b"a", 0);
+        taken.insert((b's', h), b"b".to_vec());
+        let p = Exp::placeholder("s-", b"a", 0, &mut taken);
+        assert!(p == alt_std::format!("s-{:016x}", murmurHash64A(b"a", 1)).toStr());
+        assert!(Exp::placeholder("s-", b"a", 0, &mut taken) == p && Exp::placeholder("s-", b"c", 0, &mut taken) == alt_std::format!("s-{:016x}", murmurHash64A(b"c", 0)).toStr());
+
+        let mut opts = ReaderOptions::new();
+        opts.maxAtomLen = Some(4);
+        opts.atomOverflow = AtomOverflow::Spill;
+        let e = match Exp::fromSExpWithOptions(b"(\"secret-token\" \"secret-token\" #u8(1 2 3) #u8(1 2 3) #u8(4))", &opts) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = e.anonymize(&AnonymizeOptions::new());
+        let l = match &a { Exp::List(l) => l, _ => panic!("expected a list") };
+        assert!(matches!(&l[0], Exp::String(s) if s.toStr().starts_with("str-")) && l[0] == l[1]);
+        assert!(matches!(&l[2], Exp::Bytes(b) if b.len() == 8) && l[2] == l[3] && l[2] != l[4]);
+        assert!(!a.toString().asArray().windows(6).any(|w| w == b"secret"));
+    }
+}
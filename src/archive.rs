@@ -0,0 +1,377 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// archives (`archive` feature): a flat binary layout read in place, an mmap of a
+// file written by `Exp::toArchive` is walked without deserializing it. Nodes are
+// written children first, integers are little endian u64/i64:
+//
+//  "SXA1" root                 header, `root` is the offset of the root node
+//  tag payload                 one node per expression
+//    atoms                     the value, u64 length + bytes for texts and bytes
+//    List, Vector, Map         u64 count + the offsets of the elements (key, value for maps)
+//    Pair                      offsets of car and cdr
+//
+// Reading checks every access and returns None on a malformed archive, children
+// always come before their parent so a corrupted one cannot loop. `toExp` also
+// wants every subtree after the one of the previous sibling, as they are
+// written, so that a node shared by several parents is not decoded again for
+// each of them
+//
+use alt_std::*;
+use core::convert::{TryFrom, TryInto};
+use crate::*;
+
+const MAGIC : &[u8; 4] = b"SXA1";
+
+const BOOL : u8 = 0;
+const CHAR : u8 = 1;
+const INT : u8 = 2;
+const UINT : u8 = 3;
+const FLOAT : u8 = 4;
+const RATIO : u8 = 5;
+#[cfg(feature = "bigint")]
+const BIGINT : u8 = 6;
+const STRING : u8 = 7;
+const SYMBOL : u8 = 8;
+const KEYWORD : u8 = 9;
+const BYTES : u8 = 10;
+const LIST : u8 = 11;
+const VECTOR : u8 = 12;
+const PAIR : u8 = 13;
+const MAP : u8 = 14;
+
+fn put(out: &mut Vec<u8>, bytes: &[u8]) {
+    for b in bytes.iter() { out.pushBack(*b) }
+}
+
+fn putU64(out: &mut Vec<u8>, v: u64) { put(out, &v.to_le_bytes()) }
+
+impl Exp {
+    // writes the children then the node, returns where the node starts
+    fn archiveInto(&self, out: &mut Vec<u8>) -> u64 {
+        let node = |out: &mut Vec<u8>, tag: u8| {
+            let at = out.len() as u64;
+            out.pushBack(tag);
+            at
+        };
+        let text = |out: &mut Vec<u8>, tag: u8, s: &[u8]| {
+            let at = node(out, tag);
+            putU64(out, s.len() as u64);
+            put(out, s);
+            at
+        };
+        let offsets = |out: &mut Vec<u8>, tag: u8, children: &[u64]| {
+            let at = node(out, tag);
+            putU64(out, (if tag == MAP { children.len() / 2 } else { children.len() }) as u64);
+            for c in children.iter() { putU64(out, *c) }
+            at
+        };
+        match self {
+            Self::Bool(b) => { let at = node(out, BOOL); out.pushBack(*b as u8); at },
+            Self::Char(c) => { let at = node(out, CHAR); putU64(out, *c as u64); at },
+            Self::Int(i) => { let at = node(out, INT); put(out, &i.to_le_bytes()); at },
+            Self::UInt(u) => { let at = node(out, UINT); putU64(out, *u); at },
+            Self::Float(f) => { let at = node(out, FLOAT); putU64(out, f.to_bits()); at },
+            Self::Ratio(n, d) => {
+                let at = node(out, RATIO);
+                put(out, &n.to_le_bytes());
+                put(out, &d.to_le_bytes());
+                at
+            },
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => {
+                let at = node(out, BIGINT);
+                out.pushBack(b.isNegative() as u8);
+                putU64(out, b.limbs().len() as u64);
+                for l in b.limbs().iter() { put(out, &l.to_le_bytes()) }
+                at
+            },
+            Self::String(s) => text(out, STRING, s.asArray()),
+            Self::Spilled(s) => text(out, STRING, &Self::spilledBytes(s)),
+            Self::Symbol(s) => text(out, SYMBOL, s.asArray()),
+            Self::Keyword(k) => text(out, KEYWORD, k.asArray()),
+            Self::Bytes(b) => text(out, BYTES, b.asArray()),
+            Self::List(l) | Self::Vector(l) => {
+                let children : std::vec::Vec<u64> = l.asArray().iter().map(|e| e.archiveInto(out)).collect();
+                offsets(out, if let Self::List(_) = self { LIST } else { VECTOR }, &children)
+            },
+            Self::Pair(a, d) => {
+                let children = [a.archiveInto(out), d.archiveInto(out)];
+                let at = node(out, PAIR);
+                for c in children.iter() { putU64(out, *c) }
+                at
+            },
+            Self::Map(m) => {
+                let mut children = std::vec::Vec::new();
+                for (k, v) in m.asArray().iter() {
+                    children.push(k.archiveInto(out));
+                    children.push(v.archiveInto(out));
+                }
+                offsets(out, MAP, &children)
+            },
+        }
+    }
+
+    /// the archive of this expression, see `ArchivedExp::open`
+    pub fn toArchive(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        put(&mut out, MAGIC);
+        putU64(&mut out, 0);
+        let root = self.archiveInto(&mut out).to_le_bytes();
+        for i in 0..8 { out[4 + i] = root[i] }
+        out
+    }
+}
+
+/// a node of an archive, borrowed from its buffer
+#[derive(Clone, Copy)]
+pub struct ArchivedExp<'a> {
+    buf     : &'a [u8],
+    at      : usize,
+}
+
+impl<'a> ArchivedExp<'a> {
+    /// the root of the archive in `buf`, None when it does not start with one
+    pub fn open(buf: &'a [u8]) -> Option<ArchivedExp<'a>> {
+        if buf.get(..4)? != MAGIC { return None }
+        let root = Self { buf, at: 4 }.u64At(4)?;
+        let at = usize::try_from(root).ok().filter(|at| *at >= 12 && *at < buf.len())?;
+        Some(Self { buf, at })
+    }
+
+    fn tag(&self) -> Option<u8> { self.buf.get(self.at).copied() }
+
+    fn slice(&self, at: usize, len: usize) -> Option<&'a [u8]> {
+        self.buf.get(at..at.checked_add(len)?)
+    }
+
+    fn u64At(&self, at: usize) -> Option<u64> {
+        self.slice(at, 8)?.try_into().ok().map(u64::from_le_bytes)
+    }
+
+    // the payload starts after the tag
+    fn payload(&self, i: usize) -> Option<u64> { self.u64At(self.at.checked_add(1 + 8 * i)?) }
+
+    fn text(&self) -> Option<&'a [u8]> {
+        let len = usize::try_from(self.payload(0)?).ok()?;
+        self.slice(self.at + 9, len)
+    }
+
+    // the i-th offset after the count, children come before their parent
+    fn child(&self, i: usize) -> Option<ArchivedExp<'a>> {
+        let at = usize::try_from(self.payload(i)?).ok().filter(|at| *at >= 12 && *at < self.at)?;
+        Some(Self { buf: self.buf, at })
+    }
+
+    /// the value of an atom, None for compound nodes and big integers
+    pub fn atom(&self) -> Option<AtomRef<'a>> {
+        let str = |s: &'a [u8]| core::str::from_utf8(s).ok();
+        match self.tag()? {
+            BOOL => Some(AtomRef::Bool(*self.buf.get(self.at + 1)? != 0)),
+            CHAR => Some(AtomRef::Char(char::from_u32(u32::try_from(self.payload(0)?).ok()?)?)),
+            INT => Some(AtomRef::Int(self.payload(0)? as i64)),
+            UINT => Some(AtomRef::UInt(self.payload(0)?)),
+            FLOAT => Some(AtomRef::Float(f64::from_bits(self.payload(0)?))),
+            RATIO => {
+                // written in lowest terms with a denominator above 1
+                let (n, d) = (self.payload(0)? as i64, self.payload(1)? as i64);
+                match Exp::reduced(n as i128, d as i128)? {
+                    Exp::Ratio(rn, rd) if rn == n && rd == d => Some(AtomRef::Ratio(n, d)),
+                    _ => None
+                }
+            },
+            STRING => Some(AtomRef::String(str(self.text()?)?)),
+            SYMBOL => Some(AtomRef::Symbol(str(self.text()?)?)),
+            KEYWORD => Some(AtomRef::Keyword(str(self.text()?)?)),
+            BYTES => Some(AtomRef::Bytes(self.text()?)),
+            _ => None
+        }
+    }
+
+    pub fn isList(&self) -> bool { self.tag() == Some(LIST) }
+    pub fn isVector(&self) -> bool { self.tag() == Some(VECTOR) }
+    pub fn isMap(&self) -> bool { self.tag() == Some(MAP) }
+
+    /// elements of a list or a vector, entries of a map, 0 otherwise
+    pub fn len(&self) -> usize {
+        match self.tag() {
+            Some(LIST) | Some(VECTOR) | Some(MAP) => self.count().unwrap_or(0),
+            _ => 0
+        }
+    }
+
+    fn count(&self) -> Option<usize> { usize::try_from(self.payload(0)?).ok() }
+
+    pub fn is_empty(&self) -> bool { self.len() == 0 }
+
+    /// element `i` of a list or a vector
+    pub fn get(&self, i: usize) -> Option<ArchivedExp<'a>> {
+        match self.tag()? {
+            LIST | VECTOR if i < self.len() => self.child(i + 1),
+            _ => None
+        }
+    }
+
+    /// key and value of entry `i` of a map
+    pub fn entry(&self, i: usize) -> Option<(ArchivedExp<'a>, ArchivedExp<'a>)> {
+        match self.tag()? {
+            MAP if i < self.len() => Some((self.child(2 * i + 1)?, self.child(2 * i + 2)?)),
+            _ => None
+        }
+    }
+
+    /// the value of the map entry whose key is a string, symbol or keyword spelled `key`
+    pub fn lookup(&self, key: &str) -> Option<ArchivedExp<'a>> {
+        (0..self.len()).filter_map(|i| self.entry(i)).find(|(k, _)| k.atom().and_then(|a| a.text()) == Some(key)).map(|(_, v)| v)
+    }
+
+    /// car and cdr of a pair
+    pub fn pair(&self) -> Option<(ArchivedExp<'a>, ArchivedExp<'a>)> {
+        match self.tag()? {
+            PAIR => Some((self.child(0)?, self.child(1)?)),
+            _ => None
+        }
+    }
+
+    /// the expression this node was written from
+    pub fn toExp(&self) -> Option<Exp> { self.decode(12) }
+
+    // the children `first..first + n` decoded in order, the subtree of each one
+    // is past `lo` then past the previous child
+    fn children(&self, first: usize, n: usize, lo: usize) -> Option<std::vec::Vec<Exp>> {
+        let mut lo = lo;
+        let mut v = std::vec::Vec::new();
+        for i in first..first.checked_add(n)? {
+            let c = self.child(i)?;
+            v.push(c.decode(lo)?);
+            lo = c.at + 1;
+        }
+        Some(v)
+    }
+
+    // the subtree at this node, which has to start at `lo` or after
+    fn decode(&self, lo: usize) -> Option<Exp> {
+        if self.at < lo { return None }
+        let items = |n: usize| -> Option<Vec<Exp>> {
+            let mut v = Vec::new();
+            for e in self.children(1, n, lo)?.into_iter() { v.pushBack(e) }
+            Some(v)
+        };
+        Some(match self.tag()? {
+            LIST => Exp::List(items(self.count()?)?),
+            VECTOR => Exp::Vector(items(self.count()?)?),
+            MAP => {
+                let mut m = Vec::new();
+                let mut kv = self.children(1, self.count()?.checked_mul(2)?, lo)?.into_iter();
+                while let (Some(k), Some(v)) = (kv.next(), kv.next()) { m.pushBack((k, v)) }
+                Exp::Map(m)
+            },
+            PAIR => {
+                let mut ad = self.children(0, 2, lo)?.into_iter();
+                let (a, d) = (ad.next()?, ad.next()?);
+                Exp::Pair(std::boxed::Box::new(a), std::boxed::Box::new(d))
+            },
+            #[cfg(feature = "bigint")]
+            BIGINT => {
+                let n = usize::try_from(self.u64At(self.at + 2)?).ok()?;
+                let bytes = self.slice(self.at + 10, n.checked_mul(4)?)?;
+                let limbs : std::vec::Vec<u32> = bytes.chunks(4).filter_map(|c| c.try_into().ok().map(u32::from_le_bytes)).collect();
+                Exp::BigInt(BigInt::fromLimbs(*self.buf.get(self.at + 1)? != 0, &limbs))
+            },
+            _ => match self.atom()? {
+                AtomRef::Bool(b) => Exp::Bool(b),
+                AtomRef::Char(c) => Exp::Char(c),
+                AtomRef::Int(i) => Exp::Int(i),
+                AtomRef::UInt(u) => Exp::UInt(u),
+                AtomRef::Float(f) => Exp::Float(f),
+                AtomRef::Ratio(n, d) => Exp::Ratio(n, d),
+                AtomRef::String(s) => Exp::String(String::from(s)),
+                AtomRef::Symbol(s) => Exp::Symbol(String::from(s)),
+                AtomRef::Keyword(k) => Exp::Keyword(String::from(k)),
+                AtomRef::Spilled(s) => Exp::Spilled(s.clone()),
+                AtomRef::Bytes(b) => {
+                    let mut v = Vec::new();
+                    for c in b.iter() { v.pushBack(*c) }
+                    Exp::Bytes(v)
+                },
+                #[cfg(feature = "bigint")]
+                AtomRef::BigInt(b) => Exp::BigInt(b.clone()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testArchive() {
+        let e = match Exp::fromSExp(b"(data {:name \"ada\" :id 18446744073709551615} #(1 2/3 -1.5 #\\x) (a . b) #u8(0 255) #t)") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let buf = e.toArchive();
+        let root = ArchivedExp::open(buf.asArray()).unwrap();
+        assert!(root.toExp() == Some(e.clone()));
+
+        assert!(root.isList() && root.len() == 6 && root.get(6).is_none());
+        assert!(matches!(root.get(0).and_then(|d| d.atom()), Some(AtomRef::Symbol("data"))));
+        let m = root.get(1).unwrap();
+        assert!(m.isMap() && matches!(m.lookup("name").and_then(|v| v.atom()), Some(AtomRef::String("ada"))));
+        assert!(matches!(m.lookup("id").and_then(|v| v.atom()), Some(AtomRef::UInt(u64::MAX))));
+        assert!(root.get(2).unwrap().isVector() && matches!(root.get(2).and_then(|v| v.get(1)).and_then(|r| r.atom()), Some(AtomRef::Ratio(2, 3))));
+        assert!(root.get(3).and_then(|p| p.pair()).and_then(|(_, d)| d.atom()).and_then(|a| a.text()) == Some("b"));
+
+        // a damaged archive reads as None, never panics
+        for cut in 0..buf.len() {
+            if let Some(r) = ArchivedExp::open(&buf.asArray()[..cut]) { assert!(r.toExp().is_none()) }
+        }
+        let mut bad = std::vec::Vec::from(buf.asArray());
+        bad[11] = 1;
+        assert!(ArchivedExp::open(&bad).is_none() && ArchivedExp::open(b"SXA0").is_none());
+
+        // a ratio not in lowest terms or with a denominator of 1
+        let mut ratio = std::vec::Vec::from(&b"SXA1\x0c\0\0\0\0\0\0\0\x05"[..]);
+        for (n, d, ok) in [(2i64, 3i64, true), (2, 4, false), (2, 1, false), (2, -3, false), (2, 0, false)].iter() {
+            ratio.truncate(13);
+            ratio.extend_from_slice(&n.to_le_bytes());
+            ratio.extend_from_slice(&d.to_le_bytes());
+            let r = ArchivedExp::open(&ratio).unwrap();
+            assert!(r.toExp().is_some() == *ok && r.atom().is_some() == *ok);
+        }
+
+        // a node shared by every level would decode 2^40 times
+        let mut shared = std::vec::Vec::from(&b"SXA1\0\0\0\0\0\0\0\0"[..]);
+        let mut prev = shared.len();
+        shared.push(INT);
+        shared.extend_from_slice(&1i64.to_le_bytes());
+        for _ in 0..40 {
+            let at = shared.len();
+            shared.push(PAIR);
+            shared.extend_from_slice(&(prev as u64).to_le_bytes());
+            shared.extend_from_slice(&(prev as u64).to_le_bytes());
+            prev = at;
+        }
+        shared[4..12].copy_from_slice(&(prev as u64).to_le_bytes());
+        let r = ArchivedExp::open(&shared).unwrap();
+        assert!(r.pair().is_some() && r.toExp().is_none());
+    }
+}
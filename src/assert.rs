@@ -0,0 +1,75 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// test assertions: `assert_sexp_eq!(actual, "(expected form)")` reads the expected
+// text and compares structurally, a failure lists the differing paths rather than
+// two long lines to eyeball
+//
+use alt_std::*;
+use crate::*;
+
+/// what `assert_sexp_eq!` reports, None when `actual` reads the same as `expected`,
+/// which must hold exactly one form
+#[doc(hidden)]
+pub fn sexpMismatch(actual: &Exp, expected: &str) -> Option<String> {
+    let exp = match Exp::fromSExpDocument(expected.as_bytes(), &ReaderOptions::new()) {
+        PROk(e) => e,
+        PRErr(err) => return Some(alt_std::format!("expected form does not read: {} at {}", err.message.toStr(), err.offset)),
+    };
+    if actual.approxEq(&exp, 0.0) { return None }
+    let mut out = String::from("sexp mismatch\n");
+    out.append(&alt_std::format!("  actual:   {}\n  expected: {}\n", actual.toString().toStr(), exp.toString().toStr()));
+    for line in actual.diff(&exp).describe(actual).toStr().lines() {
+        out.append(&alt_std::format!("  {}\n", line));
+    }
+    Some(out)
+}
+
+/// `assert_sexp_eq!(exp, "(a b)")` fails when `exp` does not read the same as the
+/// text, whatever its layout, with the paths that differ
+#[macro_export]
+macro_rules! assert_sexp_eq {
+    ($actual:expr, $expected:expr) => {
+        if let Some(m) = $crate::sexpMismatch(&$actual, $expected) { panic!("{}", m.toStr()) }
+    };
+    ($actual:expr, $expected:expr, $($arg:tt)+) => {
+        if let Some(m) = $crate::sexpMismatch(&$actual, $expected) { panic!("{}: {}", format_args!($($arg)+), m.toStr()) }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testAssertSexpEq() {
+        let e = match Exp::fromSExp(b"(config (port 80) (hosts a b))") { PROk(e) => e, PRErr(_) => panic!("parse failed") };
+        assert_sexp_eq!(e, "(config\n  (port #x50)  ; http\n  (hosts a b))");
+        assert_sexp_eq!(&e, "(config (port 80) (hosts a b))", "with a message {}", 1);
+        let m = sexpMismatch(&e, "(config (port 8080) (hosts a))").map(|m| std::string::String::from(m.toStr()));
+        assert!(m.as_deref() == Some("sexp mismatch\n  actual:   (config (port 80) (hosts a b))\n  expected: (config (port 8080) (hosts a))\n  1.1 80 -> 8080\n  2.2 - b\n"));
+        assert!(sexpMismatch(&e, "(config").is_some_and(|m| m.toStr().starts_with("expected form does not read")));
+        // a second form is not silently ignored
+        let m = sexpMismatch(&e, "(config (port 80) (hosts a b)) (extra)").map(|m| std::string::String::from(m.toStr()));
+        assert!(m.as_deref() == Some("expected form does not read: unexpected trailing data at 31"));
+        assert!(sexpMismatch(&e, "(config (port 80) (hosts a b)) ; done\n").is_none());
+        assert!(std::panic::catch_unwind(|| assert_sexp_eq!(Exp::Int(1), "2")).is_err());
+    }
+}
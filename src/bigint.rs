@@ -0,0 +1,213 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// big integers (`bigint` feature): with `IntOverflow::BigInt` the integer literals
+// out of the i64 and u64 ranges, decimal or `#x`/`#o`/`#b`, read exactly into `Exp::BigInt`
+// so long ids and hashes keep every digit. `Number` arithmetic on them is exact within the i128
+// range and in Float past it, big integers print back in decimal and compare by value with the
+// other numbers
+//
+use alt_std::*;
+use core::cmp::Ordering;
+use core::convert::TryFrom;
+use crate::*;
+
+const BASE : u64 = 1_000_000_000;
+
+/// sign and magnitude in base 10^9 limbs, least significant first
+#[derive(Clone)]
+pub struct BigInt {
+    negative    : bool,
+    limbs       : Vec<u32>,
+}
+
+impl BigInt {
+    /// `limbs` are base 10^9 digits, least significant first
+    pub fn fromLimbs(negative: bool, limbs: &[u32]) -> BigInt {
+        let mut v = Vec::new();
+        for l in limbs.iter() { v.pushBack(*l) }
+        Self::normalized(negative, v)
+    }
+
+    /// value of `digits` (no sign or prefix) in `radix`, None when empty or on an invalid digit
+    pub fn fromDigits(negative: bool, digits: &[u8], radix: u32) -> Option<BigInt> {
+        if digits.is_empty() { return None }
+        let mut limbs : Vec<u32> = Vec::new();
+        for c in digits.iter() {
+            let mut carry = (*c as char).to_digit(radix)? as u64;
+            for i in 0..limbs.len() {
+                let v = limbs[i] as u64 * radix as u64 + carry;
+                limbs[i] = (v % BASE) as u32;
+                carry = v / BASE;
+            }
+            if carry > 0 { limbs.pushBack(carry as u32) }
+        }
+        Some(Self::normalized(negative, limbs))
+    }
+
+    /// a decimal integer with an optional sign
+    pub fn parse(s: &str) -> Option<BigInt> {
+        match s.as_bytes().first() {
+            Some(b'-') => Self::fromDigits(true, &s.as_bytes()[1..], 10),
+            Some(b'+') => Self::fromDigits(false, &s.as_bytes()[1..], 10),
+            _ => Self::fromDigits(false, s.as_bytes(), 10),
+        }
+    }
+
+    fn normalized(negative: bool, mut limbs: Vec<u32>) -> BigInt {
+        while limbs.last() == Some(&0) { limbs.pop(); }
+        BigInt { negative: negative && limbs.len() > 0, limbs }
+    }
+
+    pub fn isNegative(&self) -> bool { self.negative }
+
+    pub fn limbs(&self) -> &[u32] { self.limbs.asArray() }
+
+    pub(crate) fn toI128(&self) -> Option<i128> {
+        let mut m : i128 = 0;
+        for l in self.limbs.asArray().iter().rev() {
+            m = m.checked_mul(BASE as i128)?.checked_add(*l as i128)?;
+        }
+        Some(if self.negative { -m } else { m })
+    }
+
+    /// the value when it fits an i64
+    pub fn toI64(&self) -> Option<i64> {
+        self.toI128().and_then(|i| i64::try_from(i).ok())
+    }
+
+    /// the value when it fits a u64
+    pub fn toU64(&self) -> Option<u64> {
+        self.toI128().and_then(|i| u64::try_from(i).ok())
+    }
+
+    /// nearest f64, infinite beyond its range
+    pub fn toF64(&self) -> f64 {
+        let m = self.limbs.asArray().iter().rev().fold(0.0, |f, l| f * BASE as f64 + *l as f64);
+        if self.negative { -m } else { m }
+    }
+
+    fn cmpMagnitude(&self, other: &BigInt) -> Ordering {
+        self.limbs.len().cmp(&other.limbs.len())
+            .then_with(|| self.limbs.asArray().iter().rev().cmp(other.limbs.asArray().iter().rev()))
+    }
+}
+
+impl PartialEq for BigInt {
+    fn eq(&self, other: &BigInt) -> bool {
+        self.negative == other.negative && self.limbs.asArray() == other.limbs.asArray()
+    }
+}
+
+impl Eq for BigInt {}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &BigInt) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &BigInt) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => self.cmpMagnitude(other),
+            (true, true) => other.cmpMagnitude(self),
+        }
+    }
+}
+
+impl core::fmt::Display for BigInt {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let l = self.limbs.asArray();
+        match l.last() {
+            None => return f.write_str("0"),
+            Some(top) => write!(f, "{}{}", if self.negative { "-" } else { "" }, top)?,
+        }
+        for limb in l[..l.len() - 1].iter().rev() {
+            write!(f, "{:09}", limb)?;
+        }
+        Ok(())
+    }
+}
+
+impl Exp {
+    /// an Int or a UInt when `b` fits one, a BigInt otherwise
+    pub fn bigInt(b: BigInt) -> Exp {
+        match (b.toI64(), b.toU64()) {
+            (Some(i), _) => Exp::Int(i),
+            (None, Some(u)) => Exp::UInt(u),
+            _ => Exp::BigInt(b),
+        }
+    }
+
+    // big integers never fit an i64 or a u64, they are either below or above every Int, UInt and Ratio
+    pub(crate) fn cmpBigSmall(b: &BigInt) -> Ordering {
+        if b.isNegative() { Ordering::Less } else { Ordering::Greater }
+    }
+
+    // exact: the integer part of a finite float prints exactly in decimal
+    pub(crate) fn cmpBigFloat(b: &BigInt, f: f64) -> Ordering {
+        if !f.is_finite() { return Self::cmpFloat(0.0, f) }
+        let t = f.trunc();
+        match BigInt::parse(&std::format!("{:.0}", t)) {
+            Some(i) => b.cmp(&i).then_with(|| Self::cmpFloat(t, f)),
+            None => Self::cmpFloat(b.toF64(), f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testBigInt() {
+        let mut opts = ReaderOptions::new();
+        opts.intOverflow = IntOverflow::BigInt;
+        match Exp::fromSExpWithOptions(b"(123456789012345678901234567890 -9223372036854775809 #xffffffffffffffffffff 9223372036854775807 -1000000000000000000000)", &opts) {
+            PROk(e) => {
+                let l = match &e { Exp::List(l) => l, _ => panic!("expected a list") };
+                assert!(l[0] == Exp::BigInt(BigInt::fromLimbs(false, &[234567890, 345678901, 456789012, 123])));
+                assert!(l[3] == Exp::Int(i64::MAX));
+                assert!(e.toString() == "(123456789012345678901234567890 -9223372036854775809 1208925819614629174706175 9223372036854775807 -1000000000000000000000)");
+                assert!(Exp::fromSExpWithOptions(e.toString().asArray(), &opts) == PROk(e.clone()));
+
+                let mut v = [l[0].clone(), l[1].clone(), Exp::Int(-3), Exp::Float(1e30), l[4].clone(), Exp::Ratio(1, 2)];
+                v.sort_by(|a, b| a.totalCmp(b));
+                assert!(v[0] == l[4] && v[1] == l[1] && v[2] == Exp::Int(-3) && v[3] == Exp::Ratio(1, 2) && v[4] == l[0] && v[5] == Exp::Float(1e30));
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::bigInt(BigInt::parse("-42").unwrap()) == Exp::Int(-42));
+        assert!(BigInt::parse("12a").is_none() && BigInt::parse("-").is_none());
+    }
+
+    #[test]
+    fn testBigFloatExact() {
+        let two64 = Exp::BigInt(BigInt::parse("18446744073709551616").unwrap());
+        let above = Exp::BigInt(BigInt::parse("18446744073709551617").unwrap());
+        let f = Exp::Float(18446744073709551616.0);
+        // 2^64 + 1 rounds to 2^64 in f64
+        assert!(two64.cmpNumber(&f) == Some(Ordering::Equal) && two64.totalCmp(&f) == Ordering::Less);
+        assert!(above.totalCmp(&f) == Ordering::Greater && f.totalCmp(&above) == Ordering::Less);
+        assert!(Exp::BigInt(BigInt::parse("-100000000000000000000").unwrap()).totalCmp(&Exp::Float(-1e20 - 16384.0)) == Ordering::Greater);
+        assert!(two64.totalCmp(&Exp::Float(f64::INFINITY)) == Ordering::Less && two64.totalCmp(&Exp::Float(f64::NAN)) == Ordering::Less);
+    }
+}
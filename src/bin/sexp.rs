@@ -0,0 +1,204 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// sexp merge-driver %O %A %B    structural three way merge, the result goes to %A
+// sexp textconv FILE            one `path value` line per leaf, for structural diffs
+// sexp infer FILE...            schema accepting every top level form of the files
+// sexp bindings NAME SCHEMA     rust structs and FromExp/ToExp impls for the schema file
+// sexp docs md|html TITLE SCHEMA  field tables and an example document for the schema file
+// sexp compat OLD NEW           breaking changes from schema OLD to NEW, exits with 1 if any
+// sexp cmp A B                  whether A and B hold the same forms whatever their layout,
+//                               the differences and 1 when not
+//
+#![allow(non_snake_case)]
+
+use s_exp::*;
+use s_exp::ParseResult::*;
+use std::process::exit;
+
+fn readForms(path: &str) -> Result<Exp, String> {
+    let f = std::fs::File::open(path).map_err(|e| format!("{}: {}", path, e))?;
+    let mut forms = alt_std::Vec::new();
+    for form in FormReader::new(std::io::BufReader::new(f)) {
+        match form {
+            PROk(e) => forms.pushBack(e),
+            PRErr(err) => return Err(format!("{}:{}: {}", path, err.offset(), err.message())),
+        }
+    }
+    Ok(Exp::List(forms))
+}
+
+// the text of a file, its top level forms and the text of each form with what
+// comes before it since the previous one
+struct Source {
+    text    : String,
+    forms   : alt_std::Vec<Exp>,
+    chunks  : Vec<(usize, usize)>,
+}
+
+impl Source {
+    fn read(path: &str) -> Result<Source, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+        let mut forms = alt_std::Vec::new();
+        let mut chunks = Vec::new();
+        let mut r = FormReader::new(text.as_bytes());
+        let mut start = 0;
+        while let Some(form) = r.next() {
+            match form {
+                PROk(e) => forms.pushBack(e),
+                PRErr(err) => return Err(format!("{}:{}: {}", path, err.offset(), err.message())),
+            }
+            chunks.push((start, r.offset()));
+            start = r.offset();
+        }
+        Ok(Source { text, forms, chunks })
+    }
+
+    fn chunk(&self, i: usize) -> &str { &self.text[self.chunks[i].0..self.chunks[i].1] }
+
+    // what follows the last form
+    fn trailer(&self) -> &str { &self.text[self.chunks.last().map_or(0, |c| c.1)..] }
+}
+
+fn mergeDriver(base: &str, ours: &str, theirs: &str) -> Result<i32, String> {
+    let (b, o, t) = (Source::read(base)?, Source::read(ours)?, Source::read(theirs)?);
+    let m = Exp::merge3Forms(b.forms.asArray(), o.forms.asArray(), t.forms.asArray());
+    // forms taken from a side keep their text and the comments before them
+    let mut out = String::new();
+    for f in m.forms.asArray().iter() {
+        match f {
+            MergedForm::Ours(i) => out.push_str(o.chunk(*i)),
+            MergedForm::Theirs(j) => out.push_str(t.chunk(*j)),
+            MergedForm::Merged(e) => {
+                if !out.is_empty() { out.push('\n') }
+                out.push_str(e.toConflictString().toStr());
+            },
+        }
+    }
+    out.push_str(o.trailer());
+    if !out.ends_with('\n') { out.push('\n') }
+    std::fs::write(ours, out).map_err(|e| format!("{}: {}", ours, e))?;
+    for c in m.conflicts.asArray().iter() {
+        eprintln!("conflict at {}", c.path.toString().toStr());
+    }
+    Ok(if m.isClean() { 0 } else { 1 })
+}
+
+fn textconv(path: &str) -> Result<i32, String> {
+    let forms = readForms(path)?;
+    let mut out = String::new();
+    for (p, v) in forms.flatten().asArray().iter() {
+        out.push_str(p.toString().toStr());
+        out.push(' ');
+        out.push_str(v.toString().toStr());
+        out.push('\n');
+    }
+    print!("{}", out);
+    Ok(0)
+}
+
+fn infer(paths: &[&str]) -> Result<i32, String> {
+    let mut docs = alt_std::Vec::new();
+    for path in paths.iter() {
+        if let Exp::List(l) = readForms(path)? {
+            for e in l.asArray().iter() { docs.pushBack(e.clone()) }
+        }
+    }
+    println!("{}", Schema::infer(docs.asArray()).toExp().toPrettyString(80).toStr());
+    Ok(0)
+}
+
+fn readSchema(path: &str) -> Result<Schema, String> {
+    match readForms(path)? {
+        Exp::List(l) if l.len() == 1 => Schema::fromExp(&l[0]).map_err(|m| format!("{}: {}", path, m.toStr())),
+        _ => Err(format!("{}: expected a single schema form", path)),
+    }
+}
+
+fn bindings(name: &str, path: &str) -> Result<i32, String> {
+    print!("{}", readSchema(path)?.toRust(name).toStr());
+    Ok(0)
+}
+
+fn docs(html: bool, title: &str, path: &str) -> Result<i32, String> {
+    let schema = readSchema(path)?;
+    print!("{}", if html { schema.toHtmlDoc(title) } else { schema.toMarkdown(title) }.toStr());
+    Ok(0)
+}
+
+fn compat(old: &str, new: &str) -> Result<i32, String> {
+    let changes = readSchema(new)?.compatibleWith(&readSchema(old)?);
+    for c in changes.asArray().iter() {
+        println!("{}", c.toString().toStr());
+    }
+    Ok(if changes.len() == 0 { 0 } else { 1 })
+}
+
+fn cmp(a: &str, b: &str) -> Result<i32, String> {
+    let (ea, eb) = (readForms(a)?, readForms(b)?);
+    if ea.approxEq(&eb, 0.0) { return Ok(0) }
+    print!("{}", ea.diff(&eb).describe(&ea).toStr());
+    Ok(1)
+}
+
+fn printGrammar(asExp: bool) -> Result<i32, String> {
+    let g = grammar();
+    match asExp {
+        true => println!("{}", g.toExp().toPrettyString(80).toStr()),
+        false => print!("{}", g.toEbnf().toStr()),
+    }
+    Ok(0)
+}
+
+fn usage() -> ! {
+    eprintln!("usage: sexp merge-driver BASE OURS THEIRS");
+    eprintln!("       sexp textconv FILE");
+    eprintln!("       sexp infer FILE...");
+    eprintln!("       sexp bindings NAME SCHEMA");
+    eprintln!("       sexp docs md|html TITLE SCHEMA");
+    eprintln!("       sexp compat OLD NEW");
+    eprintln!("       sexp cmp A B");
+    eprintln!("       sexp grammar ebnf|sexp");
+    exit(2)
+}
+
+fn main() {
+    let args : Vec<String> = std::env::args().collect();
+    let res = match args.iter().map(|a| a.as_str()).collect::<Vec<&str>>().as_slice() {
+        [_, "merge-driver", base, ours, theirs] => mergeDriver(base, ours, theirs),
+        [_, "textconv", path] => textconv(path),
+        [_, "infer", paths @ ..] if !paths.is_empty() => infer(paths),
+        [_, "bindings", name, path] => bindings(name, path),
+        [_, "docs", "md", title, path] => docs(false, title, path),
+        [_, "docs", "html", title, path] => docs(true, title, path),
+        [_, "compat", old, new] => compat(old, new),
+        [_, "cmp", a, b] => cmp(a, b),
+        [_, "grammar", "ebnf"] => printGrammar(false),
+        [_, "grammar", "sexp"] => printGrammar(true),
+        _ => usage()
+    };
+    match res {
+        Ok(code) => exit(code),
+        Err(msg) => {
+            eprintln!("sexp: {}", msg);
+            exit(2)
+        }
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// bytevectors: `#u8(0 255 16)` reads into `Exp::Bytes`, elements are integers in
+// 0..=255 in any radix (`#u8(#xff)`), and `#u8"00ff10"` is the hex shorthand
+// (whitespace between digits is ignored). Printing always uses the list form
+//
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    pub(crate) fn isBytesStart(src: &[u8], offset: usize) -> bool {
+        src.get(offset..).is_some_and(|s| s.starts_with(b"#u8(") || s.starts_with(b"#u8\""))
+    }
+
+    fn parseHexBytes(src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let mut bytes = Vec::new();
+        let mut high : Option<(u8, usize)> = None;
+        loop {
+            let at = *offset;
+            match Self::getchar(src, offset) {
+                Some(b'"') => match high {
+                    Some((_, at)) => return PRErr(ParseError { message: String::from("odd number of hex digits (bytes)"), offset: at, kind: ErrorKind::Syntax }),
+                    None => return PROk(Exp::Bytes(bytes)),
+                },
+                Some(c) if Self::isWS(c) || c == b'\r' => (),
+                Some(c) => match ((c as char).to_digit(16).map(|d| d as u8), high.take()) {
+                    (Some(d), None) => high = Some((d, at)),
+                    (Some(d), Some((h, _))) => bytes.pushBack(h << 4 | d),
+                    (None, _) => return PRErr(ParseError { message: String::from("invalid hex digit (bytes)"), offset: at, kind: ErrorKind::Syntax }),
+                },
+                None => return PRErr(ParseError { message: String::from("unexpected end of stream (bytes)"), offset: at, kind: ErrorKind::Eof }),
+            }
+        }
+    }
+
+    pub(crate) fn parseBytes(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        // `#u8` then `(` or `"`
+        *offset += 4;
+        if src[*offset - 1] == b'"' { return Self::parseHexBytes(src, offset) }
+        let mut bytes = Vec::new();
+        loop {
+            Self::skipWS(src, offset);
+            let at = *offset;
+            match Self::peek(src, at) {
+                Some(b')') => {
+                    *offset += 1;
+                    return PROk(Exp::Bytes(bytes))
+                },
+                None => return PRErr(ParseError { message: String::from("unexpected end of stream (bytes)"), offset: at, kind: ErrorKind::Eof }),
+                Some(_) => match Self::parseToken(rd, src, offset) {
+                    PROk(Exp::Int(i)) if (0..=255).contains(&i) => bytes.pushBack(i as u8),
+                    PROk(Exp::Int(_)) => return PRErr(ParseError { message: String::from("byte out of range (bytes)"), offset: at, kind: ErrorKind::Syntax }),
+                    PROk(_) => return PRErr(ParseError { message: String::from("expected a byte (bytes)"), offset: at, kind: ErrorKind::Syntax }),
+                    PRErr(err) => return PRErr(err),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testBytes() {
+        match Exp::fromSExp(b"(blob #u8(0 255 #x10) #u8\"DE ad\" #u8() #u8\"\")") {
+            PROk(Exp::List(l)) => {
+                assert!(matches!(&l[1], Exp::Bytes(b) if b.asArray() == [0, 255, 16]));
+                assert!(matches!(&l[2], Exp::Bytes(b) if b.asArray() == [0xde, 0xad]));
+                assert!(l[3] == l[4] && l[3] != Exp::List(Vec::new()));
+                assert!(Exp::List(l).toString() == "(blob #u8(0 255 16) #u8(222 173) #u8() #u8())");
+            },
+            _ => panic!("expected a list")
+        }
+        assert!(Exp::fromSExp(b"#u8(1 256)") == PRErr(ParseError { message: String::from("byte out of range (bytes)"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"#u8(1 a)") == PRErr(ParseError { message: String::from("expected a byte (bytes)"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"#u8\"abc\"") == PRErr(ParseError { message: String::from("odd number of hex digits (bytes)"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"#u8\"ag\"") == PRErr(ParseError { message: String::from("invalid hex digit (bytes)"), offset: 5, kind: ErrorKind::Syntax }));
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use std::collections::HashMap;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+use alt_std::*;
+use crate::*;
+
+/// shared, immutable snapshot of a parsed file
+#[derive(Clone)]
+pub struct ArcExp(Arc<Exp>);
+
+// alt_std's String and Vec own their buffers like the std ones and have no
+// interior mutability, an immutable Exp is safe to share between threads
+unsafe impl Send for ArcExp {}
+unsafe impl Sync for ArcExp {}
+
+impl ArcExp {
+    // Send/Sync come from the wrapper
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn new(e: Exp) -> Self { Self(Arc::new(e)) }
+    pub fn ptrEq(a: &ArcExp, b: &ArcExp) -> bool { Arc::ptr_eq(&a.0, &b.0) }
+}
+
+impl core::ops::Deref for ArcExp {
+    type Target = Exp;
+    fn deref(&self) -> &Exp { &self.0 }
+}
+
+struct CacheEntry {
+    mtime   : Option<SystemTime>,
+    digest  : u64,
+    exp     : ArcExp,
+}
+
+/// thread safe cache of parsed files: an entry is reused while the file keeps
+/// its mtime, or its content digest when the mtime changed
+pub struct SexpCache {
+    entries : Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+impl SexpCache {
+    pub fn new() -> Self {
+        Self { entries: Mutex::new(HashMap::new()) }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<PathBuf, CacheEntry>> {
+        // entries are always left consistent, a panicking holder does not matter
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn ioError(msg: &str) -> ParseResult<ArcExp> {
+        PRErr(ParseError { message: String::from(msg), offset: 0, kind: ErrorKind::Syntax })
+    }
+
+    pub fn get<P: AsRef<FsPath>>(&self, path: P) -> ParseResult<ArcExp> {
+        let path = path.as_ref();
+        let mtime = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+        if let Some(e) = self.lock().get(path) {
+            if mtime.is_some() && e.mtime == mtime { return PROk(e.exp.clone()) }
+        }
+
+        // parse without holding the lock, the last writer wins
+        let src = match std::fs::read(path) {
+            Ok(src) => src,
+            Err(_) => return Self::ioError("unable to read file")
+        };
+        let digest = Exp::digest(&src);
+        if let Some(e) = self.lock().get_mut(path) {
+            if e.digest == digest {
+                e.mtime = mtime;
+                return PROk(e.exp.clone())
+            }
+        }
+
+        match Exp::fromSExp(&src) {
+            PROk(e) => {
+                let exp = ArcExp::new(e);
+                self.lock().insert(path.to_path_buf(), CacheEntry { mtime, digest, exp: exp.clone() });
+                PROk(exp)
+            },
+            PRErr(err) => PRErr(err)
+        }
+    }
+
+    pub fn invalidate<P: AsRef<FsPath>>(&self, path: P) {
+        self.lock().remove(path.as_ref());
+    }
+
+    pub fn len(&self) -> usize { self.lock().len() }
+    pub fn is_empty(&self) -> bool { self.lock().is_empty() }
+}
+
+impl Default for SexpCache {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alt_std::io::File;
+
+    #[test]
+    fn testSexpCache() {
+        let name = File::tmpname();
+        let path = std::string::String::from(name.toStr());
+        std::fs::write(&path, "(server (port 80))").unwrap();
+
+        let cache = Arc::new(SexpCache::new());
+        let first = match cache.get(&path) { PROk(e) => e, PRErr(err) => panic!("{}", err.message()) };
+        let threads : std::vec::Vec<_> = (0..4).map(|_| {
+            let (cache, path, first) = (cache.clone(), path.clone(), first.clone());
+            std::thread::spawn(move || match cache.get(&path) {
+                PROk(e) => assert!(ArcExp::ptrEq(&e, &first)),
+                PRErr(err) => panic!("{}", err.message())
+            })
+        }).collect();
+        for t in threads { t.join().unwrap() }
+
+        // a rewrite is picked up by the next get, it is repeated until the mtime
+        // moves on since filesystems keep it at a coarse granularity
+        let mtime = std::fs::metadata(&path).and_then(|m| m.modified()).unwrap();
+        loop {
+            std::fs::write(&path, "(server (port 8080))").unwrap();
+            if std::fs::metadata(&path).and_then(|m| m.modified()).unwrap() != mtime { break }
+            std::thread::sleep(std::time::Duration::from_millis(10));
+        }
+        match cache.get(&path) {
+            PROk(e) => assert!(e.toString() == "(server (port 8080))" && !ArcExp::ptrEq(&e, &first)),
+            PRErr(err) => panic!("{}", err.message())
+        }
+        assert!(cache.len() == 1);
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(match cache.get(&path) { PRErr(_) => true, _ => false });
+    }
+}
@@ -0,0 +1,188 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use alt_std::{format};
+use crate::*;
+
+impl Exp {
+    fn rustFloat(f: f64) -> String {
+        match f {
+            f if f.is_nan() => String::from("f64::NAN"),
+            f if f == f64::INFINITY => String::from("f64::INFINITY"),
+            f if f == f64::NEG_INFINITY => String::from("f64::NEG_INFINITY"),
+            f => format!("{:?}", f),
+        }
+    }
+
+    // alt_std's String writes chars as single bytes, escape anything beyond ASCII
+    fn rustChar(c: char) -> String {
+        match c {
+            c if c.is_ascii() => format!("{:?}", c),
+            c => format!("'\\u{{{:x}}}'", c as u32),
+        }
+    }
+
+    /// rust expression rebuilding this expression (with `s_exp` and `alt_std` as dependencies)
+    pub fn toRustTokens(&self) -> String {
+        match self {
+            Self::Bool(b) => format!("::s_exp::Exp::Bool({})", b),
+            Self::Char(c) => format!("::s_exp::Exp::Char({})", Self::rustChar(*c)),
+            Self::Int(i) => format!("::s_exp::Exp::Int({})", i),
+            Self::UInt(u) => format!("::s_exp::Exp::UInt({})", u),
+            Self::Float(f) => format!("::s_exp::Exp::Float({})", Self::rustFloat(*f)),
+            Self::Ratio(n, d) => format!("::s_exp::Exp::Ratio({}, {})", n, d),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => format!("::s_exp::Exp::BigInt(::s_exp::BigInt::fromLimbs({}, &{:?}))", b.isNegative(), b.limbs()),
+            Self::String(s) => format!("::s_exp::Exp::String(::alt_std::String::from({:?}))", s.toStr()),
+            Self::Symbol(s) => format!("::s_exp::Exp::Symbol(::alt_std::String::from({:?}))", s.toStr()),
+            Self::Keyword(k) => format!("::s_exp::Exp::Keyword(::alt_std::String::from({:?}))", k.toStr()),
+            Self::Spilled(s) => format!("::s_exp::Exp::String(::alt_std::String::from({:?}))", std::string::String::from_utf8_lossy(&Self::spilledBytes(s))),
+            Self::List(l) => {
+                let mut s = String::from("::s_exp::Exp::List({ let mut v = ::alt_std::Vec::new(); ");
+                for i in 0..l.len() {
+                    s.append("v.pushBack(");
+                    s.append(&l[i].toRustTokens());
+                    s.append("); ");
+                }
+                s.append("v })");
+                s
+            },
+            Self::Vector(v) => {
+                let mut s = String::from("::s_exp::Exp::Vector({ let mut v = ::alt_std::Vec::new(); ");
+                for i in 0..v.len() {
+                    s.append("v.pushBack(");
+                    s.append(&v[i].toRustTokens());
+                    s.append("); ");
+                }
+                s.append("v })");
+                s
+            },
+            Self::Bytes(b) => format!("::s_exp::Exp::Bytes({{ let mut v = ::alt_std::Vec::new(); for b in {:?}.iter() {{ v.pushBack(*b) }} v }})", b.asArray()),
+            Self::Pair(a, d) => format!("::s_exp::Exp::Pair(::std::boxed::Box::new({}), ::std::boxed::Box::new({}))", a.toRustTokens().toStr(), d.toRustTokens().toStr()),
+            Self::Map(m) => {
+                let mut s = String::from("::s_exp::Exp::Map({ let mut v = ::alt_std::Vec::new(); ");
+                for i in 0..m.len() {
+                    s.append("v.pushBack((");
+                    s.append(&m[i].0.toRustTokens());
+                    s.append(", ");
+                    s.append(&m[i].1.toRustTokens());
+                    s.append(")); ");
+                }
+                s.append("v })");
+                s
+            }
+        }
+    }
+
+    /// const expression of the matching `StaticExp`
+    pub fn toStaticRustTokens(&self) -> String {
+        match self {
+            Self::Bool(b) => format!("::s_exp::StaticExp::Bool({})", b),
+            Self::Char(c) => format!("::s_exp::StaticExp::Char({})", Self::rustChar(*c)),
+            Self::Int(i) => format!("::s_exp::StaticExp::Int({})", i),
+            Self::UInt(u) => format!("::s_exp::StaticExp::UInt({})", u),
+            Self::Float(f) => format!("::s_exp::StaticExp::Float({})", Self::rustFloat(*f)),
+            Self::Ratio(n, d) => format!("::s_exp::StaticExp::Ratio({}, {})", n, d),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => format!("::s_exp::StaticExp::BigInt({}, &{:?})", b.isNegative(), b.limbs()),
+            Self::String(s) => format!("::s_exp::StaticExp::String({:?})", s.toStr()),
+            Self::Symbol(s) => format!("::s_exp::StaticExp::Symbol({:?})", s.toStr()),
+            Self::Keyword(k) => format!("::s_exp::StaticExp::Keyword({:?})", k.toStr()),
+            Self::Spilled(s) => format!("::s_exp::StaticExp::String({:?})", std::string::String::from_utf8_lossy(&Self::spilledBytes(s))),
+            Self::List(l) => {
+                let mut s = String::from("::s_exp::StaticExp::List(&[");
+                for i in 0..l.len() {
+                    s.append(&l[i].toStaticRustTokens());
+                    if i != l.len() - 1 {
+                        s.append(", ");
+                    }
+                }
+                s.append("])");
+                s
+            },
+            Self::Vector(v) => {
+                let mut s = String::from("::s_exp::StaticExp::Vector(&[");
+                for i in 0..v.len() {
+                    if i != 0 { s.append(", ") }
+                    s.append(&v[i].toStaticRustTokens());
+                }
+                s.append("])");
+                s
+            },
+            Self::Bytes(b) => format!("::s_exp::StaticExp::Bytes(&{:?})", b.asArray()),
+            Self::Pair(a, d) => format!("::s_exp::StaticExp::Pair(&{}, &{})", a.toStaticRustTokens().toStr(), d.toStaticRustTokens().toStr()),
+            Self::Map(m) => {
+                let mut s = String::from("::s_exp::StaticExp::Map(&[");
+                for i in 0..m.len() {
+                    s.append("(");
+                    s.append(&m[i].0.toStaticRustTokens());
+                    s.append(", ");
+                    s.append(&m[i].1.toStaticRustTokens());
+                    s.append(")");
+                    if i != m.len() - 1 {
+                        s.append(", ");
+                    }
+                }
+                s.append("])");
+                s
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToRustTokens() {
+        let sexp = String::from("(abcd 12 \"x\" 1.5)");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => {
+                let s = r.toRustTokens();
+                assert!(s == "::s_exp::Exp::List({ let mut v = ::alt_std::Vec::new(); \
+                              v.pushBack(::s_exp::Exp::Symbol(::alt_std::String::from(\"abcd\"))); \
+                              v.pushBack(::s_exp::Exp::Int(12)); \
+                              v.pushBack(::s_exp::Exp::String(::alt_std::String::from(\"x\"))); \
+                              v.pushBack(::s_exp::Exp::Float(1.5)); v })")
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testRustChar() {
+        assert!(Exp::Char('λ').toRustTokens() == "::s_exp::Exp::Char('\\u{3bb}')");
+        assert!(Exp::Char('\'').toRustTokens() == "::s_exp::Exp::Char('\\'')");
+    }
+
+    #[test]
+    fn testToStaticRustTokens() {
+        let sexp = String::from("(abcd (12))");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => {
+                let s = r.toStaticRustTokens();
+                assert!(s == "::s_exp::StaticExp::List(&[::s_exp::StaticExp::Symbol(\"abcd\"), \
+                              ::s_exp::StaticExp::List(&[::s_exp::StaticExp::Int(12)])])")
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
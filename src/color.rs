@@ -0,0 +1,151 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+const RESET : &str = "\x1b[0m";
+
+// ANSI SGR sequences, the parens cycle with the nesting depth
+#[derive(Clone)]
+pub struct Theme {
+    pub symbol  : &'static str,
+    pub string  : &'static str,
+    pub number  : &'static str,
+    pub literal : &'static str,
+    pub parens  : &'static [&'static str],
+}
+
+impl Theme {
+    pub fn new() -> Self {
+        Self {
+            symbol  : "\x1b[36m",
+            string  : "\x1b[32m",
+            number  : "\x1b[33m",
+            literal : "\x1b[35m",
+            parens  : &["\x1b[91m", "\x1b[93m", "\x1b[92m", "\x1b[96m", "\x1b[94m", "\x1b[95m"],
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self { Self::new() }
+}
+
+impl Exp {
+    fn writeColored(&self, theme: &Theme, depth: usize, out: &mut String) {
+        let color = match self {
+            Self::Bool(_) | Self::Char(_) | Self::Keyword(_) | Self::Bytes(_) => theme.literal,
+            Self::Int(_) | Self::UInt(_) | Self::Ratio(..) | Self::Float(_) => theme.number,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(_) => theme.number,
+            Self::String(_) | Self::Spilled(_) => theme.string,
+            Self::Symbol(_) => theme.symbol,
+            Self::List(l) => {
+                let paren = if theme.parens.is_empty() { "" } else { theme.parens[depth % theme.parens.len()] };
+                out.append(paren);
+                out.add(b'(');
+                out.append(RESET);
+                for i in 0..l.len() {
+                    l[i].writeColored(theme, depth + 1, out);
+                    if i != l.len() - 1 {
+                        out.add(b' ');
+                    }
+                }
+                out.append(paren);
+                out.add(b')');
+                out.append(RESET);
+                return
+            },
+            Self::Vector(v) => {
+                let paren = if theme.parens.is_empty() { "" } else { theme.parens[depth % theme.parens.len()] };
+                out.append(paren);
+                out.append("#(");
+                out.append(RESET);
+                for i in 0..v.len() {
+                    if i != 0 { out.add(b' ') }
+                    v[i].writeColored(theme, depth + 1, out);
+                }
+                out.append(paren);
+                out.add(b')');
+                out.append(RESET);
+                return
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                let paren = if theme.parens.is_empty() { "" } else { theme.parens[depth % theme.parens.len()] };
+                out.append(paren);
+                out.add(b'(');
+                out.append(RESET);
+                for i in 0..items.len() {
+                    if i != 0 { out.add(b' ') }
+                    items[i].writeColored(theme, depth + 1, out);
+                }
+                if let Some(t) = tail {
+                    out.append(" . ");
+                    t.writeColored(theme, depth + 1, out);
+                }
+                out.append(paren);
+                out.add(b')');
+                out.append(RESET);
+                return
+            },
+            Self::Map(m) => {
+                let paren = if theme.parens.is_empty() { "" } else { theme.parens[depth % theme.parens.len()] };
+                out.append(paren);
+                out.add(b'{');
+                out.append(RESET);
+                for i in 0..m.len() {
+                    if i != 0 { out.add(b' ') }
+                    m[i].0.writeColored(theme, depth + 1, out);
+                    out.add(b' ');
+                    m[i].1.writeColored(theme, depth + 1, out);
+                }
+                out.append(paren);
+                out.add(b'}');
+                out.append(RESET);
+                return
+            }
+        };
+        out.append(color);
+        out.append(&self.toString());
+        out.append(RESET);
+    }
+
+    pub fn toColoredString(&self, theme: &Theme) -> String {
+        let mut s = String::new();
+        self.writeColored(theme, 0, &mut s);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testColoredString() {
+        let sexp = String::from("(a (1 \"s\"))");
+        let theme = Theme { symbol: "<y>", string: "<s>", number: "<n>", literal: "<l>", parens: &["<0>", "<1>"] };
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toColoredString(&theme) == "<0>(\x1b[0m<y>a\x1b[0m <1>(\x1b[0m<n>1\x1b[0m <s>\"s\"\x1b[0m<1>)\x1b[0m<0>)\x1b[0m"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// columnar export following the Arrow memory layout: LSB first validity
+// bitmaps, bools packed the same way, plain value buffers and i32 offsets +
+// bytes for strings. Values are kept exact, ints only widen to floats: ratios
+// and big ints have no column type and unsigned ints do not mix with others
+//
+use alt_std::*;
+use crate::*;
+
+pub enum ColumnData {
+    // bit i is the value of row i
+    Bool(Vec<u8>),
+    Int64(Vec<i64>),
+    UInt64(Vec<u64>),
+    Float64(Vec<f64>),
+    // chars, strings, symbols and keyword names
+    Utf8 { offsets: Vec<i32>, values: Vec<u8> },
+}
+
+pub struct ArrowColumn {
+    pub name        : String,
+    // bit i is set when row i is not null
+    pub validity    : Vec<u8>,
+    pub data        : ColumnData,
+}
+
+impl ArrowColumn {
+    /// false past the last row
+    pub fn isValid(&self, row: usize) -> bool {
+        Self::bit(self.validity.asArray(), row)
+    }
+
+    /// the value of a bool column, `None` for a null or past the last row
+    pub fn boolAt(&self, row: usize) -> Option<bool> {
+        match &self.data {
+            ColumnData::Bool(bits) if self.isValid(row) => Some(Self::bit(bits.asArray(), row)),
+            _ => None
+        }
+    }
+
+    fn bit(bits: &[u8], i: usize) -> bool {
+        bits.get(i / 8).is_some_and(|b| b & (1 << (i % 8)) != 0)
+    }
+
+    fn bitmap(rows: usize, set: impl Fn(usize) -> bool) -> Vec<u8> {
+        let mut bits = Vec::new();
+        for _ in 0..(rows + 7) / 8 {
+            bits.pushBack(0u8);
+        }
+        for i in 0..rows {
+            if set(i) { bits[i / 8] |= 1 << (i % 8) }
+        }
+        bits
+    }
+}
+
+pub struct RecordBatch {
+    pub rows    : usize,
+    pub columns : Vec<ArrowColumn>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind { Null, Bool, Int, UInt, Float, Utf8 }
+
+impl Exp {
+    fn columnKind(&self) -> Option<Kind> {
+        match self {
+            Self::Bool(_) => Some(Kind::Bool),
+            Self::Int(_) => Some(Kind::Int),
+            Self::UInt(_) => Some(Kind::UInt),
+            Self::Float(_) => Some(Kind::Float),
+            Self::Char(_) | Self::String(_) | Self::Symbol(_) | Self::Keyword(_) | Self::Spilled(_) => Some(Kind::Utf8),
+            // no exact column for them
+            Self::Ratio(..) => None,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(_) => None,
+            Self::Bytes(_) | Self::List(_) | Self::Vector(_) | Self::Pair(..) | Self::Map(_) => None,
+        }
+    }
+
+    fn buildColumn(name: &String, cells: &Vec<Option<&Exp>>) -> Option<ArrowColumn> {
+        // ints widen to floats, any other mix is an error
+        let mut kind = Kind::Null;
+        for c in cells.asArray().iter() {
+            let k = match c {
+                Some(e) => e.columnKind()?,
+                None => continue
+            };
+            kind = match (kind, k) {
+                (Kind::Null, k) => k,
+                (a, b) if a == b => a,
+                (Kind::Int, Kind::Float) | (Kind::Float, Kind::Int) => Kind::Float,
+                _ => return None
+            };
+        }
+
+        let validity = ArrowColumn::bitmap(cells.len(), |i| cells[i].is_some());
+        let data = match kind {
+            Kind::Bool => ColumnData::Bool(ArrowColumn::bitmap(cells.len(), |i| matches!(cells[i], Some(Self::Bool(true))))),
+            Kind::Int => {
+                let mut v = Vec::new();
+                for c in cells.asArray().iter() {
+                    v.pushBack(match c { Some(Self::Int(i)) => *i, _ => 0 });
+                }
+                ColumnData::Int64(v)
+            },
+            Kind::UInt => {
+                let mut v = Vec::new();
+                for c in cells.asArray().iter() {
+                    v.pushBack(match c { Some(Self::UInt(u)) => *u, _ => 0 });
+                }
+                ColumnData::UInt64(v)
+            },
+            Kind::Float => {
+                let mut v = Vec::new();
+                for c in cells.asArray().iter() {
+                    v.pushBack(match c { Some(Self::Int(i)) => *i as f64, Some(Self::Float(f)) => *f, _ => 0.0 });
+                }
+                ColumnData::Float64(v)
+            },
+            Kind::Null | Kind::Utf8 => {
+                let mut offsets = Vec::new();
+                let mut values = Vec::new();
+                offsets.pushBack(0i32);
+                for c in cells.asArray().iter() {
+                    let mut buf = [0u8; 4];
+                    let spilled;
+                    let s : &[u8] = match c {
+                        Some(Self::String(s)) | Some(Self::Symbol(s)) | Some(Self::Keyword(s)) => s.asArray(),
+                        Some(Self::Char(ch)) => ch.encode_utf8(&mut buf).as_bytes(),
+                        Some(Self::Spilled(sp)) => { spilled = Self::spilledBytes(sp); &spilled },
+                        _ => &[]
+                    };
+                    for b in s.iter() { values.pushBack(*b) }
+                    offsets.pushBack(values.len() as i32);
+                }
+                ColumnData::Utf8 { offsets, values }
+            },
+        };
+        Some(ArrowColumn { name: String::from(name.toStr()), validity, data })
+    }
+
+    /// columnar batch of this list of records, columns picked like `toCsv`
+    /// missing fields are null, `None` on nested lists, mixed column types or
+    /// values without an exact column type
+    pub fn toRecordBatch(&self, columns: &[CsvColumn]) -> Option<RecordBatch> {
+        let records = match self {
+            Self::List(l) => l,
+            _ => return None
+        };
+
+        let mut out = Vec::new();
+        for col in columns.iter() {
+            let mut cells = Vec::new();
+            for r in records.asArray().iter() {
+                if !r.isCsvRecord() { return None }
+                cells.pushBack(Self::csvField(r, &col.field));
+            }
+            out.pushBack(Self::buildColumn(&col.header, &cells)?);
+        }
+        Some(RecordBatch { rows: records.len(), columns: out })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToRecordBatch() {
+        let sexp = String::from("((row (name \"ab\") (age 3)) (row (age 4.5) (name c)) (row (ok #t)))");
+        let mut spec = CsvSpec::csv();
+        spec.addColumn("name", CsvField::Key(String::from("name")));
+        spec.addColumn("age", CsvField::Key(String::from("age")));
+        spec.addColumn("ok", CsvField::Key(String::from("ok")));
+        let r = match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => r,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+
+        let b = r.toRecordBatch(spec.columns.asArray()).unwrap();
+        assert!(b.rows == 3 && b.columns.len() == 3);
+        match &b.columns[0].data {
+            ColumnData::Utf8 { offsets, values } => assert!(offsets.asArray() == [0, 2, 3, 3] && values.asArray() == b"abc"),
+            _ => panic!("expected a utf8 column")
+        }
+        match &b.columns[1].data {
+            ColumnData::Float64(v) => assert!(v.asArray() == [3.0, 4.5, 0.0]),
+            _ => panic!("expected a float column")
+        }
+        assert!(b.columns[1].isValid(1) && !b.columns[1].isValid(2));
+        assert!(b.columns[2].validity.asArray() == [0b100]);
+        match &b.columns[2].data {
+            ColumnData::Bool(bits) => assert!(bits.asArray() == [0b100] && b.columns[2].boolAt(2) == Some(true)),
+            _ => panic!("expected a bool column")
+        }
+        assert!(!b.columns[1].isValid(3) && !b.columns[1].isValid(64) && b.columns[2].boolAt(0).is_none());
+
+        spec.addColumn("kind", CsvField::Index(1));
+        assert!(r.toRecordBatch(spec.columns.asArray()).is_none());
+    }
+
+    #[test]
+    fn testExactColumns() {
+        let batch = |src: &str| {
+            let r = match Exp::fromSExp(src.as_bytes()) {
+                PROk(r) => r,
+                PRErr(err) => panic!("{}", err.message.toStr())
+            };
+            let mut spec = CsvSpec::csv();
+            spec.addColumn("v", CsvField::Key(String::from("v")));
+            r.toRecordBatch(spec.columns.asArray())
+        };
+        let b = batch("((r (v #t)) (r (v #f)) (r) (r (v #t)) (r (v #f)) (r (v #f)) (r (v #f)) (r (v #f)) (r (v #t)))").unwrap();
+        match &b.columns[0].data {
+            ColumnData::Bool(bits) => assert!(bits.asArray() == [0b1001, 0b1] && b.columns[0].validity.asArray() == [0b11111011, 0b1]),
+            _ => panic!("expected a bool column")
+        }
+        match batch("((r (v 18446744073709551615)) (r))").map(|b| b.columns) {
+            Some(c) => assert!(matches!(&c[0].data, ColumnData::UInt64(v) if v.asArray() == [u64::MAX, 0])),
+            None => panic!("expected a uint column")
+        }
+        // no exact common type
+        for src in ["((r (v 1/3)))", "((r (v 18446744073709551615)) (r (v 1)))", "((r (v 18446744073709551615)) (r (v 1.5)))"].iter() {
+            assert!(batch(src).is_none(), "{}", src);
+        }
+        // keyword entries and map records
+        match batch("((r (:v 1)) {v 2} {:v 3} (r))").map(|b| b.columns) {
+            Some(c) => assert!(matches!(&c[0].data, ColumnData::Int64(v) if v.asArray() == [1, 2, 3, 0])),
+            None => panic!("expected an int column")
+        }
+        assert!(batch("((r (v 1)) #(v 2))").is_none());
+    }
+
+    #[test]
+    fn testKeywordColumn() {
+        let r = match Exp::fromSExp(b"((row (role :admin)) (row (role \"guest\")))") {
+            PROk(r) => r,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut spec = CsvSpec::csv();
+        spec.addColumn("role", CsvField::Key(String::from("role")));
+        let b = r.toRecordBatch(spec.columns.asArray()).unwrap();
+        match &b.columns[0].data {
+            ColumnData::Utf8 { offsets, values } => assert!(offsets.asArray() == [0, 5, 10] && values.asArray() == b"adminguest"),
+            _ => panic!("expected a utf8 column")
+        }
+    }
+}
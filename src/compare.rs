@@ -0,0 +1,315 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use core::cmp::Ordering;
+use crate::*;
+
+//
+// total order:
+//  Bool < Char < numbers < String < Bytes < Symbol < Keyword < List < Vector < Pair < Map < Spilled
+//  - numbers compare by value across Int, UInt, Ratio, BigInt and Float, when equal the exact one comes first
+//  - NaN is greater than every other number and equal to itself
+//  - strings, bytes, symbols and keywords compare bytewise, lists and vectors lexicographically
+//  - pairs compare by car then cdr
+//  - maps compare as the lists of their entries sorted by key
+//  - spilled strings compare bytewise by their content
+//
+impl Exp {
+    fn rank(&self) -> u32 {
+        match self {
+            Self::Bool(_) => 0,
+            Self::Char(_) => 1,
+            Self::Int(_) | Self::UInt(_) | Self::Ratio(..) | Self::Float(_) => 2,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(_) => 2,
+            Self::String(_) => 3,
+            Self::Bytes(_) => 4,
+            Self::Symbol(_) => 5,
+            Self::Keyword(_) => 6,
+            Self::List(_) => 7,
+            Self::Vector(_) => 8,
+            Self::Pair(..) => 9,
+            Self::Map(_) => 10,
+            Self::Spilled(_) => 11,
+        }
+    }
+
+    fn numberRank(&self) -> u32 {
+        match self {
+            Self::Int(_) => 0,
+            Self::UInt(_) => 1,
+            Self::Ratio(..) => 2,
+            Self::Float(_) => 4,
+            // BigInt
+            _ => 3,
+        }
+    }
+
+    // `i` is an Int or a UInt
+    fn cmpIntFloat(i: i128, f: f64) -> Ordering {
+        if f.is_nan() { return Ordering::Less }
+        // 2^63 and 2^64 are exact in f64, anything outside [-2^63, 2^64) is out of the i64 and u64 ranges
+        if f >= 18446744073709551616.0 { return Ordering::Less }
+        if f < -9223372036854775808.0 { return Ordering::Greater }
+
+        let t = f.trunc();
+        match i.cmp(&(t as i128)) {
+            Ordering::Equal => match (f - t).partial_cmp(&0.0) {
+                Some(Ordering::Greater) => Ordering::Less,
+                Some(Ordering::Less) => Ordering::Greater,
+                _ => Ordering::Equal,
+            },
+            o => o
+        }
+    }
+
+    // n0/d0 against n1/d1, denominators are positive, numerators fit a u64 and
+    // denominators an i64 so that the products fit
+    fn cmpRatio(n0: i128, d0: i128, n1: i128, d1: i128) -> Ordering {
+        (n0 * d1).cmp(&(n1 * d0))
+    }
+
+    // exact: `f` splits into its floor and a fraction `m / 2^s`, compared with the
+    // floor and the remainder of n/d
+    fn cmpRatioFloat(n: i64, d: i64, f: f64) -> Ordering {
+        if f.is_nan() { return Ordering::Less }
+        // |n/d| < 2^63
+        if f >= 9223372036854775808.0 { return Ordering::Less }
+        if f < -9223372036854775808.0 { return Ordering::Greater }
+
+        let (n, d) = (n as i128, d as i128);
+        let t = f.floor();
+        match n.div_euclid(d).cmp(&(t as i128)) {
+            Ordering::Equal => (),
+            o => return o
+        }
+        let r = n.rem_euclid(d) as u128;
+        // the fraction of a float is exact
+        let frac = f - t;
+        if frac == 0.0 {
+            r.cmp(&0)
+        } else {
+            let bits = frac.to_bits();
+            let e = ((bits >> 52) & 0x7ff) as i32;
+            let (m, s) = if e == 0 { (bits & ((1 << 52) - 1), 1074) } else { ((bits & ((1 << 52) - 1)) | (1 << 52), 1075 - e) };
+            // r / d against m / 2^s, that is r 2^s against m d (below 2^116)
+            let md = m as u128 * d as u128;
+            if s < 128 && r.leading_zeros() > s as u32 { (r << s).cmp(&md) } else if r == 0 { Ordering::Less } else { Ordering::Greater }
+        }
+    }
+
+    pub(crate) fn cmpFloat(f0: f64, f1: f64) -> Ordering {
+        match (f0.is_nan(), f1.is_nan()) {
+            (true, true) => Ordering::Equal,
+            (true, false) => Ordering::Greater,
+            (false, true) => Ordering::Less,
+            // -0.0 before 0.0
+            _ => f0.total_cmp(&f1),
+        }
+    }
+
+    fn sortedEntries(m: &[(Exp, Exp)]) -> std::vec::Vec<&(Exp, Exp)> {
+        let mut v : std::vec::Vec<&(Exp, Exp)> = m.iter().collect();
+        v.sort_by(|a, b| a.0.totalCmp(&b.0));
+        v
+    }
+
+    // two numbers by value, NaN above the others and equal to itself, None
+    // unless both are numbers
+    pub(crate) fn cmpNumber(&self, other: &Exp) -> Option<Ordering> {
+        Some(match (self, other) {
+            (Self::Int(i0), Self::Int(i1)) => i0.cmp(i1),
+            (Self::Int(i), Self::Float(f)) => Self::cmpIntFloat(*i as i128, *f),
+            (Self::Float(f), Self::Int(i)) => Self::cmpIntFloat(*i as i128, *f).reverse(),
+            (Self::UInt(u0), Self::UInt(u1)) => u0.cmp(u1),
+            (Self::UInt(u), Self::Float(f)) => Self::cmpIntFloat(*u as i128, *f),
+            (Self::Float(f), Self::UInt(u)) => Self::cmpIntFloat(*u as i128, *f).reverse(),
+            (Self::UInt(u), Self::Int(i)) => (*u as i128).cmp(&(*i as i128)),
+            (Self::Int(i), Self::UInt(u)) => (*i as i128).cmp(&(*u as i128)),
+            (Self::UInt(u), Self::Ratio(n, d)) => Self::cmpRatio(*u as i128, 1, *n as i128, *d as i128),
+            (Self::Ratio(n, d), Self::UInt(u)) => Self::cmpRatio(*n as i128, *d as i128, *u as i128, 1),
+            (Self::Float(f0), Self::Float(f1)) => Self::cmpFloat(*f0, *f1),
+            (Self::Ratio(n0, d0), Self::Ratio(n1, d1)) => Self::cmpRatio(*n0 as i128, *d0 as i128, *n1 as i128, *d1 as i128),
+            (Self::Ratio(n, d), Self::Int(i)) => Self::cmpRatio(*n as i128, *d as i128, *i as i128, 1),
+            (Self::Int(i), Self::Ratio(n, d)) => Self::cmpRatio(*i as i128, 1, *n as i128, *d as i128),
+            (Self::Ratio(n, d), Self::Float(f)) => Self::cmpRatioFloat(*n, *d, *f),
+            (Self::Float(f), Self::Ratio(n, d)) => Self::cmpRatioFloat(*n, *d, *f).reverse(),
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b0), Self::BigInt(b1)) => b0.cmp(b1),
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b), Self::Int(_) | Self::UInt(_) | Self::Ratio(..)) => Self::cmpBigSmall(b),
+            #[cfg(feature = "bigint")]
+            (Self::Int(_) | Self::UInt(_) | Self::Ratio(..), Self::BigInt(b)) => Self::cmpBigSmall(b).reverse(),
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b), Self::Float(f)) => Self::cmpBigFloat(b, *f),
+            #[cfg(feature = "bigint")]
+            (Self::Float(f), Self::BigInt(b)) => Self::cmpBigFloat(b, *f).reverse(),
+            _ => return None
+        })
+    }
+
+    /// deterministic total order over all expressions (never panics, handles NaN)
+    pub fn totalCmp(&self, other: &Exp) -> Ordering {
+        if let Some(o) = self.cmpNumber(other) {
+            // same value: Int, UInt, Ratio, BigInt then Float
+            return o.then_with(|| self.numberRank().cmp(&other.numberRank()))
+        }
+        match (self, other) {
+            (Self::Bool(b0), Self::Bool(b1)) => b0.cmp(b1),
+            (Self::Char(c0), Self::Char(c1)) => c0.cmp(c1),
+            (Self::String(s0), Self::String(s1)) => s0.asArray().cmp(s1.asArray()),
+            (Self::Bytes(b0), Self::Bytes(b1)) => b0.asArray().cmp(b1.asArray()),
+            (Self::Symbol(s0), Self::Symbol(s1)) => s0.asArray().cmp(s1.asArray()),
+            (Self::Keyword(k0), Self::Keyword(k1)) => k0.asArray().cmp(k1.asArray()),
+            (Self::List(l0), Self::List(l1)) | (Self::Vector(l0), Self::Vector(l1)) => {
+                for i in 0..usize::min(l0.len(), l1.len()) {
+                    match l0[i].totalCmp(&l1[i]) {
+                        Ordering::Equal => (),
+                        o => return o
+                    }
+                }
+                l0.len().cmp(&l1.len())
+            },
+            (Self::Pair(a0, d0), Self::Pair(a1, d1)) => a0.totalCmp(a1).then_with(|| d0.totalCmp(d1)),
+            (Self::Spilled(s0), Self::Spilled(s1)) => Self::spilledBytes(s0).cmp(&Self::spilledBytes(s1)),
+            (Self::Map(m0), Self::Map(m1)) => {
+                let (s0, s1) = (Self::sortedEntries(m0.asArray()), Self::sortedEntries(m1.asArray()));
+                for (a, b) in s0.iter().zip(s1.iter()) {
+                    match a.0.totalCmp(&b.0).then_with(|| a.1.totalCmp(&b.1)) {
+                        Ordering::Equal => (),
+                        o => return o
+                    }
+                }
+                s0.len().cmp(&s1.len())
+            },
+            _ => self.rank().cmp(&other.rank())
+        }
+    }
+
+    /// structural equality with floats equal when within `eps` (NaNs are equal to each other)
+    pub fn approxEq(&self, other: &Exp, eps: f64) -> bool {
+        match (self, other) {
+            (Self::Float(f0), Self::Float(f1)) => f0 == f1 || (f0 - f1).abs() <= eps || (f0.is_nan() && f1.is_nan()),
+            (Self::List(s), Self::List(o)) | (Self::Vector(s), Self::Vector(o)) => {
+                if s.len() != o.len() { return false }
+                for i in 0..s.len() {
+                    if !s[i].approxEq(&o[i], eps) { return false }
+                }
+                true
+            },
+            (Self::Pair(a0, d0), Self::Pair(a1, d1)) => a0.approxEq(a1, eps) && d0.approxEq(d1, eps),
+            (Self::Map(s), Self::Map(o)) =>
+                s.len() == o.len() && s.asArray().iter().all(|(k, v)| match Self::mapGet(o.asArray(), k) { Some(w) => v.approxEq(w, eps), None => false }),
+            _ => self == other
+        }
+    }
+
+    // every top level form of `src`, None when it does not all read
+    fn readAll(src: &[u8]) -> Option<std::vec::Vec<Exp>> {
+        FormReader::new(src).map(|f| match f { PROk(e) => Some(e), PRErr(_) => None }).collect()
+    }
+}
+
+/// whether `a` and `b` read as the same forms whatever their spacing, comments and
+/// spelling (`#x10` and `16`), NaNs are equal. Text that does not read is only
+/// equal to the same text
+pub fn sexpTextEq(a: &str, b: &str) -> bool {
+    match (Exp::readAll(a.as_bytes()), Exp::readAll(b.as_bytes())) {
+        (Some(fa), Some(fb)) => fa.len() == fb.len() && fa.iter().zip(fb.iter()).all(|(x, y)| x.approxEq(y, 0.0)),
+        _ => a == b
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testApproxEq() {
+        let a = Exp::fromSExp(String::from("(a 0.3 (1 1e-9))").asArray());
+        let b = Exp::fromSExp(String::from("(a 0.30000000000000004 (1 1.1e-9))").asArray());
+        match (a, b) {
+            (PROk(a), PROk(b)) => {
+                assert!(a != b);
+                assert!(a.approxEq(&b, 1e-9));
+                assert!(!a.approxEq(&b, 1e-20));
+            },
+            _ => panic!("parse failed")
+        }
+
+        assert!(Exp::Float(f64::NAN).approxEq(&Exp::Float(f64::NAN), 0.0));
+        assert!(!Exp::Int(1).approxEq(&Exp::Float(1.0), 0.5));
+
+        assert!(sexpTextEq("(a  #x10 ; n\n  [b . c])  +nan.0", "(a 16 (b . c)) #| x |# +nan.0"));
+        assert!(!sexpTextEq("(a 1)", "(a 1) (b)") && !sexpTextEq("(a 1)", "(a 1.0)"));
+        assert!(sexpTextEq("(a", "(a") && !sexpTextEq("(a", "(a)"));
+    }
+
+    #[test]
+    fn testTotalCmp() {
+        let mut v = [Exp::Float(f64::NAN), Exp::Float(2.0), Exp::Symbol(alt_std::String::from("a")), Exp::Int(2),
+                     Exp::Float(1.5), Exp::Int(i64::MAX), Exp::Float(1e300), Exp::Bool(true), Exp::Int(-3)];
+        v.sort_by(|a, b| a.totalCmp(b));
+        let e = [Exp::Bool(true), Exp::Int(-3), Exp::Float(1.5), Exp::Int(2), Exp::Float(2.0),
+                 Exp::Int(i64::MAX), Exp::Float(1e300), Exp::Float(f64::NAN), Exp::Symbol(alt_std::String::from("a"))];
+        for i in 0..v.len() {
+            assert!(v[i].totalCmp(&e[i]) == Ordering::Equal);
+        }
+        assert!(Exp::Int(2).totalCmp(&Exp::Float(2.0)) == Ordering::Less);
+        assert!(Exp::Float(2.0).totalCmp(&Exp::Int(2)) == Ordering::Greater);
+        assert!(Exp::Int(2).totalCmp(&Exp::Float(2.5)) == Ordering::Less);
+        assert!(Exp::Int(-2).totalCmp(&Exp::Float(-2.5)) == Ordering::Greater);
+    }
+
+    #[test]
+    fn testTotalCmpExact() {
+        // through f64 the ratio was below the Int and above the Float
+        let (r, i, f) = (Exp::Ratio(5911568242833805857, 91630), Exp::Int(64515641633022), Exp::Float(64515641633022.0));
+        assert!(r.totalCmp(&i) == Ordering::Less && i.totalCmp(&f) == Ordering::Less && r.totalCmp(&f) == Ordering::Less);
+        assert!(f.totalCmp(&r) == Ordering::Greater);
+
+        let cases = [
+            (Exp::Ratio(1, 3), Exp::Float(0.3333333333333333), Ordering::Greater),
+            (Exp::Ratio(1, 2), Exp::Float(0.5), Ordering::Less),
+            (Exp::Ratio(-1, 2), Exp::Float(-0.5), Ordering::Less),
+            (Exp::Ratio(-1, 3), Exp::Float(-0.3333333333333333), Ordering::Less),
+            (Exp::Ratio(1, 3), Exp::Float(1e-300), Ordering::Greater),
+            (Exp::Ratio(-1, 3), Exp::Float(-1e-300), Ordering::Less),
+            (Exp::Ratio(1, 3), Exp::Float(f64::NAN), Ordering::Less),
+            (Exp::Ratio(7, 2), Exp::Int(3), Ordering::Greater),
+            (Exp::Ratio(7, 2), Exp::UInt(4), Ordering::Less),
+            (Exp::UInt(u64::MAX), Exp::Int(i64::MAX), Ordering::Greater),
+            (Exp::UInt(u64::MAX), Exp::Float(18446744073709551615.0), Ordering::Less),
+            (Exp::UInt(5), Exp::Int(7), Ordering::Less),
+            (Exp::UInt(5), Exp::Int(5), Ordering::Greater),
+            (Exp::UInt(9007199254740993), Exp::Float(9007199254740992.0), Ordering::Greater),
+            (Exp::UInt(9007199254740993), Exp::Ratio(18014398509481987, 2), Ordering::Less),
+        ];
+        for (a, b, o) in cases.iter() {
+            assert!(a.totalCmp(b) == *o && b.totalCmp(a) == o.reverse(), "{} {}", a.toString().toStr(), b.toString().toStr());
+        }
+
+        // sorting mixed numbers stays consistent with every pairwise comparison
+        let mut v = std::vec![r.clone(), i.clone(), f.clone(), Exp::UInt(64515641633022), Exp::Ratio(129031283266045, 2), Exp::Float(64515641633022.5)];
+        v.sort_by(|a, b| a.totalCmp(b));
+        for x in 0..v.len() {
+            for y in x + 1..v.len() { assert!(v[x].totalCmp(&v[y]) == Ordering::Less) }
+        }
+    }
+}
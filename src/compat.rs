@@ -0,0 +1,195 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// schema evolution: `compatibleWith` lists what a new version of a schema breaks
+// for documents written against the old one. Adding optional fields and widening
+// types (int to float, a member added to an or) is fine, removing a field, making
+// one required or narrowing a type is not
+//
+use alt_std::*;
+use crate::*;
+
+// the positional and variadic parameters of a form
+type Params<'a> = (&'a Vec<Field>, &'a Option<std::boxed::Box<Field>>);
+
+#[derive(Clone, PartialEq)]
+pub enum Change {
+    FieldRemoved(String),
+    // a required field the old documents do not have
+    FieldAdded(String),
+    // was optional or had a default
+    FieldRequired(String),
+    // the new type does not accept every value of the old one
+    TypeChanged(Schema, Schema),
+}
+
+#[derive(Clone)]
+pub struct BreakingChange {
+    // the record or map holding the field, the value for type changes
+    pub path    : Path,
+    pub change  : Change,
+}
+
+impl BreakingChange {
+    pub fn toString(&self) -> String {
+        let path = self.path.toString();
+        match &self.change {
+            Change::FieldRemoved(f) => alt_std::format!("{}: field {} removed", path.toStr(), f.toStr()),
+            Change::FieldAdded(f) => alt_std::format!("{}: required field {} added", path.toStr(), f.toStr()),
+            Change::FieldRequired(f) => alt_std::format!("{}: field {} is now required", path.toStr(), f.toStr()),
+            Change::TypeChanged(old, new) => alt_std::format!("{}: {} changed to {}", path.toStr(),
+                old.toExp().toString().toStr(), new.toExp().toString().toStr()),
+        }
+    }
+}
+
+impl Schema {
+    fn fieldChanges(new: &Vec<Field>, old: &Vec<Field>, path: &Path, out: &mut Vec<BreakingChange>) {
+        for o in old.asArray().iter() {
+            match new.asArray().iter().find(|n| n.name == o.name) {
+                None => out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldRemoved(o.name.clone()) }),
+                Some(n) => {
+                    if n.required() && !o.required() {
+                        out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldRequired(o.name.clone()) });
+                    }
+                    n.schema.changes(&o.schema, &path.child(PathSeg::Key(o.name.clone())), out);
+                }
+            }
+        }
+        for n in new.asArray().iter() {
+            if n.required() && !old.asArray().iter().any(|o| o.name == n.name) {
+                out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldAdded(n.name.clone()) });
+            }
+        }
+    }
+
+    // parameters are matched by position, an old argument the new form has no
+    // parameter for must fit its variadic tail
+    fn paramChanges(new: Params, old: Params, skip: usize, path: &Path, out: &mut Vec<BreakingChange>) {
+        let (np, nr) = new;
+        let (op, or) = old;
+        for (i, o) in op.asArray().iter().enumerate() {
+            let at = path.child(PathSeg::Index(i + skip));
+            match (np.asArray().get(i), nr) {
+                (Some(n), _) => {
+                    if n.required() && !o.required() {
+                        out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldRequired(o.name.clone()) });
+                    }
+                    n.schema.changes(&o.schema, &at, out);
+                },
+                (None, Some(r)) => r.schema.changes(&o.schema, &at, out),
+                (None, None) => out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldRemoved(o.name.clone()) }),
+            }
+        }
+        for (i, n) in np.asArray().iter().enumerate().skip(op.len()) {
+            match or {
+                Some(r) => n.schema.changes(&r.schema, &path.child(PathSeg::Index(i + skip)), out),
+                None if n.required() => out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldAdded(n.name.clone()) }),
+                None => (),
+            }
+        }
+        match (nr, or) {
+            (Some(n), Some(o)) => n.schema.changes(&o.schema, &path.child(PathSeg::Index(np.len().max(op.len()) + skip)), out),
+            (None, Some(o)) => out.pushBack(BreakingChange { path: path.clone(), change: Change::FieldRemoved(o.name.clone()) }),
+            _ => ()
+        }
+    }
+
+    fn accepts(&self, old: &Schema) -> bool {
+        let mut out = Vec::new();
+        self.changes(old, &Path::new(), &mut out);
+        out.len() == 0
+    }
+
+    fn changes(&self, old: &Schema, path: &Path, out: &mut Vec<BreakingChange>) {
+        match (self, old) {
+            (Self::Any, _) => (),
+            (n, o) if n.numberRank().is_some() && o.numberRank().is_some_and(|r| Some(r) <= n.numberRank()) => (),
+            (Self::List(n), Self::List(o)) | (Self::Vector(n), Self::Vector(o)) => n.changes(o, path, out),
+            (Self::Pair(a0, d0), Self::Pair(a1, d1)) => {
+                a0.changes(a1, &path.child(PathSeg::Index(0)), out);
+                d0.changes(d1, &path.child(PathSeg::Index(1)), out);
+            },
+            (Self::Record(t0, n), Self::Record(t1, o)) if t0 == t1 => Self::fieldChanges(n, o, path, out),
+            (Self::Map(n), Self::Map(o)) => Self::fieldChanges(n, o, path, out),
+            (Self::Form(h0, np, nr), Self::Form(h1, op, or)) if h0 == h1 =>
+                Self::paramChanges((np, nr), (op, or), h0.is_some() as usize, path, out),
+            (Self::MapOf(k0, v0), Self::MapOf(k1, v1)) => {
+                k0.changes(k1, path, out);
+                v0.changes(v1, path, out);
+            },
+            // every old member must still be accepted
+            (n, Self::Or(o)) if o.asArray().iter().all(|s| n.accepts(s)) => (),
+            (Self::Or(n), o) if !matches!(o, Self::Or(_)) => match n.asArray().iter().find(|s| s.sameKind(o)) {
+                Some(s) => s.changes(o, path, out),
+                None if n.asArray().iter().any(|s| s.accepts(o)) => (),
+                None => out.pushBack(BreakingChange { path: path.clone(), change: Change::TypeChanged(old.clone(), self.clone()) }),
+            },
+            (n, o) if n.atomName().is_some() && n == o => (),
+            _ => out.pushBack(BreakingChange { path: path.clone(), change: Change::TypeChanged(old.clone(), self.clone()) }),
+        }
+    }
+
+    /// what this schema breaks for documents valid under `old`, empty when compatible
+    pub fn compatibleWith(&self, old: &Schema) -> Vec<BreakingChange> {
+        let mut out = Vec::new();
+        self.changes(old, &Path::new(), &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema(s: &str) -> Schema {
+        match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => Schema::fromExp(&e).ok().unwrap(),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testCompatibleWith() {
+        let v1 = schema("(record server (port int) (host string optional) (mode symbol) (tags (list symbol)) (limits (map (rps int))))");
+        let v2 = schema("(record server (port float) (host string optional) (mode (or symbol int)) (tags (list symbol)) \
+                         (limits (map (rps int) (burst int (default 1)))) (tls bool optional))");
+        assert!(v2.compatibleWith(&v1).len() == 0);
+
+        let v3 = schema("(record server (port int) (host string) (tags (list string)) (limits (map (rps int) (burst int))) (id int))");
+        let changes : std::vec::Vec<std::string::String> = v3.compatibleWith(&v2).asArray().iter().map(|c| c.toString().toStr().to_string()).collect();
+        assert!(changes == [
+            "port: float changed to int",
+            ": field host is now required",
+            ": field mode removed",
+            "tags: symbol changed to string",
+            "limits: field burst is now required",
+            ": field tls removed",
+            ": required field id added",
+        ]);
+        assert!(schema("(record client (port int))").compatibleWith(&schema("(record server (port int))")).len() == 1);
+
+        let f1 = schema("(form define (name symbol) (value int) (body any ...))");
+        assert!(schema("(form define (name symbol) (value float) (body any ...))").compatibleWith(&f1).len() == 0);
+        let changes : std::vec::Vec<std::string::String> = schema("(form define (name symbol) (value int) (doc string))")
+            .compatibleWith(&f1).asArray().iter().map(|c| c.toString().toStr().to_string()).collect();
+        assert!(changes == ["3: any changed to string", ": field body removed"]);
+    }
+}
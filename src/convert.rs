@@ -0,0 +1,242 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// conversions between expressions and rust values, as used by the bindings
+// `Schema::toRust` generates. `Sym` and `Kw` keep symbols and keywords apart
+// from strings.
+//
+use alt_std::*;
+use crate::*;
+
+pub trait ToExp {
+    fn toExp(&self) -> Exp;
+    // absent values (None) are left out of records
+    fn isAbsent(&self) -> bool { false }
+}
+
+pub trait FromExp: Sized {
+    fn fromExp(e: &Exp) -> Result<Self, String>;
+    // what a missing record field reads as, None when it is required
+    fn missing() -> Option<Self> { None }
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Sym(pub String);
+
+#[derive(Clone, PartialEq)]
+pub struct Kw(pub String);
+
+fn expected(what: &str, e: &Exp) -> String {
+    alt_std::format!("expected {}, found {}", what, e.toString().toStr())
+}
+
+macro_rules! convert {
+    ($t:ty, $name:expr, $variant:ident, $to:expr, $from:expr) => {
+        impl ToExp for $t {
+            fn toExp(&self) -> Exp { Exp::$variant($to(self)) }
+        }
+
+        impl FromExp for $t {
+            fn fromExp(e: &Exp) -> Result<Self, String> {
+                match e {
+                    Exp::$variant(v) => Ok($from(v)),
+                    e => Err(expected($name, e))
+                }
+            }
+        }
+    }
+}
+
+convert!(bool, "bool", Bool, |b: &bool| *b, |b: &bool| *b);
+convert!(char, "char", Char, |c: &char| *c, |c: &char| *c);
+convert!(i64, "int", Int, |i: &i64| *i, |i: &i64| *i);
+convert!(String, "string", String, |s: &String| s.clone(), |s: &String| s.clone());
+convert!(Sym, "symbol", Symbol, |s: &Sym| s.0.clone(), |s: &String| Sym(s.clone()));
+convert!(Kw, "keyword", Keyword, |k: &Kw| k.0.clone(), |k: &String| Kw(k.clone()));
+
+impl ToExp for u64 {
+    fn toExp(&self) -> Exp { Exp::uint(*self) }
+}
+
+impl FromExp for u64 {
+    fn fromExp(e: &Exp) -> Result<Self, String> {
+        match e {
+            Exp::Int(i) if *i >= 0 => Ok(*i as u64),
+            Exp::UInt(u) => Ok(*u),
+            e => Err(expected("unsigned int", e))
+        }
+    }
+}
+
+impl ToExp for f64 {
+    fn toExp(&self) -> Exp { Exp::Float(*self) }
+}
+
+impl FromExp for f64 {
+    fn fromExp(e: &Exp) -> Result<Self, String> {
+        match e {
+            Exp::Float(f) => Ok(*f),
+            Exp::Int(i) => Ok(*i as f64),
+            Exp::UInt(u) => Ok(*u as f64),
+            Exp::Ratio(n, d) => Ok(*n as f64 / *d as f64),
+            #[cfg(feature = "bigint")]
+            Exp::BigInt(b) => Ok(b.toF64()),
+            e => Err(expected("float", e))
+        }
+    }
+}
+
+impl ToExp for Exp {
+    fn toExp(&self) -> Exp { self.clone() }
+}
+
+impl FromExp for Exp {
+    fn fromExp(e: &Exp) -> Result<Self, String> { Ok(e.clone()) }
+}
+
+impl<T: ToExp> ToExp for Vec<T> {
+    fn toExp(&self) -> Exp {
+        let mut v = Vec::new();
+        for e in self.asArray().iter() { v.pushBack(e.toExp()) }
+        Exp::List(v)
+    }
+}
+
+impl<T: FromExp> FromExp for Vec<T> {
+    fn fromExp(e: &Exp) -> Result<Self, String> {
+        let l = match e { Exp::List(l) => l, e => return Err(expected("list", e)) };
+        let mut v = Vec::new();
+        for e in l.asArray().iter() { v.pushBack(T::fromExp(e)?) }
+        Ok(v)
+    }
+}
+
+impl<T: ToExp> ToExp for Option<T> {
+    fn toExp(&self) -> Exp {
+        match self {
+            Some(v) => v.toExp(),
+            None => Exp::List(Vec::new()),
+        }
+    }
+    fn isAbsent(&self) -> bool { self.is_none() }
+}
+
+impl<T: FromExp> FromExp for Option<T> {
+    fn fromExp(e: &Exp) -> Result<Self, String> { Ok(Some(T::fromExp(e)?)) }
+    fn missing() -> Option<Self> { Some(None) }
+}
+
+impl Exp {
+    /// the `(key value...)` entries of a record, after its head symbol when `tag` is given
+    pub fn recordEntries(&self, tag: Option<&str>) -> Result<&[Exp], String> {
+        let l = match self { Self::List(l) => l.asArray(), e => return Err(expected("record", e)) };
+        match (tag, l.first()) {
+            (None, _) => Ok(l),
+            (Some(t), Some(Self::Symbol(h))) if h.toStr() == t => Ok(&l[1..]),
+            (Some(t), _) => Err(alt_std::format!("expected ({} ...), found {}", t, self.toString().toStr())),
+        }
+    }
+
+    /// reads the field `name` of record entries, `spread` for list and record
+    /// values whose elements follow the key (`(tags a b)`)
+    pub fn recordField<T: FromExp>(entries: &[Exp], name: &str, spread: bool) -> Result<T, String> {
+        match entries.iter().find(|e| e.entryKey().map(|k| k.toStr()) == Some(name)) {
+            Some(e) => T::fromExp(&if spread { e.fieldItems() } else { e.fieldValue() })
+                .map_err(|m| alt_std::format!("{}: {}", name, m.toStr())),
+            None => T::missing().ok_or_else(|| alt_std::format!("missing field {}", name)),
+        }
+    }
+
+    /// `recordField` reading a missing field as `default`
+    pub fn recordFieldOr<T: FromExp>(entries: &[Exp], name: &str, spread: bool, default: &Exp) -> Result<T, String> {
+        match entries.iter().any(|e| e.entryKey().map(|k| k.toStr()) == Some(name)) {
+            true => Self::recordField(entries, name, spread),
+            false => T::fromExp(default).map_err(|m| alt_std::format!("{}: {}", name, m.toStr())),
+        }
+    }
+
+    /// appends the `(name value)` entry, or `(name elements...)` when `spread`
+    pub fn pushRecordField<T: ToExp>(entries: &mut Vec<Exp>, name: &str, value: &T, spread: bool) {
+        if value.isAbsent() { return }
+        let mut e = Vec::new();
+        e.pushBack(Self::Symbol(String::from(name)));
+        match value.toExp() {
+            Self::List(l) if spread => for x in l.asArray().iter() { e.pushBack(x.clone()) },
+            v => e.pushBack(v),
+        }
+        entries.pushBack(Self::List(e));
+    }
+
+    /// reads the `name` key of a map
+    pub fn mapField<T: FromExp>(&self, name: &str) -> Result<T, String> {
+        match self {
+            Self::Map(_) => match self.lookup(&Self::Symbol(String::from(name))) {
+                Some(v) => T::fromExp(v).map_err(|m| alt_std::format!("{}: {}", name, m.toStr())),
+                None => T::missing().ok_or_else(|| alt_std::format!("missing field {}", name)),
+            },
+            e => Err(expected("map", e))
+        }
+    }
+
+    /// `mapField` reading a missing key as `default`
+    pub fn mapFieldOr<T: FromExp>(&self, name: &str, default: &Exp) -> Result<T, String> {
+        match self.lookup(&Self::Symbol(String::from(name))) {
+            None if matches!(self, Self::Map(_)) => T::fromExp(default).map_err(|m| alt_std::format!("{}: {}", name, m.toStr())),
+            _ => self.mapField(name),
+        }
+    }
+
+    /// sets the `name` key of a map, absent values are left out
+    pub fn setMapField<T: ToExp>(&mut self, name: &str, value: &T) {
+        if !value.isAbsent() {
+            self.insert(Self::Symbol(String::from(name)), value.toExp());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testRecordFields() {
+        let e = match Exp::fromSExp(b"(server (port 80) (tags web) (limits {rps 2.5}))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let entries = e.recordEntries(Some("server")).ok().unwrap();
+        let port : i64 = Exp::recordField(entries, "port", false).ok().unwrap();
+        let tags : Vec<Sym> = Exp::recordField(entries, "tags", true).ok().unwrap();
+        let host : Option<String> = Exp::recordField(entries, "host", false).ok().unwrap();
+        let limits : Exp = Exp::recordField(entries, "limits", false).ok().unwrap();
+        assert!(port == 80 && tags.len() == 1 && tags[0] == Sym(String::from("web")) && host.is_none());
+        assert!(limits.mapField::<f64>("rps").ok() == Some(2.5));
+        assert!(Exp::recordField::<bool>(entries, "port", false).err().unwrap() == "port: expected bool, found 80");
+        assert!(Exp::recordField::<bool>(entries, "tls", false).err().unwrap() == "missing field tls");
+
+        let mut v = Vec::new();
+        v.pushBack(Exp::Symbol(String::from("server")));
+        Exp::pushRecordField(&mut v, "port", &port, false);
+        Exp::pushRecordField(&mut v, "tags", &tags, true);
+        Exp::pushRecordField(&mut v, "host", &host, false);
+        Exp::pushRecordField(&mut v, "limits", &limits, false);
+        assert!(Exp::List(v) == e);
+    }
+}
@@ -0,0 +1,161 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+pub enum CsvField {
+    // n-th element of the record, key i at 2i and its value at 2i + 1 in a map record
+    Index(usize),
+    // second element of the `(key value)` or `(:key value)` entry of the record,
+    // the value of the `key` or `:key` key in a map record
+    Key(String),
+}
+
+pub struct CsvColumn {
+    pub header  : String,
+    pub field   : CsvField,
+}
+
+pub struct CsvSpec {
+    pub separator   : u8,
+    // write the column headers as the first row
+    pub header      : bool,
+    pub columns     : Vec<CsvColumn>,
+}
+
+impl CsvSpec {
+    pub fn csv() -> Self {
+        Self { separator: b',', header: true, columns: Vec::new() }
+    }
+
+    pub fn tsv() -> Self {
+        Self { separator: b'\t', header: true, columns: Vec::new() }
+    }
+
+    pub fn addColumn(&mut self, header: &str, field: CsvField) {
+        self.columns.pushBack(CsvColumn { header: String::from(header), field });
+    }
+}
+
+impl Exp {
+    fn csvCell(out: &mut String, s: &[u8], sep: u8) {
+        let quote = s.iter().any(|c| *c == sep || *c == b'"' || *c == b'\n' || *c == b'\r');
+        if !quote {
+            for c in s.iter() { out.add(*c) }
+            return
+        }
+
+        out.add(b'"');
+        for c in s.iter() {
+            if *c == b'"' { out.add(b'"') }
+            out.add(*c);
+        }
+        out.add(b'"');
+    }
+
+    pub(crate) fn isCsvRecord(&self) -> bool {
+        matches!(self, Self::List(_) | Self::Map(_))
+    }
+
+    pub(crate) fn csvField<'a>(record: &'a Exp, field: &CsvField) -> Option<&'a Exp> {
+        let isKey = |e: &Exp, k: &String| matches!(e, Self::Symbol(s) | Self::Keyword(s) if s == k);
+        match (record, field) {
+            (_, CsvField::Index(i)) => record.child(*i),
+            (Self::List(l), CsvField::Key(k)) => l.asArray().iter().find_map(|e| match e {
+                Self::List(kv) if kv.len() >= 2 && isKey(&kv[0], k) => Some(&kv[1]),
+                _ => None
+            }),
+            (Self::Map(m), CsvField::Key(k)) => m.asArray().iter().find(|(key, _)| isKey(key, k)).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    /// one row per record of this list of lists or maps, `None` if it is not one
+    /// strings and symbols are written raw, missing fields as empty cells
+    pub fn toCsv(&self, spec: &CsvSpec) -> Option<String> {
+        let records = match self {
+            Self::List(l) => l,
+            _ => return None
+        };
+
+        let mut out = String::new();
+        let cols = spec.columns.asArray();
+        if spec.header {
+            for i in 0..cols.len() {
+                if i != 0 { out.add(spec.separator) }
+                Self::csvCell(&mut out, cols[i].header.asArray(), spec.separator);
+            }
+            out.add(b'\n');
+        }
+
+        for r in records.asArray().iter() {
+            if !r.isCsvRecord() { return None }
+            for i in 0..cols.len() {
+                if i != 0 { out.add(spec.separator) }
+                match Self::csvField(r, &cols[i].field) {
+                    Some(Self::String(s)) | Some(Self::Symbol(s)) => Self::csvCell(&mut out, s.asArray(), spec.separator),
+                    Some(e) => Self::csvCell(&mut out, e.toString().asArray(), spec.separator),
+                    None => ()
+                }
+            }
+            out.add(b'\n');
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToCsv() {
+        let sexp = String::from("((row (name \"a, b\") (age 3)) (row (age 4.5) (name x)) (row (name \"q\\\"\")))");
+        let mut spec = CsvSpec::csv();
+        spec.addColumn("name", CsvField::Key(String::from("name")));
+        spec.addColumn("age", CsvField::Key(String::from("age")));
+        spec.addColumn("kind", CsvField::Index(0));
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => {
+                let s = r.toCsv(&spec).unwrap();
+                assert!(s == "name,age,kind\n\"a, b\",3,row\nx,4.5,row\n\"q\"\"\",,row\n");
+                spec.separator = b'\t';
+                spec.header = false;
+                let s = r.toCsv(&spec).unwrap();
+                assert!(s == "a, b\t3\trow\nx\t4.5\trow\n\"q\"\"\"\t\trow\n");
+                assert!(Exp::Int(1).toCsv(&spec).is_none());
+                assert!(matches!(Exp::fromSExp(b"((a 1) 2)"), PROk(e) if e.toCsv(&spec).is_none()));
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testToCsvKeywordsAndMaps() {
+        let mut spec = CsvSpec::csv();
+        spec.addColumn("name", CsvField::Key(String::from("name")));
+        spec.addColumn("age", CsvField::Key(String::from("age")));
+        spec.addColumn("first", CsvField::Index(1));
+        match Exp::fromSExp(b"((row (:name a) (:age 3)) {name b age 4} {:age 5 :name c})") {
+            PROk(r) => assert!(r.toCsv(&spec).unwrap() == "name,age,first\na,3,(:name a)\nb,4,b\nc,5,5\n"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
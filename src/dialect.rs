@@ -0,0 +1,283 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// dialects: the reader and the printer agree on how booleans and characters
+// are spelled, a `Document` remembers the dialect it was read in so that it is
+// written back in the same one (and the text it was read from, see spans)
+//
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Dialect {
+    // #t #f #\a
+    Scheme,
+    // t nil #\a
+    CommonLisp,
+    // true false \a
+    Clojure,
+}
+
+impl Dialect {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Scheme => "scheme",
+            Self::CommonLisp => "common-lisp",
+            Self::Clojure => "clojure",
+        }
+    }
+
+    pub fn booleanName(&self, b: bool) -> &'static str {
+        match (self, b) {
+            (Self::Scheme, true) => "#t",
+            (Self::Scheme, false) => "#f",
+            (Self::CommonLisp, true) => "t",
+            (Self::CommonLisp, false) => "nil",
+            (Self::Clojure, true) => "true",
+            (Self::Clojure, false) => "false",
+        }
+    }
+
+    // the dialect specific boolean a symbol stands for (#t/#f are always read)
+    pub(crate) fn boolean(&self, s: &str) -> Option<bool> {
+        match *self {
+            Self::Scheme => None,
+            _ if s == self.booleanName(true) => Some(true),
+            _ if s == self.booleanName(false) => Some(false),
+            _ => None
+        }
+    }
+
+    pub fn charPrefix(&self) -> &'static str {
+        match self {
+            Self::Clojure => "\\",
+            _ => "#\\",
+        }
+    }
+
+    // between the namespace and the name of a qualified symbol
+    pub fn namespaceSeparator(&self) -> &'static str {
+        match self {
+            Self::CommonLisp => ":",
+            _ => "/",
+        }
+    }
+
+    pub fn readerOptions(&self) -> ReaderOptions {
+        let mut opts = ReaderOptions::new();
+        opts.dialect = *self;
+        opts
+    }
+
+    pub fn printOptions(&self) -> PrintOptions {
+        let mut opts = PrintOptions::new();
+        opts.dialect = *self;
+        opts
+    }
+}
+
+pub struct DialectGuess {
+    pub dialect     : Dialect,
+    // share of the evidence pointing at `dialect`, 0 when there was none
+    pub confidence  : f64,
+    // the markers that were seen, once each
+    pub evidence    : Vec<String>,
+}
+
+// marker, scheme, common lisp, clojure
+const MARKERS : [(&str, usize, usize, usize); 14] = [
+    ("#t", 2, 0, 0), ("#f", 2, 0, 0), ("#\\", 1, 1, 0), ("#|", 1, 1, 0), ("#'", 0, 2, 0),
+    (":keyword", 0, 1, 1), ("[]", 0, 0, 2), ("{}", 0, 0, 2), ("\\char", 0, 0, 2),
+    ("true/false", 0, 0, 1), ("nil", 0, 1, 1), ("t", 0, 1, 0),
+    ("define", 1, 0, 0), ("defun", 0, 2, 0),
+];
+
+fn marker(tok: &[u8]) -> Option<&'static str> {
+    match tok {
+        b"#t" | b"#f" | b"#true" | b"#false" => Some(if tok[1] == b't' { "#t" } else { "#f" }),
+        [b'#', b'\\', ..] => Some("#\\"),
+        [b'#', b'|', ..] => Some("#|"),
+        [b'#', b'\'', ..] => Some("#'"),
+        [b':', _, ..] => Some(":keyword"),
+        [b'\\', _, ..] => Some("\\char"),
+        b"true" | b"false" => Some("true/false"),
+        b"nil" => Some("nil"),
+        b"t" => Some("t"),
+        b"define" | b"lambda" | b"let*" => Some("define"),
+        b"defun" | b"defvar" | b"defparameter" | b"setf" => Some("defun"),
+        _ => None
+    }
+}
+
+impl Dialect {
+    /// best guess from cheap lexical markers, Scheme when nothing stands out
+    pub fn detect(src: &[u8]) -> DialectGuess {
+        let mut seen : Vec<&'static str> = Vec::new();
+        let mut note = |m: &'static str| if !seen.asArray().contains(&m) { seen.pushBack(m) };
+        let mut i = 0;
+        while i < src.len() {
+            match src[i] {
+                b'"' => {
+                    i += 1;
+                    while i < src.len() && src[i] != b'"' { i += if src[i] == b'\\' { 2 } else { 1 } }
+                    i += 1;
+                },
+                b';' => while i < src.len() && src[i] != b'\n' { i += 1 },
+                b'[' | b']' => { note("[]"); i += 1 },
+                b'{' | b'}' => { note("{}"); i += 1 },
+                c if Exp::isWS(c) || c == b'(' || c == b')' => i += 1,
+                _ => {
+                    let start = i;
+                    // keep #\( and #\) inside their token
+                    if src[i..].starts_with(b"#\\") { i = usize::min(i + 3, src.len()) }
+                    while i < src.len() && !Exp::isWS(src[i]) && !b"()[]{}\";".contains(&src[i]) { i += 1 }
+                    if let Some(m) = marker(&src[start..i]) { note(m) }
+                    i = usize::max(i, start + 1);
+                }
+            }
+        }
+
+        let mut scores = [0usize; 3];
+        for (m, s, cl, clj) in MARKERS.iter() {
+            if seen.asArray().contains(m) {
+                scores[0] += s;
+                scores[1] += cl;
+                scores[2] += clj;
+            }
+        }
+        let best = (0..3).fold(0, |b, i| if scores[i] > scores[b] { i } else { b });
+        let total : usize = scores.iter().sum();
+        let mut evidence = Vec::new();
+        for m in seen.asArray().iter() { evidence.pushBack(String::from(m)) }
+        DialectGuess {
+            dialect     : [Self::Scheme, Self::CommonLisp, Self::Clojure][best],
+            confidence  : if total == 0 { 0.0 } else { scores[best] as f64 / total as f64 },
+            evidence,
+        }
+    }
+}
+
+/// an expression together with the dialect it was read in
+pub struct Document {
+    pub(crate) root     : Exp,
+    dialect             : Dialect,
+    // the text read and where each node of `root` is in it
+    pub(crate) src      : std::vec::Vec<u8>,
+    pub(crate) spans    : Spans,
+}
+
+impl Document {
+    pub fn new(root: Exp, dialect: Dialect) -> Self { Self { root, dialect, src: std::vec::Vec::new(), spans: Spans::new() } }
+
+    pub fn parse(src: &[u8], opts: &ReaderOptions) -> ParseResult<Document> {
+        match Exp::fromSExpWithOptions(src, opts) {
+            PROk(root) => {
+                let mut spans = Spans::new();
+                if let Some(start) = Exp::wsEnd(src, Exp::documentStart(src, opts), true) {
+                    root.collectSpans(src, start, &mut spans);
+                }
+                // the root moves with the document, it goes by 0
+                if let Some(span) = spans.remove(&(&root as *const Exp as usize)) { spans.insert(0, span); }
+                PROk(Self { root, dialect: opts.dialect, src: src.to_vec(), spans })
+            },
+            PRErr(err) => PRErr(err)
+        }
+    }
+
+    pub fn root(&self) -> &Exp { &self.root }
+    /// forgets the source spans, the tree may no longer match the text
+    pub fn rootMut(&mut self) -> &mut Exp { self.spans.clear(); &mut self.root }
+    pub fn intoRoot(self) -> Exp { self.root }
+    pub fn dialect(&self) -> Dialect { self.dialect }
+
+    /// written in the document dialect
+    pub fn toString(&self) -> String {
+        self.root.toStringWithOptions(&self.dialect.printOptions())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testDialects() {
+        let clj = Dialect::Clojure.readerOptions();
+        match Document::parse(b"(a true \\x \\space false)", &clj) {
+            PROk(d) => {
+                assert!(d.toString().toStr() == "(a true \\x \\space false)");
+                assert!(d.root().toString().toStr() == "(a #t #\\x #\\space #f)");
+                assert!(d.root().toStringWithOptions(&Dialect::CommonLisp.printOptions()).toStr() == "(a t #\\x #\\space nil)");
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        match Document::parse(b"(t nil true #t)", &Dialect::CommonLisp.readerOptions()) {
+            PROk(d) => assert!(d.toString().toStr() == "(t nil true t)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(match Exp::fromSExp(b"(true \\x)") { PROk(e) => e.toString().toStr() == "(true \\x)", _ => false });
+    }
+
+    #[test]
+    fn testDialectSymbols() {
+        // symbols spelled like another dialect's booleans and chars
+        let e = match Exp::fromSExp(b"(true false \\x \\space t nil #t)") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        for (d, printed) in [
+            (Dialect::Scheme, "(true false \\x \\space t nil #t)"),
+            (Dialect::CommonLisp, "(true false \\x \\space |t| |nil| t)"),
+            (Dialect::Clojure, "(|true| |false| |\\\\x| |\\\\space| t nil true)"),
+        ].iter() {
+            let p = d.printOptions();
+            let s = e.toStringWithOptions(&p);
+            assert!(s.toStr() == *printed, "{}", s.toStr());
+            assert!(e.serializedLen(&p) == s.asArray().len());
+            assert!(match Exp::fromSExpWithOptions(s.asArray(), &d.readerOptions()) { PROk(r) => r == e, _ => false });
+        }
+    }
+
+    #[test]
+    fn testClojureBackslashSymbol() {
+        let mut l = Vec::new();
+        for s in ["\\", "a", "\\"].iter() { l.pushBack(Exp::Symbol(String::from(s))) }
+        let e = Exp::List(l);
+        for d in [Dialect::Scheme, Dialect::CommonLisp, Dialect::Clojure].iter() {
+            let s = e.toStringWithOptions(&d.printOptions());
+            assert!(match Exp::fromSExpWithOptions(s.asArray(), &d.readerOptions()) { PROk(r) => r == e, _ => false }, "{}", s.toStr());
+        }
+        assert!(e.toStringWithOptions(&Dialect::Clojure.printOptions()).toStr() == "(|\\\\| a |\\\\|)");
+    }
+
+    #[test]
+    fn testDetectDialect() {
+        let g = Dialect::detect(b"(define (f x) (if x #t #f)) ; [not] :code");
+        assert!(g.dialect == Dialect::Scheme && g.confidence == 1.0);
+        let g = Dialect::detect(b"(defun f (x) (when x t)) #'f (f nil)");
+        assert!(g.dialect == Dialect::CommonLisp && g.evidence.len() == 4);
+        let g = Dialect::detect(b"(defn f [x] {:a true, :b \\c})");
+        assert!(g.dialect == Dialect::Clojure);
+        assert!(g.evidence.asArray().iter().any(|e| e.toStr() == ":keyword"));
+        assert!(Dialect::detect(b"(a #\\").evidence.len() == 1);
+        let g = Dialect::detect(b"(a \"#t\" b)");
+        assert!(g.dialect == Dialect::Scheme && g.confidence == 0.0);
+    }
+}
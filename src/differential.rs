@@ -0,0 +1,109 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// differential testing: parse a shared corpus with this crate and with lexpr
+// and report every input on which they disagree
+//
+use alt_std::*;
+use alt_std::{format};
+use crate::*;
+
+// inputs exercising the places where dialects usually differ
+const DIALECT : [&str; 12] = [
+    "()",
+    "(1. -0.5 +7)",
+    "\"a b\"",
+    "#t",
+    "'x",
+    "(a . b)",
+    "(a ; comment\n b)",
+    "[a b]",
+    "#\\a",
+    "#:kw",
+    "#(1 (a) #(b))",
+    "#u8(0 16 255)",
+];
+
+fn agrees(e: &Exp, v: &lexpr::Value) -> bool {
+    match (e, v) {
+        (Exp::Bool(b), lexpr::Value::Bool(o)) => b == o,
+        (Exp::Char(c), lexpr::Value::Char(o)) => c == o,
+        (Exp::Int(i), v) => v.as_i64() == Some(*i),
+        (Exp::Float(f), v) => v.is_f64() && v.as_f64() == Some(*f),
+        (Exp::String(s), lexpr::Value::String(o)) => s.toStr() == &**o,
+        (Exp::Symbol(s), lexpr::Value::Symbol(o)) => s.toStr() == &**o,
+        (Exp::Keyword(k), lexpr::Value::Keyword(o)) => k.toStr() == &**o,
+        (Exp::Bytes(b), lexpr::Value::Bytes(o)) => b.asArray() == &**o,
+        (Exp::Vector(l), lexpr::Value::Vector(o)) => l.len() == o.len() && (0..l.len()).all(|i| agrees(&l[i], &o[i])),
+        (Exp::Pair(a, d), lexpr::Value::Cons(c)) => agrees(a, c.car()) && agrees(d, c.cdr()),
+        (Exp::List(l), v) if v.is_list() || v.is_null() => {
+            let items : std::vec::Vec<&lexpr::Value> = match v.list_iter() {
+                Some(it) => it.collect(),
+                None => std::vec::Vec::new(),
+            };
+            if items.len() != l.len() { return false }
+            for i in 0..l.len() {
+                if !agrees(&l[i], items[i]) { return false }
+            }
+            true
+        },
+        _ => false
+    }
+}
+
+/// inputs on which this parser and lexpr disagree
+pub fn divergences(corpus: &[&str]) -> Vec<String> {
+    let mut res = Vec::new();
+    for src in corpus.iter() {
+        let same = match (Exp::fromSExp(src.as_bytes()), lexpr::from_str(src)) {
+            (PROk(e), Ok(v)) => agrees(&e, &v),
+            (PRErr(_), Err(_)) => true,
+            _ => false
+        };
+        if !same {
+            res.pushBack(format!("{}", src));
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the current dialect differences, update when the reader changes
+    const KNOWN : [&str; 2] = [
+        "(1. -0.5 +7)",
+        "#:kw",
+    ];
+
+    #[test]
+    fn testDivergences() {
+        let mut corpus = std::vec::Vec::new();
+        corpus.extend_from_slice(&crate::mutate::CORPUS);
+        corpus.extend_from_slice(&DIALECT);
+        let d = divergences(&corpus);
+        assert!(d.len() == KNOWN.len());
+        for i in 0..d.len() {
+            assert!(d[i] == KNOWN[i], "unexpected divergence on {}", d[i]);
+        }
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use alt_std::hash::murmurHash64A;
+use alt_std::io::{FileReader, FileWriter, StreamSeek};
+use crate::*;
+
+const DIGEST_SEED   : u64   = 0x5e4d_1635;
+const DIGEST_PREFIX : &str  = "; sexp-digest: ";
+// prefix + 16 hex digits + '\n'
+const HEADER_LEN    : usize = 32;
+
+pub enum LoadResult {
+    LRUnchanged,
+    LRChanged(Exp, u64),
+    LRErr(ParseError),
+}
+
+use LoadResult::*;
+
+impl Exp {
+    /// digest of the serialized bytes (the header line excluded)
+    pub fn digest(src: &[u8]) -> u64 {
+        murmurHash64A(src, DIGEST_SEED)
+    }
+
+    fn hexDigit(v: u64) -> u8 {
+        match v as u8 {
+            d if d < 10 => b'0' + d,
+            d => b'a' + d - 10,
+        }
+    }
+
+    fn parseDigestHeader(src: &[u8]) -> Option<u64> {
+        let prefix = DIGEST_PREFIX.as_bytes();
+        if src.len() < HEADER_LEN || &src[..prefix.len()] != prefix || src[HEADER_LEN - 1] != b'\n' {
+            return None
+        }
+
+        let mut d : u64 = 0;
+        for c in src[prefix.len()..HEADER_LEN - 1].iter() {
+            let v = match *c {
+                c if c >= b'0' && c <= b'9' => c - b'0',
+                c if c >= b'a' && c <= b'f' => c - b'a' + 10,
+                _ => return None
+            };
+            d = (d << 4) | v as u64;
+        }
+        Some(d)
+    }
+
+    /// the expression preceded by a `; sexp-digest: ...` header line
+    pub fn toStringWithDigest(&self) -> String {
+        let body = self.toString();
+        let d = Self::digest(body.asArray());
+        let mut s = String::from(DIGEST_PREFIX);
+        for i in 0..16 {
+            s.add(Self::hexDigit((d >> ((15 - i) * 4)) & 0xF));
+        }
+        s.add(b'\n');
+        s.append(&body);
+        s
+    }
+
+    pub fn saveWithDigest(&self, path: &str) -> Option<u64> {
+        let s = self.toStringWithDigest();
+        let mut f = match FileWriter::create(path) {
+            Ok(f) => f,
+            Err(_) => return None
+        };
+        match f.write(s.asArray()) {
+            Ok(n) if n == s.asArray().len() => Self::parseDigestHeader(s.asArray()),
+            _ => None
+        }
+    }
+
+    /// reparse `path` only when its digest differs from `lastDigest`
+    pub fn loadIfChanged(path: &str, lastDigest: u64) -> LoadResult {
+        let mut f = match FileReader::open(path) {
+            Ok(f) => f,
+            Err(_) => return LRErr(ParseError { message: String::from("unable to open file"), offset: 0, kind: ErrorKind::Syntax })
+        };
+
+        let size = f.size();
+        let mut buff = Vec::new();
+        for _ in 0..size {
+            buff.pushBack(0u8);
+        }
+        let _ = f.seek(0);
+        match f.read(buff.asMutArray()) {
+            Ok(n) if n == size => (),
+            _ => return LRErr(ParseError { message: String::from("unable to read file"), offset: 0, kind: ErrorKind::Syntax })
+        }
+
+        // the header is skipped, not trusted: the body may have been edited after it was written
+        let src = buff.asArray();
+        let start = match Self::parseDigestHeader(src) {
+            Some(_) => HEADER_LEN,
+            None => 0
+        };
+
+        let d = Self::digest(&src[start..]);
+        if d == lastDigest {
+            return LRUnchanged
+        }
+
+        match Self::fromSExp(&src[start..]) {
+            PROk(e) => LRChanged(e, d),
+            PRErr(ParseError { message, offset, kind }) => LRErr(ParseError { message, offset: offset + start, kind }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alt_std::io::File;
+
+    #[test]
+    fn testLoadIfChanged() {
+        let sexp = String::from("(abcd 123 abc)");
+        let e = match Exp::fromSExp(sexp.asArray()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+
+        let name = File::tmpname();
+        let d = match e.saveWithDigest(name.toStr()) {
+            Some(d) => d,
+            None => panic!("couldn't save file")
+        };
+        assert!(d == Exp::digest(sexp.asArray()));
+
+        match Exp::loadIfChanged(name.toStr(), 0) {
+            LRChanged(r, rd) => assert!(r == e && rd == d),
+            _ => panic!("expected a changed file")
+        }
+
+        match Exp::loadIfChanged(name.toStr(), d) {
+            LRUnchanged => (),
+            _ => panic!("expected an unchanged file")
+        }
+
+        // an edited body under the old header
+        let mut edited = e.toStringWithDigest();
+        edited.append(" (x)");
+        std::fs::write(name.toStr(), edited.asArray()).unwrap();
+        match Exp::loadIfChanged(name.toStr(), d) {
+            LRChanged(r, rd) => assert!(r == e && rd == Exp::digest(b"(abcd 123 abc) (x)")),
+            _ => panic!("expected a changed file")
+        }
+
+        File::remove(name.toStr()).unwrap();
+    }
+}
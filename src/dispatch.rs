@@ -0,0 +1,129 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// `#` dispatch: dialects register a handler for `#c`, called with the stream
+// positioned right after the two characters, much like Common Lisp reader macros.
+// The characters of the built in `#` syntax cannot be taken over
+//
+use alt_std::*;
+use crate::*;
+
+pub type DispatchFn = std::sync::Arc<dyn Fn(&mut ReadStream) -> ParseResult<Exp> + Send + Sync>;
+
+/// the input as seen by a dispatch handler
+pub struct ReadStream<'r, 'a> {
+    rd      : &'r mut Reader<'a>,
+    src     : &'r [u8],
+    offset  : &'r mut usize,
+}
+
+impl<'r, 'a> ReadStream<'r, 'a> {
+    pub fn offset(&self) -> usize { *self.offset }
+
+    pub fn peek(&self) -> Option<u8> { Exp::peek(self.src, *self.offset) }
+
+    pub fn getchar(&mut self) -> Option<u8> { Exp::getchar(self.src, self.offset) }
+
+    /// the rest of the input
+    pub fn rest(&self) -> &[u8] { &self.src[*self.offset..] }
+
+    pub fn skip(&mut self, n: usize) { *self.offset = usize::min(*self.offset + n, self.src.len()) }
+
+    /// reads the next expression with the current options (and handlers)
+    pub fn readDatum(&mut self) -> ParseResult<Exp> {
+        Exp::skipWS(self.src, self.offset);
+        Exp::parseToken(self.rd, self.src, self.offset)
+    }
+
+    pub fn error(&self, message: &str) -> ParseResult<Exp> {
+        PRErr(ParseError { message: String::from(message), offset: *self.offset, kind: ErrorKind::Syntax })
+    }
+}
+
+// `#\` characters, `#x` `#o` `#b` radixes, `#|` and `#;` comments, `#(` vectors,
+// `#u8` bytes, `#t` `#f` booleans and `#0=` datum labels
+const RESERVED : &[u8] = b"\\xXoObB|;(utf0123456789";
+
+impl ReaderOptions {
+    /// reads `#c...` with `f`, fails for a character of the built in `#` syntax
+    /// (`\`, `x`, `o`, `b`, `|`, `;`, `(`, `u`, `t`, `f` and the digits)
+    pub fn addDispatch<F: Fn(&mut ReadStream) -> ParseResult<Exp> + Send + Sync + 'static>(&mut self, c: u8, f: F) -> Result<&mut Self, String> {
+        if RESERVED.contains(&c) {
+            return Err(alt_std::format!("#{} is reserved (dispatch)", c as char))
+        }
+        self.dispatch.pushBack((c, std::sync::Arc::new(f)));
+        Ok(self)
+    }
+
+    pub(crate) fn dispatchFor(&self, c: u8) -> Option<DispatchFn> {
+        self.dispatch.asArray().iter().rev().find(|(d, _)| *d == c).map(|(_, f)| f.clone())
+    }
+}
+
+impl Exp {
+    // `#c...` where `c` has a dispatch function
+    pub(crate) fn parseDispatch(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let f = match Self::peek(src, *offset + 1).and_then(|c| rd.opts.dispatchFor(c)) {
+            Some(f) => f,
+            None => return PRErr(ParseError { message: String::from("unexpected character (dispatch)"), offset: *offset, kind: ErrorKind::Syntax }),
+        };
+        *offset += 2;
+        f(&mut ReadStream { rd, src, offset })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testDispatch() {
+        let mut opts = ReaderOptions::new();
+        // #?x reads as (maybe x), #h followed by hex digits as an Int
+        assert!(opts.addDispatch(b'?', |s| match s.readDatum() {
+            PROk(e) => { let mut l = Vec::new(); l.pushBack(Exp::Symbol(String::from("maybe"))); l.pushBack(e); PROk(Exp::List(l)) },
+            err => err
+        }).is_ok());
+        assert!(opts.addDispatch(b'h', |s| {
+            let mut v : i64 = 0;
+            let start = s.offset();
+            while let Some(d) = s.peek().and_then(|c| (c as char).to_digit(16)) {
+                v = v * 16 + d as i64;
+                s.skip(1);
+            }
+            if s.offset() == start { s.error("expected hex digits") } else { PROk(Exp::Int(v)) }
+        }).is_ok());
+
+        match Exp::fromSExpWithOptions(b"(a #?(b #hff) #t #\\x)", &opts) {
+            PROk(e) => assert!(e.toString().toStr() == "(a (maybe (b 255)) #t #\\x)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(match Exp::fromSExpWithOptions(b"(#h)", &opts) { PRErr(err) => err.offset() == 3 && err.message() == "expected hex digits", _ => false });
+        assert!(match Exp::fromSExp(b"#?x") { PROk(Exp::Symbol(s)) => s.toStr() == "#?x", _ => false });
+
+        for c in b"\\xb|;(ut7".iter() {
+            assert!(match opts.addDispatch(*c, |_| PROk(Exp::Int(0))) { Err(m) => m == alt_std::format!("#{} is reserved (dispatch)", *c as char), Ok(_) => false });
+        }
+        match Exp::fromSExpWithOptions(b"(#x10 #(1) #u8(2) #t)", &opts) {
+            PROk(e) => assert!(e.toString().toStr() == "(16 #(1) #u8(2) #t)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
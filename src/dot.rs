@@ -0,0 +1,122 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use alt_std::{format};
+use crate::*;
+
+impl Exp {
+    fn dotLabel(out: &mut String, s: &[u8]) {
+        out.add(b'"');
+        for c in s.iter() {
+            if *c == b'"' || *c == b'\\' { out.add(b'\\') }
+            out.add(*c);
+        }
+        out.add(b'"');
+    }
+
+    // the cluster label and children of a non empty container, map keys and
+    // values alternate
+    fn dotChildren(&self) -> Option<(&'static str, std::vec::Vec<&Exp>)> {
+        match self {
+            Self::List(l) if l.len() != 0 => Some(("()", l.asArray().iter().collect())),
+            Self::Vector(v) if v.len() != 0 => Some(("#()", v.asArray().iter().collect())),
+            Self::Map(m) if m.len() != 0 => Some(("{}", m.asArray().iter().flat_map(|(k, v)| [k, v]).collect())),
+            Self::Pair(a, d) => Some(("(.)", std::vec![&**a, &**d])),
+            _ => None
+        }
+    }
+
+    // returns the id of the first node, atoms are nodes and containers are clusters
+    fn writeDot(&self, out: &mut String, id: &mut usize, indent: usize) -> usize {
+        let mine = *id;
+        *id += 1;
+        for _ in 0..indent { out.add(b' ') }
+        match self.dotChildren() {
+            Some((label, children)) => {
+                out.append(&format!("subgraph cluster_{} {{\n", mine));
+                for _ in 0..indent + 4 { out.add(b' ') }
+                out.append("label=");
+                Self::dotLabel(out, label.as_bytes());
+                out.append(";\n");
+                let mut head = mine;
+                let mut prev = None;
+                for c in children.iter() {
+                    let first = c.writeDot(out, id, indent + 4);
+                    // keep the siblings in order
+                    if let Some(p) = prev {
+                        for _ in 0..indent + 4 { out.add(b' ') }
+                        out.append(&format!("n{} -> n{} [style=invis];\n", p, first));
+                    } else {
+                        head = first;
+                    }
+                    prev = Some(first);
+                }
+                for _ in 0..indent { out.add(b' ') }
+                out.append("}\n");
+                head
+            },
+            None => {
+                out.append(&format!("n{} [label=", mine));
+                Self::dotLabel(out, self.toString().asArray());
+                out.append("];\n");
+                mine
+            }
+        }
+    }
+
+    /// GraphViz graph of the tree: atoms are nodes, lists, vectors, maps and pairs are clusters
+    pub fn toDot(&self) -> String {
+        let mut s = String::from("digraph sexp {\n    node [shape=box];\n");
+        let mut id = 0;
+        self.writeDot(&mut s, &mut id, 4);
+        s.append("}\n");
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToDot() {
+        let sexp = String::from("(a \"b\" ())");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toDot() == "digraph sexp {\n    node [shape=box];\n    subgraph cluster_0 {\n        label=\"()\";\n        n1 [label=\"a\"];\n        n2 [label=\"\\\"b\\\"\"];\n        n1 -> n2 [style=invis];\n        n3 [label=\"()\"];\n        n2 -> n3 [style=invis];\n    }\n}\n"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+
+        // a nested list is ordered by its first node, not its last
+        match Exp::fromSExp(b"((a c) b)") {
+            PROk(r) => assert!(r.toDot().toStr().contains("n2 -> n4 [style=invis];\n") && !r.toDot().toStr().contains("n3 -> n4")),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+
+        match Exp::fromSExp(b"(#(1) {k v} (a . b))") {
+            PROk(r) => {
+                let d = r.toDot();
+                assert!(d.toStr().contains("subgraph cluster_1 {\n            label=\"#()\";\n            n2 [label=\"1\"];\n        }\n"));
+                assert!(d.toStr().contains("subgraph cluster_3 {\n            label=\"{}\";\n            n4 [label=\"k\"];\n            n5 [label=\"v\"];\n            n4 -> n5 [style=invis];\n"));
+                assert!(d.toStr().contains("subgraph cluster_6 {\n            label=\"(.)\";\n            n7 [label=\"a\"];\n            n8 [label=\"b\"];\n"));
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
@@ -0,0 +1,217 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// environment variables of a config tree: the prefix and the path segments are
+// joined with '_', lowercase letters are upper cased, '-' becomes "__" and any
+// other byte than ASCII letters and digits becomes '_'. Kebab-case keys of
+// lowercase letters and digits round trip, `server.max-conn 10` with prefix
+// `app` is `APP_SERVER_MAX__CONN=10`. Two leaves landing on the same name are
+// an error rather than one silently shadowing the other
+//
+use alt_std::*;
+use core::cmp::Ordering;
+use crate::*;
+
+impl Exp {
+    fn envName(out: &mut String, s: &[u8]) {
+        for c in s.iter() {
+            match *c {
+                c if c.is_ascii_lowercase() => out.add(c.to_ascii_uppercase()),
+                c if c.is_ascii_alphanumeric() => out.add(c),
+                b'-' => { out.add(b'_'); out.add(b'_') }
+                _ => out.add(b'_'),
+            }
+        }
+    }
+
+    /// `(NAME, value)` per leaf, strings and symbols are written raw, fails when
+    /// two leaves get the same name (`a_b` and `a.b`, `K` and `k`)
+    pub fn toEnv(&self, prefix: &str) -> Result<Vec<(String, String)>, String> {
+        let mut out = Vec::new();
+        let mut seen : std::collections::HashMap<std::vec::Vec<u8>, Path> = std::collections::HashMap::new();
+        for (p, v) in self.flatten().asArray().iter() {
+            let mut name = String::new();
+            Self::envName(&mut name, prefix.as_bytes());
+            for seg in p.segments().iter() {
+                if !name.asArray().is_empty() { name.add(b'_') }
+                match seg {
+                    PathSeg::Key(k) => Self::envName(&mut name, k.asArray()),
+                    PathSeg::Index(i) => name.append(&alt_std::format!("{}", i)),
+                }
+            }
+            if let Some(q) = seen.insert(name.asArray().to_vec(), p.clone()) {
+                return Err(alt_std::format!("{}: {} and {} collide", name.toStr(), q.toString().toStr(), p.toString().toStr()))
+            }
+            let value = match v {
+                Self::String(s) | Self::Symbol(s) => String::from(s.toStr()),
+                v => v.toString(),
+            };
+            out.pushBack((name, value));
+        }
+        Ok(out)
+    }
+
+    /// path of an env name past the prefix: "__" is '-', a single '_' separates segments
+    fn envPath(name: &[u8]) -> Path {
+        let mut p = Path::new();
+        let mut k = String::new();
+        let mut i = 0;
+        loop {
+            if i == name.len() || (name[i] == b'_' && name.get(i + 1) != Some(&b'_')) {
+                p.push(match k.toStr().parse::<usize>() {
+                    Ok(i) if k.asArray().iter().all(|c| c.is_ascii_digit()) => PathSeg::Index(i),
+                    _ => PathSeg::Key(k),
+                });
+                if i == name.len() { return p }
+                k = String::new();
+                i += 1;
+            } else if name[i] == b'_' {
+                k.add(b'-');
+                i += 2;
+            } else {
+                k.add(name[i].to_ascii_lowercase());
+                i += 1;
+            }
+        }
+    }
+
+    fn envValue(v: &str) -> Exp {
+        match Self::fromSExpDocument(v.as_bytes(), &ReaderOptions::new()) {
+            PROk(e @ Self::Bool(_)) | PROk(e @ Self::Char(_)) | PROk(e @ Self::Int(_)) | PROk(e @ Self::Float(_)) => e,
+            _ => Self::String(String::from(v)),
+        }
+    }
+
+    fn cmpPath(a: &Path, b: &Path) -> Ordering {
+        for (x, y) in a.segments().iter().zip(b.segments().iter()) {
+            let o = match (x, y) {
+                (PathSeg::Index(x), PathSeg::Index(y)) => x.cmp(y),
+                (PathSeg::Index(_), PathSeg::Key(_)) => Ordering::Less,
+                (PathSeg::Key(_), PathSeg::Index(_)) => Ordering::Greater,
+                (PathSeg::Key(x), PathSeg::Key(y)) => x.asArray().cmp(y.asArray()),
+            };
+            if o != Ordering::Equal { return o }
+        }
+        a.len().cmp(&b.len())
+    }
+
+    /// reverse of `toEnv` over the `PREFIX_...` variables: keys come back lower
+    /// cased, values reading whole as a bool, char or number are kept, anything
+    /// else (`8080 extra` too) is a string. Fails when two names give the same path or a leaf is also a parent
+    pub fn fromEnv(prefix: &str, vars: &[(&str, &str)]) -> Result<Exp, String> {
+        let mut start = String::new();
+        Self::envName(&mut start, prefix.as_bytes());
+        if !start.asArray().is_empty() { start.add(b'_') }
+
+        let mut pairs = Vec::new();
+        for (name, value) in vars.iter() {
+            let name = name.as_bytes();
+            if name.len() <= start.asArray().len() || &name[..start.asArray().len()] != start.asArray() {
+                continue
+            }
+            pairs.pushBack((Self::envPath(&name[start.asArray().len()..]), Self::envValue(value)));
+        }
+
+        pairs.asMutArray().sort_by(|a, b| Self::cmpPath(&a.0, &b.0));
+        // sorted, a path is directly followed by its duplicates and descendants
+        for w in pairs.asArray().windows(2) {
+            let (a, b) = (w[0].0.segments(), w[1].0.segments());
+            if a.len() <= b.len() && a == &b[..a.len()] {
+                return Err(alt_std::format!("{} and {} collide", w[0].0.toString().toStr(), w[1].0.toString().toStr()))
+            }
+        }
+        Ok(Self::unflatten(pairs.asArray()))
+    }
+
+    /// `fromEnv` over the variables of the current process
+    pub fn fromProcessEnv(prefix: &str) -> Result<Exp, String> {
+        let vars : std::vec::Vec<(std::string::String, std::string::String)> = std::env::vars().collect();
+        let refs : std::vec::Vec<(&str, &str)> = vars.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+        Self::fromEnv(prefix, &refs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testEnv() {
+        let sexp = String::from("((server (port 8080) (host \"localhost\") (args \"-v\" \"-q\")) (debug #t) (max-conn 10))");
+        let e = match Exp::fromSExp(sexp.asArray()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+
+        let vars = match e.toEnv("app") { Ok(v) => v, Err(m) => panic!("{}", m.toStr()) };
+        let mut s = String::new();
+        for (n, v) in vars.asArray().iter() {
+            s.append(n);
+            s.add(b'=');
+            s.append(v);
+            s.add(b'\n');
+        }
+        assert!(s == "APP_SERVER_PORT=8080\nAPP_SERVER_HOST=localhost\nAPP_SERVER_ARGS_0=-v\nAPP_SERVER_ARGS_1=-q\nAPP_DEBUG=#t\nAPP_MAX__CONN=10\n");
+
+        let vars = [("APP_SERVER_PORT", "8080"), ("APP_SERVER_ARGS_1", "-q"), ("APP_SERVER_ARGS_0", "-v"),
+                    ("OTHER_X", "1"), ("APP_DEBUG", "#t"), ("APP_SERVER_HOST", "localhost")];
+        let r = match Exp::fromEnv("app", &vars) { Ok(r) => r, Err(m) => panic!("{}", m.toStr()) };
+        assert!(r.toString() == "((debug #t) (server (args \"-v\" \"-q\") (host \"localhost\") (port 8080)))");
+    }
+
+    #[test]
+    fn testEnvKebabCase() {
+        let e = match Exp::fromSExp(b"((max-conn 10) (tls (cert-file \"a.pem\") (x--y 1)) (retry- 2))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let vars = match e.toEnv("app") { Ok(v) => v, Err(m) => panic!("{}", m.toStr()) };
+        let refs : std::vec::Vec<(&str, &str)> = vars.asArray().iter().map(|(n, v)| (n.toStr(), v.toStr())).collect();
+        assert!(refs[0].0 == "APP_MAX__CONN" && refs[1].0 == "APP_TLS_CERT__FILE");
+        assert!(refs[2].0 == "APP_TLS_X____Y" && refs[3].0 == "APP_RETRY__");
+        let r = match Exp::fromEnv("app", &refs) { Ok(r) => r, Err(m) => panic!("{}", m.toStr()) };
+        assert!(r.toString() == "((max-conn 10) (retry- 2) (tls (cert-file \"a.pem\") (x--y 1)))");
+    }
+
+    #[test]
+    fn testEnvCollisions() {
+        let e = match Exp::fromSExp(b"((a_b 1) (a (b 2)))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        match e.toEnv("app") {
+            Err(m) => assert!(m == "APP_A_B: a_b and a.b collide"),
+            Ok(_) => panic!("collision not reported"),
+        }
+
+        assert!(Exp::fromEnv("app", &[("APP_A", "1"), ("APP_A_B", "2")]).is_err());
+        assert!(Exp::fromEnv("app", &[("APP_A_0", "1"), ("APP_A_00", "2")]).is_err());
+        assert!(Exp::fromEnv("app", &[("APP_A_B", "1"), ("APP_A__B", "2")]).is_ok());
+    }
+
+    #[test]
+    fn testEnvValues() {
+        for (v, e) in [("8080", "8080"), (" 2.5 ", "2.5"), ("#t ; on", "#t"), ("8080 extra", "\"8080 extra\""), ("1 2 3", "\"1 2 3\""),
+                       ("#t garbage", "\"#t garbage\""), ("12)", "\"12)\""), ("(1 2)", "\"(1 2)\""), ("", "\"\"")].iter() {
+            assert!(Exp::envValue(v).toString() == *e, "{}", v);
+        }
+    }
+}
@@ -0,0 +1,167 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    // `(key ...)` entries are the nodes of a config tree
+    pub(crate) fn entryKey(&self) -> Option<&String> {
+        match self {
+            Self::List(l) if l.len() != 0 => match &l[0] {
+                Self::Symbol(k) => Some(k),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn flattenChildren(l: &[Exp], path: &Path, out: &mut Vec<(Path, Exp)>) {
+        for i in 0..l.len() {
+            match l[i].entryKey() {
+                Some(_) => l[i].flattenEntry(path, out),
+                None => out.pushBack((path.child(PathSeg::Index(i)), l[i].clone())),
+            }
+        }
+    }
+
+    fn flattenEntry(&self, path: &Path, out: &mut Vec<(Path, Exp)>) {
+        let l = match self {
+            Self::List(l) => l,
+            _ => return
+        };
+        let path = path.child(PathSeg::Key(l[0].entryKeyName()));
+        match l.len() {
+            1 => out.pushBack((path, Self::List(Vec::new()))),
+            // `(key ())` goes through the children to tell it from `(key)`
+            2 if l[1].entryKey().is_none() && !l[1].isEmptyList() => out.pushBack((path, l[1].clone())),
+            _ => Self::flattenChildren(&l.asArray()[1..], &path, out),
+        }
+    }
+
+    fn entryKeyName(&self) -> String {
+        match self {
+            Self::Symbol(k) => String::from(k.toStr()),
+            _ => String::new()
+        }
+    }
+
+    fn isEmptyList(&self) -> bool {
+        matches!(self, Self::List(l) if l.len() == 0)
+    }
+
+    /// leaves of the tree with their paths: `((server (port 8080) (args a b)))` gives
+    /// `server.port 8080`, `server.args.0 a` and `server.args.1 b`. The root is
+    /// always the list of entries, a root `(server ...)` gives `0 server` and the
+    /// paths of its entries
+    pub fn flatten(&self) -> Vec<(Path, Exp)> {
+        let mut out = Vec::new();
+        match self {
+            Self::List(l) => Self::flattenChildren(l.asArray(), &Path::new(), &mut out),
+            e => out.pushBack((Path::new(), e.clone())),
+        }
+        out
+    }
+
+    // `start` skips the key of an entry
+    fn unflattenInto(children: &mut Vec<Exp>, start: usize, segs: &[PathSeg], value: Exp) {
+        let k = match segs.first() {
+            Some(PathSeg::Key(k)) => k,
+            // indices only position leaves
+            _ => return children.pushBack(value)
+        };
+
+        let mut at = children.len();
+        for i in start..children.len() {
+            if children[i].entryKey() == Some(k) { at = i; break }
+        }
+        if at == children.len() {
+            let mut e = Vec::new();
+            e.pushBack(Self::Symbol(String::from(k.toStr())));
+            children.pushBack(Self::List(e));
+        }
+
+        match &mut children[at] {
+            // `(key)` is flattened to `key ()`
+            Self::List(_) if segs.len() == 1 && value.isEmptyList() => (),
+            Self::List(e) if segs.len() == 1 => e.pushBack(value),
+            Self::List(e) => Self::unflattenInto(e, 1, &segs[1..], value),
+            _ => ()
+        }
+    }
+
+    /// inverse of `flatten` for trees without repeated keys in a list (their
+    /// leaves are merged into the first entry of the key)
+    pub fn unflatten(pairs: &[(Path, Exp)]) -> Exp {
+        if pairs.len() == 1 && pairs[0].0.is_empty() {
+            return pairs[0].1.clone()
+        }
+
+        let mut root = Vec::new();
+        for (p, v) in pairs.iter() {
+            Self::unflattenInto(&mut root, 0, p.segments(), v.clone());
+        }
+        Self::List(root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testFlatten() {
+        let sexp = String::from("((server (port 8080) (host \"localhost\") (args a (b 1) c) (tls (cert \"x\"))) (debug #t))");
+        let e = match Exp::fromSExp(sexp.asArray()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+
+        let flat = e.flatten();
+        let mut keys = String::new();
+        for (p, v) in flat.asArray().iter() {
+            keys.append(&p.toString());
+            keys.add(b'=');
+            keys.append(&v.toString());
+            keys.add(b' ');
+        }
+        assert!(keys == "server.port=8080 server.host=\"localhost\" server.args.0=a server.args.b=1 server.args.2=c server.tls.cert=\"x\" debug=#t ");
+        assert!(Exp::unflatten(flat.asArray()) == e);
+        assert!(Exp::unflatten(Exp::Int(1).flatten().asArray()) == Exp::Int(1));
+    }
+
+    #[test]
+    fn testFlattenRoundTrip() {
+        let forms = ["(server (port 8080))", "((server (port 8080)))", "(debug #t)", "((debug #t))", "((a) (b ()) (c (())))",
+                     "(|a.b| (|c\\\\d| 1) (e 1 2))", "(())", "()", "42", "((k (1 2)) (l x (m y) z))"];
+        for s in forms.iter() {
+            let e = match Exp::fromSExpWithOptions(s.as_bytes(), &ReaderOptions { pipeSymbols: true, ..ReaderOptions::default() }) {
+                PROk(e) => e,
+                PRErr(err) => panic!("{}", err.message.toStr())
+            };
+            let flat = e.flatten();
+            assert!(Exp::unflatten(flat.asArray()) == e, "{}", s);
+
+            // the textual paths read back to the same paths
+            let mut reparsed = Vec::new();
+            for (p, v) in flat.asArray().iter() { reparsed.pushBack((Path::parse(p.toString().toStr()), v.clone())) }
+            assert!(Exp::unflatten(reparsed.asArray()) == e, "{}", s);
+        }
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// framed records: every form is preceded by `(#:meta (len N) (crc X))` giving
+// the byte length and the CRC-32 of its canonical text, so that truncated or
+// corrupted records of long lived files are detected when reading them back
+//
+use alt_std::*;
+use crate::*;
+
+const META : &str = "#:meta";
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in data.iter() {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+fn metaEntry(key: &str, v: i64) -> Exp {
+    let mut l = Vec::new();
+    l.pushBack(Exp::Symbol(String::from(key)));
+    l.pushBack(Exp::Int(v));
+    Exp::List(l)
+}
+
+impl Exp {
+    /// the meta form and the text of `self` as written by `FramedWriter`
+    pub fn toFramed(&self) -> (Exp, String) {
+        let text = self.toString();
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from(META)));
+        l.pushBack(metaEntry("len", text.asArray().len() as i64));
+        l.pushBack(metaEntry("crc", crc32(text.asArray()) as i64));
+        (Exp::List(l), text)
+    }
+
+    // (len, crc) of a meta form
+    fn framedMeta(&self) -> Option<(usize, u32)> {
+        let l = match self { Exp::List(l) if l.len() == 3 => l, _ => return None };
+        match (&l[0], &l[1], &l[2]) {
+            (Exp::Symbol(m), Exp::List(len), Exp::List(crc)) if m.toStr() == META && len.len() == 2 && crc.len() == 2 => {
+                match (&len[0], &len[1], &crc[0], &crc[1]) {
+                    (Exp::Symbol(lk), Exp::Int(n), Exp::Symbol(ck), Exp::Int(c)) if lk.toStr() == "len" && ck.toStr() == "crc" && *n >= 0 =>
+                        Some((*n as usize, *c as u32)),
+                    _ => None
+                }
+            },
+            _ => None
+        }
+    }
+}
+
+pub struct FramedWriter<W: std::io::Write> {
+    out : W,
+}
+
+impl<W: std::io::Write> FramedWriter<W> {
+    pub fn new(out: W) -> Self { Self { out } }
+
+    pub fn intoInner(self) -> W { self.out }
+
+    pub fn write(&mut self, e: &Exp) -> std::io::Result<()> {
+        let (meta, text) = e.toFramed();
+        let mut s = meta.toString();
+        s.add(b'\n');
+        s.append(&text);
+        s.add(b'\n');
+        self.out.write_all(s.asArray())
+    }
+}
+
+/// reads the records of a `FramedWriter`, failing on the first bad one
+pub struct FramedReader<R: std::io::Read> {
+    forms   : FormReader<R>,
+    // set by the first bad record, nothing after it can be trusted
+    failed  : bool,
+}
+
+impl<R: std::io::Read> FramedReader<R> {
+    pub fn new(input: R) -> Self { Self { forms: FormReader::new(input), failed: false } }
+
+    fn fail(&mut self, message: &str, offset: usize) -> Option<ParseResult<Exp>> {
+        self.failed = true;
+        Some(PRErr(ParseError { message: String::from(message), offset, kind: ErrorKind::Syntax }))
+    }
+}
+
+impl<R: std::io::Read> Iterator for FramedReader<R> {
+    type Item = ParseResult<Exp>;
+
+    fn next(&mut self) -> Option<ParseResult<Exp>> {
+        if self.failed { return None }
+        let at = self.forms.offset();
+        let (len, crc) = match self.forms.next()? {
+            PROk(m) => match m.framedMeta() {
+                Some(meta) => meta,
+                None => return self.fail("missing record meta", at)
+            },
+            PRErr(err) => return Some(PRErr(err))
+        };
+
+        let at = self.forms.offset();
+        let e = match self.forms.next() {
+            Some(PROk(e)) => e,
+            Some(PRErr(err)) => return Some(PRErr(err)),
+            None => return self.fail("truncated record", at)
+        };
+
+        let text = e.toString();
+        if text.asArray().len() != len {
+            self.fail("record length mismatch", at)
+        } else if crc32(text.asArray()) != crc {
+            self.fail("record checksum mismatch", at)
+        } else {
+            Some(PROk(e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(src: &[u8]) -> std::vec::Vec<std::string::String> {
+        FramedReader::new(src).map(|r| match r {
+            PROk(e) => e.toString().toStr().to_string(),
+            PRErr(err) => std::format!("error {} at {}", err.message(), err.offset()),
+        }).collect()
+    }
+
+    #[test]
+    fn testFramed() {
+        assert!(crc32(b"123456789") == 0xCBF4_3926);
+
+        let mut w = FramedWriter::new(std::vec::Vec::new());
+        for src in ["(a 1)", "(b \"x\")"].iter() {
+            match Exp::fromSExp(src.as_bytes()) { PROk(e) => w.write(&e).unwrap(), PRErr(err) => panic!("{}", err.message.toStr()) }
+        }
+        let out = w.intoInner();
+        assert!(read(&out) == ["(a 1)", "(b \"x\")"]);
+
+        let mut corrupt = out.clone();
+        let n = corrupt.len();
+        corrupt[n - 4] = b'y';
+        // offsets are the end of the previous form
+        assert!(read(&corrupt) == ["(a 1)", "error record checksum mismatch at 72"]);
+        assert!(read(&out[..n - 8]) == ["(a 1)", "error truncated record at 72"]);
+        assert!(read(&out[..50]) == ["(a 1)", "error unexpected end of stream (list) at 50"]);
+        assert!(read(b"(a 1)") == ["error missing record meta at 0"]);
+
+        // the rest of the stream is not read after a bad record
+        let mut r = FramedReader::new(std::io::Read::chain(&b"(a 1)"[..], std::io::repeat(b' ')));
+        assert!(matches!(r.next(), Some(PRErr(_))));
+        assert!(r.next().is_none());
+    }
+}
@@ -0,0 +1,358 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// grammar: the syntax accepted by a set of reader options as data, a list of
+// rules made of railroad diagram nodes, plus the brackets, comments and prefixes
+// editors need. It is built from the same options the reader dispatches on, so a
+// new option or dialect changes both in the same place
+//
+use alt_std::*;
+use alt_std::{format};
+use crate::*;
+
+/// railroad diagram node
+#[derive(Clone)]
+pub enum Production {
+    // literal text
+    Terminal(String),
+    // a set of characters described in words ("digit", "whitespace")
+    Class(String),
+    // reference to another rule
+    NonTerminal(String),
+    Sequence(Vec<Production>),
+    Choice(Vec<Production>),
+    Optional(std::boxed::Box<Production>),
+    ZeroOrMore(std::boxed::Box<Production>),
+    OneOrMore(std::boxed::Box<Production>),
+}
+
+pub struct GrammarRule {
+    pub name        : String,
+    pub production  : Production,
+}
+
+pub struct GrammarDesc {
+    // the first rule is the start rule
+    pub rules       : Vec<GrammarRule>,
+    // open and close of the bracketed forms
+    pub brackets    : Vec<(String, String)>,
+    pub lineComment : String,
+    // block comments nest
+    pub blockComment : (String, String),
+    // comments out the datum that follows
+    pub datumComment : String,
+    // prefix and the head of the form it reads as: ' reads as (quote x)
+    pub prefixes    : Vec<(String, String)>,
+}
+
+fn t(s: &str) -> Production { Production::Terminal(String::from(s)) }
+fn class(s: &str) -> Production { Production::Class(String::from(s)) }
+fn nt(s: &str) -> Production { Production::NonTerminal(String::from(s)) }
+fn opt(p: Production) -> Production { Production::Optional(std::boxed::Box::new(p)) }
+fn star(p: Production) -> Production { Production::ZeroOrMore(std::boxed::Box::new(p)) }
+fn plus(p: Production) -> Production { Production::OneOrMore(std::boxed::Box::new(p)) }
+
+fn list<const N: usize>(ps: [Production; N]) -> Vec<Production> {
+    let mut v = Vec::new();
+    for p in IntoIterator::into_iter(ps) { v.pushBack(p) }
+    v
+}
+
+fn seq<const N: usize>(ps: [Production; N]) -> Production { Production::Sequence(list(ps)) }
+fn choice<const N: usize>(ps: [Production; N]) -> Production { Production::Choice(list(ps)) }
+
+impl Production {
+    fn writeEbnf(&self, nested: bool, out: &mut String) {
+        let items = |ps: &Vec<Production>, sep: &str, out: &mut String| {
+            for i in 0..ps.len() {
+                if i > 0 { out.append(sep) }
+                ps[i].writeEbnf(true, out);
+            }
+        };
+        match self {
+            Self::Terminal(s) if s.asArray().contains(&b'\'') => out.append(&format!("\"{}\"", s.toStr())),
+            Self::Terminal(s) => out.append(&format!("'{}'", s.toStr())),
+            Self::Class(s) => out.append(&format!("<{}>", s.toStr())),
+            Self::NonTerminal(s) => out.append(s.toStr()),
+            Self::Sequence(ps) | Self::Choice(ps) if nested && ps.len() > 1 => {
+                out.add(b'(');
+                self.writeEbnf(false, out);
+                out.add(b')');
+            },
+            Self::Sequence(ps) => items(ps, " ", out),
+            Self::Choice(ps) => items(ps, " | ", out),
+            Self::Optional(p) => { p.writeEbnf(true, out); out.add(b'?') },
+            Self::ZeroOrMore(p) => { p.writeEbnf(true, out); out.add(b'*') },
+            Self::OneOrMore(p) => { p.writeEbnf(true, out); out.add(b'+') },
+        }
+    }
+
+    /// `(seq ...)`, `(choice ...)`, `(optional p)`, `(zero-or-more p)`, `(one-or-more p)`,
+    /// `(terminal "text")`, `(class "words")` and `(ref rule)`
+    pub fn toExp(&self) -> Exp {
+        let form = |head: &str, args: &[Exp]| {
+            let mut v = Vec::new();
+            v.pushBack(Exp::Symbol(String::from(head)));
+            for a in args.iter() { v.pushBack(a.clone()) }
+            Exp::List(v)
+        };
+        let all = |ps: &Vec<Production>| ps.asArray().iter().map(|p| p.toExp()).collect::<std::vec::Vec<Exp>>();
+        match self {
+            Self::Terminal(s) => form("terminal", &[Exp::String(s.clone())]),
+            Self::Class(s) => form("class", &[Exp::String(s.clone())]),
+            Self::NonTerminal(s) => form("ref", &[Exp::Symbol(s.clone())]),
+            Self::Sequence(ps) => form("seq", &all(ps)),
+            Self::Choice(ps) => form("choice", &all(ps)),
+            Self::Optional(p) => form("optional", &[p.toExp()]),
+            Self::ZeroOrMore(p) => form("zero-or-more", &[p.toExp()]),
+            Self::OneOrMore(p) => form("one-or-more", &[p.toExp()]),
+        }
+    }
+}
+
+impl GrammarDesc {
+    pub fn rule(&self, name: &str) -> Option<&Production> {
+        self.rules.asArray().iter().find(|r| r.name.toStr() == name).map(|r| &r.production)
+    }
+
+    /// one `name ::= production` line per rule
+    pub fn toEbnf(&self) -> String {
+        let mut out = String::new();
+        for r in self.rules.asArray().iter() {
+            out.append(r.name.toStr());
+            out.append(" ::= ");
+            r.production.writeEbnf(false, &mut out);
+            out.add(b'\n');
+        }
+        out
+    }
+
+    /// `(grammar (rule name production)... (brackets (open close)...) (comments line (open close) datum)
+    /// (prefixes (prefix head)...))`
+    pub fn toExp(&self) -> Exp {
+        let pairs = |head: &str, ps: &Vec<(String, String)>| {
+            let mut v = Vec::new();
+            v.pushBack(Exp::Symbol(String::from(head)));
+            for (a, b) in ps.asArray().iter() {
+                let mut p = Vec::new();
+                p.pushBack(Exp::String(a.clone()));
+                p.pushBack(Exp::String(b.clone()));
+                v.pushBack(Exp::List(p));
+            }
+            Exp::List(v)
+        };
+        let mut v = Vec::new();
+        v.pushBack(Exp::Symbol(String::from("grammar")));
+        for r in self.rules.asArray().iter() {
+            let mut rule = Vec::new();
+            rule.pushBack(Exp::Symbol(String::from("rule")));
+            rule.pushBack(Exp::Symbol(r.name.clone()));
+            rule.pushBack(r.production.toExp());
+            v.pushBack(Exp::List(rule));
+        }
+        v.pushBack(pairs("brackets", &self.brackets));
+        let mut comments = Vec::new();
+        comments.pushBack(Exp::Symbol(String::from("comments")));
+        comments.pushBack(Exp::String(self.lineComment.clone()));
+        let mut block = Vec::new();
+        block.pushBack(Exp::String(self.blockComment.0.clone()));
+        block.pushBack(Exp::String(self.blockComment.1.clone()));
+        comments.pushBack(Exp::List(block));
+        comments.pushBack(Exp::String(self.datumComment.clone()));
+        v.pushBack(Exp::List(comments));
+        v.pushBack(pairs("prefixes", &self.prefixes));
+        Exp::List(v)
+    }
+}
+
+impl ReaderOptions {
+    /// the syntax these options read
+    pub fn grammar(&self) -> GrammarDesc {
+        let mut rules = Vec::new();
+        let mut rule = |name: &str, production: Production| rules.pushBack(GrammarRule { name: String::from(name), production });
+
+        let mut prefixes = Vec::new();
+        if self.apostrophe == Apostrophe::Quote { prefixes.pushBack((String::from("'"), String::from("quote"))) }
+        if self.quasiquoteSugar {
+            prefixes.pushBack((String::from("`"), String::from("quasiquote")));
+            prefixes.pushBack((String::from(",@"), String::from("unquote-splicing")));
+            prefixes.pushBack((String::from(","), String::from("unquote")));
+        }
+
+        let mut data = list([nt("list"), nt("vector")]);
+        if self.braces { data.pushBack(nt("map")) }
+        for n in ["bytes", "text-block", "string", "number", "char", "boolean"].iter() { data.pushBack(nt(n)) }
+        if self.keywords { data.pushBack(nt("keyword")) }
+        if self.pipeSymbols { data.pushBack(nt("pipe-symbol")) }
+        data.pushBack(nt("symbol"));
+        if prefixes.len() > 0 { data.pushBack(nt("prefixed")) }
+        if self.dispatch.len() > 0 { data.pushBack(nt("dispatch")) }
+        if self.datumLabels { data.pushBack(nt("label")) }
+
+        let mut document = Vec::new();
+        document.pushBack(opt(class("UTF-8 byte order mark")));
+        if self.skipShebang { document.pushBack(opt(seq([t("#!"), star(class("any character except newline"))]))) }
+        document.pushBack(star(nt("atmosphere")));
+        document.pushBack(star(seq([nt("datum"), star(nt("atmosphere"))])));
+        rule("document", Production::Sequence(document));
+        rule("datum", Production::Choice(data));
+        rule("atmosphere", choice([class("whitespace"), nt("comment")]));
+        rule("comment", choice([seq([t(";"), star(class("any character except newline"))]),
+            seq([t("#|"), star(choice([nt("comment"), class("any character")])), t("|#")]),
+            seq([t("#;"), star(nt("atmosphere")), nt("datum")])]));
+
+        let items = || choice([seq([plus(nt("datum")), t("."), nt("datum")]), star(nt("datum"))]);
+        rule("list", if !self.squareBrackets {
+            seq([t("("), items(), t(")")])
+        } else if self.strictBrackets {
+            choice([seq([t("("), items(), t(")")]), seq([t("["), items(), t("]")])])
+        } else {
+            seq([choice([t("("), t("[")]), items(), choice([t(")"), t("]")])])
+        });
+        rule("vector", seq([t("#("), star(nt("datum")), t(")")]));
+        if self.braces { rule("map", seq([t("{"), star(seq([nt("datum"), nt("datum")])), t("}")])); }
+        rule("bytes", choice([seq([t("#u8("), star(nt("number")), t(")")]),
+            seq([t("#u8\""), star(choice([class("hex digit"), class("whitespace")])), t("\"")])]));
+
+        let mut escapes = Vec::new();
+        for c in b"\"\\ntr".iter() { escapes.pushBack(t(format!("{}", *c as char).toStr())) }
+        escapes.pushBack(seq([t("u{"), plus(class("hex digit")), t("}")]));
+        rule("escape", seq([t("\\"), Production::Choice(escapes)]));
+        rule("string", seq([t("\""), star(choice([class("any character except \" and \\"), nt("escape")])), t("\"")]));
+        rule("text-block", seq([t("\"\"\""), class("newline"), star(choice([class("any character"), nt("escape")])),
+            class("newline"), star(class("whitespace")), t("\"\"\"")]));
+
+        rule("sign", choice([t("+"), t("-")]));
+        // `_` separates digits: 1_000, 0xFF_FF
+        rule("digits", seq([plus(class("digit")), star(seq([t("_"), plus(class("digit"))]))]));
+        rule("radix-digits", seq([plus(class("digit of the radix")), star(seq([t("_"), plus(class("digit of the radix"))]))]));
+        rule("integer", seq([opt(nt("sign")), nt("digits")]));
+        rule("number", choice([
+            seq([nt("integer"), t("/"), nt("digits")]),
+            seq([nt("integer"), opt(seq([t("."), opt(nt("digits"))])), opt(seq([choice([t("e"), t("E")]), nt("integer")]))]),
+            seq([choice([t("#x"), t("#o"), t("#b")]), opt(nt("sign")), nt("radix-digits")]),
+            seq([opt(nt("sign")), choice([t("0x"), t("0o"), t("0b")]), nt("radix-digits")]),
+            t("+inf.0"), t("-inf.0"), t("+nan.0")]));
+
+        let mut names = Vec::new();
+        for (name, _) in Exp::CHAR_NAMES.iter() { names.pushBack(t(name)) }
+        names.pushBack(seq([t("x"), plus(class("hex digit"))]));
+        names.pushBack(class(if self.graphemeChars { "grapheme cluster" } else { "any character" }));
+        rule("char", seq([t(self.dialect.charPrefix()), Production::Choice(names)]));
+
+        let mut booleans = list([t("#t"), t("#f"), t("#true"), t("#false")]);
+        if self.dialect != Dialect::Scheme {
+            booleans.pushBack(t(self.dialect.booleanName(true)));
+            booleans.pushBack(t(self.dialect.booleanName(false)));
+        }
+        if self.wordBooleans && self.dialect != Dialect::Clojure {
+            booleans.pushBack(t("true"));
+            booleans.pushBack(t("false"));
+        }
+        rule("boolean", Production::Choice(booleans));
+        if self.keywords { rule("keyword", seq([t(":"), plus(class("symbol character"))])); }
+        if self.pipeSymbols {
+            rule("pipe-symbol", seq([t("|"), star(choice([class("any character except | and \\"), nt("escape")])), t("|")]));
+        }
+        rule("symbol", seq([class(if self.apostrophe == Apostrophe::Quote { "symbol start except '" } else { "symbol start" }),
+            star(class("symbol character"))]));
+
+        if prefixes.len() > 0 {
+            let mut ps = Vec::new();
+            for (p, _) in prefixes.asArray().iter() { ps.pushBack(t(p.toStr())) }
+            rule("prefixed", seq([Production::Choice(ps), star(nt("atmosphere")), nt("datum")]));
+        }
+        if self.datumLabels {
+            rule("label", seq([t("#"), plus(class("digit")),
+                choice([seq([t("="), star(nt("atmosphere")), nt("datum")]), t("#")])]));
+        }
+        if self.dispatch.len() > 0 {
+            let mut ds = Vec::new();
+            for (c, _) in self.dispatch.asArray().iter() { ds.pushBack(t(format!("#{}", *c as char).toStr())) }
+            rule("dispatch", seq([Production::Choice(ds), class("handler defined")]));
+        }
+
+        let mut brackets = Vec::new();
+        for (o, c) in [("(", ")"), ("[", "]"), ("{", "}")].iter().filter(|(o, _)| match *o {
+            "[" => self.squareBrackets,
+            "{" => self.braces,
+            _ => true
+        }) { brackets.pushBack((String::from(o), String::from(c))) }
+        GrammarDesc {
+            rules,
+            brackets,
+            lineComment : String::from(";"),
+            blockComment : (String::from("#|"), String::from("|#")),
+            datumComment : String::from("#;"),
+            prefixes,
+        }
+    }
+}
+
+/// the syntax read by the default options
+pub fn grammar() -> GrammarDesc {
+    ReaderOptions::new().grammar()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn refs<'a>(p: &'a Production, out: &mut std::vec::Vec<&'a str>) {
+        match p {
+            Production::NonTerminal(n) => out.push(n.toStr()),
+            Production::Sequence(ps) | Production::Choice(ps) => for p in ps.asArray().iter() { refs(p, out) },
+            Production::Optional(p) | Production::ZeroOrMore(p) | Production::OneOrMore(p) => refs(p, out),
+            _ => (),
+        }
+    }
+
+    #[test]
+    fn testGrammar() {
+        let mut opts = ReaderOptions::new();
+        opts.quasiquoteSugar = true;
+        assert!(opts.addDispatch(b'!', |_| PROk(Exp::Bool(true))).is_ok());
+        for g in [grammar(), opts.grammar(), Dialect::Clojure.readerOptions().grammar()].iter() {
+            let mut names = std::vec::Vec::new();
+            for r in g.rules.asArray().iter() { refs(&r.production, &mut names) }
+            assert!(names.iter().all(|n| g.rule(n).is_some()));
+            assert!(match Exp::fromSExp(g.toExp().toString().asArray()) { PROk(e) => e == g.toExp(), _ => false });
+        }
+
+        let ebnf = grammar().toEbnf();
+        assert!(ebnf.toStr().lines().any(|l| l == "list ::= ('(' ((datum+ '.' datum) | datum*) ')') | ('[' ((datum+ '.' datum) | datum*) ']')"));
+        assert!(ebnf.toStr().lines().any(|l| l == "prefixed ::= \"'\" atmosphere* datum"));
+        assert!(ebnf.toStr().lines().any(|l| l == "boolean ::= '#t' | '#f' | '#true' | '#false'"));
+
+        let g = opts.grammar();
+        assert!(g.prefixes.len() == 4 && g.prefixes[2].0 == ",@" && g.prefixes[2].1 == "unquote-splicing");
+        assert!(g.toEbnf().toStr().lines().any(|l| l == "dispatch ::= '#!' <handler defined>"));
+
+        opts = Dialect::Clojure.readerOptions();
+        opts.strictBrackets = false;
+        opts.pipeSymbols = false;
+        opts.apostrophe = Apostrophe::Symbol;
+        let ebnf = opts.grammar().toEbnf();
+        assert!(ebnf.toStr().lines().any(|l| l == "list ::= ('(' | '[') ((datum+ '.' datum) | datum*) (')' | ']')"));
+        assert!(ebnf.toStr().lines().any(|l| l == "boolean ::= '#t' | '#f' | '#true' | '#false' | 'true' | 'false'"));
+        assert!(ebnf.toStr().lines().any(|l| l.starts_with("char ::= '\\' (")));
+        assert!(opts.grammar().rule("pipe-symbol").is_none() && opts.grammar().rule("prefixed").is_none());
+    }
+}
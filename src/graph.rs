@@ -0,0 +1,344 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// datum graphs: `#n=` / `#n#` read into an arena of nodes where a label refers to
+// its node instead of a copy, so shared structure stays shared and cycles
+// (`#0=(a . #0#)`) can be read and written back. Atoms are read by the reader,
+// the graph only holds the containers and the labels between them
+//
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone, PartialEq)]
+pub enum GraphNode {
+    // an expression read whole: atoms and what dispatch handlers return
+    Atom(Exp),
+    List(std::vec::Vec<usize>),
+    Vector(std::vec::Vec<usize>),
+    Pair(usize, usize),
+    Map(std::vec::Vec<(usize, usize)>),
+}
+
+pub struct DatumGraph {
+    nodes   : std::vec::Vec<GraphNode>,
+    root    : usize,
+}
+
+struct GraphReader<'a> {
+    src     : &'a [u8],
+    opts    : &'a ReaderOptions,
+    nodes   : std::vec::Vec<GraphNode>,
+    // label -> node, bound before its datum is read
+    labels  : std::vec::Vec<(&'a [u8], usize)>,
+    depth   : usize,
+}
+
+fn error(kind: ErrorKind, message: &str, offset: usize) -> ParseError {
+    ParseError { message: String::from(message), offset, kind }
+}
+
+impl<'a> GraphReader<'a> {
+    fn place(&mut self, slot: Option<usize>, n: GraphNode) -> usize {
+        match slot {
+            Some(s) => { self.nodes[s] = n; s },
+            None => { self.nodes.push(n); self.nodes.len() - 1 },
+        }
+    }
+
+    // the datum at `offset` into `slot` when a label reserved one
+    fn datum(&mut self, offset: &mut usize, slot: Option<usize>) -> Result<usize, ParseError> {
+        let src = self.src;
+        *offset = Exp::wsEnd(src, *offset, true).unwrap_or(src.len());
+        let start = *offset;
+        match src.get(start) {
+            None => Err(error(ErrorKind::Eof, "unexpected end of stream", start)),
+            Some(b')' | b']' | b'}') => Err(error(ErrorKind::Syntax, "unexpected character (list)", start)),
+            Some(b'#') if self.opts.datumLabels && Exp::datumLabelEnd(src, start).is_some() => {
+                let end = Exp::datumLabelEnd(src, start).unwrap_or(start + 1);
+                let name = &src[start + 1..end - 1];
+                *offset = end;
+                if src[end - 1] == b'=' {
+                    let s = match slot {
+                        Some(s) => s,
+                        None => self.place(None, GraphNode::List(std::vec::Vec::new())),
+                    };
+                    self.labels.push((name, s));
+                    return self.datum(offset, Some(s))
+                }
+                match self.labels.iter().rev().find(|(n, _)| *n == name).map(|(_, i)| *i) {
+                    Some(i) if slot == Some(i) => Err(error(ErrorKind::Syntax, "label refers to itself (label)", start)),
+                    Some(i) => match slot {
+                        Some(s) => { self.nodes[s] = self.nodes[i].clone(); Ok(s) },
+                        None => Ok(i),
+                    },
+                    None => Err(error(ErrorKind::Syntax, "undefined datum label (label)", start)),
+                }
+            },
+            Some(b'(' | b'[' | b'{') => self.container(offset, slot),
+            Some(b'#') if src.get(start + 1) == Some(&b'(') => self.container(offset, slot),
+            Some(b'\'') if self.opts.apostrophe == Apostrophe::Quote => self.prefixed(offset, slot, 1, "quote"),
+            Some(b'`') if self.opts.quasiquoteSugar => self.prefixed(offset, slot, 1, "quasiquote"),
+            Some(b',') if self.opts.quasiquoteSugar && src.get(start + 1) == Some(&b'@') => self.prefixed(offset, slot, 2, "unquote-splicing"),
+            Some(b',') if self.opts.quasiquoteSugar => self.prefixed(offset, slot, 1, "unquote"),
+            Some(_) => {
+                let end = match Exp::formEnd(src, start, true) {
+                    Ok(Some(end)) => end,
+                    Ok(None) => src.len(),
+                    Err(err) => return Err(err),
+                };
+                *offset = end;
+                match Exp::fromSExpWithOptions(&src[start..end], self.opts) {
+                    PROk(e) => Ok(self.place(slot, GraphNode::Atom(e))),
+                    PRErr(err) => Err(error(err.kind, err.message.toStr(), start + err.offset)),
+                }
+            }
+        }
+    }
+
+    fn prefixed(&mut self, offset: &mut usize, slot: Option<usize>, len: usize, name: &str) -> Result<usize, ParseError> {
+        let at = self.place(slot, GraphNode::List(std::vec::Vec::new()));
+        let head = self.place(None, GraphNode::Atom(Exp::Symbol(String::from(name))));
+        *offset += len;
+        let d = self.datum(offset, None)?;
+        self.nodes[at] = GraphNode::List(std::vec![head, d]);
+        Ok(at)
+    }
+
+    fn container(&mut self, offset: &mut usize, slot: Option<usize>) -> Result<usize, ParseError> {
+        let src = self.src;
+        let start = *offset;
+        let vector = src[start] == b'#';
+        if vector { *offset += 1 }
+        let (open, close) = match src[*offset] {
+            b'[' => (b'[', b']'),
+            b'{' => (b'{', b'}'),
+            _ => (b'(', b')'),
+        };
+        *offset += 1;
+        self.depth += 1;
+        if self.opts.maxDepth.is_some_and(|max| self.depth > max) { return Err(error(ErrorKind::Syntax, "nesting too deep", start)) }
+        // reserved now so that labels inside can refer to it
+        let at = self.place(slot, GraphNode::List(std::vec::Vec::new()));
+        let mut items = std::vec::Vec::new();
+        let mut tail = None;
+        loop {
+            *offset = Exp::wsEnd(src, *offset, true).unwrap_or(src.len());
+            match src.get(*offset) {
+                None => return Err(error(ErrorKind::Eof, "unexpected end of stream (list)", src.len())),
+                Some(c) if *c == close => { *offset += 1; break },
+                Some(b')' | b']' | b'}') => return Err(error(ErrorKind::Syntax, "mismatched bracket (list)", *offset)),
+                Some(b'.') if open != b'{' && !vector && !items.is_empty() && tail.is_none() && Exp::isDot(src, *offset) => {
+                    *offset += 1;
+                    tail = Some(self.datum(offset, None)?);
+                    *offset = Exp::wsEnd(src, *offset, true).unwrap_or(src.len());
+                    if src.get(*offset) != Some(&close) { return Err(error(ErrorKind::Syntax, "expected ) after dotted tail (list)", *offset)) }
+                },
+                Some(_) => items.push(self.datum(offset, None)?),
+            }
+        }
+        self.depth -= 1;
+        let node = match (open, tail) {
+            (b'{', _) => {
+                if items.len() % 2 == 1 { return Err(error(ErrorKind::Syntax, "missing value (map)", *offset - 1)) }
+                GraphNode::Map(items.chunks(2).map(|kv| (kv[0], kv[1])).collect())
+            },
+            (_, Some(mut cdr)) => {
+                for i in (1..items.len()).rev() { cdr = self.place(None, GraphNode::Pair(items[i], cdr)) }
+                GraphNode::Pair(items[0], cdr)
+            },
+            _ if vector => GraphNode::Vector(items),
+            _ => GraphNode::List(items),
+        };
+        self.nodes[at] = node;
+        Ok(at)
+    }
+}
+
+impl DatumGraph {
+    pub fn parse(src: &[u8]) -> ParseResult<DatumGraph> {
+        Self::parseWithOptions(src, &ReaderOptions::new())
+    }
+
+    /// one datum, shared nodes and cycles made by labels are kept as they are
+    pub fn parseWithOptions(src: &[u8], opts: &ReaderOptions) -> ParseResult<DatumGraph> {
+        let mut rd = GraphReader { src, opts, nodes: std::vec::Vec::new(), labels: std::vec::Vec::new(), depth: 0 };
+        let mut offset = Exp::documentStart(src, opts);
+        let root = match rd.datum(&mut offset, None) {
+            Ok(root) => root,
+            Err(err) => return PRErr(err),
+        };
+        if Exp::wsEnd(src, offset, true) != Some(src.len()) {
+            return PRErr(error(ErrorKind::Syntax, "unexpected trailing data", offset))
+        }
+        PROk(DatumGraph { nodes: rd.nodes, root })
+    }
+
+    /// the tree `e` as a graph without sharing
+    pub fn fromExp(e: &Exp) -> DatumGraph {
+        fn add(e: &Exp, nodes: &mut std::vec::Vec<GraphNode>) -> usize {
+            let n = match e {
+                Exp::List(l) => GraphNode::List(l.asArray().iter().map(|c| add(c, nodes)).collect()),
+                Exp::Vector(l) => GraphNode::Vector(l.asArray().iter().map(|c| add(c, nodes)).collect()),
+                Exp::Pair(a, d) => GraphNode::Pair(add(a, nodes), add(d, nodes)),
+                Exp::Map(m) => GraphNode::Map(m.asArray().iter().map(|(k, v)| (add(k, nodes), add(v, nodes))).collect()),
+                e => GraphNode::Atom(e.clone()),
+            };
+            nodes.push(n);
+            nodes.len() - 1
+        }
+        let mut nodes = std::vec::Vec::new();
+        let root = add(e, &mut nodes);
+        DatumGraph { nodes, root }
+    }
+
+    pub fn root(&self) -> usize { self.root }
+    pub fn node(&self, i: usize) -> Option<&GraphNode> { self.nodes.get(i) }
+    pub fn len(&self) -> usize { self.nodes.len() }
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+
+    fn children(&self, i: usize) -> std::vec::Vec<usize> {
+        match &self.nodes[i] {
+            GraphNode::Atom(_) => std::vec::Vec::new(),
+            GraphNode::List(l) | GraphNode::Vector(l) => l.clone(),
+            GraphNode::Pair(a, d) => std::vec![*a, *d],
+            GraphNode::Map(m) => m.iter().flat_map(|(k, v)| [*k, *v]).collect(),
+        }
+    }
+
+    /// the tree the graph unfolds to, None when it has a cycle or more than `maxNodes` nodes
+    pub fn toExp(&self, maxNodes: usize) -> Option<Exp> {
+        fn unfold(g: &DatumGraph, i: usize, onPath: &mut std::vec::Vec<bool>, budget: &mut usize) -> Option<Exp> {
+            if onPath[i] || *budget == 0 { return None }
+            *budget -= 1;
+            onPath[i] = true;
+            let mut list = |l: &[usize], onPath: &mut std::vec::Vec<bool>| -> Option<Vec<Exp>> {
+                let mut v = Vec::new();
+                for c in l.iter() { v.pushBack(unfold(g, *c, onPath, budget)?) }
+                Some(v)
+            };
+            let e = match &g.nodes[i] {
+                GraphNode::Atom(e) => e.clone(),
+                GraphNode::List(l) => Exp::List(list(l, onPath)?),
+                GraphNode::Vector(l) => Exp::Vector(list(l, onPath)?),
+                GraphNode::Pair(a, d) => {
+                    let l = list(&[*a, *d], onPath)?;
+                    Exp::Pair(std::boxed::Box::new(l[0].clone()), std::boxed::Box::new(l[1].clone()))
+                },
+                GraphNode::Map(m) => {
+                    let flat : std::vec::Vec<usize> = m.iter().flat_map(|(k, v)| [*k, *v]).collect();
+                    let l = list(&flat, onPath)?;
+                    let mut entries = Vec::new();
+                    for kv in l.asArray().chunks(2) { entries.pushBack((kv[0].clone(), kv[1].clone())) }
+                    Exp::Map(entries)
+                },
+            };
+            onPath[i] = false;
+            Some(e)
+        }
+        unfold(self, self.root, &mut std::vec![false; self.nodes.len()], &mut usize::max(maxNodes, 1))
+    }
+
+    /// the graph with a `#n=` on every node reached more than once and `#n#` after
+    pub fn toString(&self) -> String {
+        // how many times each node is reached, the children of a node are walked once
+        let mut reached = std::vec![0usize; self.nodes.len()];
+        let mut stack = std::vec![self.root];
+        while let Some(i) = stack.pop() {
+            reached[i] += 1;
+            if reached[i] == 1 { stack.extend(self.children(i)) }
+        }
+        let mut labels : std::vec::Vec<Option<usize>> = std::vec![None; self.nodes.len()];
+        let mut out = String::new();
+        self.print(self.root, &reached, &mut labels, &mut 0, &mut out);
+        out
+    }
+
+    fn print(&self, i: usize, reached: &[usize], labels: &mut [Option<usize>], next: &mut usize, out: &mut String) {
+        if let Some(n) = labels[i] { return out.append(&alt_std::format!("#{}#", n)) }
+        if reached[i] > 1 {
+            labels[i] = Some(*next);
+            out.append(&alt_std::format!("#{}=", *next));
+            *next += 1;
+        }
+        let items = |l: &[usize], out: &mut String, labels: &mut [Option<usize>], next: &mut usize| for (k, c) in l.iter().enumerate() {
+            if k != 0 { out.add(b' ') }
+            self.print(*c, reached, labels, next, out);
+        };
+        match &self.nodes[i] {
+            GraphNode::Atom(e) => out.append(&e.toString()),
+            GraphNode::List(l) => { out.add(b'('); items(l, out, labels, next); out.add(b')') },
+            GraphNode::Vector(l) => { out.append("#("); items(l, out, labels, next); out.add(b')') },
+            GraphNode::Map(m) => {
+                out.add(b'{');
+                let flat : std::vec::Vec<usize> = m.iter().flat_map(|(k, v)| [*k, *v]).collect();
+                items(&flat, out, labels, next);
+                out.add(b'}')
+            },
+            GraphNode::Pair(a, d) => {
+                out.add(b'(');
+                self.print(*a, reached, labels, next, out);
+                // the cdr chain is written inline until a node that needs a label
+                let mut cur = *d;
+                while let (GraphNode::Pair(a, d), 1, None) = (&self.nodes[cur], reached[cur], labels[cur]) {
+                    out.add(b' ');
+                    self.print(*a, reached, labels, next, out);
+                    cur = *d;
+                }
+                out.append(" . ");
+                self.print(cur, reached, labels, next, out);
+                out.add(b')')
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> DatumGraph {
+        match DatumGraph::parse(s.as_bytes()) { PROk(g) => g, PRErr(err) => panic!("{} at {}", err.message.toStr(), err.offset) }
+    }
+
+    #[test]
+    fn testDatumGraph() {
+        // a cycle through the cdr and one through a child
+        let g = parse("#0=(a b . #0#)");
+        assert!(g.toString() == "#0=(a b . #0#)" && g.toExp(100).is_none());
+        let g = parse("#5=(x #(1 #5#) {:k #5#})");
+        assert!(g.toString() == "#0=(x #(1 #0#) {:k #0#})");
+        assert!(parse(g.toString().toStr()).toString() == g.toString());
+
+        // sharing without copies: the node count stays linear
+        let mut src = std::string::String::from("(#0=(x x)");
+        for i in 1..64 { src.push_str(&std::format!(" #{}=(#{}# #{}#)", i, i - 1, i - 1)) }
+        src.push(')');
+        let g = parse(&src);
+        assert!(g.len() < 200 && g.toExp(1000).is_none());
+        assert!(g.toString().toStr().ends_with(" (#62# #62#))"));
+
+        let g = parse("(#1='q #1# (a . b))");
+        assert!(g.toString() == "(#0=(quote q) #0# (a . b))");
+        assert!(g.toExp(100).map(|e| e.toString()) == Some(String::from("((quote q) (quote q) (a . b))")));
+        assert!(DatumGraph::fromExp(&Exp::Int(1)).toString() == "1");
+        assert!(match DatumGraph::parse(b"(a #0=#0#)") { PRErr(err) => err.offset == 6, _ => false });
+        assert!(match DatumGraph::parse(b"(a #1#)") { PRErr(err) => err.message.toStr() == "undefined datum label (label)", _ => false });
+    }
+}
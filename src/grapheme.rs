@@ -0,0 +1,84 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// grapheme clusters, close enough for character literals: a base code point
+// followed by combining marks, variation selectors, emoji modifiers and tags,
+// zero width joiner sequences and regional indicator pairs
+//
+use crate::*;
+
+fn isExtender(c: char) -> bool {
+    match c as u32 {
+        0x0300..=0x036F | 0x1AB0..=0x1AFF | 0x1DC0..=0x1DFF | 0x20D0..=0x20FF | 0xFE20..=0xFE2F => true,
+        0xFE00..=0xFE0F | 0xE0100..=0xE01EF => true,
+        0x1F3FB..=0x1F3FF | 0xE0020..=0xE007F => true,
+        _ => false
+    }
+}
+
+fn isRegionalIndicator(c: char) -> bool {
+    (0x1F1E6..=0x1F1FF).contains(&(c as u32))
+}
+
+impl Exp {
+    // end of the cluster starting with `first`, which ends at `offset`
+    pub(crate) fn graphemeEnd(src: &[u8], first: char, offset: usize) -> usize {
+        let mut end = offset;
+        let mut prev = first;
+        let mut pairs = if isRegionalIndicator(first) { 1 } else { 0 };
+        loop {
+            let mut next = end;
+            let c = match Self::readUtf8(src, &mut next) { Some(c) => c, None => return end };
+            let joined = prev == '\u{200D}';
+            if isExtender(c) || c == '\u{200D}' || joined || (isRegionalIndicator(c) && pairs == 1) {
+                if isRegionalIndicator(c) { pairs += 1 }
+                prev = c;
+                end = next;
+            } else {
+                return end
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testGraphemeChars() {
+        let mut opts = ReaderOptions::new();
+        opts.graphemeChars = true;
+        let src = "(#\\e\u{301} #\\\u{1F469}\u{200D}\u{1F4BB} #\\\u{1F1EB}\u{1F1F7} #\\\u{1F1EB} #\\a #\\λ #\\space)";
+        match Exp::fromSExpWithOptions(src.as_bytes(), &opts) {
+            PROk(Exp::List(l)) => {
+                assert!(l.len() == 7);
+                assert!(match &l[0] { Exp::String(s) => s.asArray() == "e\u{301}".as_bytes(), _ => false });
+                assert!(match &l[1] { Exp::String(s) => s.asArray() == "\u{1F469}\u{200D}\u{1F4BB}".as_bytes(), _ => false });
+                assert!(match &l[2] { Exp::String(s) => s.asArray() == "\u{1F1EB}\u{1F1F7}".as_bytes(), _ => false });
+                assert!(l[3] == Exp::Char('\u{1F1EB}'));
+                assert!(l[4] == Exp::Char('a') && l[5] == Exp::Char('λ') && l[6] == Exp::Char(' '));
+            },
+            _ => panic!("grapheme chars failed to parse")
+        }
+        // without the option only the base code point is read
+        assert!(match Exp::fromSExp("#\\e\u{301}".as_bytes()) { PROk(Exp::Char('e')) => true, _ => false });
+    }
+}
@@ -0,0 +1,163 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use alt_std::hash::murmurHash64A;
+use crate::*;
+
+pub(crate) const HASH_SEED : u64 = 0x6a09_e667;
+
+/// expression with its structural hash and node count computed once, so
+/// unequal trees are usually told apart without walking them
+#[derive(Clone)]
+pub struct HashedExp {
+    exp     : Exp,
+    hash    : u64,
+    size    : usize,
+}
+
+impl Exp {
+    // equal expressions (as per `==`) hash the same, `sink` sees every subtree bottom up
+    pub(crate) fn hashSubtrees<'a>(&'a self, sink: &mut dyn FnMut(&'a Exp, u64, usize)) -> (u64, usize) {
+        let mut buff = Vec::new();
+        let mut size = 1;
+        let tag = |buff: &mut Vec<u8>, t: u8, payload: &[u8]| {
+            buff.pushBack(t);
+            for c in payload.iter() { buff.pushBack(*c) }
+        };
+        match self {
+            Self::Bool(b) => tag(&mut buff, 0, &[*b as u8]),
+            Self::Char(c) => tag(&mut buff, 1, &(*c as u32).to_le_bytes()),
+            Self::Int(i) => tag(&mut buff, 2, &i.to_le_bytes()),
+            Self::UInt(u) => tag(&mut buff, 17, &u.to_le_bytes()),
+            // 0.0 == -0.0
+            Self::Float(f) => tag(&mut buff, 3, &(if *f == 0.0 { 0.0f64 } else { *f }).to_bits().to_le_bytes()),
+            Self::String(s) => tag(&mut buff, 4, s.asArray()),
+            Self::Symbol(s) => tag(&mut buff, 5, s.asArray()),
+            Self::Keyword(k) => tag(&mut buff, 11, k.asArray()),
+            Self::Bytes(b) => tag(&mut buff, 14, b.asArray()),
+            // by content like the equality, two spills of the same text hash the same
+            Self::Spilled(s) => tag(&mut buff, 18, &Self::spilledBytes(s)),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => {
+                tag(&mut buff, 16, &[b.isNegative() as u8]);
+                for l in b.limbs().iter() {
+                    for c in l.to_le_bytes().iter() { buff.pushBack(*c) }
+                }
+            },
+            Self::Ratio(n, d) => {
+                tag(&mut buff, 15, &n.to_le_bytes());
+                for c in d.to_le_bytes().iter() { buff.pushBack(*c) }
+            },
+            Self::List(l) => {
+                tag(&mut buff, 6, &l.len().to_le_bytes());
+                for e in l.asArray().iter() {
+                    let (h, n) = e.hashSubtrees(sink);
+                    tag(&mut buff, 7, &h.to_le_bytes());
+                    size += n;
+                }
+            },
+            Self::Vector(v) => {
+                tag(&mut buff, 13, &v.len().to_le_bytes());
+                for e in v.asArray().iter() {
+                    let (h, n) = e.hashSubtrees(sink);
+                    tag(&mut buff, 7, &h.to_le_bytes());
+                    size += n;
+                }
+            },
+            Self::Pair(a, d) => {
+                let (ha, na) = a.hashSubtrees(sink);
+                let (hd, nd) = d.hashSubtrees(sink);
+                tag(&mut buff, 12, &ha.to_le_bytes());
+                tag(&mut buff, 12, &hd.to_le_bytes());
+                size += na + nd;
+            },
+            Self::Map(m) => {
+                // entries are summed so that their order does not matter
+                tag(&mut buff, 8, &m.len().to_le_bytes());
+                let mut entries = 0u64;
+                for (k, v) in m.asArray().iter() {
+                    let (hk, nk) = k.hashSubtrees(sink);
+                    let (hv, nv) = v.hashSubtrees(sink);
+                    let mut pair = Vec::new();
+                    tag(&mut pair, 9, &hk.to_le_bytes());
+                    tag(&mut pair, 9, &hv.to_le_bytes());
+                    entries = entries.wrapping_add(murmurHash64A(pair.asArray(), HASH_SEED));
+                    size += nk + nv;
+                }
+                tag(&mut buff, 10, &entries.to_le_bytes());
+            },
+        }
+        let h = murmurHash64A(buff.asArray(), HASH_SEED);
+        sink(self, h, size);
+        (h, size)
+    }
+
+    pub(crate) fn structuralHash(&self) -> (u64, usize) {
+        self.hashSubtrees(&mut |_, _, _| ())
+    }
+}
+
+impl HashedExp {
+    pub fn new(exp: Exp) -> Self {
+        let (hash, size) = exp.structuralHash();
+        Self { exp, hash, size }
+    }
+
+    pub fn exp(&self) -> &Exp { &self.exp }
+    pub fn hash(&self) -> u64 { self.hash }
+    pub fn size(&self) -> usize { self.size }
+
+    /// `==` on the expressions, deciding on the hash and size whenever they differ
+    pub fn eqFast(&self, other: &HashedExp) -> bool {
+        self.hash == other.hash && self.size == other.size && self.exp == other.exp
+    }
+}
+
+impl PartialEq for HashedExp {
+    fn eq(&self, other: &HashedExp) -> bool { self.eqFast(other) }
+}
+
+impl Exp {
+    /// `==` for expressions whose hashes are already known (as kept by `HashedExp`)
+    pub fn eqFast(&self, selfHash: u64, other: &Exp, otherHash: u64) -> bool {
+        selfHash == otherHash && self == other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testEqFast() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => HashedExp::new(e),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = parse("(a (b 1 2.5) \"x\" (c (d -0.0)))");
+        let b = parse("(a (b 1 2.5) \"x\" (c (d 0.0)))");
+        let c = parse("(a (b 1 2.5) \"x\" (c (d 1)))");
+        assert!(a.size() == 12);
+        assert!(a.eqFast(&b) && a == b);
+        assert!(a.hash() != c.hash() && !a.eqFast(&c));
+        assert!(a.exp().eqFast(a.hash(), b.exp(), b.hash()));
+        assert!(HashedExp::new(Exp::Int(1)) != HashedExp::new(Exp::Float(1.0)));
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    pub(crate) fn escapeHtml(out: &mut String, s: &[u8]) {
+        for c in s.iter() {
+            match *c {
+                b'&' => out.append("&amp;"),
+                b'<' => out.append("&lt;"),
+                b'>' => out.append("&gt;"),
+                b'"' => out.append("&quot;"),
+                b'\'' => out.append("&#39;"),
+                c => out.add(c),
+            }
+        }
+    }
+
+    fn htmlClass(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "sexp-bool",
+            Self::Char(_) => "sexp-char",
+            Self::Int(_) | Self::UInt(_) | Self::Ratio(..) | Self::Float(_) => "sexp-number",
+            #[cfg(feature = "bigint")]
+            Self::BigInt(_) => "sexp-number",
+            Self::String(_) | Self::Spilled(_) => "sexp-string",
+            Self::Symbol(_) => "sexp-symbol",
+            Self::Keyword(_) => "sexp-keyword",
+            Self::List(_) => "sexp-list",
+            Self::Bytes(_) => "sexp-bytes",
+            Self::Vector(_) => "sexp-vector",
+            Self::Pair(..) => "sexp-pair",
+            Self::Map(_) => "sexp-map",
+        }
+    }
+
+    fn writeHtml(&self, out: &mut String) {
+        out.append("<span class=\"");
+        out.append(self.htmlClass());
+        out.append("\">");
+        match self {
+            Self::List(l) => {
+                out.append("<span class=\"sexp-paren\">(</span>");
+                for i in 0..l.len() {
+                    l[i].writeHtml(out);
+                    if i != l.len() - 1 {
+                        out.add(b' ');
+                    }
+                }
+                out.append("<span class=\"sexp-paren\">)</span>");
+            },
+            Self::Vector(v) => {
+                out.append("<span class=\"sexp-paren\">#(</span>");
+                for i in 0..v.len() {
+                    if i != 0 { out.add(b' ') }
+                    v[i].writeHtml(out);
+                }
+                out.append("<span class=\"sexp-paren\">)</span>");
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                out.append("<span class=\"sexp-paren\">(</span>");
+                for i in 0..items.len() {
+                    if i != 0 { out.add(b' ') }
+                    items[i].writeHtml(out);
+                }
+                if let Some(t) = tail {
+                    out.append(" . ");
+                    t.writeHtml(out);
+                }
+                out.append("<span class=\"sexp-paren\">)</span>");
+            },
+            Self::Map(m) => {
+                out.append("<span class=\"sexp-paren\">{</span>");
+                for i in 0..m.len() {
+                    if i != 0 { out.add(b' ') }
+                    m[i].0.writeHtml(out);
+                    out.add(b' ');
+                    m[i].1.writeHtml(out);
+                }
+                out.append("<span class=\"sexp-paren\">}</span>");
+            },
+            _ => Self::escapeHtml(out, self.toString().asArray()),
+        }
+        out.append("</span>");
+    }
+
+    /// `<span class="sexp-...">` markup, style with the sexp-bool/char/number/string/symbol/keyword/bytes/list/vector/pair/map/paren classes
+    pub fn toHtml(&self) -> String {
+        let mut s = String::new();
+        self.writeHtml(&mut s);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToHtml() {
+        let sexp = String::from("(< \"a&b\" 1)");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toHtml() == "<span class=\"sexp-list\"><span class=\"sexp-paren\">(</span>\
+                                              <span class=\"sexp-symbol\">&lt;</span> \
+                                              <span class=\"sexp-string\">&quot;a&amp;b&quot;</span> \
+                                              <span class=\"sexp-number\">1</span>\
+                                              <span class=\"sexp-paren\">)</span></span>"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+}
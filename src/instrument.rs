@@ -0,0 +1,114 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// phase instrumentation: with the `tracing` feature each phase is a debug span
+// (`sexp.parse`, `sexp.print`, `sexp.validate`) closed by an event carrying the
+// input size in bytes, the elapsed time in microseconds and the outcome,
+// without it everything here compiles to nothing
+//
+
+#[derive(Clone, Copy)]
+pub(crate) enum PhaseKind {
+    Parse,
+    Print,
+    Validate,
+}
+
+#[cfg(feature = "tracing")]
+pub(crate) struct Phase {
+    span    : tracing::span::EnteredSpan,
+    bytes   : usize,
+    start   : std::time::Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl Phase {
+    pub(crate) fn begin(kind: PhaseKind, bytes: usize) -> Self {
+        let span = match kind {
+            PhaseKind::Parse => tracing::debug_span!("sexp.parse", bytes),
+            PhaseKind::Print => tracing::debug_span!("sexp.print"),
+            PhaseKind::Validate => tracing::debug_span!("sexp.validate"),
+        };
+        Self { span: span.entered(), bytes, start: std::time::Instant::now() }
+    }
+
+    pub(crate) fn endWithSize(self, ok: bool, bytes: usize) {
+        let elapsed_us = self.start.elapsed().as_micros() as u64;
+        tracing::debug!(parent: self.span.id(), bytes, elapsed_us, ok, "done");
+    }
+
+    pub(crate) fn end(self, ok: bool) {
+        let bytes = self.bytes;
+        self.endWithSize(ok, bytes)
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Phase;
+
+#[cfg(not(feature = "tracing"))]
+impl Phase {
+    #[inline(always)]
+    pub(crate) fn begin(_kind: PhaseKind, _bytes: usize) -> Self { Phase }
+    #[inline(always)]
+    pub(crate) fn endWithSize(self, _ok: bool, _bytes: usize) {}
+    #[inline(always)]
+    pub(crate) fn end(self, _ok: bool) {}
+}
+
+#[cfg(all(test, feature = "tracing"))]
+mod tests {
+    use crate::*;
+    use std::sync::{Arc, Mutex};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    // keeps the span names and the number of events
+    struct Collect(Arc<Mutex<(std::vec::Vec<&'static str>, usize)>>);
+
+    impl Subscriber for Collect {
+        fn enabled(&self, _: &Metadata<'_>) -> bool { true }
+        fn new_span(&self, a: &Attributes<'_>) -> Id {
+            let mut c = self.0.lock().unwrap();
+            c.0.push(a.metadata().name());
+            Id::from_u64(c.0.len() as u64)
+        }
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+        fn event(&self, _: &Event<'_>) { self.0.lock().unwrap().1 += 1 }
+        fn enter(&self, _: &Id) {}
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn testPhaseSpans() {
+        let seen = Arc::new(Mutex::new((std::vec::Vec::new(), 0)));
+        tracing::subscriber::with_default(Collect(seen.clone()), || {
+            match Exp::fromSExp(b"(a (b 1))") {
+                PROk(e) => { e.toString(); },
+                PRErr(_) => panic!("parse failed")
+            }
+        });
+        let seen = seen.lock().unwrap();
+        assert!(seen.0 == ["sexp.parse", "sexp.print"]);
+        assert!(seen.1 == 2);
+    }
+}
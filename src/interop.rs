@@ -0,0 +1,120 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// lexpr interop: conversions between `Exp` and `lexpr::Value`. Lists map to
+// proper lists, pairs to cons cells and maps to association lists of
+// `(key . value)` pairs
+//
+use alt_std::*;
+use crate::*;
+
+impl From<&Exp> for lexpr::Value {
+    fn from(e: &Exp) -> Self {
+        match e {
+            Exp::Bool(b) => lexpr::Value::Bool(*b),
+            Exp::Char(c) => lexpr::Value::Char(*c),
+            Exp::Int(i) => lexpr::Value::Number(lexpr::Number::from(*i)),
+            Exp::UInt(u) => lexpr::Value::Number(lexpr::Number::from(*u)),
+            Exp::Float(f) => lexpr::Value::Number(lexpr::Number::from(*f)),
+            // lexpr has no rationals
+            Exp::Ratio(n, d) => lexpr::Value::Number(lexpr::Number::from(*n as f64 / *d as f64)),
+            #[cfg(feature = "bigint")]
+            Exp::BigInt(b) => lexpr::Value::Number(lexpr::Number::from(b.toF64())),
+            Exp::String(s) => lexpr::Value::String(s.toStr().into()),
+            Exp::Spilled(s) => lexpr::Value::String(std::string::String::from_utf8_lossy(&Exp::spilledBytes(s)).into()),
+            Exp::Symbol(s) => lexpr::Value::Symbol(s.toStr().into()),
+            Exp::Keyword(k) => lexpr::Value::Keyword(k.toStr().into()),
+            Exp::List(l) => lexpr::Value::list(l.asArray().iter().map(lexpr::Value::from)),
+            Exp::Bytes(b) => lexpr::Value::Bytes(b.asArray().into()),
+            Exp::Vector(v) => lexpr::Value::vector(v.asArray().iter().map(lexpr::Value::from)),
+            Exp::Pair(a, d) => lexpr::Value::cons(lexpr::Value::from(&**a), lexpr::Value::from(&**d)),
+            Exp::Map(m) => lexpr::Value::list(m.asArray().iter().map(|(k, v)| lexpr::Value::cons(lexpr::Value::from(k), lexpr::Value::from(v)))),
+        }
+    }
+}
+
+impl From<Exp> for lexpr::Value {
+    fn from(e: Exp) -> Self { lexpr::Value::from(&e) }
+}
+
+fn listOf<'a, I: Iterator<Item = &'a lexpr::Value>>(items: I) -> Vec<Exp> {
+    let mut l = Vec::new();
+    for v in items { l.pushBack(Exp::from(v)) }
+    l
+}
+
+impl From<&lexpr::Value> for Exp {
+    fn from(v: &lexpr::Value) -> Self {
+        match v {
+            lexpr::Value::Nil | lexpr::Value::Null => Exp::List(Vec::new()),
+            lexpr::Value::Bool(b) => Exp::Bool(*b),
+            lexpr::Value::Char(c) => Exp::Char(*c),
+            lexpr::Value::Number(n) => match (n.as_i64(), n.as_f64()) {
+                (Some(i), _) => Exp::Int(i),
+                (None, Some(f)) => Exp::Float(f),
+                (None, None) => Exp::Float(f64::NAN),
+            },
+            lexpr::Value::String(s) => Exp::String(String::from(s)),
+            lexpr::Value::Symbol(s) => Exp::Symbol(String::from(s)),
+            lexpr::Value::Keyword(k) => Exp::Keyword(String::from(k)),
+            lexpr::Value::Bytes(b) => {
+                let mut v = Vec::new();
+                for c in b.iter() { v.pushBack(*c) }
+                Exp::Bytes(v)
+            },
+            lexpr::Value::Vector(items) => Exp::Vector(listOf(items.iter())),
+            lexpr::Value::Cons(c) => {
+                let mut l = Vec::new();
+                let mut cur = c;
+                loop {
+                    l.pushBack(Exp::from(cur.car()));
+                    match cur.cdr() {
+                        lexpr::Value::Cons(next) => cur = next,
+                        lexpr::Value::Null => break,
+                        tail => return l.asArray().iter().rev().fold(Exp::from(tail), |cdr, car| Exp::cons(car.clone(), cdr)),
+                    }
+                }
+                Exp::List(l)
+            },
+        }
+    }
+}
+
+impl From<lexpr::Value> for Exp {
+    fn from(v: lexpr::Value) -> Self { Exp::from(&v) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testLexprInterop() {
+        let src = "(a \"s\" 12 -1.5 #t #\\x (b ()))";
+        let e = match Exp::fromSExp(src.as_bytes()) { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let v = lexpr::Value::from(&e);
+        assert!(v == lexpr::from_str(src).unwrap());
+        assert!(Exp::from(&v) == e);
+
+        let v = lexpr::from_str("(#(1 2) #:kw (x . y))").unwrap();
+        assert!(Exp::from(&v).toString().toStr() == "(#(1 2) :kw (x . y))");
+        assert!(lexpr::Value::from(Exp::from(&v)) == v);
+    }
+}
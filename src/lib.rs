@@ -18,13 +18,161 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
 // THE SOFTWARE.
 #![allow(non_snake_case, non_camel_case_types)]
+#![allow(clippy::match_like_matches_macro, clippy::manual_range_contains, clippy::single_match, clippy::needless_return, clippy::needless_range_loop, clippy::char_lit_as_u8, clippy::useless_conversion)]
+// no explicit panics outside of tests, a panic aborts a wasm instance
+#![cfg_attr(not(test), deny(clippy::panic, clippy::unwrap_used, clippy::expect_used, clippy::unreachable, clippy::todo, clippy::unimplemented))]
 
 use alt_std::*;
 use alt_std::{format};
 
+// generated bindings compiled by the tests name the crate as `::s_exp`
+#[cfg(test)]
+extern crate self as s_exp;
+
+mod digest;
+mod codegen;
+mod staticexp;
+mod compare;
+mod number;
+#[cfg(feature = "color")]
+mod color;
+mod html;
+mod dot;
+mod instrument;
+use instrument::*;
+mod csv;
+mod path;
+mod flatten;
+mod env;
+mod query;
+mod stream;
+mod rename;
+mod anonymize;
+mod hashed;
+mod search;
+mod similarity;
+mod merge;
+mod patch;
+mod session;
+mod transaction;
+mod metrics;
+mod trace;
+mod transcode;
+mod grapheme;
+mod spill;
+mod pretty;
+mod record;
+mod router;
+mod framed;
+mod dispatch;
+mod dialect;
+mod writeinto;
+mod measure;
+mod nodeid;
+mod annotations;
+mod map;
+mod pair;
+mod bytes;
+mod share;
+mod schema;
+mod convert;
+mod rustgen;
+mod schemadoc;
+mod compat;
+mod textblock;
+mod namespace;
+mod recover;
+mod split;
+mod ratio;
+mod uint;
+mod view;
+mod symtab;
+mod spans;
+mod parser;
+mod assert;
+mod graph;
+mod grammar;
+#[cfg(feature = "lexpr")]
+mod interop;
+#[cfg(feature = "serde")]
+mod serdes;
+#[cfg(feature = "async")]
+mod sink;
+#[cfg(feature = "log")]
+mod logger;
+#[cfg(feature = "std")]
+mod cache;
+#[cfg(feature = "columnar")]
+mod columnar;
+#[cfg(feature = "bigint")]
+mod bigint;
+#[cfg(feature = "archive")]
+mod archive;
+#[cfg(test)]
+mod mutate;
+#[cfg(all(test, feature = "lexpr"))]
+mod differential;
+pub use digest::*;
+pub use staticexp::*;
+pub use number::*;
+pub use csv::*;
+pub use path::*;
+pub use query::*;
+pub use stream::*;
+pub use anonymize::*;
+pub use hashed::*;
+pub use merge::*;
+pub use patch::*;
+pub use session::*;
+pub use metrics::*;
+pub use trace::*;
+pub use transcode::*;
+pub use record::*;
+pub use router::*;
+pub use framed::*;
+pub use dispatch::*;
+pub use dialect::*;
+pub use writeinto::*;
+pub use nodeid::*;
+pub use annotations::*;
+pub use schema::*;
+pub use convert::*;
+pub use compat::*;
+pub use namespace::*;
+pub use grammar::*;
+pub use view::*;
+pub use symtab::*;
+use spans::*;
+pub use parser::*;
+pub use compare::*;
+pub use assert::*;
+pub use graph::*;
+pub use spill::*;
+#[cfg(feature = "async")]
+pub use sink::*;
+#[cfg(feature = "log")]
+pub use logger::*;
+#[cfg(feature = "std")]
+pub use cache::*;
+#[cfg(feature = "columnar")]
+pub use columnar::*;
+#[cfg(feature = "color")]
+pub use color::*;
+#[cfg(feature = "bigint")]
+pub use bigint::*;
+#[cfg(feature = "archive")]
+pub use archive::*;
+
 pub struct ParseError {
     message : String,
-    offset  : usize
+    offset  : usize,
+    kind    : ErrorKind,
+}
+
+impl ParseError {
+    pub fn message(&self) -> &str { self.message.toStr() }
+    pub fn offset(&self) -> usize { self.offset }
+    pub fn kind(&self) -> ErrorKind { self.kind }
 }
 
 pub enum ParseResult<T> {
@@ -34,11 +182,196 @@ pub enum ParseResult<T> {
 
 use ParseResult::*;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntOverflow {
+    // reject integer literals that do not fit in an i64
+    Error,
+    // read them as a (lossy) Float and report a warning
+    Float,
+    // read them exactly as a BigInt
+    #[cfg(feature = "bigint")]
+    BigInt,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Apostrophe {
+    // 'x reads as (quote x), inside a token (don't) it is a symbol character
+    Quote,
+    // a symbol character everywhere, 'x is the symbol `'x`
+    Symbol,
+    // fail with "unexpected ' (symbol)" wherever it appears
+    Error,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AtomOverflow {
+    // fail with "atom too long" at the start of the atom
+    Error,
+    // decode the string into a temporary file, read as `Exp::Spilled`
+    Spill,
+}
+
+#[derive(Clone)]
+pub struct ReaderOptions {
+    pub intOverflow : IntOverflow,
+    // shared counters updated after every parse
+    pub metrics     : Option<std::sync::Arc<ParserMetrics>>,
+    // read a `#\` followed by a multi code point grapheme cluster (e + accent,
+    // emoji sequences, flags) as a String of the whole cluster
+    pub graphemeChars : bool,
+    // longest atom accepted (decoded bytes), None for no limit
+    pub maxAtomLen  : Option<usize>,
+    // what to do with strings over `maxAtomLen`, other atoms are always an error
+    pub atomOverflow : AtomOverflow,
+    // read the bare words true/false as booleans, whatever the dialect
+    pub wordBooleans : bool,
+    // booleans and characters spelled the way of this dialect are read too
+    pub dialect     : Dialect,
+    // `[a b]` is a list too, when strict `]` only closes `[` and `)` only `(`
+    pub strictBrackets : bool,
+    // how ' reads, quote sugar by default
+    pub apostrophe  : Apostrophe,
+    // read `x, ,x and ,@x as (quasiquote x), (unquote x) and (unquote-splicing x)
+    pub quasiquoteSugar : bool,
+    // read `(def $n x)` as x and `(ref $n)` as a copy of it
+    pub expandRefs  : bool,
+    // read |a b| as the symbol `a b` (escapes as in strings) and || as the empty
+    // one, otherwise `|` is an ordinary symbol character (`|>`). Other runs of bars
+    // ending a token (a lone |, the ||||||| of merge conflicts) are plain symbols
+    pub pipeSymbols : bool,
+    // skip a first line starting with #! (`#!/usr/bin/env tool` of scripts)
+    pub skipShebang : bool,
+    // read `#n=x` as x and `#n#` as a copy of it, a `#n#` inside its own `#n=` is an
+    // error since trees have no cycles (`DatumGraph` reads those). Otherwise they
+    // are plain symbols
+    pub datumLabels : bool,
+    // most nodes copied in by `#n#` and `(ref $n)` over a whole parse, copies of
+    // copies grow exponentially with the input
+    pub maxExpansion : usize,
+    // read `:k` as a keyword (`:|k l|` with pipeSymbols, never empty), otherwise as the symbol `:k`
+    pub keywords    : bool,
+    // lower case symbols as they are read (`#!fold-case`), |...| symbols are kept as written
+    pub foldCase    : bool,
+    // `[...]` lists and `{...}` maps, an error otherwise
+    pub squareBrackets : bool,
+    pub braces      : bool,
+    // deepest nesting of lists, vectors and maps accepted, None for no limit
+    pub maxDepth    : Option<usize>,
+    // `#c` handlers, see `addDispatch`
+    dispatch        : Vec<(u8, DispatchFn)>,
+}
+
+impl ReaderOptions {
+    pub fn new() -> Self {
+        Self {
+            intOverflow : IntOverflow::Float,
+            metrics     : None,
+            graphemeChars : false,
+            maxAtomLen  : None,
+            atomOverflow : AtomOverflow::Error,
+            wordBooleans : false,
+            dialect     : Dialect::Scheme,
+            strictBrackets : true,
+            apostrophe  : Apostrophe::Quote,
+            quasiquoteSugar : false,
+            expandRefs  : false,
+            pipeSymbols : true,
+            skipShebang : true,
+            datumLabels : true,
+            maxExpansion : 1 << 20,
+            keywords    : true,
+            foldCase    : false,
+            squareBrackets : true,
+            braces      : true,
+            maxDepth    : None,
+            dispatch    : Vec::new(),
+        }
+    }
+}
+
+impl Default for ReaderOptions {
+    fn default() -> Self { Self::new() }
+}
+
+#[derive(Clone)]
+pub struct PrintOptions {
+    // when false, strings that read back as symbols are written bare
+    pub quoteStrings    : bool,
+    // write symbols (keywords) that would not read back as such as |...| (:|...|), otherwise as they are
+    pub pipeSymbols     : bool,
+    // escape everything beyond ASCII in strings as \u{XXXX}, otherwise pass UTF-8 through
+    pub asciiOnly       : bool,
+    // spelling of booleans and characters
+    pub dialect         : Dialect,
+    // subtrees of at least this many nodes written more than once are
+    // printed as `(def $n ...)` then `(ref $n)`, see `ReaderOptions::expandRefs`
+    pub shareAbove      : Option<usize>,
+    // write the subtrees of `shareAbove` as `#n=...` then `#n#` instead
+    pub datumLabels     : bool,
+}
+
+impl PrintOptions {
+    pub fn new() -> Self {
+        Self {
+            quoteStrings    : true,
+            pipeSymbols     : true,
+            asciiOnly       : false,
+            dialect         : Dialect::Scheme,
+            shareAbove      : None,
+            datumLabels     : false,
+        }
+    }
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self { Self::new() }
+}
+
+// per parse state threaded through the reader
+struct Reader<'a> {
+    opts        : &'a ReaderOptions,
+    warnings    : &'a mut Vec<ParseError>,
+    depth       : usize,
+    maxDepth    : usize,
+    // every tokenizer decision when debugging with `fromSExpTraced`
+    trace       : Option<&'a mut ParseTrace>,
+    // `(def $n ...)` bindings seen so far
+    refs        : Vec<(String, Exp, usize)>,
+    // `#n=` labels seen so far with their node count, None while their datum is being read
+    labels      : Vec<(String, Option<(Exp, usize)>)>,
+    // nodes copied in by refs and labels so far
+    expanded    : usize,
+    // strings a `FormReader` already streamed to spill files, by the offset of the `""` left in their place
+    spilled     : &'a [(usize, std::sync::Arc<SpilledString>)],
+}
+
+impl<'a> Reader<'a> {
+    fn new(opts: &'a ReaderOptions, warnings: &'a mut Vec<ParseError>) -> Self {
+        Self { opts, warnings, depth: 0, maxDepth: 0, trace: None, refs: Vec::new(), labels: Vec::new(), expanded: 0, spilled: &[] }
+    }
+
+    // one level deeper at `offset`, an error past `ReaderOptions::maxDepth`
+    fn enter(&mut self, offset: usize) -> Result<(), ParseError> {
+        self.depth += 1;
+        self.maxDepth = usize::max(self.maxDepth, self.depth);
+        match self.opts.maxDepth {
+            Some(max) if self.depth > max => Err(ParseError { message: String::from("nesting too deep"), offset, kind: ErrorKind::Syntax }),
+            _ => Ok(())
+        }
+    }
+
+    fn record(&self, bytes: usize, res: &ParseResult<Exp>) {
+        if let Some(m) = &self.opts.metrics {
+            m.record(bytes, match res { PROk(_) => None, PRErr(err) => Some(err) }, self.maxDepth);
+        }
+    }
+}
+
 impl<T : core::cmp::PartialEq> PartialEq for ParseResult<T> {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
             (PROk(s), PROk(o)) => *s == *o,
-            (PRErr (ParseError{ message: msg1, offset: offset1 }), PRErr (ParseError{ message: msg2, offset: offset2 })) => *msg1 == *msg2 && *offset1 == *offset2,
+            (PRErr (ParseError{ message: msg1, offset: offset1, .. }), PRErr (ParseError{ message: msg2, offset: offset2, .. })) => *msg1 == *msg2 && *offset1 == *offset2,
             _ => false
         }
     }
@@ -49,10 +382,29 @@ pub enum Exp {
     Bool(bool),
     Char(char),
     Int(i64),
+    // above i64::MAX, see `Exp::uint`
+    UInt(u64),
     Float(f64),
+    // `n/d` in lowest terms, d > 1, see `Exp::ratio`
+    Ratio(i64, i64),
+    // out of the i64 and u64 ranges, see `Exp::bigInt`
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
     String(String),
     Symbol(String),
+    // `:name`, stored without the colon
+    Keyword(String),
     List(Vec<Exp>),
+    // `#(...)`, an array of data rather than a form
+    Vector(Vec<Exp>),
+    // `#u8(...)`
+    Bytes(Vec<u8>),
+    // `(car . cdr)`, see `Exp::cons`
+    Pair(std::boxed::Box<Exp>, std::boxed::Box<Exp>),
+    // {key value ...}, keys are unique and kept in reading order
+    Map(Vec<(Exp, Exp)>),
+    // a string decoded into a temporary file by `AtomOverflow::Spill`
+    Spilled(std::sync::Arc<SpilledString>),
 }
 
 impl PartialEq<Exp> for Exp {
@@ -61,16 +413,37 @@ impl PartialEq<Exp> for Exp {
             (Self::Bool(b0),            Self::Bool(bo))     => b0 == bo,
             (Self::Char(c0),            Self::Char(c1))     => c0 == c1,
             (Self::Int(i0),             Self::Int(i1))      => i0 == i1,
+            (Self::UInt(u0),            Self::UInt(u1))     => u0 == u1,
             (Self::Float(f0),           Self::Float(f1))    => f0 == f1,
+            (Self::Ratio(n0, d0),       Self::Ratio(n1, d1)) => n0 == n1 && d0 == d1,
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(b0),          Self::BigInt(b1))   => b0 == b1,
             (Self::String(s0),          Self::String(s1))   => s0 == s1,
             (Self::Symbol(s0),          Self::Symbol(s1))   => s0 == s1,
+            (Self::Keyword(k0),         Self::Keyword(k1))  => k0 == k1,
+            (Self::Pair(a0, d0),        Self::Pair(a1, d1)) => a0 == a1 && d0 == d1,
+            (Self::Vector(v0),          Self::Vector(v1))   => v0.asArray() == v1.asArray(),
+            (Self::Bytes(b0),           Self::Bytes(b1))    => b0.asArray() == b1.asArray(),
+            (Self::Spilled(s0),         Self::Spilled(s1))  => s0.path() == s1.path() || (s0.len() == s1.len() && Self::spilledBytes(s0) == Self::spilledBytes(s1)),
             (Self::List(s), Self::List(o)) => {
                 if s.len() != o.len() { return false }
+                // atoms and sublist lengths first, so shallow differences are found before any deep walk
                 for i in 0..s.len() {
-                    if !Self::eq(&s[i], &o[i]) { return false }
+                    let same = match (&s[i], &o[i]) {
+                        (Self::List(a), Self::List(b)) => a.len() == b.len(),
+                        (a, b) => Self::eq(a, b),
+                    };
+                    if !same { return false }
+                }
+                for i in 0..s.len() {
+                    if let (Self::List(_), Self::List(_)) = (&s[i], &o[i]) {
+                        if !Self::eq(&s[i], &o[i]) { return false }
+                    }
                 }
                 true
             },
+            // the order of the entries does not matter
+            (Self::Map(s), Self::Map(o)) => s.len() == o.len() && s.asArray().iter().all(|(k, v)| Self::mapGet(o.asArray(), k) == Some(v)),
             _ => false
         }
     }
@@ -122,69 +495,248 @@ impl Exp {
 
     fn isSeparator(c: u8) -> bool {
         match c as char {
-            '(' | ')' | '{' | '}' | ',' | '\'' | '"' => true,
+            '(' | ')' | '[' | ']' | '{' | '}' | ',' | '"' | ';' => true,
             x if Self::isWS(x as u8) => true,
             _ => false
         }
     }
 
     pub fn parseNumber(src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let opts = ReaderOptions::new();
+        let mut warnings = Vec::new();
+        Self::readNumber(&mut Reader::new(&opts, &mut warnings), src, offset)
+    }
+
+    fn isIntLiteral(s: &[u8]) -> bool {
+        let digits = match s.first() {
+            Some(b'+') | Some(b'-') => &s[1..],
+            _ => s
+        };
+        !digits.is_empty() && digits.iter().all(|c| Self::isDigit(*c))
+    }
+
+    // `#x1F`, `#o-17`, `-0b1010`: where the digits start, the radix and the sign
+    fn radixPrefix(src: &[u8], offset: usize) -> Option<(usize, u32, bool)> {
+        let radix = |c: Option<&u8>| match c {
+            Some(b'x') | Some(b'X') => Some(16),
+            Some(b'o') | Some(b'O') => Some(8),
+            Some(b'b') | Some(b'B') => Some(2),
+            _ => None
+        };
+        let sign = |o: usize| match src.get(o) { Some(b'+') | Some(b'-') => 1, _ => 0 };
+        let (digits, r, negative) = match src.get(offset) {
+            Some(b'#') => {
+                let r = radix(src.get(offset + 1))?;
+                (offset + 2 + sign(offset + 2), r, src.get(offset + 2) == Some(&b'-'))
+            },
+            _ => {
+                let s = sign(offset);
+                if src.get(offset + s) != Some(&b'0') { return None }
+                (offset + s + 2, radix(src.get(offset + s + 1))?, s == 1 && src[offset] == b'-')
+            }
+        };
+        match src.get(digits) {
+            Some(c) if (*c as char).is_digit(r) => Some((digits, r, negative)),
+            // `0x_FF`, read as a number to report the separator
+            Some(b'_') if src.get(digits + 1).is_some_and(|c| (*c as char).is_digit(r)) => Some((digits, r, negative)),
+            _ => None
+        }
+    }
+
+    // a `_` between two digits of `radix`: `1_000`, `0xFF_FF`
+    fn isDigitSeparator(src: &[u8], offset: usize, radix: u32) -> bool {
+        let digit = |c: Option<&u8>| c.is_some_and(|c| (*c as char).is_digit(radix));
+        src.get(offset) == Some(&b'_') && offset > 0 && digit(src.get(offset - 1)) && digit(src.get(offset + 1))
+    }
+
+    fn readRadix(rd: &mut Reader, src: &[u8], offset: &mut usize, digits: usize, radix: u32, negative: bool) -> ParseResult<Exp> {
+        let start = *offset;
+        *offset = digits;
+        let mut i : Option<i64> = Some(0);
+        let mut f = 0.0f64;
+        // the digits without separators
+        let mut ds = Vec::new();
+        loop {
+            match Self::peek(src, *offset) {
+                Some(c) if Self::isSeparator(c) => break,
+                None => break,
+                Some(b'_') if *offset > digits && Self::isDigitSeparator(src, *offset, radix) => *offset += 1,
+                Some(b'_') => return PRErr (ParseError { message: String::from("misplaced digit separator (number)"), offset: *offset, kind: ErrorKind::Number }),
+                Some(c) => match (c as char).to_digit(radix) {
+                    Some(d) => {
+                        i = i.and_then(|i| i.checked_mul(radix as i64))
+                             .and_then(|i| if negative { i.checked_sub(d as i64) } else { i.checked_add(d as i64) });
+                        f = f * radix as f64 + d as f64;
+                        ds.pushBack(c);
+                        *offset += 1;
+                    },
+                    None => return PRErr (ParseError { message: String::from("invalid digit (radix)"), offset: *offset, kind: ErrorKind::Number })
+                }
+            }
+        }
+
+        match (i, if negative { None } else { Self::parseU64(ds.asArray(), radix) }) {
+            (Some(i), _) => PROk(Exp::Int(i)),
+            (None, Some(u)) => PROk(Exp::uint(u)),
+            (None, None) => match rd.opts.intOverflow {
+                IntOverflow::Error => PRErr (ParseError { message: String::from("integer overflow"), offset: start, kind: ErrorKind::Number }),
+                IntOverflow::Float => {
+                    rd.warnings.pushBack(ParseError { message: String::from("integer overflow, read as float"), offset: start, kind: ErrorKind::Number });
+                    PROk(Exp::Float(if negative { -f } else { f }))
+                },
+                #[cfg(feature = "bigint")]
+                IntOverflow::BigInt => match BigInt::fromDigits(negative, ds.asArray(), radix) {
+                    Some(b) => PROk(Exp::bigInt(b)),
+                    None => PRErr (ParseError { message: String::from("invalid digit (radix)"), offset: start, kind: ErrorKind::Number }),
+                },
+            }
+        }
+    }
+
+    fn readNumber(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let start = *offset;
+        if let Some((digits, radix, negative)) = Self::radixPrefix(src, start) {
+            return Self::readRadix(rd, src, offset, digits, radix, negative)
+        }
         let mut s = String::new();
         loop {
             match Self::peek(src, *offset) {
-                Some(c) if c == b'+' || c == b'-' || c == b'.' || c == b'e' || c == b'E' || Self::isDigit(c) => {
+                Some(c) if c == b'+' || c == b'-' || c == b'.' || c == b'e' || c == b'E' || c == b'/' || Self::isDigit(c) => {
                     s.add(c);
                     Self::getchar(src, offset);
                 },
+                Some(b'_') if Self::isDigitSeparator(src, *offset, 10) => { Self::getchar(src, offset); },
+                Some(b'_') => return PRErr (ParseError { message: String::from("misplaced digit separator (number)"), offset: *offset, kind: ErrorKind::Number }),
                 Some(c) if Self::isSeparator(c) => break,
                 None => break,
-                _ => return PRErr (ParseError { message: String::from("Unexpected end of stream (sign)"), offset: *offset })
+                _ => return PRErr (ParseError { message: String::from("Unexpected end of stream (sign)"), offset: *offset, kind: ErrorKind::Number })
             }
         }
 
+        if s.asArray().contains(&b'/') {
+            return Self::readRatio(rd, s.toStr(), start)
+        }
+
         match str::parse::<i64>(s.toStr()) {
             Ok(i) => return ParseResult::PROk(Exp::Int(i)),
             _ => ()
         }
 
+        if let Ok(u) = str::parse::<u64>(s.toStr()) {
+            return PROk(Exp::uint(u))
+        }
+
+        if Self::isIntLiteral(s.asArray()) {
+            match rd.opts.intOverflow {
+                IntOverflow::Error => return PRErr (ParseError { message: String::from("integer overflow"), offset: start, kind: ErrorKind::Number }),
+                IntOverflow::Float => rd.warnings.pushBack(ParseError { message: String::from("integer overflow, read as float"), offset: start, kind: ErrorKind::Number }),
+                #[cfg(feature = "bigint")]
+                IntOverflow::BigInt => if let Some(b) = BigInt::parse(s.toStr()) { return PROk(Exp::bigInt(b)) },
+            }
+        }
+
         match str::parse::<f64>(s.toStr()) {
             Ok(f) => return ParseResult::PROk(Exp::Float(f)),
             _ => ()
         }
 
-        PRErr (ParseError { message: String::from("invalid number format"), offset: *offset })
+        PRErr (ParseError { message: String::from("invalid number format"), offset: *offset, kind: ErrorKind::Number })
     }
 
     fn parseString(src: &[u8], offset: &mut usize) -> ParseResult<String> {
         let mut s = String::new();
+        let start = *offset;
+        match Self::decodeString(src, offset, &mut |c| s.add(c)) {
+            PROk(()) => Self::utf8Checked(s, start, "string"),
+            PRErr(err) => PRErr(err)
+        }
+    }
+
+    // decoded text has to be UTF-8, alt_std strings assume it
+    pub(crate) fn utf8Checked(s: String, start: usize, what: &str) -> ParseResult<String> {
+        match core::str::from_utf8(s.asArray()) {
+            Ok(_) => PROk(s),
+            Err(_) => PRErr(ParseError { message: alt_std::format!("invalid UTF-8 ({})", what), offset: start, kind: ErrorKind::Syntax }),
+        }
+    }
+
+    // the byte written as `\c` in a string
+    fn unescape(c: u8) -> Option<u8> {
+        match c {
+            b'"' => Some(b'"'),
+            b'\\' => Some(b'\\'),
+            b'n' => Some(b'\n'),
+            b't' => Some(b'\t'),
+            b'r' => Some(b'\r'),
+            _ => None
+        }
+    }
+
+    // `u{1F600}` after a backslash at `offset`, the code point and the offset past `}`
+    fn unicodeEscape(src: &[u8], offset: usize) -> Option<(char, usize)> {
+        let digits = src.get(offset..)?.strip_prefix(b"u{")?;
+        let len = digits.iter().position(|c| *c == b'}')?;
+        if len == 0 || len > 6 { return None }
+        let code = u32::from_str_radix(core::str::from_utf8(&digits[..len]).ok()?, 16).ok()?;
+        char::from_u32(code).map(|c| (c, offset + 3 + len))
+    }
+
+    // feeds the UTF-8 bytes of `c` to `out`
+    fn encodeUtf8(c: char, out: &mut dyn FnMut(u8)) {
+        let mut buf = [0u8; 4];
+        for b in c.encode_utf8(&mut buf).as_bytes().iter() { out(*b) }
+    }
+
+    // reads a string literal, feeding the decoded bytes to `out`
+    fn decodeString(src: &[u8], offset: &mut usize, out: &mut dyn FnMut(u8)) -> ParseResult<()> {
         match Self::peek(src, *offset) {
             Some(c) if c as char == '"' => (),
-            _ => return PRErr (ParseError{ message: String::from("Expected \""), offset: *offset })
+            _ => return PRErr (ParseError{ message: String::from("Expected \""), offset: *offset, kind: ErrorKind::Syntax })
         }
 
         Self::getchar(src, offset);
-        // TODO: handle '\' case
         loop {
             match Self::getchar(src, offset) {
-                None => return PRErr (ParseError{ message: String::from("Unexpected end of stream (string)"), offset: *offset }),
+                None => return PRErr (ParseError{ message: String::from("Unexpected end of stream (string)"), offset: *offset, kind: ErrorKind::Eof }),
                 Some(c) if c as char == '"' => break,
-                Some(c) => s.add(c),
+                Some(c) if c as char == '\\' => {
+                    match Self::getchar(src, offset) {
+                        None => return PRErr (ParseError{ message: String::from("Unexpected end of stream (string)"), offset: *offset, kind: ErrorKind::Eof }),
+                        Some(b'u') => match Self::unicodeEscape(src, *offset - 1) {
+                            Some((c, end)) => { Self::encodeUtf8(c, out); *offset = end },
+                            None => return PRErr (ParseError{ message: String::from("invalid unicode escape (string)"), offset: *offset - 2, kind: ErrorKind::Escape }),
+                        },
+                        Some(c) => match Self::unescape(c) {
+                            Some(c) => out(c),
+                            None => return PRErr (ParseError{ message: String::from("invalid escape (string)"), offset: *offset - 2, kind: ErrorKind::Escape }),
+                        }
+                    }
+                },
+                Some(c) => out(c),
             }
         }
 
-        return PROk(s)
+        return PROk(())
     }
 
     fn parseSymbol(src: &[u8], offset: &mut usize) -> ParseResult<String> {
         let mut s = String::new();
         match Self::peek(src, *offset) {
-            Some(c) if Self::isAlpha(c) || Self::isOp(c) => (),
-            _ => return PRErr (ParseError{ message: String::from("Expected alpha/operator"), offset: *offset })
+            Some(c) if Self::isAlpha(c) || Self::isOp(c) || Self::symbolCharLen(src, *offset) > 0 => (),
+            _ => return PRErr (ParseError{ message: String::from("Expected alpha/operator"), offset: *offset, kind: ErrorKind::Syntax })
         }
 
         loop {
             match Self::peek(src, *offset) {
                 Some(c) if Self::isAlpha(c) || Self::isOp(c) || Self::isDigit(c) => s.add(c),
+                Some(c) if c >= 0x80 => match Self::symbolCharLen(src, *offset) {
+                    0 => break,
+                    n => {
+                        for b in src[*offset..*offset + n].iter() { s.add(*b) }
+                        *offset += n;
+                        continue
+                    }
+                },
                 _ => break,
             }
             Self::getchar(src, offset);
@@ -193,104 +745,656 @@ impl Exp {
         return PROk(s)
     }
 
-    fn skipWS(src: &[u8], offset: &mut usize) {
+    // whether the `|` at `offset` opens a |...| symbol
+    pub(crate) fn isPipeSymbolStart(src: &[u8], offset: usize) -> bool {
+        let bars = src[offset..].iter().take_while(|c| **c == b'|').count();
+        bars == 2 || src.get(offset + bars).is_some_and(|c| !Self::isWS(*c) && !b")]}\r".contains(c))
+    }
+
+    // |...|, the bars are not part of the symbol
+    fn parsePipeSymbol(src: &[u8], offset: &mut usize) -> ParseResult<String> {
+        let mut s = String::new();
+        let start = *offset;
+        *offset += 1;
+        loop {
+            match Self::getchar(src, offset) {
+                None => return PRErr(ParseError { message: String::from("unexpected end of stream (symbol)"), offset: start, kind: ErrorKind::Eof }),
+                Some(b'|') => return Self::utf8Checked(s, start, "symbol"),
+                Some(b'\\') => match Self::getchar(src, offset) {
+                    Some(b'|') => s.add(b'|'),
+                    Some(b'u') => match Self::unicodeEscape(src, *offset - 1) {
+                        Some((c, end)) => { Self::encodeUtf8(c, &mut |b| s.add(b)); *offset = end },
+                        None => return PRErr(ParseError { message: String::from("invalid unicode escape (symbol)"), offset: *offset - 2, kind: ErrorKind::Escape }),
+                    },
+                    Some(c) => match Self::unescape(c) {
+                        Some(c) => s.add(c),
+                        None => return PRErr(ParseError { message: String::from("invalid escape (symbol)"), offset: *offset - 2, kind: ErrorKind::Escape }),
+                    },
+                    None => return PRErr(ParseError { message: String::from("unexpected end of stream (symbol)"), offset: start, kind: ErrorKind::Eof }),
+                },
+                Some(c) => s.add(c),
+            }
+        }
+    }
+
+    fn utf8Len(c: u8) -> usize {
+        match c {
+            c if c < 0x80 => 1,
+            c if c & 0xE0 == 0xC0 => 2,
+            c if c & 0xF0 == 0xE0 => 3,
+            _ => 4,
+        }
+    }
+
+    fn readUtf8(src: &[u8], offset: &mut usize) -> Option<char> {
+        let c = Self::peek(src, *offset)?;
+        let end = *offset + Self::utf8Len(c);
+        if end > src.len() { return None }
+        match core::str::from_utf8(&src[*offset..end]) {
+            Ok(s) => {
+                *offset = end;
+                s.chars().next()
+            },
+            Err(_) => None
+        }
+    }
+
+    // bytes of the non ASCII character at `offset` when it can be part of a symbol
+    // (anything but spaces and controls), 0 otherwise or when it is not valid UTF-8
+    fn symbolCharLen(src: &[u8], offset: usize) -> usize {
+        let mut end = offset;
+        match Self::readUtf8(src, &mut end) {
+            Some(c) if !c.is_ascii() && !c.is_whitespace() && !c.is_control() => end - offset,
+            _ => 0
+        }
+    }
+
+    const CHAR_NAMES : [(&'static str, char); 9] = [("space", ' '), ("newline", '\n'), ("tab", '\t'), ("return", '\r'), ("null", '\0'),
+                                                     ("alarm", '\x07'), ("backspace", '\x08'), ("escape", '\x1B'), ("delete", '\x7F')];
+
+    // unnamed control characters are written as #\xNN
+    fn isHexChar(c: char) -> bool {
+        c.is_control() && Self::CHAR_NAMES.iter().all(|(_, n)| *n != c)
+    }
+
+    // #\a, #\space, ...
+    // `prefix` is the length of the introducer, #\ or \ (Clojure)
+    fn parseChar(rd: &Reader, src: &[u8], offset: &mut usize, prefix: usize) -> ParseResult<Exp> {
+        let start = *offset;
+        *offset += prefix;
+        let c = match Self::readUtf8(src, offset) {
+            Some(c) => c,
+            None => return PRErr(ParseError { message: String::from("invalid character literal"), offset: start, kind: ErrorKind::Char })
+        };
+
+        if rd.opts.graphemeChars {
+            let end = Self::graphemeEnd(src, c, *offset);
+            if end != *offset {
+                let mut s = String::new();
+                for b in src[start + prefix..end].iter() { s.add(*b) }
+                *offset = end;
+                return PROk(Exp::String(s))
+            }
+        }
+
+        if !Self::isAlpha(c as u8) || !c.is_ascii() {
+            return PROk(Exp::Char(c))
+        }
+
+        // #\x41, a lone #\x is the letter
+        if c == 'x' && Self::peek(src, *offset).is_some_and(|d| d.is_ascii_hexdigit()) {
+            let digits = *offset;
+            while Self::peek(src, *offset).is_some_and(|d| d.is_ascii_hexdigit()) {
+                *offset += 1;
+            }
+            let code = core::str::from_utf8(&src[digits..*offset]).ok().and_then(|h| u32::from_str_radix(h, 16).ok());
+            return match code.and_then(char::from_u32) {
+                Some(c) => PROk(Exp::Char(c)),
+                None => PRErr(ParseError { message: String::from("invalid character literal"), offset: start, kind: ErrorKind::Char })
+            }
+        }
+
+        let nameStart = *offset - 1;
         loop {
             match Self::peek(src, *offset) {
-                Some(c) if Self::isWS(c) => { Self::getchar(src, offset); },
+                Some(c) if Self::isAlpha(c) => { Self::getchar(src, offset); },
                 _ => break
             }
         }
+
+        if *offset - nameStart == 1 {
+            return PROk(Exp::Char(c))
+        }
+
+        for (name, c) in Self::CHAR_NAMES.iter() {
+            if name.as_bytes() == &src[nameStart..*offset] {
+                return PROk(Exp::Char(*c))
+            }
+        }
+        PRErr(ParseError { message: String::from("unknown character name"), offset: start, kind: ErrorKind::Char })
     }
 
-    fn parseToken(src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+    fn skipWS(src: &[u8], offset: &mut usize) {
+        *offset = Self::wsEnd(src, *offset, true).unwrap_or(src.len());
+    }
+
+    // end of the whitespace and comments at `offset`, None when `src` may stop
+    // in the middle of a comment (`eof` false)
+    pub(crate) fn wsEnd(src: &[u8], offset: usize, eof: bool) -> Option<usize> {
+        let mut i = offset;
+        // #; datum comments waiting for their datum, and where the first one is
+        let mut pending = 0;
+        let mut opener = offset;
+        loop {
+            match Self::peek(src, i) {
+                Some(c) if Self::isWS(c) => i += 1,
+                Some(b';') => match src[i..].iter().position(|c| *c == b'\n') {
+                    Some(n) => i += n + 1,
+                    None if eof => return Some(src.len()),
+                    None => return None,
+                },
+                // an unterminated block comment is left to parseToken to report
+                Some(b'#') if Self::peek(src, i + 1) == Some(b'|') => match Self::blockCommentEnd(src, i) {
+                    Some(end) => i = end,
+                    None if eof => return Some(if pending > 0 { opener } else { i }),
+                    None => return None,
+                },
+                Some(b'#') if Self::peek(src, i + 1) == Some(b';') => {
+                    if pending == 0 { opener = i }
+                    pending += 1;
+                    i += 2;
+                },
+                // the datum of a datum comment, a missing or broken one is left to parseToken
+                Some(c) if pending > 0 && c != b')' && c != b']' && c != b'}' => match Self::formEnd(src, i, eof) {
+                    Ok(Some(end)) => { pending -= 1; i = end },
+                    Ok(None) => return None,
+                    Err(_) => return Some(opener),
+                },
+                _ if pending > 0 => return if eof || i < src.len() { Some(opener) } else { None },
+                _ => return Some(i)
+            }
+        }
+    }
+
+    // end of the (nested) #| ... |# comment at `offset`
+    fn blockCommentEnd(src: &[u8], offset: usize) -> Option<usize> {
+        let mut depth = 0;
+        let mut i = offset;
+        while i + 1 < src.len() {
+            match (src[i], src[i + 1]) {
+                (b'#', b'|') => { depth += 1; i += 2 },
+                (b'|', b'#') => {
+                    depth -= 1;
+                    i += 2;
+                    if depth == 0 { return Some(i) }
+                },
+                _ => i += 1,
+            }
+        }
+        None
+    }
+
+    fn parseToken(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let start = *offset;
+        let (rule, res) = Self::parseTokenRule(rd, src, offset);
+        if let Some(t) = &mut rd.trace {
+            // lists trace their own steps, nested errors are already in
+            match (&res, rule) {
+                (_, Rule::ListOpen) => (),
+                (PRErr(err), _) => t.push(Rule::Error, err.offset, 0),
+                (PROk(_), rule) => t.push(rule, start, *offset - start),
+            }
+        }
+        res
+    }
+
+    fn parseTokenRule(rd: &mut Reader, src: &[u8], offset: &mut usize) -> (Rule, ParseResult<Exp>) {
+        let start = *offset;
+        let (rule, res) = Self::parseAtomRule(rd, src, offset);
+        match (rule, rd.opts.maxAtomLen) {
+            (Rule::Number, Some(max)) | (Rule::Symbol, Some(max)) if *offset - start > max =>
+                (rule, PRErr(ParseError { message: String::from("atom too long"), offset: start, kind: ErrorKind::Syntax })),
+            _ => (rule, res)
+        }
+    }
+
+    fn parseAtomRule(rd: &mut Reader, src: &[u8], offset: &mut usize) -> (Rule, ParseResult<Exp>) {
         match Self::peek(src, *offset) {
+            Some(b'"') if Self::isTextBlockStart(src, *offset) => {
+                let start = *offset;
+                (Rule::String, match Self::parseTextBlock(src, offset) {
+                    PROk(s) if rd.opts.maxAtomLen.is_some_and(|max| s.asArray().len() > max) =>
+                        PRErr(ParseError { message: String::from("atom too long"), offset: start, kind: ErrorKind::Syntax }),
+                    PROk(s) => PROk(Exp::String(s)),
+                    PRErr(err) => PRErr(err)
+                })
+            },
             Some(c) if c as char == '"' => {
+                if let Some((_, s)) = rd.spilled.iter().find(|(at, _)| *at == *offset) {
+                    *offset += 2;
+                    return (Rule::String, PROk(Exp::Spilled(s.clone())))
+                }
+                if let Some(max) = rd.opts.maxAtomLen {
+                    if Self::stringLen(src, *offset, max) > max {
+                        return (Rule::String, Self::overlongString(rd, src, offset))
+                    }
+                }
                 let stringRes = Self::parseString(src, offset);
-                match stringRes {
+                (Rule::String, match stringRes {
                     PROk(r) => PROk(Exp::String(r)),
                     PRErr(err) => PRErr(err)
-                }
+                })
             },
             Some(c) if Self::isDigit(c) || ((c as char == '+' || c as char == '-') && match Self::peek(src, *offset + 1) { Some(c) if Self::isDigit(c) => true, _ => false })  => {
-                let numRes = Self::parseNumber(src, offset);
-                match numRes {
+                let numRes = Self::readNumber(rd, src, offset);
+                (Rule::Number, match numRes {
                     PROk(r) => PROk(r),
                     PRErr(err) => PRErr(err)
-                }
+                })
+            },
+            Some(b'#') if Self::radixPrefix(src, *offset).is_some() => (Rule::Number, Self::readNumber(rd, src, offset)),
+            Some(c) if c as char == '#' && match Self::peek(src, *offset + 1) { Some(c) if c as char == '\\' => true, _ => false } => (Rule::Char, Self::parseChar(rd, src, offset, 2)),
+            Some(b'#') if rd.opts.datumLabels && Self::datumLabelEnd(src, *offset).is_some() => (Rule::Dispatch, Self::parseDatumLabel(rd, src, offset)),
+            Some(b'#') if Self::peek(src, *offset + 1) == Some(b'|') => (Rule::Error, PRErr(ParseError { message: String::from("unterminated block comment"), offset: *offset, kind: ErrorKind::Eof })),
+            Some(b'#') if Self::peek(src, *offset + 1) == Some(b';') => (Rule::Error, PRErr(ParseError { message: String::from("missing datum after #;"), offset: *offset, kind: ErrorKind::Syntax })),
+            Some(b'#') if Self::peek(src, *offset + 1).and_then(|c| rd.opts.dispatchFor(c)).is_some() => (Rule::Dispatch, Self::parseDispatch(rd, src, offset)),
+            Some(b'#') if Self::peek(src, *offset + 1) == Some(b'(') => (Rule::ListOpen, Self::parseList(rd, src, offset)),
+            Some(b'#') if Self::isBytesStart(src, *offset) => (Rule::Dispatch, Self::parseBytes(rd, src, offset)),
+            Some(b'\'') if rd.opts.apostrophe == Apostrophe::Quote => (Rule::Dispatch, Self::parseQuoted(rd, src, offset, 1, "quote")),
+            Some(b'`') if rd.opts.quasiquoteSugar => (Rule::Dispatch, Self::parseQuoted(rd, src, offset, 1, "quasiquote")),
+            Some(b',') if rd.opts.quasiquoteSugar && Self::peek(src, *offset + 1) == Some(b'@') => (Rule::Dispatch, Self::parseQuoted(rd, src, offset, 2, "unquote-splicing")),
+            Some(b',') if rd.opts.quasiquoteSugar => (Rule::Dispatch, Self::parseQuoted(rd, src, offset, 1, "unquote")),
+            Some(b'\\') if rd.opts.dialect == Dialect::Clojure && *offset + 1 < src.len() => (Rule::Char, Self::parseChar(rd, src, offset, 1)),
+            Some(b'|') if rd.opts.pipeSymbols && Self::isPipeSymbolStart(src, *offset) => (Rule::Symbol, match Self::parsePipeSymbol(src, offset) {
+                PROk(s) => PROk(Exp::Symbol(s)),
+                PRErr(err) => PRErr(err)
+            }),
+            Some(b':') if rd.opts.keywords && rd.opts.pipeSymbols && Self::peek(src, *offset + 1) == Some(b'|') && Self::isPipeSymbolStart(src, *offset + 1) => {
+                let start = *offset;
+                *offset += 1;
+                (Rule::Symbol, match Self::parsePipeSymbol(src, offset) {
+                    PROk(k) if k.asArray().is_empty() => PRErr(ParseError { message: String::from("empty keyword"), offset: start, kind: ErrorKind::Syntax }),
+                    PROk(k) => PROk(Exp::Keyword(k)),
+                    PRErr(err) => PRErr(err)
+                })
             },
-            Some(c) if Self::isAlpha(c) || Self::isOp(c) => {
+            Some(c) if Self::isAlpha(c) || Self::isOp(c) || Self::symbolCharLen(src, *offset) > 0 => {
+                let start = *offset;
                 let symbolRes = Self::parseSymbol(src, offset);
-                match symbolRes {
-                    PROk(r) => PROk(Exp::Symbol(r)),
+                (Rule::Symbol, match symbolRes {
+                    PROk(r) if rd.opts.apostrophe == Apostrophe::Error && r.asArray().contains(&b'\'') => {
+                        let at = r.asArray().iter().position(|c| *c == b'\'').unwrap_or(0);
+                        PRErr(ParseError { message: String::from("unexpected ' (symbol)"), offset: start + at, kind: ErrorKind::Syntax })
+                    },
+                    PROk(r) if rd.opts.foldCase =>
+                        Self::symbolAtom(rd, String::from(r.toStr().to_lowercase().as_str())),
+                    PROk(r) => Self::symbolAtom(rd, r),
                     PRErr(err) => PRErr(err)
-                }
+                })
             },
-            Some(c) if c as char == '(' => Self::parseList(src, offset),
-            Some(_) => PRErr(ParseError { message: String::from("unexpected char (token)"), offset: *offset}),
-            None => PRErr(ParseError { message: String::from("unexpected end of stream (token)"), offset: *offset}),
+            Some(b'[') if !rd.opts.squareBrackets => (Rule::Error, PRErr(ParseError { message: String::from("unexpected character (list)"), offset: *offset, kind: ErrorKind::Syntax })),
+            Some(b'{') if !rd.opts.braces => (Rule::Error, PRErr(ParseError { message: String::from("unexpected character (map)"), offset: *offset, kind: ErrorKind::Syntax })),
+
+            Some(b'(') | Some(b'[') => (Rule::ListOpen, Self::parseList(rd, src, offset)),
+            Some(b'{') => (Rule::ListOpen, Self::parseMap(rd, src, offset)),
+            Some(_) => (Rule::Error, PRErr(ParseError { message: String::from("unexpected char (token)"), offset: *offset, kind: ErrorKind::Syntax })),
+            None => (Rule::Error, PRErr(ParseError { message: String::from("unexpected end of stream (token)"), offset: *offset, kind: ErrorKind::Eof })),
         }
     }
 
-    fn parseList(src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
-        match Self::getchar(src, offset) {
-            Some(c) if c as char == '(' => (),
-            Some(_) => return PRErr(ParseError { message: String::from("unexpected character (list)"), offset: *offset}),
-            None => return PRErr(ParseError { message: String::from("unexpected end of stream (list)"), offset: *offset}),
+    // `prefix` then a datum, read as `(name datum)`
+    fn parseQuoted(rd: &mut Reader, src: &[u8], offset: &mut usize, prefix: usize, name: &str) -> ParseResult<Exp> {
+        let start = *offset;
+        *offset += prefix;
+        Self::skipWS(src, offset);
+        match Self::peek(src, *offset) {
+            None | Some(b')') | Some(b']') | Some(b'}') =>
+                return PRErr(ParseError { message: alt_std::format!("missing datum after {}", core::str::from_utf8(&src[start..start + prefix]).unwrap_or("quote")), offset: start, kind: ErrorKind::Syntax }),
+            _ => ()
+        }
+        match Self::parseToken(rd, src, offset) {
+            PROk(e) => {
+                let mut l = Vec::new();
+                l.pushBack(Exp::Symbol(String::from(name)));
+                l.pushBack(e);
+                PROk(Exp::List(l))
+            },
+            PRErr(err) => PRErr(err)
         }
+    }
+
+    /// the keyword `:name`, None when `name` is empty
+    pub fn keyword(name: &str) -> Option<Exp> {
+        if name.is_empty() { None } else { Some(Exp::Keyword(String::from(name))) }
+    }
 
+    // the atom a symbol token reads as
+    fn symbolAtom(rd: &Reader, r: String) -> ParseResult<Exp> {
+        match r.toStr() {
+            "#t" | "#true" => PROk(Exp::Bool(true)),
+            "#f" | "#false" => PROk(Exp::Bool(false)),
+            "true" if rd.opts.wordBooleans => PROk(Exp::Bool(true)),
+            "false" if rd.opts.wordBooleans => PROk(Exp::Bool(false)),
+            "+inf.0" => PROk(Exp::Float(f64::INFINITY)),
+            "-inf.0" => PROk(Exp::Float(f64::NEG_INFINITY)),
+            "+nan.0" => PROk(Exp::Float(f64::NAN)),
+            k if rd.opts.keywords && k.len() > 1 && k.starts_with(':') => PROk(Exp::Keyword(String::from(&k[1..]))),
+            b => match rd.opts.dialect.boolean(b) {
+                Some(b) => PROk(Exp::Bool(b)),
+                None => PROk(Exp::Symbol(r)),
+            },
+        }
+    }
+
+    // `(...)`, `[...]` or the vector `#(...)`
+    fn parseList(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let start = *offset;
+        let vector = Self::peek(src, *offset) == Some(b'#');
+        if vector { *offset += 1 }
+        let close = match Self::getchar(src, offset) {
+            Some(b'(') => b')',
+            Some(b'[') => b']',
+            Some(_) => return PRErr(ParseError { message: String::from("unexpected character (list)"), offset: *offset, kind: ErrorKind::Syntax }),
+            None => return PRErr(ParseError { message: String::from("unexpected end of stream (list)"), offset: *offset, kind: ErrorKind::Eof }),
+        };
+
+        if let Err(err) = rd.enter(start) { return PRErr(err) }
+        if let Some(t) = &mut rd.trace { t.push(Rule::ListOpen, start, *offset - start) }
         let mut cells = Vec::new();
         loop {
             Self::skipWS(src, offset);
             match Self::peek(src, *offset) {
-                Some(c) if c as char == ')' => {
+                Some(c) if c == b')' || c == b']' => {
+                    if c != close && rd.opts.strictBrackets {
+                        if let Some(t) = &mut rd.trace { t.push(Rule::Error, *offset, 0) }
+                        return PRErr(ParseError { message: String::from("mismatched bracket (list)"), offset: *offset, kind: ErrorKind::Syntax })
+                    }
                     Self::getchar(src, offset);
-                    return PROk(Exp::List(cells))
+                    rd.depth -= 1;
+                    if let Some(t) = &mut rd.trace { t.push(Rule::ListClose, *offset - 1, 1) }
+                    return if vector {
+                        PROk(Exp::Vector(cells))
+                    } else if rd.opts.expandRefs {
+                        Self::expandRef(rd, cells, start)
+                    } else {
+                        PROk(Exp::List(cells))
+                    }
+                },
+                Some(b'.') if vector && Self::isDot(src, *offset) => {
+                    if let Some(t) = &mut rd.trace { t.push(Rule::Error, *offset, 0) }
+                    return PRErr(ParseError { message: String::from("unexpected . (vector)"), offset: *offset, kind: ErrorKind::Syntax })
                 },
+                Some(b'.') if Self::isDot(src, *offset) => return Self::parseDotted(rd, src, offset, cells, close),
                 Some(_) => {
-                    match Self::parseToken(src, offset) {
+                    match Self::parseToken(rd, src, offset) {
                         PROk(c) => cells.pushBack(c),
                         PRErr(err) => return PRErr(err),
                     }
                 },
-                None => return PRErr(ParseError { message: String::from("unexpected end of stream (list)"), offset: *offset})
+                None => {
+                    if let Some(t) = &mut rd.trace { t.push(Rule::Error, *offset, 0) }
+                    return PRErr(ParseError { message: String::from("unexpected end of stream (list)"), offset: *offset, kind: ErrorKind::Eof })
+                }
             }
         }
     }
 
+    // where the document starts: past a UTF-8 byte order mark then, when `shebang`,
+    // a `#!` first line and its newline. None when `src` is too short to tell and
+    // more input may follow
+    pub(crate) fn preambleEnd(src: &[u8], eof: bool, shebang: bool) -> Option<usize> {
+        let bom = match src {
+            [0xEF, 0xBB, 0xBF, ..] => 3,
+            [] | [0xEF] | [0xEF, 0xBB] if !eof => return None,
+            _ => 0
+        };
+        if !shebang { return Some(bom) }
+        match &src[bom..] {
+            [b'#', b'!', line @ ..] => match line.iter().position(|c| *c == b'\n') {
+                Some(n) => Some(bom + n + 3),
+                None if eof => Some(src.len()),
+                None => None,
+            },
+            [] | [b'#'] if !eof => None,
+            _ => Some(bom),
+        }
+    }
+
+    pub(crate) fn documentStart(src: &[u8], opts: &ReaderOptions) -> usize {
+        Self::preambleEnd(src, true, opts.skipShebang).unwrap_or(0)
+    }
+
     pub fn fromSExp(src: &[u8]) -> ParseResult<Exp> {
-        let mut offset : usize = 0;
+        Self::fromSExpWithOptions(src, &ReaderOptions::new())
+    }
+
+    pub fn fromSExpWithOptions(src: &[u8], opts: &ReaderOptions) -> ParseResult<Exp> {
+        let mut warnings = Vec::new();
+        Self::fromSExpWithWarnings(src, opts, &mut warnings)
+    }
+
+    /// like `fromSExpWithOptions`, non fatal issues are appended to `warnings`
+    pub fn fromSExpWithWarnings(src: &[u8], opts: &ReaderOptions, warnings: &mut Vec<ParseError>) -> ParseResult<Exp> {
+        if let [0xFF, 0xFE, ..] | [0xFE, 0xFF, ..] = src {
+            return PRErr(ParseError { message: String::from("UTF-16 byte order mark, read with fromSExpUtf16"), offset: 0, kind: ErrorKind::Syntax })
+        }
+        let phase = Phase::begin(PhaseKind::Parse, src.len());
+        let mut rd = Reader::new(opts, warnings);
+        let mut offset = Self::documentStart(src, opts);
         Self::skipWS(src, &mut offset);
-        Self::parseToken(src, &mut offset)
+        let res = Self::parseToken(&mut rd, src, &mut offset);
+        rd.record(src.len(), &res);
+        phase.end(match res { PROk(_) => true, PRErr(_) => false });
+        res
+    }
+
+    // one datum with nothing but whitespace and comments after it
+    pub(crate) fn fromSExpDocument(src: &[u8], opts: &ReaderOptions) -> ParseResult<Exp> {
+        let mut warnings = Vec::new();
+        let mut rd = Reader::new(opts, &mut warnings);
+        let mut offset = Self::documentStart(src, opts);
+        Self::skipWS(src, &mut offset);
+        match Self::parseToken(&mut rd, src, &mut offset) {
+            PROk(e) => match Self::wsEnd(src, offset, true) {
+                Some(end) if end == src.len() => PROk(e),
+                end => PRErr(ParseError { message: String::from("unexpected trailing data"), offset: end.unwrap_or(offset), kind: ErrorKind::Syntax }),
+            },
+            res => res,
+        }
     }
 
     pub fn toString(&self) -> String {
+        self.toStringWithOptions(&PrintOptions::new())
+    }
+
+    // true when `s` written as is reads back as the same symbol in `dialect`
+    // (`true` is a boolean in Clojure, `nil` in Common Lisp), followed by a
+    // space as in a list (a lone `\` starts the char `\ ` in Clojure)
+    fn readsAsSymbol(s: &[u8], dialect: Dialect) -> bool {
+        let opts = dialect.readerOptions();
+        let mut warnings = Vec::new();
+        let mut offset = 0;
+        let mut src = s.to_vec();
+        src.push(b' ');
+        match Self::parseToken(&mut Reader::new(&opts, &mut warnings), &src, &mut offset) {
+            PROk(Self::Symbol(r)) => offset == s.len() && r.asArray() == s && s != b".",
+            _ => false
+        }
+    }
+
+    // true when `:k` reads back as the keyword `k`
+    pub(crate) fn readsAsKeyword(k: &[u8]) -> bool {
+        let mut s = String::from(":");
+        for c in k.iter() { s.add(*c) }
+        let opts = ReaderOptions::new();
+        let mut warnings = Vec::new();
+        let mut offset = 0;
+        match Self::parseToken(&mut Reader::new(&opts, &mut warnings), s.asArray(), &mut offset) {
+            PROk(Self::Keyword(r)) => offset == s.asArray().len() && r.asArray() == k,
+            _ => false
+        }
+    }
+
+    fn writeEscaped(out: &mut String, s: &[u8], opts: &PrintOptions) {
+        let mut offset = 0;
+        while offset < s.len() {
+            let start = offset;
+            match s[offset] {
+                b'"' => { out.append("\\\""); offset += 1 },
+                b'\\' => { out.append("\\\\"); offset += 1 },
+                b'\n' => { out.append("\\n"); offset += 1 },
+                b'\t' => { out.append("\\t"); offset += 1 },
+                b'\r' => { out.append("\\r"); offset += 1 },
+                c if c < 0x80 || !opts.asciiOnly => { out.add(c); offset += 1 },
+                _ => match Self::readUtf8(s, &mut offset) {
+                    Some(c) => out.append(&format!("\\u{{{:X}}}", c as u32)),
+                    None => { out.add(s[start]); offset = start + 1 }
+                }
+            }
+        }
+    }
+
+    pub fn toStringWithOptions(&self, opts: &PrintOptions) -> String {
+        let phase = Phase::begin(PhaseKind::Print, 0);
+        let s = match opts.shareAbove {
+            Some(min) => self.printShared(min, opts),
+            None => self.print(opts),
+        };
+        phase.endWithSize(true, s.asArray().len());
+        s
+    }
+
+    fn print(&self, opts: &PrintOptions) -> String {
         match self {
-            Self::Bool(b) => format!("{}", b),
-            Self::Char(c) => format!("{}", c),
+            Self::Bool(b) => String::from(opts.dialect.booleanName(*b)),
+            Self::Char(c) => {
+                let mut s = String::from(opts.dialect.charPrefix());
+                for (name, n) in Self::CHAR_NAMES.iter() {
+                    if n == c {
+                        s.append(*name);
+                        return s
+                    }
+                }
+                if Self::isHexChar(*c) {
+                    s.append(&format!("x{:x}", *c as u32));
+                    return s
+                }
+                let mut buff = [0u8; 4];
+                s.append(&*c.encode_utf8(&mut buff));
+                s
+            },
             Self::Int(i) => format!("{}", i),
-            Self::Float(f) => format!("{}", f),
+            Self::UInt(u) => format!("{}", u),
+            Self::Ratio(n, d) => format!("{}/{}", n, d),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => format!("{}", b),
+            Self::Float(f) if f.is_nan() => String::from("+nan.0"),
+            Self::Float(f) if f.is_infinite() => String::from(if *f > 0.0 { "+inf.0" } else { "-inf.0" }),
+            Self::Float(f) => {
+                // keep a '.' or an exponent so it reads back as a Float
+                let mut s = format!("{}", f);
+                if !s.asArray().iter().any(|c| *c == b'.' || *c == b'e') {
+                    s.append(".0");
+                }
+                s
+            },
+            Self::String(s) if !opts.quoteStrings && Self::readsAsSymbol(s.asArray(), opts.dialect) => s.clone(),
             Self::String(s) => {
                 let mut sr = String::new();
                 sr.add('"' as u8);
-                let a = s.asArray();
-                for i in a.iter() {
-                    sr.add(*i);
-                }
+                Self::writeEscaped(&mut sr, s.asArray(), opts);
                 sr.add('"' as u8);
                 sr
             },
+            // the content is read back from the file
+            Self::Spilled(s) => {
+                let mut sr = String::new();
+                sr.add(b'"');
+                Self::writeEscaped(&mut sr, &Self::spilledBytes(s), opts);
+                sr.add(b'"');
+                sr
+            },
+            Self::Symbol(s) if opts.pipeSymbols && !Self::readsAsSymbol(s.asArray(), opts.dialect) => {
+                let mut sr = String::new();
+                sr.add(b'|');
+                for c in s.asArray().iter() {
+                    if *c == b'|' || *c == b'\\' { sr.add(b'\\') }
+                    sr.add(*c);
+                }
+                sr.add(b'|');
+                sr
+            },
             Self::Symbol(s) => s.clone(),
+            Self::Keyword(k) if opts.pipeSymbols && !Self::readsAsKeyword(k.asArray()) => {
+                let mut sr = String::from(":|");
+                for c in k.asArray().iter() {
+                    if *c == b'|' || *c == b'\\' { sr.add(b'\\') }
+                    sr.add(*c);
+                }
+                sr.add(b'|');
+                sr
+            },
+            Self::Keyword(k) => {
+                let mut s = String::from(":");
+                s.append(k);
+                s
+            },
             Self::List(l) => {
                 let mut s = String::new();
                 s.add('(' as u8);
                 for i in 0..l.len() {
-                    s.append(&(l[i].toString()));
+                    s.append(&(l[i].print(opts)));
                     if i != l.len() - 1 {
                         s.add(' ' as u8);
                     }
                 }
                 s.add(')' as u8);
                 s
+            },
+            Self::Vector(v) => {
+                let mut s = String::from("#(");
+                for i in 0..v.len() {
+                    if i != 0 { s.add(b' ') }
+                    s.append(&v[i].print(opts));
+                }
+                s.add(b')');
+                s
+            },
+            Self::Bytes(b) => {
+                let mut s = String::from("#u8(");
+                for i in 0..b.len() {
+                    if i != 0 { s.add(b' ') }
+                    s.append(&format!("{}", b[i]));
+                }
+                s.add(b')');
+                s
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                let mut s = String::new();
+                s.add(b'(');
+                for i in 0..items.len() {
+                    if i != 0 { s.add(b' ') }
+                    s.append(&items[i].print(opts));
+                }
+                if let Some(t) = tail {
+                    s.append(" . ");
+                    s.append(&t.print(opts));
+                }
+                s.add(b')');
+                s
+            },
+            Self::Map(m) => {
+                let mut s = String::new();
+                s.add(b'{');
+                for i in 0..m.len() {
+                    if i != 0 { s.add(b' ') }
+                    s.append(&m[i].0.print(opts));
+                    s.add(b' ');
+                    s.append(&m[i].1.print(opts));
+                }
+                s.add(b'}');
+                s
             }
         }
     }
@@ -381,6 +1485,158 @@ mod tests {
         assert!(res == PROk(Exp::Float(-1234.0E-10)));
     }
 
+    #[test]
+    fn testIntOverflow() {
+        let s = String::from("(1 99999999999999999999)");
+        let mut warnings = Vec::new();
+        let res = Exp::fromSExpWithWarnings(s.asArray(), &ReaderOptions::new(), &mut warnings);
+        match res {
+            PROk(Exp::List(l)) => assert!(l[1] == Exp::Float(99999999999999999999.0)),
+            _ => panic!("expected a list")
+        }
+        assert!(warnings.len() == 1 && warnings[0].offset() == 3);
+
+        let mut opts = ReaderOptions::new();
+        opts.intOverflow = IntOverflow::Error;
+        let res = Exp::fromSExpWithOptions(s.asArray(), &opts);
+        assert!(res == PRErr(ParseError { message: String::from("integer overflow"), offset: 3, kind: ErrorKind::Number }));
+
+        let s = String::from("-9223372036854775808");
+        let res = Exp::fromSExpWithOptions(s.asArray(), &opts);
+        assert!(res == PROk(Exp::Int(i64::MIN)));
+    }
+
+    #[test]
+    fn testRadixIntegers() {
+        let s = String::from("(#x1F #XfF #o-17 #b1010 0x10 -0b11 +0o7 #x7fffffffffffffff #x-8000000000000000 #xyz 0)");
+        match Exp::fromSExp(s.asArray()) {
+            PROk(e) => assert!(e.toString() == "(31 255 -15 10 16 -3 7 9223372036854775807 -9223372036854775808 #xyz 0)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::fromSExp(b"(#b102)") == PRErr(ParseError { message: String::from("invalid digit (radix)"), offset: 5, kind: ErrorKind::Number }));
+
+        let mut warnings = Vec::new();
+        let res = Exp::fromSExpWithWarnings(b"#x10000000000000000", &ReaderOptions::new(), &mut warnings);
+        assert!(res == PROk(Exp::Float(18446744073709551616.0)) && warnings.len() == 1);
+    }
+
+    #[test]
+    fn testShebang() {
+        let src = b"#!/usr/bin/env sexp run\n(main 1)\n(main 2)";
+        assert!(match Exp::fromSExp(src) { PROk(e) => e.toString() == "(main 1)", _ => false });
+        assert!(Exp::fromSExp(b"#!") == PRErr(ParseError { message: String::from("unexpected end of stream (token)"), offset: 2, kind: ErrorKind::Eof }));
+        let (forms, end) = Exp::fromSExpPrefix(src);
+        assert!(forms.len() == 2 && end == src.len());
+        assert!(match Exp::splitBalanced(src, 8) { Ok(r) => r.len() == 2 && r[0] == (0..32), Err(_) => false });
+        let mut heads = std::vec::Vec::new();
+        let routed = Router::new().otherwise(|f, at| heads.push((f.len(), at))).route(&src[..]);
+        assert!(routed.is_ok() && heads == [(8, 24), (8, 33)]);
+
+        let mut opts = ReaderOptions::new();
+        opts.skipShebang = false;
+        assert!(Exp::fromSExpWithOptions(src, &opts) == PROk(Exp::Symbol(String::from("#!/usr/bin/env"))));
+    }
+
+    #[test]
+    fn testByteOrderMark() {
+        let src = b"\xEF\xBB\xBF#!/bin/tool\n(a \"\xC3\xA9\")";
+        assert!(match Exp::fromSExp(&src[..3]) { PRErr(err) => err.offset() == 3, _ => false });
+        assert!(match Exp::fromSExp(src) { PROk(e) => e.toString() == "(a \"\u{e9}\")", _ => false });
+        assert!(match Exp::fromSExp(b"\xEF\xBB\xBF(b)") { PROk(e) => e.toString() == "(b)", _ => false });
+        assert!(match FormReader::new(&src[..]).next() { Some(PROk(e)) => e.toString() == "(a \"\u{e9}\")", _ => false });
+        assert!(Exp::fromSExp(b"\xFF\xFE(\x00)\x00") == PRErr(ParseError { message: String::from("UTF-16 byte order mark, read with fromSExpUtf16"), offset: 0, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testDigitSeparators() {
+        match Exp::fromSExp(b"(1_000_000 0xFF_FF #b1010_1010 -1_000.000_1 1_0/3 1e1_0 a_1)") {
+            PROk(e) => assert!(e.toString() == "(1000000 65535 170 -1000.0001 10/3 10000000000.0 a_1)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        for (src, at) in [("1__0", 1), ("(a 10_)", 5), ("0x_FF", 2), ("1_.5", 1), ("#x-_1", 3), ("1e_5", 2)].iter() {
+            assert!(Exp::fromSExp(src.as_bytes()) == PRErr(ParseError { message: String::from("misplaced digit separator (number)"), offset: *at, kind: ErrorKind::Number }));
+        }
+    }
+
+    #[test]
+    fn testBrackets() {
+        match Exp::fromSExp(b"(let ([x 1] [y #\\]]) [f x y])") {
+            PROk(e) => assert!(e.toString() == "(let ((x 1) (y #\\])) (f x y))"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::fromSExp(b"(a [b c)]") == PRErr(ParseError { message: String::from("mismatched bracket (list)"), offset: 7, kind: ErrorKind::Syntax }));
+        let mut opts = ReaderOptions::new();
+        opts.strictBrackets = false;
+        assert!(match Exp::fromSExpWithOptions(b"(a [b c)]", &opts) { PROk(e) => e.toString() == "(a (b c))", _ => false });
+    }
+
+    #[test]
+    fn testRoundTrip() {
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from("abc")));
+        l.pushBack(Exp::List(Vec::new()));
+        let cells = [Exp::Bool(true), Exp::Bool(false), Exp::Char('a'), Exp::Char(' '), Exp::Char('\n'), Exp::Char('('), Exp::Char('λ'),
+                     Exp::Int(-12), Exp::Float(2.0), Exp::Float(-1.5e-7), Exp::Float(f64::INFINITY), Exp::Float(f64::NEG_INFINITY),
+                     Exp::String(String::from("a b")), Exp::Symbol(String::from("+")), Exp::List(l)];
+        for c in cells.iter() {
+            match Exp::fromSExp(c.toString().asArray()) {
+                PROk(r) => assert!(r == *c, "{} didn't round-trip", c.toString()),
+                PRErr(err) => panic!("{}: {}", c.toString(), err.message.toStr())
+            }
+        }
+
+        match Exp::fromSExp(Exp::Float(f64::NAN).toString().asArray()) {
+            PROk(Exp::Float(f)) => assert!(f.is_nan()),
+            _ => panic!("+nan.0 didn't round-trip")
+        }
+        assert!(Exp::Char(' ').toString() == "#\\space");
+    }
+
+    #[test]
+    fn testCharLiterals() {
+        match Exp::fromSExp(b"(#\\a #\\space #\\newline #\\x41 #\\x #\\x3bb #\\null #\\x1)") {
+            PROk(r) => assert!(r.toString() == "(#\\a #\\space #\\newline #\\A #\\x #\\λ #\\null #\\x1)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        for c in ['\x01', '\x7F', '\r', '\u{85}'].iter() {
+            assert!(match Exp::fromSExp(Exp::Char(*c).toString().asArray()) { PROk(Exp::Char(r)) => r == *c, _ => false });
+        }
+        assert!(match Exp::fromSExp(b"#\\xD800") { PRErr(err) => err.offset() == 0, _ => false });
+    }
+
+    #[test]
+    fn testPrintOptions() {
+        let mut l = Vec::new();
+        l.pushBack(Exp::String(String::from("say \"héllo\"\n")));
+        l.pushBack(Exp::String(String::from("plain")));
+        l.pushBack(Exp::Symbol(String::from("a b")));
+        let e = Exp::List(l);
+
+        assert!(e.toString() == "(\"say \\\"héllo\\\"\\n\" \"plain\" |a b|)");
+        match Exp::fromSExp(e.toString().asArray()) {
+            PROk(Exp::List(r)) => assert!(r[0] == Exp::String(String::from("say \"héllo\"\n"))),
+            _ => panic!("escaped string didn't read back")
+        }
+
+        let mut opts = PrintOptions::new();
+        opts.asciiOnly = true;
+        opts.quoteStrings = false;
+        opts.pipeSymbols = true;
+        assert!(e.toStringWithOptions(&opts) == "(\"say \\\"h\\u{E9}llo\\\"\\n\" plain |a b|)");
+
+        let s = String::from("\"bad \\q\"");
+        let mut offset = 0;
+        let res = Exp::parseString(s.asArray(), &mut offset);
+        assert!(res == PRErr(ParseError { message: String::from("invalid escape (string)"), offset: 5, kind: ErrorKind::Escape }));
+
+        assert!(Exp::fromSExp(b"\"h\\u{E9}llo \\u{1F600}\"") == PROk(Exp::String(String::from("h\u{E9}llo \u{1F600}"))));
+        let ascii = Exp::String(String::from("h\u{E9}llo \u{1F600}")).toStringWithOptions(&opts);
+        assert!(ascii == "\"h\\u{E9}llo \\u{1F600}\"" && Exp::fromSExp(ascii.asArray()) == PROk(Exp::String(String::from("h\u{E9}llo \u{1F600}"))));
+        for bad in ["\"\\u{D800}\"", "\"\\u{}\"", "\"\\u{1234567}\"", "\"\\u41\""].iter() {
+            assert!(Exp::fromSExp(bad.as_bytes()) == PRErr(ParseError { message: String::from("invalid unicode escape (string)"), offset: 1, kind: ErrorKind::Escape }));
+        }
+    }
+
     #[test]
     fn testParseString() {
         let s = String::from("\"1234\"");
@@ -422,6 +1678,116 @@ mod tests {
         assert!(res != PROk(String::from("12t123")));
     }
 
+    #[test]
+    fn testUtf8Symbols() {
+        match Exp::fromSExp("(λ (café) 日本語 x→y :ключ 🙂)".as_bytes()) {
+            PROk(e) => {
+                let l = match &e { Exp::List(l) => l, _ => panic!("expected a list") };
+                assert!(l[0] == Exp::Symbol(String::from("λ")));
+                assert!(l[1] == Exp::List({ let mut v = Vec::new(); v.pushBack(Exp::Symbol(String::from("café"))); v }));
+                assert!(l[2] == Exp::Symbol(String::from("日本語")) && l[3] == Exp::Symbol(String::from("x→y")));
+                assert!(l[4] == Exp::Keyword(String::from("ключ")) && l[5] == Exp::Symbol(String::from("🙂")));
+                let mut opts = PrintOptions::new();
+                opts.pipeSymbols = true;
+                assert!(e.toStringWithOptions(&opts) == "(λ (café) 日本語 x→y :ключ 🙂)");
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        // a symbol stops at the first byte that is not part of a valid character
+        let mut offset = 0;
+        assert!(Exp::parseSymbol(b"ab\xCE(", &mut offset) == PROk(String::from("ab")) && offset == 2);
+        assert!(Exp::fromSExp(b"(ab\xE9)") == PRErr(ParseError { message: String::from("unexpected char (token)"), offset: 3, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp("(a\u{A0}b)".as_bytes()) == PRErr(ParseError { message: String::from("unexpected char (token)"), offset: 2, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testPipeSymbols() {
+        match Exp::fromSExp(b"(|a b| |x\\|y| || |(| ||||||| | |\\u{3BB}|)") {
+            PROk(Exp::List(l)) => {
+                let names : std::vec::Vec<&str> = l.asArray().iter().map(|e| match e { Exp::Symbol(s) => s.toStr(), _ => panic!("expected symbols") }).collect();
+                assert!(names == ["a b", "x|y", "", "(", "|||||||", "|", "λ"]);
+            },
+            _ => panic!("expected a list")
+        }
+        for s in ["a b", "", "(", "12", "#t", "x|y", "a\\b", ":k", "plain", "|||||||"].iter() {
+            let e = Exp::Symbol(String::from(s));
+            assert!(Exp::fromSExp(e.toString().asArray()) == PROk(e.clone()));
+        }
+        assert!(Exp::Symbol(String::from("12")).toString() == "|12|");
+        assert!(Exp::fromSExp(b"|a b") == PRErr(ParseError { message: String::from("unexpected end of stream (symbol)"), offset: 0, kind: ErrorKind::Eof }));
+        let mut opts = ReaderOptions::new();
+        opts.pipeSymbols = false;
+        assert!(match Exp::fromSExpWithOptions(b"(|> x)", &opts) { PROk(Exp::List(l)) => l[0] == Exp::Symbol(String::from("|>")), _ => false });
+    }
+
+    #[test]
+    fn testKeywords() {
+        match Exp::fromSExp(b"(f :name \"x\" : ::k {:a 1})") {
+            PROk(Exp::List(l)) => {
+                assert!(l[1] == Exp::Keyword(String::from("name")) && l[1] != Exp::Symbol(String::from(":name")));
+                assert!(l[3] == Exp::Symbol(String::from(":")) && l[4] == Exp::Keyword(String::from(":k")));
+                assert!(l[5].lookup(&Exp::Keyword(String::from("a"))) == Some(&Exp::Int(1)));
+                assert!(Exp::List(l).toString() == "(f :name \"x\" : ::k {:a 1})");
+            },
+            _ => panic!("expected a list")
+        }
+
+        for k in ["x y", "|a", "(k)", "\\ ", "\"", "k;"].iter() {
+            let e = Exp::Keyword(String::from(k));
+            let s = e.toString();
+            assert!(s.asArray()[1] == b'|', "{}", s.toStr());
+            assert!(Exp::fromSExp(s.asArray()) == PROk(e.clone()));
+            assert!(e.serializedLen(&PrintOptions::new()) == s.asArray().len());
+        }
+        assert!(Exp::keyword("").is_none() && Exp::keyword("k") == Some(Exp::Keyword(String::from("k"))));
+        assert!(matches!(Exp::formEnd(b":|k l| x", 0, true), Ok(Some(6))));
+        assert!(Exp::fromSExp(b"(:|| 1)") == PRErr(ParseError { message: String::from("empty keyword"), offset: 1, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testQuote() {
+        match Exp::fromSExp(b"(a 'b '(c 'd) ' e)") {
+            PROk(e) => assert!(e.toString() == "(a (quote b) (quote (c (quote d))) (quote e))"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::fromSExp(b"(a ')") == PRErr(ParseError { message: String::from("missing datum after '"), offset: 3, kind: ErrorKind::Syntax }));
+        // inside a token the apostrophe is a symbol character, numbers do not take one
+        assert!(match Exp::fromSExp(b"(don't 'a'b)") { PROk(e) => e.toString() == "(don't (quote a'b))", _ => false });
+        assert!(Exp::fromSExp(b"(1'a)") == PRErr(ParseError { message: String::from("Unexpected end of stream (sign)"), offset: 2, kind: ErrorKind::Number }));
+        let mut opts = ReaderOptions::new();
+        opts.apostrophe = Apostrophe::Symbol;
+        assert!(match Exp::fromSExpWithOptions(b"(a 'b don't)", &opts) { PROk(e) => e.toString() == "(a |'b| don't)", _ => false });
+        opts.apostrophe = Apostrophe::Error;
+        assert!(Exp::fromSExpWithOptions(b"(a 'b)", &opts) == PRErr(ParseError { message: String::from("unexpected ' (symbol)"), offset: 3, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExpWithOptions(b"(a don't)", &opts) == PRErr(ParseError { message: String::from("unexpected ' (symbol)"), offset: 6, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testVector() {
+        match Exp::fromSExp(b"(v #(1 #(a) \"s\") #() (x))") {
+            PROk(Exp::List(l)) => {
+                assert!(matches!(&l[1], Exp::Vector(v) if v.len() == 3 && matches!(&v[1], Exp::Vector(_))));
+                assert!(l[2] == Exp::Vector(Vec::new()) && l[2] != Exp::List(Vec::new()));
+                assert!(Exp::List(l).toString() == "(v #(1 #(a) \"s\") #() (x))");
+            },
+            _ => panic!("expected a list")
+        }
+        assert!(Exp::fromSExp(b"#(a . b)") == PRErr(ParseError { message: String::from("unexpected . (vector)"), offset: 4, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"#(a]") == PRErr(ParseError { message: String::from("mismatched bracket (list)"), offset: 3, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testQuasiquote() {
+        let mut opts = ReaderOptions::new();
+        assert!(match Exp::fromSExpWithOptions(b"`(a ,b)", &opts) { PRErr(err) => err.offset == 0, _ => false });
+        opts.quasiquoteSugar = true;
+        match Exp::fromSExpWithOptions(b"`(a ,b ,@ (c 'd) ,,e)", &opts) {
+            PROk(e) => assert!(e.toString() == "(quasiquote (a (unquote b) (unquote-splicing (c (quote d))) (unquote (unquote e))))"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::fromSExpWithOptions(b"(a ,@)", &opts) == PRErr(ParseError { message: String::from("missing datum after ,@"), offset: 3, kind: ErrorKind::Syntax }));
+    }
+
     #[test]
     fn testParseList() {
 
@@ -454,4 +1820,53 @@ mod tests {
             PRErr(err) => panic!("{}", err.message.toStr())
         }
     }
+
+    #[test]
+    fn testLineComments() {
+        let sexp = String::from("; header\n(foo ; this is ignored\n bar;tail\n \"a;b\") ; trailing");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toString() == "(foo bar \"a;b\")"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(match Exp::fromSExp(b"(a ; b)") { PRErr(err) => err.offset() == 7, _ => false });
+    }
+
+    #[test]
+    fn testBlockComments() {
+        let sexp = String::from("#| header |#(foo #| a #| nested |# (b |# bar #||#\n #|x|#baz)");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toString() == "(foo bar baz)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        match Exp::fromSExp(b"(a #| b #| c |# d)") {
+            PRErr(err) => assert!(err.offset() == 3 && err.message() == "unterminated block comment"),
+            PROk(_) => panic!("unterminated comment accepted")
+        }
+    }
+
+    #[test]
+    fn testDatumComments() {
+        let sexp = String::from("#;(skipped) (a #;(b (c \")\")) d #; e #;#;f g h)");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => assert!(r.toString() == "(a d h)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        for (src, at) in [("(a #;)", 3), ("(a #; (b)", 9), ("#;", 0)].iter() {
+            assert!(match Exp::fromSExp(src.as_bytes()) { PRErr(err) => err.offset() == *at, _ => false }, "{}", src);
+        }
+    }
+
+    #[test]
+    fn testBooleans() {
+        match Exp::fromSExp(b"(#t #f #true #false true false)") {
+            PROk(r) => assert!(r.toString() == "(#t #f #t #f true false)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        let mut opts = ReaderOptions::new();
+        opts.wordBooleans = true;
+        match Exp::fromSExpWithOptions(b"(true false truex)", &opts) {
+            PROk(r) => assert!(r.toString() == "(#t #f truex)"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
 }
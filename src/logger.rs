@@ -0,0 +1,111 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// `log` adapter: every record is written as one canonical s-expression line
+//
+//     (log (level info) (target "app::db") (msg "connected") (file "src/db.rs") (line 42))
+//
+use alt_std::*;
+use std::sync::Mutex;
+use crate::*;
+
+pub struct SexpLogger<W: std::io::Write + Send> {
+    out     : Mutex<W>,
+    level   : log::LevelFilter,
+}
+
+fn entry(key: &str, value: Exp) -> Exp {
+    let mut l = Vec::new();
+    l.pushBack(Exp::Symbol(String::from(key)));
+    l.pushBack(value);
+    Exp::List(l)
+}
+
+impl<W: std::io::Write + Send> SexpLogger<W> {
+    pub fn new(out: W, level: log::LevelFilter) -> Self {
+        Self { out: Mutex::new(out), level }
+    }
+
+    pub fn intoInner(self) -> W {
+        match self.out.into_inner() { Ok(w) => w, Err(p) => p.into_inner() }
+    }
+
+    /// the s-expression a record is written as
+    pub fn recordToExp(record: &log::Record) -> Exp {
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from("log")));
+        let level = record.level().as_str().to_ascii_lowercase();
+        l.pushBack(entry("level", Exp::Symbol(String::from(level.as_str()))));
+        l.pushBack(entry("target", Exp::String(String::from(record.target()))));
+        let msg = std::fmt::format(*record.args());
+        l.pushBack(entry("msg", Exp::String(String::from(msg.as_str()))));
+        if let Some(file) = record.file() {
+            l.pushBack(entry("file", Exp::String(String::from(file))));
+        }
+        if let Some(line) = record.line() {
+            l.pushBack(entry("line", Exp::Int(line as i64)));
+        }
+        Exp::List(l)
+    }
+
+    /// installs the logger as the global `log` backend
+    pub fn install(self) -> Result<(), log::SetLoggerError> where W: 'static {
+        let level = self.level;
+        log::set_boxed_logger(std::boxed::Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<W: std::io::Write + Send> log::Log for SexpLogger<W> {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) { return }
+        let mut line = Self::recordToExp(record).toString();
+        line.add(b'\n');
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.write_all(line.asArray());
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut out) = self.out.lock() {
+            let _ = out.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Log;
+
+    #[test]
+    fn testSexpLogger() {
+        let logger = SexpLogger::new(std::vec::Vec::new(), log::LevelFilter::Info);
+        logger.log(&log::Record::builder().level(log::Level::Warn).target("app::db").args(format_args!("lost {} \"conns\"", 3)).line(Some(7)).build());
+        logger.log(&log::Record::builder().level(log::Level::Debug).target("app").args(format_args!("hidden")).build());
+        let out = logger.intoInner();
+        assert!(out == b"(log (level warn) (target \"app::db\") (msg \"lost 3 \\\"conns\\\"\") (line 7))\n");
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// maps: `{key value ...}` read into `Exp::Map`, looked up and edited by key
+//
+use alt_std::*;
+use crate::*;
+use std::collections::HashMap;
+
+impl Exp {
+    pub(crate) fn mapGet<'a>(m: &'a [(Exp, Exp)], key: &Exp) -> Option<&'a Exp> {
+        m.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    pub(crate) fn parseMap(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        match Self::getchar(src, offset) {
+            Some(b'{') => (),
+            Some(_) => return PRErr(ParseError { message: String::from("unexpected character (map)"), offset: *offset, kind: ErrorKind::Syntax }),
+            None => return PRErr(ParseError { message: String::from("unexpected end of stream (map)"), offset: *offset, kind: ErrorKind::Eof }),
+        }
+
+        if let Err(err) = rd.enter(*offset - 1) { return PRErr(err) }
+        if let Some(t) = &mut rd.trace { t.push(Rule::ListOpen, *offset - 1, 1) }
+        let mut entries : Vec<(Exp, Exp)> = Vec::new();
+        // key hash -> indices of the entries with that hash, so duplicates cost one bucket scan
+        let mut seen : HashMap<u64, std::vec::Vec<usize>> = HashMap::new();
+        let mut key : Option<(Exp, usize, u64)> = None;
+        loop {
+            Self::skipWS(src, offset);
+            match Self::peek(src, *offset) {
+                Some(b'}') => {
+                    if key.is_some() {
+                        if let Some(t) = &mut rd.trace { t.push(Rule::Error, *offset, 0) }
+                        return PRErr(ParseError { message: String::from("missing value (map)"), offset: *offset, kind: ErrorKind::Syntax })
+                    }
+                    Self::getchar(src, offset);
+                    rd.depth -= 1;
+                    if let Some(t) = &mut rd.trace { t.push(Rule::ListClose, *offset - 1, 1) }
+                    return PROk(Exp::Map(entries))
+                },
+                Some(_) => {
+                    let start = *offset;
+                    let e = match Self::parseToken(rd, src, offset) {
+                        PROk(e) => e,
+                        PRErr(err) => return PRErr(err),
+                    };
+                    match key.take() {
+                        None => {
+                            let h = e.structuralHash().0;
+                            let es = entries.asArray();
+                            let dup = match seen.get(&h) { Some(b) => b.iter().any(|i| es[*i].0 == e), None => false };
+                            if dup {
+                                if let Some(t) = &mut rd.trace { t.push(Rule::Error, start, 0) }
+                                return PRErr(ParseError { message: String::from("duplicate key (map)"), offset: start, kind: ErrorKind::Syntax })
+                            }
+                            key = Some((e, start, h))
+                        },
+                        Some((k, _, h)) => {
+                            seen.entry(h).or_default().push(entries.len());
+                            entries.pushBack((k, e))
+                        },
+                    }
+                },
+                None => {
+                    if let Some(t) = &mut rd.trace { t.push(Rule::Error, *offset, 0) }
+                    return PRErr(ParseError { message: String::from("unexpected end of stream (map)"), offset: *offset, kind: ErrorKind::Eof })
+                }
+            }
+        }
+    }
+
+    /// value under `key` when this is a map
+    pub fn lookup(&self, key: &Exp) -> Option<&Exp> {
+        match self {
+            Self::Map(m) => Self::mapGet(m.asArray(), key),
+            _ => None
+        }
+    }
+
+    pub fn lookupMut(&mut self, key: &Exp) -> Option<&mut Exp> {
+        match self {
+            Self::Map(m) => m.asMutArray().iter_mut().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None
+        }
+    }
+
+    /// sets `key` to `value` in a map (new keys go last), returns the previous
+    /// value, a non map expression is left as is and gets `value` back
+    pub fn insert(&mut self, key: Exp, value: Exp) -> Option<Exp> {
+        let m = match self {
+            Self::Map(m) => m,
+            _ => return Some(value)
+        };
+        match m.asMutArray().iter_mut().find(|(k, _)| *k == key) {
+            Some((_, v)) => Some(core::mem::replace(v, value)),
+            None => { m.pushBack((key, value)); None }
+        }
+    }
+
+    pub fn remove(&mut self, key: &Exp) -> Option<Exp> {
+        let m = match self {
+            Self::Map(m) => m,
+            _ => return None
+        };
+        let i = m.asArray().iter().position(|(k, _)| k == key)?;
+        let mut rest = Vec::new();
+        let mut removed = None;
+        for (n, (k, v)) in m.asArray().iter().enumerate() {
+            if n == i { removed = Some(v.clone()) } else { rest.pushBack((k.clone(), v.clone())) }
+        }
+        *m = rest;
+        removed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testMap() {
+        let sym = |s: &str| Exp::Symbol(String::from(s));
+        let mut e = match Exp::fromSExp(b"{name \"srv\" port 80 tags {a 1}}") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(e.lookup(&sym("port")) == Some(&Exp::Int(80)));
+        assert!(e.lookup(&sym("tags")).and_then(|t| t.lookup(&sym("a"))) == Some(&Exp::Int(1)));
+        assert!(e.insert(sym("port"), Exp::Int(8080)) == Some(Exp::Int(80)));
+        assert!(e.insert(sym("tls"), Exp::Bool(true)).is_none());
+        assert!(e.remove(&sym("name")) == Some(Exp::String(String::from("srv"))));
+        assert!(e.toString() == "{port 8080 tags {a 1} tls #t}");
+        assert!(match Exp::fromSExp(b"{tls #t tags {a 1} port 8080}") { PROk(o) => o == e, _ => false });
+
+        assert!(Exp::fromSExp(b"{a 1 b}") == PRErr(ParseError { message: String::from("missing value (map)"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"{a 1 a 2}") == PRErr(ParseError { message: String::from("duplicate key (map)"), offset: 5, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"{(a 1) x (a 2) y (a 1) z}") == PRErr(ParseError { message: String::from("duplicate key (map)"), offset: 17, kind: ErrorKind::Syntax }));
+    }
+
+    #[test]
+    fn testLargeMap() {
+        let mut src = std::string::String::from("{");
+        for i in 0..50_000 { src.push_str(&std::format!("k{} {} ", i, i)) }
+        src.push('}');
+        let e = match Exp::fromSExp(src.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(matches!(&e, Exp::Map(m) if m.len() == 50_000));
+        src.insert_str(src.len() - 1, "k49999 0");
+        assert!(matches!(Exp::fromSExp(src.as_bytes()), PRErr(err) if err.message.toStr() == "duplicate key (map)"));
+    }
+}
@@ -0,0 +1,171 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// output size: `serializedLen` is the exact length `toStringWithOptions` would
+// return, counted without building the text
+//
+use core::fmt::Write;
+use crate::*;
+
+// counts the bytes written and remembers if a '.' or an exponent went by
+struct Counter {
+    len     : usize,
+    point   : bool,
+}
+
+impl Write for Counter {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.len += s.len();
+        self.point |= s.bytes().any(|c| c == b'.' || c == b'e');
+        Ok(())
+    }
+}
+
+fn counted(args: core::fmt::Arguments) -> Counter {
+    let mut c = Counter { len: 0, point: false };
+    let _ = c.write_fmt(args);
+    c
+}
+
+impl Exp {
+    fn escapedLen(s: &[u8], opts: &PrintOptions) -> usize {
+        let mut len = 0;
+        let mut offset = 0;
+        while offset < s.len() {
+            let start = offset;
+            len += match s[offset] {
+                b'"' | b'\\' | b'\n' | b'\t' | b'\r' => { offset += 1; 2 },
+                c if c < 0x80 || !opts.asciiOnly => { offset += 1; 1 },
+                _ => match Self::readUtf8(s, &mut offset) {
+                    Some(c) => counted(format_args!("\\u{{{:X}}}", c as u32)).len,
+                    None => { offset = start + 1; 1 }
+                }
+            }
+        }
+        len
+    }
+
+    /// number of bytes `toStringWithOptions(opts)` produces
+    pub fn serializedLen(&self, opts: &PrintOptions) -> usize {
+        // which subtrees are shared is only known by printing
+        if opts.shareAbove.is_some() { return self.toStringWithOptions(opts).asArray().len() }
+        match self {
+            Self::Bool(b) => opts.dialect.booleanName(*b).len(),
+            Self::Char(c) => opts.dialect.charPrefix().len() + match Self::CHAR_NAMES.iter().find(|(_, n)| n == c) {
+                Some((name, _)) => name.len(),
+                None if Self::isHexChar(*c) => counted(format_args!("x{:x}", *c as u32)).len,
+                None => c.len_utf8(),
+            },
+            Self::Int(i) => counted(format_args!("{}", i)).len,
+            Self::UInt(u) => counted(format_args!("{}", u)).len,
+            Self::Ratio(n, d) => counted(format_args!("{}/{}", n, d)).len,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => counted(format_args!("{}", b)).len,
+            Self::Float(f) if f.is_nan() || f.is_infinite() => 6,
+            Self::Float(f) => {
+                let c = counted(format_args!("{}", f));
+                if c.point { c.len } else { c.len + 2 }
+            },
+            Self::String(s) if !opts.quoteStrings && Self::readsAsSymbol(s.asArray(), opts.dialect) => s.asArray().len(),
+            Self::String(s) => 2 + Self::escapedLen(s.asArray(), opts),
+            Self::Spilled(s) => 2 + Self::escapedLen(&Self::spilledBytes(s), opts),
+            Self::Symbol(s) if opts.pipeSymbols && !Self::readsAsSymbol(s.asArray(), opts.dialect) =>
+                2 + s.asArray().iter().map(|c| if *c == b'|' || *c == b'\\' { 2 } else { 1 }).sum::<usize>(),
+            Self::Symbol(s) => s.asArray().len(),
+            Self::Keyword(k) if opts.pipeSymbols && !Self::readsAsKeyword(k.asArray()) =>
+                3 + k.asArray().iter().map(|c| if *c == b'|' || *c == b'\\' { 2 } else { 1 }).sum::<usize>(),
+            Self::Keyword(k) => 1 + k.asArray().len(),
+            Self::List(l) => {
+                let items : usize = l.asArray().iter().map(|e| e.serializedLen(opts)).sum();
+                2 + items + l.len().saturating_sub(1)
+            },
+            Self::Bytes(b) => {
+                let digits : usize = b.asArray().iter().map(|c| if *c >= 100 { 3 } else if *c >= 10 { 2 } else { 1 }).sum();
+                5 + digits + b.len().saturating_sub(1)
+            },
+            Self::Vector(v) => {
+                let items : usize = v.asArray().iter().map(|e| e.serializedLen(opts)).sum();
+                3 + items + v.len().saturating_sub(1)
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                let len : usize = items.iter().map(|e| e.serializedLen(opts)).sum();
+                2 + len + (items.len() - 1) + tail.map_or(0, |t| 3 + t.serializedLen(opts))
+            },
+            Self::Map(m) => {
+                let items : usize = m.asArray().iter().map(|(k, v)| k.serializedLen(opts) + v.serializedLen(opts)).sum();
+                2 + items + (2 * m.len()).saturating_sub(1)
+            }
+        }
+    }
+
+    /// `serializedLen` when it is at most `limit`, otherwise a length over
+    /// `limit`, without measuring the rest of the tree
+    pub(crate) fn serializedLenWithin(&self, opts: &PrintOptions, limit: usize) -> usize {
+        let within = |open: usize, items: &mut dyn Iterator<Item = &Exp>| {
+            let mut len = open + 1;
+            for (i, e) in items.enumerate() {
+                if i != 0 { len += 1 }
+                if len > limit { break }
+                len += e.serializedLenWithin(opts, limit - len);
+            }
+            len
+        };
+        if opts.shareAbove.is_some() { return self.serializedLen(opts) }
+        match self {
+            Self::List(l) => within(1, &mut l.asArray().iter()),
+            Self::Vector(v) => within(2, &mut v.asArray().iter()),
+            Self::Map(m) => within(1, &mut m.asArray().iter().flat_map(|(k, v)| [k, v])),
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                let len = within(1, &mut items.into_iter());
+                match tail {
+                    Some(t) if len <= limit => len + 3 + t.serializedLenWithin(opts, limit.saturating_sub(len + 3)),
+                    _ => len,
+                }
+            },
+            e => e.serializedLen(opts),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSerializedLen() {
+        let src = "(a \"q\\\"\\n\" #\\space #\\x7 #\\λ 12 -3.5 2.0 +inf.0 #f (\"héllo wörld\" |a b| ()) {k 1 \"v\" ()} {} :key (a b . 1.5) #(1 #()) #u8(0 16 255) #u8() x)";
+        let e = match Exp::fromSExp(src.as_bytes()) { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let mut plain = PrintOptions::new();
+        plain.asciiOnly = true;
+        plain.quoteStrings = false;
+        plain.pipeSymbols = true;
+        for opts in [PrintOptions::new(), plain, Dialect::Clojure.printOptions()].iter() {
+            assert!(e.serializedLen(opts) == e.toStringWithOptions(opts).asArray().len());
+        }
+
+        let len = e.serializedLen(&PrintOptions::new());
+        for limit in 0..len + 2 {
+            let within = e.serializedLenWithin(&PrintOptions::new(), limit);
+            assert!(if len <= limit { within == len } else { within > limit });
+        }
+    }
+}
@@ -0,0 +1,395 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+//
+// three way structural merge:
+//  - a side equal to the base loses against the other one
+//  - entries of `(key (k0 ...) (k1 ...))` forms merge key by key (deletions included)
+//  - other lists are aligned with the base by longest common subsequence, the runs
+//    between elements kept on all three sides merge like nodes: a run changed on one
+//    side only takes that side, runs of the same length changed on both sides merge
+//    element by element
+//  - anything else changed on both sides is a conflict, left in the tree as
+//    `(<<<<<<< ours ||||||| base ======= theirs >>>>>>>)` (a missing side is omitted)
+//  - the top level forms of files merge the same way, `merge3Forms` says which forms
+//    are taken as they are from one side so that their text can be kept
+//
+pub const CONFLICT_OURS     : &str = "<<<<<<<";
+pub const CONFLICT_BASE     : &str = "|||||||";
+pub const CONFLICT_THEIRS   : &str = "=======";
+pub const CONFLICT_END      : &str = ">>>>>>>";
+
+pub struct Conflict {
+    pub path    : Path,
+    // None when the side does not have the node
+    pub base    : Option<Exp>,
+    pub ours    : Option<Exp>,
+    pub theirs  : Option<Exp>,
+}
+
+pub struct Merge {
+    pub merged      : Exp,
+    pub conflicts   : Vec<Conflict>,
+}
+
+/// a form of the result of `merge3Forms`
+#[derive(Clone, PartialEq)]
+pub enum MergedForm {
+    // the form at this index of ours, unchanged
+    Ours(usize),
+    // the form at this index of theirs, unchanged
+    Theirs(usize),
+    // merged from both sides, or a conflict
+    Merged(Exp),
+}
+
+pub struct FormsMerge {
+    pub forms       : Vec<MergedForm>,
+    pub conflicts   : Vec<Conflict>,
+}
+
+impl FormsMerge {
+    pub fn isClean(&self) -> bool { self.conflicts.len() == 0 }
+}
+
+impl Merge {
+    pub fn isClean(&self) -> bool { self.conflicts.len() == 0 }
+
+    /// the merged tree, conflict markers on their own lines (still reads back)
+    pub fn toConflictString(&self) -> String {
+        self.merged.toConflictString()
+    }
+
+    fn writeConflicts(e: &Exp, out: &mut String) {
+        let l = match e {
+            Exp::List(l) => l,
+            e => return out.append(&e.toString())
+        };
+
+        let marker = Exp::isConflictMarker(e);
+        out.add(b'(');
+        for i in 0..l.len() {
+            match (marker, &l[i]) {
+                (true, Exp::Symbol(s)) if s.toStr() == CONFLICT_OURS => out.append(CONFLICT_OURS),
+                (true, Exp::Symbol(s)) if s.toStr() == CONFLICT_END => { out.add(b'\n'); out.append(CONFLICT_END) },
+                (true, Exp::Symbol(s)) if s.toStr() == CONFLICT_BASE || s.toStr() == CONFLICT_THEIRS => { out.add(b'\n'); out.append(s) },
+                (true, e) => { out.add(b'\n'); Self::writeConflicts(e, out) },
+                (false, e) => {
+                    if i != 0 { out.add(b' ') }
+                    Self::writeConflicts(e, out)
+                },
+            }
+        }
+        out.add(b')');
+    }
+}
+
+impl Exp {
+    /// `toString` with the conflict markers left by `merge3` on their own lines
+    pub fn toConflictString(&self) -> String {
+        let mut s = String::new();
+        Merge::writeConflicts(self, &mut s);
+        s
+    }
+
+    pub fn isConflictMarker(e: &Exp) -> bool {
+        match e {
+            Self::List(l) if l.len() >= 4 => match (&l[0], &l[l.len() - 1]) {
+                (Self::Symbol(a), Self::Symbol(b)) => a.toStr() == CONFLICT_OURS && b.toStr() == CONFLICT_END,
+                _ => false
+            },
+            _ => false
+        }
+    }
+
+    fn conflictMarker(base: &[Exp], ours: &[Exp], theirs: &[Exp]) -> Exp {
+        let mut v = Vec::new();
+        v.pushBack(Self::Symbol(String::from(CONFLICT_OURS)));
+        for o in ours.iter() { v.pushBack(o.clone()) }
+        v.pushBack(Self::Symbol(String::from(CONFLICT_BASE)));
+        for b in base.iter() { v.pushBack(b.clone()) }
+        v.pushBack(Self::Symbol(String::from(CONFLICT_THEIRS)));
+        for t in theirs.iter() { v.pushBack(t.clone()) }
+        v.pushBack(Self::Symbol(String::from(CONFLICT_END)));
+        Self::List(v)
+    }
+
+    fn conflict(path: &Path, base: Option<&Exp>, ours: Option<&Exp>, theirs: Option<&Exp>, conflicts: &mut Vec<Conflict>) -> Exp {
+        conflicts.pushBack(Conflict { path: path.clone(), base: base.cloned(), ours: ours.cloned(), theirs: theirs.cloned() });
+        fn side(e: Option<&Exp>) -> &[Exp] { e.map_or(&[], core::slice::from_ref) }
+        Self::conflictMarker(side(base), side(ours), side(theirs))
+    }
+
+    // a conflict over runs of elements, a side of one element is recorded as that element
+    fn runConflict(path: &Path, base: &[Exp], ours: &[Exp], theirs: &[Exp], conflicts: &mut Vec<Conflict>) -> Exp {
+        let side = |r: &[Exp]| match r {
+            [] => None,
+            [e] => Some(e.clone()),
+            r => {
+                let mut v = Vec::new();
+                for e in r.iter() { v.pushBack(e.clone()) }
+                Some(Self::List(v))
+            }
+        };
+        conflicts.pushBack(Conflict { path: path.clone(), base: side(base), ours: side(ours), theirs: side(theirs) });
+        Self::conflictMarker(base, ours, theirs)
+    }
+
+    // longest common subsequence of `a` and `b`: for each element of `a`, its match in `b`
+    fn lcsMatches(a: &[Exp], b: &[Exp]) -> std::vec::Vec<Option<usize>> {
+        let (n, m) = (a.len(), b.len());
+        // len[i][j]: LCS of a[i..] and b[j..]
+        let mut len = std::vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                len[i * (m + 1) + j] = if a[i] == b[j] { len[(i + 1) * (m + 1) + j + 1] + 1 }
+                    else { u32::max(len[(i + 1) * (m + 1) + j], len[i * (m + 1) + j + 1]) };
+            }
+        }
+        let mut matches = std::vec![None; n];
+        let (mut i, mut j) = (0, 0);
+        while i < n && j < m {
+            if a[i] == b[j] { matches[i] = Some(j); i += 1; j += 1 }
+            else if len[(i + 1) * (m + 1) + j] >= len[i * (m + 1) + j + 1] { i += 1 }
+            else { j += 1 }
+        }
+        matches
+    }
+
+    // one node present in ours and/or theirs, taken from a side when the other did not change it
+    fn pick(path: &Path, base: Option<&Exp>, ours: Option<(usize, &Exp)>, theirs: Option<(usize, &Exp)>, conflicts: &mut Vec<Conflict>) -> Option<MergedForm> {
+        let (o, t) = (ours.map(|(_, e)| e), theirs.map(|(_, e)| e));
+        match (ours, theirs) {
+            (Some((i, _)), _) if o == t || base == t => Some(MergedForm::Ours(i)),
+            (_, Some((j, _))) if base == o => Some(MergedForm::Theirs(j)),
+            (None, _) if o == t || base == t => None,
+            (_, None) if base == o => None,
+            _ => Self::merge3At(path, base, o, t, conflicts).map(MergedForm::Merged),
+        }
+    }
+
+    // the elements of three lists merged, `leaf` when they are the `(key value)` of an entry
+    fn mergeSeq(path: &Path, lb: &[Exp], lo: &[Exp], lt: &[Exp], leaf: bool, conflicts: &mut Vec<Conflict>) -> std::vec::Vec<MergedForm> {
+        let childPath = |i: usize| if leaf && i == 1 { path.clone() } else { path.child(PathSeg::Index(i)) };
+        let (mo, mt) = (Self::lcsMatches(lb, lo), Self::lcsMatches(lb, lt));
+        let mut out = std::vec::Vec::new();
+        let (mut i, mut j, mut k) = (0, 0, 0);
+        loop {
+            // the next base element kept on both sides, or the ends
+            let sync = (i..lb.len()).find_map(|x| match (mo[x], mt[x]) { (Some(y), Some(z)) => Some((x, y, z)), _ => None });
+            let (si, sj, sk) = sync.unwrap_or((lb.len(), lo.len(), lt.len()));
+            let (rb, ro, rt) = (&lb[i..si], &lo[j..sj], &lt[k..sk]);
+            if ro == rb {
+                out.extend((k..sk).map(MergedForm::Theirs));
+            } else if rt == rb || ro == rt {
+                out.extend((j..sj).map(MergedForm::Ours));
+            } else if rb.len() == ro.len() && ro.len() == rt.len() {
+                for x in 0..ro.len() {
+                    out.extend(Self::pick(&childPath(j + x), Some(&rb[x]), Some((j + x, &ro[x])), Some((k + x, &rt[x])), conflicts));
+                }
+            } else {
+                out.push(MergedForm::Merged(Self::runConflict(&childPath(j), rb, ro, rt, conflicts)));
+            }
+            if sync.is_none() { return out }
+            out.push(MergedForm::Ours(sj));
+            i = si + 1; j = sj + 1; k = sk + 1;
+        }
+    }
+
+    // the keys of forms made of entries with distinct keys
+    fn distinctKeys(l: &[Exp]) -> Option<std::vec::Vec<&String>> {
+        let mut keys = std::vec::Vec::new();
+        for e in l.iter() {
+            let k = e.entryKey()?;
+            if keys.contains(&k) { return None }
+            keys.push(k);
+        }
+        Some(keys)
+    }
+
+    /// three way merge of the top level forms of files: forms keyed by distinct heads
+    /// merge by head, others are aligned with the base
+    pub fn merge3Forms(base: &[Exp], ours: &[Exp], theirs: &[Exp]) -> FormsMerge {
+        let mut conflicts = Vec::new();
+        let mut forms = Vec::new();
+        match (Self::distinctKeys(base), Self::distinctKeys(ours), Self::distinctKeys(theirs)) {
+            (Some(kb), Some(ko), Some(kt)) => {
+                let mut keys : std::vec::Vec<&String> = std::vec::Vec::new();
+                for k in ko.iter().chain(kt.iter()).chain(kb.iter()) {
+                    if !keys.contains(k) { keys.push(k) }
+                }
+                let find = |ks: &[&String], k: &String| ks.iter().position(|x| *x == k);
+                for k in keys.iter() {
+                    let (b, o, t) = (find(&kb, k), find(&ko, k), find(&kt, k));
+                    let p = Path::new().child(PathSeg::Key((*k).clone()));
+                    if let Some(f) = Self::pick(&p, b.map(|i| &base[i]), o.map(|i| (i, &ours[i])), t.map(|i| (i, &theirs[i])), &mut conflicts) {
+                        forms.pushBack(f)
+                    }
+                }
+            },
+            _ => for f in Self::mergeSeq(&Path::new(), base, ours, theirs, false, &mut conflicts).into_iter() { forms.pushBack(f) },
+        }
+        FormsMerge { forms, conflicts }
+    }
+
+    // `(head (k0 ...) (k1 ...))` with distinct keys
+    fn keyedEntries(e: &Exp) -> Option<(&String, &[Exp])> {
+        let head = e.entryKey()?;
+        let l = match e { Self::List(l) => &l.asArray()[1..], _ => return None };
+        for i in 0..l.len() {
+            let k = l[i].entryKey()?;
+            if l[..i].iter().any(|p| p.entryKey() == Some(k)) { return None }
+        }
+        Some((head, l))
+    }
+
+    fn findEntry<'a>(l: &'a [Exp], k: &String) -> Option<&'a Exp> {
+        l.iter().find(|e| e.entryKey() == Some(k))
+    }
+
+    fn merge3At(path: &Path, base: Option<&Exp>, ours: Option<&Exp>, theirs: Option<&Exp>, conflicts: &mut Vec<Conflict>) -> Option<Exp> {
+        if ours == theirs { return ours.cloned() }
+        if base == ours { return theirs.cloned() }
+        if base == theirs { return ours.cloned() }
+
+        let (o, t) = match (ours, theirs) {
+            (Some(o), Some(t)) => (o, t),
+            // changed on one side, deleted on the other
+            _ => return Some(Self::conflict(path, base, ours, theirs, conflicts))
+        };
+
+        match (Self::keyedEntries(o), Self::keyedEntries(t), base.map(Self::keyedEntries)) {
+            (Some((ho, lo)), Some((ht, lt)), b) if ho == ht && b.map_or(true, |b| b.is_some_and(|(hb, _)| hb == ho)) => {
+                let lb : &[Exp] = match b { Some(Some((_, lb))) => lb, _ => &[] };
+                let mut v = Vec::new();
+                v.pushBack(Self::Symbol(ho.clone()));
+
+                let mut keys = Vec::new();
+                for k in lo.iter().chain(lt.iter()).chain(lb.iter()).filter_map(|e| e.entryKey()) {
+                    if !keys.asArray().iter().any(|x: &String| x == k) { keys.pushBack(k.clone()) }
+                }
+                for k in keys.asArray().iter() {
+                    let p = path.child(PathSeg::Key(k.clone()));
+                    if let Some(e) = Self::merge3At(&p, Self::findEntry(lb, k), Self::findEntry(lo, k), Self::findEntry(lt, k), conflicts) {
+                        v.pushBack(e);
+                    }
+                }
+                return Some(Self::List(v))
+            },
+            _ => ()
+        }
+
+        match (base, o, t) {
+            (Some(Self::List(lb)), Self::List(lo), Self::List(lt)) => {
+                // the value of a `(key value)` leaf is at the path of the entry
+                let leaf = !core::ptr::eq(o.entryValue(), o);
+                let mut v = Vec::new();
+                for f in Self::mergeSeq(path, lb.asArray(), lo.asArray(), lt.asArray(), leaf, conflicts).into_iter() {
+                    v.pushBack(match f {
+                        MergedForm::Ours(i) => lo[i].clone(),
+                        MergedForm::Theirs(j) => lt[j].clone(),
+                        MergedForm::Merged(e) => e,
+                    });
+                }
+                Some(Self::List(v))
+            },
+            _ => Some(Self::conflict(path, base, ours, theirs, conflicts))
+        }
+    }
+
+    /// three way merge of `ours` and `theirs` against their common ancestor `base`
+    pub fn merge3(base: &Exp, ours: &Exp, theirs: &Exp) -> Merge {
+        let mut conflicts = Vec::new();
+        // like a query, a lone entry is the top level node
+        let mut path = Path::new();
+        if let Some(k) = ours.entryKey() { path.push(PathSeg::Key(k.clone())) }
+        let merged = match Self::merge3At(&path, Some(base), Some(ours), Some(theirs), &mut conflicts) {
+            Some(e) => e,
+            None => Self::List(Vec::new())
+        };
+        Merge { merged, conflicts }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Exp {
+        match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testMerge3() {
+        let base = parse("(server (port 80) (host \"a\") (workers 4) (log info))");
+        let ours = parse("(server (port 8080) (host \"a\") (workers 4) (log info))");
+        let theirs = parse("(server (port 80) (host \"b\") (log info) (tls #t))");
+        let m = Exp::merge3(&base, &ours, &theirs);
+        assert!(m.isClean());
+        assert!(m.merged.toString() == "(server (port 8080) (host \"b\") (log info) (tls #t))");
+
+        let theirs = parse("(server (port 9090) (host \"a\") (log info))");
+        let m = Exp::merge3(&base, &ours, &theirs);
+        assert!(m.conflicts.len() == 1);
+        assert!(m.conflicts[0].path.toString() == "server.port");
+        assert!(m.merged.toString() == "(server (port (<<<<<<< 8080 ||||||| 80 ======= 9090 >>>>>>>)) (host \"a\") (log info))");
+
+        let text = m.toConflictString();
+        assert!(text == "(server (port (<<<<<<<\n8080\n|||||||\n80\n=======\n9090\n>>>>>>>)) (host \"a\") (log info))");
+        assert!(parse(text.toStr()) == m.merged);
+    }
+
+    #[test]
+    fn testMerge3Positional() {
+        let m = Exp::merge3(&parse("(f 1 2 3)"), &parse("(f 1 20 3)"), &parse("(f 1 2 30)"));
+        assert!(m.isClean() && m.merged.toString() == "(f 1 20 30)");
+
+        let m = Exp::merge3(&parse("(f 1)"), &parse("(f 1 2)"), &parse("(f 0)"));
+        assert!(m.conflicts.len() == 1 && m.conflicts[0].path.toString() == "f.1");
+
+        // insertions and deletions on different sides
+        let m = Exp::merge3(&parse("(f a b c d)"), &parse("(f x a b c d)"), &parse("(f a b d y)"));
+        assert!(m.isClean() && m.merged.toString() == "(f x a b d y)");
+        let m = Exp::merge3(&parse("(f a b c)"), &parse("(f a q r c)"), &parse("(f a s c)"));
+        assert!(m.conflicts.len() == 1 && m.conflicts[0].path.toString() == "f.2");
+        assert!(m.merged.toString() == "(f a (<<<<<<< q r ||||||| b ======= s >>>>>>>) c)");
+    }
+
+    #[test]
+    fn testMerge3Forms() {
+        let forms = |s: &str| match parse(&std::format!("({})", s)) { Exp::List(l) => l, _ => Vec::new() };
+        let (b, o, t) = (forms("(a 1) (b 2)"), forms("(a 1) (b 2) (c 3)"), forms("(a 1) (b 20)"));
+        let m = Exp::merge3Forms(b.asArray(), o.asArray(), t.asArray());
+        assert!(m.isClean() && m.forms.asArray() == [MergedForm::Ours(0), MergedForm::Theirs(1), MergedForm::Ours(2)]);
+
+        let (b, o, t) = (forms("1 2 3"), forms("0 1 2 3"), forms("1 3 (x)"));
+        let m = Exp::merge3Forms(b.asArray(), o.asArray(), t.asArray());
+        assert!(m.isClean() && m.forms.asArray() == [MergedForm::Ours(0), MergedForm::Ours(1), MergedForm::Ours(3), MergedForm::Theirs(2)]);
+
+        let (b, o, t) = (forms("(a 1)"), forms("(a 2)"), forms("(a 3)"));
+        let m = Exp::merge3Forms(b.asArray(), o.asArray(), t.asArray());
+        assert!(m.conflicts.len() == 1 && m.conflicts[0].path.toString() == "a");
+    }
+}
@@ -0,0 +1,136 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// parser metrics: a `ParserMetrics` sink shared through `ReaderOptions::metrics`
+// accumulates the bytes read, the parsed documents, the errors by kind and the
+// deepest nesting seen, `toPrometheus` renders them in the text exposition format
+//
+use alt_std::*;
+use alt_std::{format};
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    // the input ended inside a form
+    Eof,
+    // unexpected character
+    Syntax,
+    // malformed or overflowing number
+    Number,
+    // invalid string escape
+    Escape,
+    // invalid character literal
+    Char,
+}
+
+const KINDS : [ErrorKind; 5] = [ErrorKind::Eof, ErrorKind::Syntax, ErrorKind::Number, ErrorKind::Escape, ErrorKind::Char];
+
+impl ErrorKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Eof => "eof",
+            Self::Syntax => "syntax",
+            Self::Number => "number",
+            Self::Escape => "escape",
+            Self::Char => "char",
+        }
+    }
+}
+
+pub struct ParserMetrics {
+    bytes       : AtomicU64,
+    documents   : AtomicU64,
+    errors      : [AtomicU64; 5],
+    maxDepth    : AtomicUsize,
+}
+
+impl ParserMetrics {
+    pub fn new() -> Self {
+        Self {
+            bytes       : AtomicU64::new(0),
+            documents   : AtomicU64::new(0),
+            errors      : [AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0), AtomicU64::new(0)],
+            maxDepth    : AtomicUsize::new(0),
+        }
+    }
+
+    pub fn bytes(&self) -> u64 { self.bytes.load(Ordering::Relaxed) }
+    pub fn documents(&self) -> u64 { self.documents.load(Ordering::Relaxed) }
+    pub fn errors(&self, kind: ErrorKind) -> u64 { self.errors[kind as usize].load(Ordering::Relaxed) }
+    pub fn maxDepth(&self) -> usize { self.maxDepth.load(Ordering::Relaxed) }
+
+    pub(crate) fn record(&self, bytes: usize, err: Option<&ParseError>, depth: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::Relaxed);
+        match err {
+            None => { self.documents.fetch_add(1, Ordering::Relaxed); },
+            Some(e) => { self.errors[e.kind() as usize].fetch_add(1, Ordering::Relaxed); },
+        }
+        self.maxDepth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    /// Prometheus text exposition, every metric name starts with `prefix`
+    pub fn toPrometheus(&self, prefix: &str) -> String {
+        let mut s = String::new();
+        s.append(&format!("# TYPE {}_bytes_total counter\n{}_bytes_total {}\n", prefix, prefix, self.bytes()));
+        s.append(&format!("# TYPE {}_documents_total counter\n{}_documents_total {}\n", prefix, prefix, self.documents()));
+        s.append(&format!("# TYPE {}_errors_total counter\n", prefix));
+        for k in KINDS.iter() {
+            s.append(&format!("{}_errors_total{{kind=\"{}\"}} {}\n", prefix, k.name(), self.errors(*k)));
+        }
+        s.append(&format!("# TYPE {}_max_depth gauge\n{}_max_depth {}\n", prefix, prefix, self.maxDepth()));
+        s
+    }
+}
+
+impl Default for ParserMetrics {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn testParserMetrics() {
+        let metrics = Arc::new(ParserMetrics::new());
+        let mut opts = ReaderOptions::new();
+        opts.metrics = Some(metrics.clone());
+        for src in ["(a (b (c)))", "(x 1)", "(a", "(\"\\q\")", "12z", "#u8\"abc\""].iter() {
+            let _ = Exp::fromSExpWithOptions(src.as_bytes(), &opts);
+        }
+        for form in FormReader::withOptions(&b"(a) ((b))"[..], opts.clone()) {
+            assert!(match form { PROk(_) => true, PRErr(_) => false });
+        }
+
+        assert!(metrics.documents() == 4);
+        assert!(metrics.errors(ErrorKind::Eof) == 1);
+        assert!(metrics.errors(ErrorKind::Escape) == 1);
+        assert!(metrics.errors(ErrorKind::Number) == 1);
+        // the kind is set where the error is made, not guessed from "number" in the message
+        assert!(metrics.errors(ErrorKind::Syntax) == 1);
+        assert!(metrics.maxDepth() == 3);
+        assert!(metrics.bytes() == 11 + 5 + 2 + 6 + 3 + 8 + 3 + 5);
+        let text = metrics.toPrometheus("sexp");
+        assert!(text.toStr().contains("sexp_errors_total{kind=\"eof\"} 1\n"));
+        assert!(text.toStr().contains("sexp_documents_total 4\n"));
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// mutation based negative tests: corrupt valid inputs in targeted ways and
+// check that the parser reports an error at a sensible offset (and never panics)
+//
+use alt_std::*;
+use crate::*;
+
+pub const CORPUS : [&str; 6] = [
+    "(abcd 123 abc)",
+    "(define (square x) (* x x))",
+    "(server (port 8080) (host \"localhost\") (ratio -1.5e3))",
+    "((a b) (c (d (e \"f g\"))) h)",
+    "(\"(not a list)\" \"\" x)",
+    "(+ -12 +3 4.5 #t)",
+];
+
+pub enum Mutation {
+    // drop the n-th closing paren
+    DropClose(usize),
+    // drop the n-th opening paren
+    DropOpen(usize),
+    // cut the input right after the n-th string quote
+    TruncateString(usize),
+    // cut the input at the given byte
+    Truncate(usize),
+    // insert an invalid escape right after the n-th string quote
+    BadEscape(usize),
+}
+
+// xorshift, good enough to pick mutation sites deterministically
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 { 0 } else { (self.next() % n as u64) as usize }
+    }
+}
+
+// parens inside string literals are not structural, skip them
+fn isSite(src: &[u8], i: usize, c: u8) -> bool {
+    src[i] == c && (c == b'"' || count(&src[..i], b'"') & 1 == 0)
+}
+
+fn count(src: &[u8], c: u8) -> usize {
+    (0..src.len()).filter(|i| isSite(src, *i, c)).count()
+}
+
+fn nth(src: &[u8], c: u8, n: usize) -> usize {
+    let mut seen = 0;
+    for i in 0..src.len() {
+        if isSite(src, i, c) {
+            if seen == n { return i }
+            seen += 1;
+        }
+    }
+    src.len()
+}
+
+pub fn mutate(src: &[u8], m: &Mutation) -> Vec<u8> {
+    let mut out = Vec::new();
+    if let Mutation::BadEscape(n) = m {
+        let at = nth(src, b'"', *n) + 1;
+        for i in 0..src.len() {
+            if i == at { out.pushBack(b'\\'); out.pushBack(b'q'); }
+            out.pushBack(src[i]);
+        }
+        return out
+    }
+
+    let (skip, end) = match m {
+        Mutation::DropClose(n) => (nth(src, b')', *n), src.len()),
+        Mutation::DropOpen(n) => (nth(src, b'(', *n), src.len()),
+        Mutation::TruncateString(n) => (src.len(), usize::min(nth(src, b'"', *n) + 1, src.len())),
+        Mutation::Truncate(n) => (src.len(), *n),
+        Mutation::BadEscape(_) => (src.len(), src.len()),
+    };
+
+    for i in 0..end {
+        if i != skip { out.pushBack(src[i]) }
+    }
+    out
+}
+
+pub fn randomMutation(src: &[u8], rng: &mut Rng) -> Mutation {
+    loop {
+        match rng.below(5) {
+            0 if count(src, b')') > 0 => return Mutation::DropClose(rng.below(count(src, b')'))),
+            1 if count(src, b'(') > 0 => return Mutation::DropOpen(rng.below(count(src, b'('))),
+            2 if count(src, b'"') > 0 => return Mutation::TruncateString(rng.below(count(src, b'"') / 2) * 2),
+            3 => return Mutation::Truncate(rng.below(src.len())),
+            4 if count(src, b'"') > 0 => return Mutation::BadEscape(rng.below(count(src, b'"') / 2) * 2),
+            _ => ()
+        }
+    }
+}
+
+// syntax heavy fragments glued at random, with a random byte now and then
+const FRAGMENTS : [&str; 40] = [
+    "(", ")", "[", "]", "{", "}", " ", "\n", "\"", "\\", "#", "#\\", "#x", "#u8(", "#u8\"", "#(", "#|", "|#", "#;", ";",
+    "'", "`", ",@", ",", "|", ".", "1", "-", "/", "e", "9223372036854775808", "a", "λ", "\"\"\"\n", ":", "$1", "def", "ref", "\\u{", "}",
+];
+
+pub fn randomInput(rng: &mut Rng) -> Vec<u8> {
+    let mut out = Vec::new();
+    for _ in 0..rng.below(24) {
+        if rng.below(8) == 0 {
+            out.pushBack(rng.next() as u8);
+        } else {
+            for c in FRAGMENTS[rng.below(FRAGMENTS.len())].as_bytes().iter() { out.pushBack(*c) }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every reader and printer entry point on `src`, none may panic
+    fn exercise(src: &[u8], opts: &[ReaderOptions]) {
+        let _ = Exp::fromSExpPrefix(src);
+        let _ = Exp::splitBalanced(src, 4);
+        let _ = Dialect::detect(src);
+        let _ = Router::new().otherwise(|_, _| ()).route(src);
+        for _ in FormReader::new(src).take(32) {}
+        for o in opts.iter() {
+            let _ = Exp::fromSExpTraced(src, o);
+            if let PROk(e) = Exp::fromSExpWithOptions(src, o) {
+                let text = e.toString();
+                assert!(Exp::fromSExp(text.asArray()) == PROk(e.clone()) || e.findFirst(|x| matches!(x, Exp::Float(f) if f.is_nan())).is_some());
+                let _ = (e.toPrettyString(20), e.toPrettyStringBudgeted(16), e.toHtml(), e.toRustTokens(), e.toStaticRustTokens());
+                let _ = (e.serializedLen(&PrintOptions::new()), e.writeInto(&mut [0u8; 32]), HashedExp::new(e.clone()).hash());
+                let _ = (e.flatten(), e.diff(&Exp::List(Vec::new())).apply(&e), Exp::merge3(&e, &e, &Exp::Int(0)), e.similarity(&e));
+                let s = Schema::infer(core::slice::from_ref(&e));
+                let _ = (s.validate(&e), s.compatibleWith(&s), Schema::fromExp(&e), e.totalCmp(&e));
+                #[cfg(feature = "archive")]
+                assert!(ArchivedExp::open(e.toArchive().asArray()).and_then(|a| a.toExp()).is_some());
+            }
+        }
+    }
+
+    #[test]
+    fn testNoPanics() {
+        let mut opts = std::vec::Vec::new();
+        opts.push(ReaderOptions::new());
+        let mut o = ReaderOptions::new();
+        o.quasiquoteSugar = true;
+        o.expandRefs = true;
+        o.graphemeChars = true;
+        o.maxAtomLen = Some(3);
+        o.intOverflow = IntOverflow::Error;
+        opts.push(o);
+        let mut o = Dialect::Clojure.readerOptions();
+        o.strictBrackets = false;
+        o.pipeSymbols = false;
+        opts.push(o);
+
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        for _ in 0..4000 {
+            let src = randomInput(&mut rng);
+            exercise(src.asArray(), &opts);
+        }
+        for src in CORPUS.iter() {
+            for cut in 0..src.len() { exercise(&src.as_bytes()[..cut], &opts) }
+        }
+    }
+
+    #[test]
+    fn testMutations() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        for src in CORPUS.iter() {
+            let src = src.as_bytes();
+            assert!(match Exp::fromSExp(src) { PROk(_) => true, _ => false });
+
+            for _ in 0..64 {
+                let m = randomMutation(src, &mut rng);
+                let bad = mutate(src, &m);
+                let res = Exp::fromSExp(bad.asArray());
+                match (&m, res) {
+                    // an unterminated form must fail at the end of the input
+                    (Mutation::DropClose(_), PRErr(err)) |
+                    (Mutation::TruncateString(_), PRErr(err)) |
+                    (Mutation::Truncate(_), PRErr(err)) => assert!(err.offset() == bad.len()),
+                    (Mutation::DropOpen(_), PRErr(err)) => assert!(err.offset() <= bad.len()),
+                    (Mutation::BadEscape(n), PRErr(err)) => assert!(err.offset() == nth(src, b'"', *n) + 1),
+                    (Mutation::DropOpen(_), PROk(_)) => (),
+                    (_, PROk(r)) => panic!("mutated input parsed as {}", r.toString().toStr()),
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// qualified symbols: `ns/name` (`pkg:name` and `pkg::name` in Common Lisp) stay
+// a single Symbol or Keyword, `namespace` and `name` split them. `/` alone and
+// symbols with an empty side (`ns/`) are not qualified
+//
+// namespace policies: symbols qualified with a separator (`net/http`, `net.http.get`) are
+// checked against a policy. Reported are the symbols in a reserved namespace, the
+// ones with an empty segment (`net/`, `a..b`) and the collisions, two spellings of
+// the same qualified name (`net/get` and `net.get`). Symbols made of separators
+// only (`/`, `...`) are not qualified
+//
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone)]
+pub struct NamespacePolicy {
+    // bytes splitting a symbol into segments
+    pub separators  : Vec<u8>,
+    // namespaces user symbols may not use, a reserved `sys` covers `sys.io/x` too
+    pub reserved    : Vec<String>,
+    // symbols of a reserved namespace that are fine anyway
+    pub allowed     : Vec<String>,
+}
+
+impl NamespacePolicy {
+    pub fn new() -> Self {
+        let mut separators = Vec::new();
+        separators.pushBack(b'/');
+        separators.pushBack(b'.');
+        Self {
+            separators,
+            reserved    : Vec::new(),
+            allowed     : Vec::new(),
+        }
+    }
+
+    pub fn reserve(&mut self, ns: &str) {
+        self.reserved.pushBack(String::from(ns));
+    }
+
+    pub fn allow(&mut self, s: &str) {
+        self.allowed.pushBack(String::from(s));
+    }
+
+    fn isSeparator(&self, c: u8) -> bool {
+        self.separators.asArray().contains(&c)
+    }
+
+    fn segments<'s>(&self, s: &'s str) -> std::vec::Vec<&'s str> {
+        s.split(|c: char| c.is_ascii() && self.isSeparator(c as u8)).collect()
+    }
+
+    fn checkSymbol(&self, s: &String, path: &Path, seen: &mut Vec<(String, String)>, errs: &mut Vec<String>) {
+        let b = s.asArray();
+        if !b.iter().any(|c| self.isSeparator(*c)) || b.iter().all(|c| self.isSeparator(*c)) { return }
+        let segs = self.segments(s.toStr());
+        if segs.iter().any(|g| g.is_empty()) {
+            return errs.pushBack(alt_std::format!("{}: {} has an empty namespace segment", path.toString().toStr(), s.toStr()))
+        }
+        let ns = &segs[..segs.len() - 1];
+        let reserved = self.reserved.asArray().iter().find(|r| {
+            let r = self.segments(r.toStr());
+            ns.starts_with(&r)
+        });
+        if let Some(r) = reserved {
+            if !self.allowed.asArray().iter().any(|a| a == s) {
+                errs.pushBack(alt_std::format!("{}: {} uses the reserved namespace {}", path.toString().toStr(), s.toStr(), r.toStr()));
+            }
+        }
+        let name = String::from(segs.join("/").as_str());
+        match seen.asArray().iter().find(|(n, _)| *n == name) {
+            Some((_, first)) if first != s =>
+                errs.pushBack(alt_std::format!("{}: {} collides with {}", path.toString().toStr(), s.toStr(), first.toStr())),
+            Some(_) => (),
+            None => seen.pushBack((name, s.clone())),
+        }
+    }
+
+    fn checkIn(&self, e: &Exp, path: &mut Path, seen: &mut Vec<(String, String)>, errs: &mut Vec<String>) {
+        let mut child = |e: &Exp, seg: PathSeg, seen: &mut Vec<(String, String)>, errs: &mut Vec<String>| {
+            path.push(seg);
+            self.checkIn(e, path, seen, errs);
+            path.pop();
+        };
+        match e {
+            Exp::Symbol(s) => self.checkSymbol(s, path, seen, errs),
+            Exp::List(l) | Exp::Vector(l) => for (i, e) in l.asArray().iter().enumerate() { child(e, PathSeg::Index(i), seen, errs) },
+            Exp::Pair(a, d) => {
+                child(a, PathSeg::Index(0), seen, errs);
+                child(d, PathSeg::Index(1), seen, errs);
+            },
+            // key i at 2i and its value at 2i + 1, as in `Exp::child`
+            Exp::Map(m) => for (i, (k, v)) in m.asArray().iter().enumerate() {
+                child(k, PathSeg::Index(2 * i), seen, errs);
+                child(v, PathSeg::Index(2 * i + 1), seen, errs);
+            },
+            _ => ()
+        }
+    }
+
+    /// every violation in `forms` as `path: message`, paths start with the index
+    /// of the form, collisions are found across forms
+    pub fn check(&self, forms: &[Exp]) -> Result<(), Vec<String>> {
+        let (mut seen, mut errs) = (Vec::new(), Vec::new());
+        for (i, f) in forms.iter().enumerate() {
+            self.checkIn(f, &mut Path::new().child(PathSeg::Index(i)), &mut seen, &mut errs);
+        }
+        if errs.len() == 0 { Ok(()) } else { Err(errs) }
+    }
+}
+
+impl Default for NamespacePolicy {
+    fn default() -> Self { Self::new() }
+}
+
+impl Exp {
+    // (namespace, name) of a symbol or keyword
+    fn qualifiedParts(&self, dialect: Dialect) -> Option<(Option<&str>, &str)> {
+        let s = match self { Self::Symbol(s) | Self::Keyword(s) => s.toStr(), _ => return None };
+        let sep = dialect.namespaceSeparator();
+        match s.find(sep) {
+            Some(i) if i > 0 => {
+                let name = &s[i + sep.len()..];
+                // pkg::internal
+                let name = if dialect == Dialect::CommonLisp { name.strip_prefix(sep).unwrap_or(name) } else { name };
+                if name.is_empty() { Some((None, s)) } else { Some((Some(&s[..i]), name)) }
+            },
+            _ => Some((None, s)),
+        }
+    }
+
+    /// `ns` of a symbol or keyword written `ns/name`, None when unqualified
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespaceIn(Dialect::Scheme)
+    }
+
+    /// the symbol or keyword without its namespace
+    pub fn name(&self) -> Option<&str> {
+        self.nameIn(Dialect::Scheme)
+    }
+
+    pub fn namespaceIn(&self, dialect: Dialect) -> Option<&str> {
+        self.qualifiedParts(dialect).and_then(|(ns, _)| ns)
+    }
+
+    pub fn nameIn(&self, dialect: Dialect) -> Option<&str> {
+        self.qualifiedParts(dialect).map(|(_, n)| n)
+    }
+
+    /// the symbol `name` in `ns`, spelled the way of `dialect`
+    pub fn qualifiedSymbol(ns: &str, name: &str, dialect: Dialect) -> Exp {
+        let mut s = String::from(ns);
+        s.append(dialect.namespaceSeparator());
+        s.append(name);
+        Self::Symbol(s)
+    }
+
+    /// `NamespacePolicy::check` of this tree alone, paths start at its root
+    pub fn checkNamespaces(&self, policy: &NamespacePolicy) -> Result<(), Vec<String>> {
+        let (mut seen, mut errs) = (Vec::new(), Vec::new());
+        policy.checkIn(self, &mut Path::new(), &mut seen, &mut errs);
+        if errs.len() == 0 { Ok(()) } else { Err(errs) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testNamespacePolicy() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut policy = NamespacePolicy::new();
+        policy.reserve("sys");
+        policy.allow("sys/print");
+        let e = parse("(app (sys/print net/get) (sys.io/read net.get) (/ 1 2) ... (ui/ x) {net/get 1})");
+        let errs = match e.checkNamespaces(&policy) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs.len() == 3);
+        assert!(errs[0] == "2.0: sys.io/read uses the reserved namespace sys");
+        assert!(errs[1] == "2.1: net.get collides with net/get");
+        assert!(errs[2] == "5.0: ui/ has an empty namespace segment");
+
+        let docs = [parse("(a/b c)"), parse("(a.b)")];
+        match policy.check(&docs) {
+            Err(errs) => assert!(errs.len() == 1 && errs[0] == "1.0: a.b collides with a/b"),
+            Ok(()) => panic!("expected a collision")
+        }
+        assert!(parse("(net/get (sys/print 1))").checkNamespaces(&policy).is_ok());
+
+        // map keys and values are addressed as children 2i and 2i + 1, `Exp::at` reaches them
+        let m = parse("(a/b {x 1 y a.b})");
+        match m.checkNamespaces(&policy) {
+            Err(errs) => assert!(errs.len() == 1 && errs[0] == "1.3: a.b collides with a/b"),
+            Ok(()) => panic!("expected a collision")
+        }
+        let at = Path::new().child(PathSeg::Index(1)).child(PathSeg::Index(3));
+        assert!(m.at(&at) == Some(&Exp::Symbol(String::from("a.b"))));
+    }
+
+    #[test]
+    fn testQualifiedSymbols() {
+        let e = match Exp::fromSExp(b"(net/get :db/id / ns/ plain \"a/b\")") { PROk(Exp::List(l)) => l, _ => panic!("expected a list") };
+        assert!(e[0].namespace() == Some("net") && e[0].name() == Some("get"));
+        assert!(e[1] == Exp::Keyword(String::from("db/id")) && e[1].namespace() == Some("db") && e[1].name() == Some("id"));
+        assert!(e[2].namespace().is_none() && e[2].name() == Some("/"));
+        assert!(e[3].namespace().is_none() && e[3].name() == Some("ns/"));
+        assert!(e[4].namespace().is_none() && e[4].name() == Some("plain"));
+        assert!(e[5].name().is_none());
+
+        let cl = Exp::qualifiedSymbol("cl-user", "foo", Dialect::CommonLisp);
+        assert!(cl.toString() == "cl-user:foo" && cl.namespaceIn(Dialect::CommonLisp) == Some("cl-user"));
+        let internal = Exp::Symbol(String::from("pkg::bar"));
+        assert!(internal.namespaceIn(Dialect::CommonLisp) == Some("pkg") && internal.nameIn(Dialect::CommonLisp) == Some("bar"));
+        assert!(Exp::qualifiedSymbol("clojure.core", "map", Dialect::Clojure).toString() == "clojure.core/map");
+    }
+}
@@ -0,0 +1,151 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// node identifiers: a node is named by its index path and its structural hash,
+// both survive reformatting and re-parsing, so external data (review comments,
+// folded UI state, ...) keyed by them stays attached as long as the node and
+// its position are unchanged
+//
+use alt_std::*;
+use alt_std::hash::murmurHash64A;
+use crate::*;
+
+const NODE_SEED : u64 = 0xbb67_ae85;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Debug)]
+pub struct NodeId(pub u64);
+
+impl NodeId {
+    fn new(path: &Path, hash: u64) -> Self {
+        let mut buff = Vec::new();
+        for c in path.toString().asArray().iter() { buff.pushBack(*c) }
+        buff.pushBack(0);
+        for c in hash.to_le_bytes().iter() { buff.pushBack(*c) }
+        Self(murmurHash64A(buff.asArray(), NODE_SEED))
+    }
+
+    /// 16 lowercase hex digits
+    pub fn toString(&self) -> String {
+        alt_std::format!("{:016x}", self.0)
+    }
+}
+
+impl Exp {
+    /// id of the node at an index path, None when there is no such node
+    pub fn nodeId(&self, path: &Path) -> Option<NodeId> {
+        self.at(path).map(|e| NodeId::new(path, e.hashSubtrees(&mut |_, _, _| ()).0))
+    }
+
+    fn collectIds(&self, path: &mut Path, out: &mut Vec<(Path, NodeId)>) -> u64 {
+        let slot = out.len();
+        out.pushBack((path.clone(), NodeId(0)));
+        let mut hs = std::vec::Vec::new();
+        for i in 0..self.childCount() {
+            if let Some(c) = self.child(i) {
+                path.push(PathSeg::Index(i));
+                hs.push(c.collectIds(path, out));
+                path.pop();
+            }
+        }
+
+        // same bytes as `hashSubtrees`, reusing the children hashes
+        let mut buff = Vec::new();
+        let tag = |buff: &mut Vec<u8>, t: u8, payload: &[u8]| {
+            buff.pushBack(t);
+            for c in payload.iter() { buff.pushBack(*c) }
+        };
+        let hash = match self {
+            Self::List(_) | Self::Vector(_) => {
+                tag(&mut buff, if let Self::List(_) = self { 6 } else { 13 }, &hs.len().to_le_bytes());
+                for h in hs.iter() { tag(&mut buff, 7, &h.to_le_bytes()) }
+                murmurHash64A(buff.asArray(), HASH_SEED)
+            },
+            Self::Pair(..) => {
+                for h in hs.iter() { tag(&mut buff, 12, &h.to_le_bytes()) }
+                murmurHash64A(buff.asArray(), HASH_SEED)
+            },
+            Self::Map(_) => {
+                tag(&mut buff, 8, &(hs.len() / 2).to_le_bytes());
+                let mut entries = 0u64;
+                for kv in hs.chunks(2) {
+                    let mut pair = Vec::new();
+                    for h in kv.iter() { tag(&mut pair, 9, &h.to_le_bytes()) }
+                    entries = entries.wrapping_add(murmurHash64A(pair.asArray(), HASH_SEED));
+                }
+                tag(&mut buff, 10, &entries.to_le_bytes());
+                murmurHash64A(buff.asArray(), HASH_SEED)
+            },
+            _ => self.hashSubtrees(&mut |_, _, _| ()).0,
+        };
+        out[slot].1 = NodeId::new(path, hash);
+        hash
+    }
+
+    /// every node with its id, in pre-order, computed in a single pass
+    pub fn nodeIds(&self) -> Vec<(Path, NodeId)> {
+        let mut out = Vec::new();
+        self.collectIds(&mut Path::new(), &mut out);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testNodeIds() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = parse("(defn f (x) (g x) (g x))");
+        let b = parse("(defn  f\n  (x)\n  (g x)\n  (g x))");
+        let ids = a.nodeIds();
+        assert!(ids.len() == 11);
+        for (p, id) in ids.asArray().iter() {
+            assert!(a.nodeId(p) == Some(*id) && b.nodeId(p) == Some(*id));
+        }
+        // same content at another place, or other content at the same place
+        assert!(a.nodeId(&Path::parse("3")) != a.nodeId(&Path::parse("4")));
+        let c = parse("(defn f (x) (h x) (g x))");
+        assert!(c.nodeId(&Path::parse("3")) != a.nodeId(&Path::parse("3")));
+        assert!(c.nodeId(&Path::parse("4")) == a.nodeId(&Path::parse("4")));
+        assert!(a.nodeId(&Path::parse("5")).is_none());
+        assert!(ids[0].1.toString().asArray().len() == 16);
+
+        // children of vectors, maps and pairs get ids too
+        let mut opts = ReaderOptions::new();
+        opts.braces = true;
+        let d = match Exp::fromSExpWithOptions(b"(a #(1 (g x)) {k (g x)} ((g x) . y))", &opts) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let ids = d.nodeIds();
+        assert!(ids.len() == 17);
+        for (p, id) in ids.asArray().iter() {
+            assert!(d.nodeId(p) == Some(*id));
+        }
+        for p in ["1.1", "2.1", "3.0"].iter() {
+            assert!(d.at(&Path::parse(p)) == Some(&parse("(g x)")));
+            assert!(ids.asArray().iter().any(|(q, _)| q.toString() == *p));
+        }
+    }
+}
@@ -0,0 +1,240 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use core::convert::TryFrom;
+use crate::*;
+
+//
+// numeric tower: Int op Int stays Int (None on overflow or division by zero)
+// but for an uneven division which is a Ratio, an operation with a UInt, a Ratio
+// or a BigInt within the i128 range stays exact (reduced, an Int when whole, a
+// BigInt with the `bigint` feature or else None when it does not fit), anything involving a Float or a larger BigInt is
+// computed in Float
+//
+// n0/d0 op n1/d1 as an unreduced n/d, None on overflow
+type ExactOp = fn(i128, i128, i128, i128) -> Option<(i128, i128)>;
+
+#[derive(Clone, PartialEq)]
+pub enum Number {
+    Int(i64),
+    // above i64::MAX, as `Exp::UInt`
+    UInt(u64),
+    // in lowest terms, d > 1, as `Exp::Ratio`
+    Ratio(i64, i64),
+    Float(f64),
+    // out of the i64 and u64 ranges, as `Exp::BigInt`
+    #[cfg(feature = "bigint")]
+    BigInt(BigInt),
+}
+
+impl Number {
+    pub fn toExp(&self) -> Exp {
+        match self {
+            Self::Int(i) => Exp::Int(*i),
+            Self::UInt(u) => Exp::uint(*u),
+            Self::Ratio(n, d) => Exp::Ratio(*n, *d),
+            Self::Float(f) => Exp::Float(*f),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => Exp::bigInt(b.clone()),
+        }
+    }
+
+    pub fn toFloat(&self) -> f64 {
+        match self {
+            Self::Int(i) => *i as f64,
+            Self::UInt(u) => *u as f64,
+            Self::Ratio(n, d) => *n as f64 / *d as f64,
+            Self::Float(f) => *f,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => b.toF64(),
+        }
+    }
+
+    // numerator and denominator of an exact number
+    fn exact(&self) -> Option<(i128, i128)> {
+        match self {
+            Self::Int(i) => Some((*i as i128, 1)),
+            Self::UInt(u) => Some((*u as i128, 1)),
+            Self::Ratio(n, d) => Some((*n as i128, *d as i128)),
+            Self::Float(_) => None,
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => b.toI128().map(|i| (i, 1)),
+        }
+    }
+
+    fn fromExact(n: i128, d: i128) -> Option<Number> {
+        match Exp::reduced(n, d) {
+            Some(e) => e.asNumber(),
+            // whole and out of the i64 range
+            None if d != 0 && n % d == 0 => match u64::try_from(n / d) {
+                Ok(u) => Some(Self::UInt(u)),
+                #[cfg(feature = "bigint")]
+                Err(_) => BigInt::parse(&std::format!("{}", n / d)).map(Self::BigInt),
+                #[cfg(not(feature = "bigint"))]
+                Err(_) => None,
+            },
+            None => None,
+        }
+    }
+
+    // `exact` on the terms of two exact numbers, `float` otherwise
+    fn combine(&self, other: &Number, exact: ExactOp, float: fn(f64, f64) -> f64) -> Option<Number> {
+        match (self.exact(), other.exact()) {
+            (Some((n0, d0)), Some((n1, d1))) => {
+                let (n, d) = exact(n0, d0, n1, d1)?;
+                Self::fromExact(n, d)
+            },
+            _ => Some(Self::Float(float(self.toFloat(), other.toFloat()))),
+        }
+    }
+
+    pub fn checkedAdd(&self, other: &Number) -> Option<Number> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.checked_add(*b).map(Self::Int),
+            (a, b) => a.combine(b, |n0, d0, n1, d1| Some((n0.checked_mul(d1)?.checked_add(n1.checked_mul(d0)?)?, d0.checked_mul(d1)?)), |a, b| a + b),
+        }
+    }
+
+    pub fn checkedSub(&self, other: &Number) -> Option<Number> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.checked_sub(*b).map(Self::Int),
+            (a, b) => a.combine(b, |n0, d0, n1, d1| Some((n0.checked_mul(d1)?.checked_sub(n1.checked_mul(d0)?)?, d0.checked_mul(d1)?)), |a, b| a - b),
+        }
+    }
+
+    pub fn checkedMul(&self, other: &Number) -> Option<Number> {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.checked_mul(*b).map(Self::Int),
+            (a, b) => a.combine(b, |n0, d0, n1, d1| Some((n0.checked_mul(n1)?, d0.checked_mul(d1)?)), |a, b| a * b),
+        }
+    }
+
+    /// exact integer division stays Int, otherwise the quotient of two Ints is a
+    /// reduced Ratio, `1/3*3` is 1
+    pub fn checkedDiv(&self, other: &Number) -> Option<Number> {
+        match (self, other) {
+            (Self::Int(_) | Self::UInt(_) | Self::Ratio(..), Self::Int(0)) => None,
+            #[cfg(feature = "bigint")]
+            (Self::BigInt(_), Self::Int(0)) => None,
+            (Self::Int(a), Self::Int(b)) => match a.checked_rem(*b) {
+                Some(0) => a.checked_div(*b).map(Self::Int),
+                Some(_) => Self::fromExact(*a as i128, *b as i128),
+                None => None,
+            },
+            (a, b) => a.combine(b, |n0, d0, n1, d1| Some((n0.checked_mul(d1)?, d0.checked_mul(n1)?)), |a, b| a / b),
+        }
+    }
+
+    pub fn checkedNeg(&self) -> Option<Number> {
+        match self {
+            Self::Int(i) => i.checked_neg().map(Self::Int),
+            Self::UInt(u) => Self::fromExact(-(*u as i128), 1),
+            Self::Ratio(n, d) => n.checked_neg().map(|n| Self::Ratio(n, *d)),
+            Self::Float(f) => Some(Self::Float(-f)),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => Exp::bigInt(BigInt::fromLimbs(!b.isNegative(), b.limbs())).asNumber(),
+        }
+    }
+}
+
+impl Exp {
+    pub fn asNumber(&self) -> Option<Number> {
+        match self {
+            Self::Int(i) => Some(Number::Int(*i)),
+            Self::UInt(u) => Some(Number::UInt(*u)),
+            Self::Ratio(n, d) => Some(Number::Ratio(*n, *d)),
+            Self::Float(f) => Some(Number::Float(*f)),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => Some(Number::BigInt(b.clone())),
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testNumberArithmetic() {
+        let a = Exp::Int(7).asNumber().unwrap();
+        let b = Exp::Int(2).asNumber().unwrap();
+        assert!(a.checkedAdd(&b) == Some(Number::Int(9)));
+        assert!(a.checkedDiv(&b) == Some(Number::Ratio(7, 2)));
+        assert!(Number::Int(1).checkedDiv(&Number::Int(3)).and_then(|t| t.checkedMul(&Number::Int(3))) == Some(Number::Int(1)));
+        assert!(Number::Int(4).checkedDiv(&Number::Int(-6)) == Some(Number::Ratio(-2, 3)));
+        assert!(Number::Int(8).checkedDiv(&b) == Some(Number::Int(4)));
+        assert!(a.checkedDiv(&Number::Int(0)).is_none());
+        assert!(Number::Int(i64::MAX).checkedAdd(&Number::Int(1)).is_none());
+        assert!(Number::Int(i64::MIN).checkedDiv(&Number::Int(-1)).is_none());
+        assert!(a.checkedMul(&Number::Float(0.5)) == Some(Number::Float(3.5)));
+        assert!(Exp::Symbol(alt_std::String::from("x")).asNumber().is_none());
+        assert!(Number::Int(3).toExp() == Exp::Int(3));
+    }
+
+    #[test]
+    fn testRatioArithmetic() {
+        let third = Exp::ratio(1, 3).and_then(|e| e.asNumber()).unwrap();
+        assert!(third == Number::Ratio(1, 3) && third.toExp() == Exp::Ratio(1, 3));
+        assert!(third.checkedAdd(&third) == Some(Number::Ratio(2, 3)));
+        assert!(third.checkedMul(&Number::Int(3)) == Some(Number::Int(1)));
+        assert!(Number::Int(1).checkedSub(&third) == Some(Number::Ratio(2, 3)));
+        assert!(third.checkedDiv(&Number::Int(2)) == Some(Number::Ratio(1, 6)));
+        assert!(third.checkedDiv(&Number::Int(0)).is_none());
+        assert!(third.checkedNeg() == Some(Number::Ratio(-1, 3)));
+        assert!(third.checkedAdd(&Number::Float(0.5)).map(|n| n.toFloat()) == Some(1.0 / 3.0 + 0.5));
+        // terms that do not fit an i64
+        assert!(Number::Ratio(1, i64::MAX).checkedMul(&Number::Ratio(1, 2)).is_none());
+    }
+
+    #[test]
+    fn testUIntArithmetic() {
+        let big = Exp::uint(u64::MAX).asNumber().unwrap();
+        assert!(big == Number::UInt(u64::MAX) && big.toExp() == Exp::UInt(u64::MAX));
+        assert!(big.checkedSub(&Number::Int(1)) == Some(Number::UInt(u64::MAX - 1)));
+        #[cfg(not(feature = "bigint"))]
+        assert!(big.checkedAdd(&Number::Int(1)).is_none());
+        assert!(big.checkedSub(&big) == Some(Number::Int(0)));
+        assert!(big.checkedDiv(&Number::Int(1)) == Some(big.clone()) && big.checkedDiv(&Number::Int(5)) == Some(Number::Int((u64::MAX / 5) as i64)));
+        // u64::MAX/2 has no i64 numerator
+        assert!(big.checkedDiv(&Number::Int(2)).is_none());
+        assert!(Number::UInt(1 << 63).checkedNeg() == Some(Number::Int(i64::MIN)));
+        assert!(Number::UInt(1 << 63).toExp() == Exp::UInt(1 << 63) && Number::UInt(7).toExp() == Exp::Int(7));
+        assert!(big.checkedMul(&Number::Float(0.5)) == Some(Number::Float(u64::MAX as f64 * 0.5)));
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn testBigIntArithmetic() {
+        let parse = |s: &str| match BigInt::parse(s) { Some(b) => Number::BigInt(b), None => panic!("{}", s) };
+        let big = parse("100000000000000000000");
+        assert!(Exp::bigInt(BigInt::parse("100000000000000000000").unwrap()).asNumber() == Some(big.clone()));
+        // exact within i128, back to a UInt when it fits
+        assert!(big.checkedSub(&parse("99999999999999999999")) == Some(Number::Int(1)));
+        assert!(big.checkedDiv(&Number::Int(10)) == Some(Number::UInt(10_000_000_000_000_000_000)));
+        assert!(big.checkedDiv(&Number::Int(3)).is_none() && big.checkedDiv(&Number::Int(0)).is_none());
+        assert!(big.checkedNeg().map(|n| n.toExp().toString()) == Some(alt_std::String::from("-100000000000000000000")));
+        assert!(parse("-9223372036854775809").checkedNeg() == Some(Number::UInt(9223372036854775809)));
+        assert!(Number::UInt(u64::MAX).checkedAdd(&Number::Int(1)) == Some(parse("18446744073709551616")));
+        // past i128, in Float
+        let huge = parse("1000000000000000000000000000000000000000000");
+        assert!(huge.checkedAdd(&Number::Int(1)) == Some(Number::Float(huge.toFloat() + 1.0)) && huge.toFloat() > 9.9e41);
+        assert!(huge.toExp().toString() == "1000000000000000000000000000000000000000000");
+    }
+}
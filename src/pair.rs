@@ -0,0 +1,141 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// pairs: `(a . b)` reads into `Exp::Pair`, `(a b . c)` into a chain of them.
+// A dotted tail that is a list is spliced, `(a . (b c))` is the list `(a b c)`, so
+// the last cdr of a chain read from text is never a list
+//
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    /// `(car . cdr)`, a list `cdr` gets `car` in front instead
+    pub fn cons(car: Exp, cdr: Exp) -> Exp {
+        match cdr {
+            Self::List(l) => {
+                let mut v = Vec::new();
+                v.pushBack(car);
+                for e in l.asArray().iter() { v.pushBack(e.clone()) }
+                Self::List(v)
+            },
+            cdr => Self::Pair(std::boxed::Box::new(car), std::boxed::Box::new(cdr)),
+        }
+    }
+
+    /// first element of a pair or a non empty list
+    pub fn car(&self) -> Option<&Exp> {
+        match self {
+            Self::Pair(a, _) => Some(a),
+            Self::List(l) => l.asArray().first(),
+            _ => None
+        }
+    }
+
+    /// what follows the first element of a pair or a non empty list
+    pub fn cdr(&self) -> Option<Exp> {
+        match self {
+            Self::Pair(_, d) => Some((**d).clone()),
+            Self::List(l) if l.len() > 0 => {
+                let mut v = Vec::new();
+                for e in l.asArray()[1..].iter() { v.pushBack(e.clone()) }
+                Some(Self::List(v))
+            },
+            _ => None
+        }
+    }
+
+    // the elements of a pair chain and its improper tail, as printed in `(a b . c)`
+    pub(crate) fn dottedItems(&self) -> (std::vec::Vec<&Exp>, Option<&Exp>) {
+        let mut items = std::vec::Vec::new();
+        let mut cur = self;
+        loop {
+            match cur {
+                Self::Pair(a, d) => { items.push(&**a); cur = d },
+                Self::List(l) => { items.extend(l.asArray().iter()); return (items, None) },
+                tail => return (items, Some(tail)),
+            }
+        }
+    }
+
+    // a lone `.` inside a list
+    pub(crate) fn isDot(src: &[u8], offset: usize) -> bool {
+        Self::peek(src, offset) == Some(b'.') && Self::peek(src, offset + 1).map_or(true, Self::isSeparator)
+    }
+
+    // the `. tail)` of a list whose elements before the dot are `cells`
+    pub(crate) fn parseDotted(rd: &mut Reader, src: &[u8], offset: &mut usize, cells: Vec<Exp>, close: u8) -> ParseResult<Exp> {
+        let err = |rd: &mut Reader, kind: ErrorKind, message: &str, offset: usize| {
+            if let Some(t) = &mut rd.trace { t.push(Rule::Error, offset, 0) }
+            PRErr(ParseError { message: String::from(message), offset, kind })
+        };
+        if cells.len() == 0 { return err(rd, ErrorKind::Syntax, "missing datum before . (list)", *offset) }
+        let dot = *offset;
+        *offset += 1;
+        Self::skipWS(src, offset);
+        match Self::peek(src, *offset) {
+            None | Some(b')') | Some(b']') | Some(b'}') => return err(rd, ErrorKind::Syntax, "missing datum after . (list)", dot),
+            _ => ()
+        }
+        let tail = match Self::parseToken(rd, src, offset) {
+            PROk(e) => e,
+            PRErr(e) => return PRErr(e),
+        };
+        Self::skipWS(src, offset);
+        match Self::peek(src, *offset) {
+            Some(c) if c == close || ((c == b')' || c == b']') && !rd.opts.strictBrackets) => {
+                Self::getchar(src, offset);
+                rd.depth -= 1;
+                if let Some(t) = &mut rd.trace { t.push(Rule::ListClose, *offset - 1, 1) }
+            },
+            Some(b')') | Some(b']') => return err(rd, ErrorKind::Syntax, "mismatched bracket (list)", *offset),
+            None => return err(rd, ErrorKind::Eof, "unexpected end of stream (list)", *offset),
+            Some(_) => return err(rd, ErrorKind::Syntax, "more than one datum after . (list)", *offset),
+        }
+        PROk(cells.asArray().iter().rev().fold(tail, |cdr, car| Self::cons(car.clone(), cdr)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testPairs() {
+        let sym = |s: &str| Exp::Symbol(String::from(s));
+        let e = match Exp::fromSExp(b"((a . 1) (b c . 2) (d . (e f)) (g . (h . 3)) (x .y))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(e.toString() == "((a . 1) (b c . 2) (d e f) (g h . 3) (x .y))");
+        match &e {
+            Exp::List(l) => {
+                assert!(l[0] == Exp::cons(sym("a"), Exp::Int(1)) && l[0].car() == Some(&sym("a")) && l[0].cdr() == Some(Exp::Int(1)));
+                assert!(l[1].cdr() == Some(Exp::cons(sym("c"), Exp::Int(2))));
+                assert!(matches!(&l[2], Exp::List(d) if d.len() == 3));
+            },
+            _ => panic!("expected a list")
+        }
+        assert!(match Exp::fromSExp(e.toString().asArray()) { PROk(o) => o == e, _ => false });
+
+        assert!(Exp::fromSExp(b"(. a)") == PRErr(ParseError { message: String::from("missing datum before . (list)"), offset: 1, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"(a .)") == PRErr(ParseError { message: String::from("missing datum after . (list)"), offset: 3, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"(a . b c)") == PRErr(ParseError { message: String::from("more than one datum after . (list)"), offset: 7, kind: ErrorKind::Syntax }));
+    }
+}
@@ -0,0 +1,112 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// parser: a builder over `ReaderOptions` for the dialects that need a handful
+// of switches flipped, `fromSExp` keeps reading with the defaults
+//
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone, Default)]
+pub struct Parser {
+    opts    : ReaderOptions,
+}
+
+impl Parser {
+    pub fn new() -> Self { Self { opts: ReaderOptions::new() } }
+
+    /// starts from the options of `dialect`
+    pub fn forDialect(dialect: Dialect) -> Self { Self { opts: dialect.readerOptions() } }
+
+    pub fn options(&self) -> &ReaderOptions { &self.opts }
+
+    // delimiters
+    pub fn squareBrackets(&mut self, on: bool) -> &mut Self { self.opts.squareBrackets = on; self }
+    pub fn braces(&mut self, on: bool) -> &mut Self { self.opts.braces = on; self }
+    pub fn strictBrackets(&mut self, on: bool) -> &mut Self { self.opts.strictBrackets = on; self }
+    pub fn pipeSymbols(&mut self, on: bool) -> &mut Self { self.opts.pipeSymbols = on; self }
+
+    // atoms
+    pub fn keywords(&mut self, on: bool) -> &mut Self { self.opts.keywords = on; self }
+    pub fn foldCase(&mut self, on: bool) -> &mut Self { self.opts.foldCase = on; self }
+    pub fn wordBooleans(&mut self, on: bool) -> &mut Self { self.opts.wordBooleans = on; self }
+    pub fn intOverflow(&mut self, o: IntOverflow) -> &mut Self { self.opts.intOverflow = o; self }
+
+    // sugar
+    pub fn apostrophe(&mut self, a: Apostrophe) -> &mut Self { self.opts.apostrophe = a; self }
+    pub fn quasiquote(&mut self, on: bool) -> &mut Self { self.opts.quasiquoteSugar = on; self }
+    pub fn datumLabels(&mut self, on: bool) -> &mut Self { self.opts.datumLabels = on; self }
+    pub fn expandRefs(&mut self, on: bool) -> &mut Self { self.opts.expandRefs = on; self }
+    pub fn skipShebang(&mut self, on: bool) -> &mut Self { self.opts.skipShebang = on; self }
+
+    // limits
+    pub fn maxDepth(&mut self, max: Option<usize>) -> &mut Self { self.opts.maxDepth = max; self }
+    pub fn maxAtomLen(&mut self, max: Option<usize>, overflow: AtomOverflow) -> &mut Self {
+        self.opts.maxAtomLen = max;
+        self.opts.atomOverflow = overflow;
+        self
+    }
+
+    /// see `ReaderOptions::addDispatch`
+    pub fn dispatch<F: Fn(&mut ReadStream) -> ParseResult<Exp> + Send + Sync + 'static>(&mut self, c: u8, f: F) -> Result<&mut Self, String> {
+        self.opts.addDispatch(c, f)?;
+        Ok(self)
+    }
+
+    pub fn parse(&self, src: &[u8]) -> ParseResult<Exp> {
+        Exp::fromSExpWithOptions(src, &self.opts)
+    }
+
+    pub fn parseWithWarnings(&self, src: &[u8], warnings: &mut Vec<ParseError>) -> ParseResult<Exp> {
+        Exp::fromSExpWithWarnings(src, &self.opts, warnings)
+    }
+
+    pub fn parseDocument(&self, src: &[u8]) -> ParseResult<Document> {
+        Document::parse(src, &self.opts)
+    }
+
+    /// the top level forms of `input`, one at a time
+    pub fn forms<R: std::io::Read>(&self, input: R) -> FormReader<R> {
+        FormReader::withOptions(input, self.opts.clone())
+    }
+
+    pub fn grammar(&self) -> GrammarDesc { self.opts.grammar() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testParser() {
+        let mut p = Parser::new();
+        p.foldCase(true).keywords(false).squareBrackets(false).maxDepth(Some(2));
+        assert!(match p.parse(b"(Define :Key |Kept| \"Str\" #T)") { PROk(e) => e.toString() == "(define |:key| Kept \"Str\" #t)", _ => false });
+        assert!(match p.parse(b"(:k)") { PROk(Exp::List(l)) => l[0] == Exp::Symbol(String::from(":k")), _ => false });
+        assert!(p.parse(b"(a [b])") == PRErr(ParseError { message: String::from("unexpected character (list)"), offset: 3, kind: ErrorKind::Syntax }));
+        assert!(p.parse(b"(a (b (c)))") == PRErr(ParseError { message: String::from("nesting too deep"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(p.parse(b"(a {b #(c)})") == PRErr(ParseError { message: String::from("nesting too deep"), offset: 6, kind: ErrorKind::Syntax }));
+        assert!(p.grammar().rule("keyword").is_none() && p.grammar().brackets.len() == 2);
+        assert!(match Parser::new().parse(b"(a [b] :k)") { PROk(Exp::List(l)) => l[2] == Exp::Keyword(String::from("k")), _ => false });
+        let mut forms = Parser::forDialect(Dialect::Clojure).braces(false).forms(&b"true {a 1}"[..]);
+        assert!(match forms.next() { Some(PROk(e)) => e == Exp::Bool(true), _ => false });
+        assert!(match forms.next() { Some(PRErr(err)) => err.message.toStr() == "unexpected character (map)", _ => false });
+    }
+}
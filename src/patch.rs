@@ -0,0 +1,366 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+//
+// patches are sequences of edits applied in order, each path (of list
+// indices) refers to the tree as left by the previous edits
+//
+#[derive(Clone, PartialEq)]
+pub enum PatchOp {
+    Replace(Path, Exp),
+    // the new element takes the index, the following ones shift right
+    Insert(Path, Exp),
+    Remove(Path),
+}
+
+#[derive(Clone)]
+pub struct Patch {
+    ops     : Vec<PatchOp>,
+}
+
+impl PatchOp {
+    pub fn path(&self) -> &Path {
+        match self {
+            Self::Replace(p, _) | Self::Insert(p, _) | Self::Remove(p) => p,
+        }
+    }
+
+    fn withPath(&self, p: Path) -> PatchOp {
+        match self {
+            Self::Replace(_, e) => Self::Replace(p, e.clone()),
+            Self::Insert(_, e) => Self::Insert(p, e.clone()),
+            Self::Remove(_) => Self::Remove(p),
+        }
+    }
+
+    pub(crate) fn indices(p: &Path) -> Option<std::vec::Vec<usize>> {
+        p.segments().iter().map(|s| match s { PathSeg::Index(i) => Some(*i), _ => None }).collect()
+    }
+
+    fn fromIndices(ix: &[usize]) -> Path {
+        let mut p = Path::new();
+        for i in ix.iter() { p.push(PathSeg::Index(*i)) }
+        p
+    }
+
+    // path of `p` once `self` is undone (`undo`) or done, None when `p` is inside the edited node
+    pub(crate) fn shift(&self, p: &Path, undo: bool) -> Option<Path> {
+        let (me, mut ix) = (Self::indices(self.path())?, Self::indices(p)?);
+        let n = me.len();
+        if n == 0 || ix.len() < n || ix[..n - 1] != me[..n - 1] {
+            return if ix.len() >= n && ix[..n] == me[..] || me.starts_with(&ix) { None } else { Some(p.clone()) }
+        }
+
+        let (i, j) = (me[n - 1], ix[n - 1]);
+        let grows = match self { Self::Insert(..) => !undo, Self::Remove(_) => undo, Self::Replace(..) => return if i == j { None } else { Some(p.clone()) } };
+        ix[n - 1] = match (grows, j.cmp(&i)) {
+            (_, core::cmp::Ordering::Less) => j,
+            (true, _) => j + 1,
+            (false, core::cmp::Ordering::Equal) => return None,
+            (false, _) => j - 1,
+        };
+        Some(Self::fromIndices(&ix))
+    }
+}
+
+impl Exp {
+    pub(crate) fn applyAt(&mut self, ix: &[usize], op: &PatchOp) -> bool {
+        let l = match (ix.len(), op) {
+            (0, PatchOp::Replace(_, e)) => { *self = e.clone(); return true },
+            (0, _) => return false,
+            (_, _) => match self { Self::List(l) => l, _ => return false }
+        };
+
+        let i = ix[0];
+        if ix.len() > 1 {
+            return i < l.len() && l[i].applyAt(&ix[1..], op)
+        }
+
+        match op {
+            PatchOp::Replace(_, e) if i < l.len() => l[i] = e.clone(),
+            PatchOp::Remove(_) if i < l.len() => {
+                let mut v = Vec::new();
+                for k in 0..l.len() { if k != i { v.pushBack(l[k].clone()) } }
+                *l = v;
+            },
+            PatchOp::Insert(_, e) if i <= l.len() => {
+                let mut v = Vec::new();
+                for k in 0..l.len() {
+                    if k == i { v.pushBack(e.clone()) }
+                    v.pushBack(l[k].clone());
+                }
+                if i == l.len() { v.pushBack(e.clone()) }
+                *l = v;
+            },
+            _ => return false
+        }
+        true
+    }
+
+    fn diffInto(a: &Exp, b: &Exp, path: &mut std::vec::Vec<usize>, out: &mut Vec<PatchOp>) {
+        if a == b { return }
+        let (la, lb) = match (a, b) {
+            (Self::List(la), Self::List(lb)) => (la.asArray(), lb.asArray()),
+            _ => return out.pushBack(PatchOp::Replace(PatchOp::fromIndices(path), b.clone()))
+        };
+
+        // longest common subsequence of the elements, the gaps between matches are edited
+        let (n, m) = (la.len(), lb.len());
+        let mut lcs = std::vec![std::vec![0usize; m + 1]; n + 1];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i][j] = if la[i] == lb[j] { lcs[i + 1][j + 1] + 1 } else { usize::max(lcs[i + 1][j], lcs[i][j + 1]) };
+            }
+        }
+
+        let matched = |i: usize, j: usize| i < n && j < m && la[i] == lb[j] && lcs[i][j] == lcs[i + 1][j + 1] + 1;
+        let (mut i, mut j, mut at) = (0, 0, 0);
+        while i < n || j < m {
+            if matched(i, j) {
+                i += 1; j += 1; at += 1;
+                continue
+            }
+            let (mut gi, mut gj) = (i, j);
+            while (gi < n || gj < m) && !matched(gi, gj) {
+                if gj >= m || (gi < n && lcs[gi + 1][gj] >= lcs[gi][gj + 1]) { gi += 1 } else { gj += 1 }
+            }
+            // pair the removed and inserted elements so that nested edits stay small
+            while i < gi && j < gj {
+                path.push(at);
+                Self::diffInto(&la[i], &lb[j], path, out);
+                path.pop();
+                i += 1; j += 1; at += 1;
+            }
+            while i < gi {
+                path.push(at);
+                out.pushBack(PatchOp::Remove(PatchOp::fromIndices(path)));
+                path.pop();
+                i += 1;
+            }
+            while j < gj {
+                path.push(at);
+                out.pushBack(PatchOp::Insert(PatchOp::fromIndices(path), lb[j].clone()));
+                path.pop();
+                j += 1; at += 1;
+            }
+        }
+    }
+
+    /// patch turning `self` into `other`
+    pub fn diff(&self, other: &Exp) -> Patch {
+        let mut ops = Vec::new();
+        Self::diffInto(self, other, &mut std::vec::Vec::new(), &mut ops);
+        Patch { ops }
+    }
+}
+
+impl Patch {
+    pub fn new() -> Self { Self { ops: Vec::new() } }
+    pub fn push(&mut self, op: PatchOp) { self.ops.pushBack(op) }
+    pub fn ops(&self) -> &[PatchOp] { self.ops.asArray() }
+    pub fn len(&self) -> usize { self.ops.len() }
+    pub fn is_empty(&self) -> bool { self.ops.len() == 0 }
+
+    /// the patched copy, `None` when a path does not exist
+    pub fn apply(&self, e: &Exp) -> Option<Exp> {
+        let mut r = e.clone();
+        for op in self.ops.asArray().iter() {
+            if !r.applyAt(&PatchOp::indices(op.path())?, op) { return None }
+        }
+        Some(r)
+    }
+
+    // `a` then `b` as a single op when possible
+    fn combine(a: &PatchOp, b: &PatchOp) -> Option<std::vec::Vec<PatchOp>> {
+        let (pa, pb) = (PatchOp::indices(a.path())?, PatchOp::indices(b.path())?);
+        match (a, b) {
+            (PatchOp::Insert(..), PatchOp::Remove(_)) if pa == pb => Some(std::vec::Vec::new()),
+            (PatchOp::Replace(..), PatchOp::Remove(_)) if pa == pb => Some(std::vec![b.clone()]),
+            // edits inside a node that was just written are folded into its value
+            (PatchOp::Replace(_, e), _) | (PatchOp::Insert(_, e), _) if pb.len() >= pa.len() && pb[..pa.len()] == pa[..] && (pb.len() > pa.len() || matches!(b, PatchOp::Replace(..))) => {
+                let mut v = e.clone();
+                if !v.applyAt(&pb[pa.len()..], b) { return None }
+                Some(std::vec![a.withValue(v)])
+            },
+            _ => None
+        }
+    }
+
+    /// smallest equivalent patch this finds by moving the edits of a same node
+    /// next to each other (re-basing the paths they cross) and merging them
+    pub fn minimize(&self) -> Patch {
+        let mut ops : std::vec::Vec<PatchOp> = self.ops.asArray().to_vec();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            'outer: for i in 0..ops.len() {
+                for j in i + 1..ops.len() {
+                    // bring ops[j] right after ops[i], stop at the first edit it cannot cross
+                    let mut moved = ops[j].clone();
+                    // `moved` re-based to just before each crossed edit
+                    let mut crossedAt = std::vec::Vec::new();
+                    let mut k = j;
+                    while k > i + 1 {
+                        match ops[k - 1].shift(moved.path(), true) {
+                            Some(p) => {
+                                moved = moved.withPath(p);
+                                crossedAt.push(moved.clone());
+                            },
+                            None => break
+                        }
+                        k -= 1;
+                    }
+                    if k != i + 1 { continue }
+
+                    if let Some(c) = Self::combine(&ops[i], &moved) {
+                        // the crossed edits now run after `moved`
+                        let mut crossed = std::vec::Vec::new();
+                        for (op, m) in ops[i + 1..j].iter().zip(crossedAt.iter().rev()) {
+                            match m.shift(op.path(), false) {
+                                Some(p) => crossed.push(op.withPath(p)),
+                                None => continue 'outer
+                            }
+                        }
+                        let mut next = ops[..i].to_vec();
+                        next.extend(c);
+                        next.extend(crossed);
+                        next.extend(ops[j + 1..].iter().cloned());
+                        ops = next;
+                        changed = true;
+                        break 'outer
+                    }
+                }
+            }
+        }
+
+        let mut p = Patch::new();
+        for op in ops.into_iter() { p.push(op) }
+        p
+    }
+
+    /// `((replace (0 1) value) (insert (2) value) (remove (3)))`
+    pub fn toExp(&self) -> Exp {
+        let mut v = Vec::new();
+        for op in self.ops.asArray().iter() {
+            let mut e = Vec::new();
+            let (name, value) = match op {
+                PatchOp::Replace(_, x) => ("replace", Some(x)),
+                PatchOp::Insert(_, x) => ("insert", Some(x)),
+                PatchOp::Remove(_) => ("remove", None),
+            };
+            e.pushBack(Exp::Symbol(String::from(name)));
+            let mut p = Vec::new();
+            for s in op.path().segments().iter() {
+                if let PathSeg::Index(i) = s { p.pushBack(Exp::Int(*i as i64)) }
+            }
+            e.pushBack(Exp::List(p));
+            if let Some(x) = value { e.pushBack(x.clone()) }
+            v.pushBack(Exp::List(e));
+        }
+        Exp::List(v)
+    }
+
+    /// one line per edit of `from`: `path old -> new`, `path + new` and `path - old`
+    pub fn describe(&self, from: &Exp) -> String {
+        let mut out = String::new();
+        // the old value as left by the previous edits
+        let mut cur = from.clone();
+        for op in self.ops.asArray().iter() {
+            let old = || cur.at(op.path()).map_or_else(|| String::from("?"), |e| e.toString());
+            let line = match op {
+                PatchOp::Replace(p, x) => alt_std::format!("{} {} -> {}\n", p.toString().toStr(), old().toStr(), x.toString().toStr()),
+                PatchOp::Insert(p, x) => alt_std::format!("{} + {}\n", p.toString().toStr(), x.toString().toStr()),
+                PatchOp::Remove(p) => alt_std::format!("{} - {}\n", p.toString().toStr(), old().toStr()),
+            };
+            out.append(&line);
+            let mut step = Patch::new();
+            step.push(op.clone());
+            if let Some(next) = step.apply(&cur) { cur = next }
+        }
+        out
+    }
+}
+
+impl PatchOp {
+    fn withValue(&self, e: Exp) -> PatchOp {
+        match self {
+            Self::Replace(p, _) => Self::Replace(p.clone(), e),
+            Self::Insert(p, _) => Self::Insert(p.clone(), e),
+            Self::Remove(p) => Self::Remove(p.clone()),
+        }
+    }
+}
+
+impl Default for Patch {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Exp {
+        match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testDiff() {
+        let a = parse("(server (port 80) (host \"a\") (log info))");
+        let b = parse("(server (port 8080) (log info) (tls #t))");
+        let p = a.diff(&b);
+        assert!(p.apply(&a) == Some(b.clone()));
+        assert!(p.toExp().toString() == "((replace (1 1) 8080) (remove (2)) (insert (3) (tls #t)))");
+        assert!(a.diff(&a).is_empty());
+    }
+
+    #[test]
+    fn testMinimize() {
+        let a = parse("(a (b 1) c d)");
+        let at = |ix: &[usize]| PatchOp::fromIndices(ix);
+        let mut p = Patch::new();
+        p.push(PatchOp::Replace(at(&[1, 1]), Exp::Int(2)));
+        p.push(PatchOp::Insert(at(&[0]), Exp::Symbol(String::from("x"))));
+        // (b 2) moved to index 2 by the insert
+        p.push(PatchOp::Replace(at(&[2, 1]), Exp::Int(3)));
+        p.push(PatchOp::Insert(at(&[4]), Exp::Symbol(String::from("y"))));
+        p.push(PatchOp::Remove(at(&[4])));
+        p.push(PatchOp::Replace(at(&[3]), Exp::Int(0)));
+        p.push(PatchOp::Replace(at(&[3]), Exp::Int(1)));
+
+        let m = p.minimize();
+        assert!(m.apply(&a) == p.apply(&a));
+        assert!(m.toExp().toString() == "((replace (1 1) 3) (insert (0) x) (replace (3) 1))");
+
+        let a = parse("(0 1 2 3 4 5 6)");
+        let mut p = Patch::new();
+        p.push(PatchOp::Replace(at(&[4]), Exp::Symbol(String::from("v"))));
+        p.push(PatchOp::Insert(at(&[5]), Exp::Symbol(String::from("y"))));
+        p.push(PatchOp::Insert(at(&[0]), Exp::Symbol(String::from("x"))));
+        p.push(PatchOp::Remove(at(&[5])));
+        let m = p.minimize();
+        assert!(m.len() == 3);
+        assert!(m.apply(&a) == p.apply(&a));
+    }
+}
@@ -0,0 +1,127 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+
+#[derive(Clone, PartialEq)]
+pub enum PathSeg {
+    // the `(key ...)` entry of a list
+    Key(String),
+    // n-th positional element
+    Index(usize),
+}
+
+/// location of a node inside a tree, written as `server.port` or `args.0`. A '.'
+/// or '\\' in a key is escaped with '\\', as is the start of an all digit key
+#[derive(Clone)]
+pub struct Path {
+    segs    : Vec<PathSeg>,
+}
+
+impl Path {
+    pub fn new() -> Self { Self { segs: Vec::new() } }
+
+    /// dot separated segments, all digit segments without escapes are indices
+    pub fn parse(s: &str) -> Self {
+        let mut p = Self::new();
+        if s.is_empty() { return p }
+        let s = s.as_bytes();
+        let mut k = String::new();
+        let mut escaped = false;
+        let mut i = 0;
+        loop {
+            if i == s.len() || s[i] == b'.' {
+                match k.toStr().parse::<usize>() {
+                    Ok(n) if !escaped && k.asArray().iter().all(|c| c.is_ascii_digit()) => p.push(PathSeg::Index(n)),
+                    _ => p.push(PathSeg::Key(k)),
+                }
+                if i == s.len() { return p }
+                k = String::new();
+                escaped = false;
+            } else if s[i] == b'\\' && i + 1 < s.len() {
+                escaped = true;
+                i += 1;
+                k.add(s[i]);
+            } else {
+                k.add(s[i]);
+            }
+            i += 1;
+        }
+    }
+
+    pub fn push(&mut self, seg: PathSeg) { self.segs.pushBack(seg) }
+    pub fn pop(&mut self) -> Option<PathSeg> { self.segs.pop() }
+    pub fn segments(&self) -> &[PathSeg] { self.segs.asArray() }
+    pub fn len(&self) -> usize { self.segs.len() }
+    pub fn is_empty(&self) -> bool { self.segs.len() == 0 }
+
+    pub fn child(&self, seg: PathSeg) -> Self {
+        let mut p = self.clone();
+        p.push(seg);
+        p
+    }
+
+    pub fn toString(&self) -> String {
+        let mut s = String::new();
+        for i in 0..self.segs.len() {
+            if i != 0 { s.add(b'.') }
+            match &self.segs[i] {
+                PathSeg::Key(k) => {
+                    let k = k.asArray();
+                    if !k.is_empty() && k.iter().all(|c| c.is_ascii_digit()) { s.add(b'\\') }
+                    for c in k.iter() {
+                        if *c == b'.' || *c == b'\\' { s.add(b'\\') }
+                        s.add(*c);
+                    }
+                }
+                PathSeg::Index(n) => s.append(&alt_std::format!("{}", n)),
+            }
+        }
+        s
+    }
+}
+
+impl PartialEq for Path {
+    fn eq(&self, other: &Self) -> bool { self.segs.asArray() == other.segs.asArray() }
+}
+
+impl Default for Path {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testPath() {
+        let p = Path::parse("server.args.10");
+        assert!(p.len() == 3);
+        assert!(p.segments()[0] == PathSeg::Key(String::from("server")));
+        assert!(p.segments()[2] == PathSeg::Index(10));
+        assert!(p.toString() == "server.args.10");
+        assert!(Path::parse("").is_empty());
+
+        let mut p = Path::new();
+        for k in ["a.b", "c\\d", "10", "e"].iter() { p.push(PathSeg::Key(String::from(k))) }
+        p.push(PathSeg::Index(3));
+        assert!(p.toString() == "a\\.b.c\\\\d.\\10.e.3");
+        assert!(Path::parse(p.toString().toStr()) == p);
+    }
+}
@@ -0,0 +1,200 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// pretty printing: a list that fits in the remaining width stays on one line,
+// otherwise its head stays on the opening line and every other element goes on
+// its own line, indented by two. Dotted lists do the same with the `. tail` on
+// the last line and vectors align their elements after the `#(`. A map that
+// does not fit gets one entry per line, aligned after the opening brace
+//
+// budgeted printing falls back to the compact form when the pretty one is over
+// the budget, then to the compact form with the elements that do not fit
+// replaced by `...`
+//
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    // elements after the first one on their own line, `step` columns in
+    fn writeLines(items: &[&Exp], tail: Option<&Exp>, width: usize, indent: usize, step: usize, out: &mut String) {
+        items[0].writePretty(width, indent + step, out);
+        let newline = |out: &mut String| {
+            out.add(b'\n');
+            for _ in 0..indent + step { out.add(b' ') }
+        };
+        for e in items[1..].iter() {
+            newline(out);
+            e.writePretty(width, indent + step, out);
+        }
+        if let Some(t) = tail {
+            newline(out);
+            out.append(". ");
+            t.writePretty(width, indent + step + 2, out);
+        }
+    }
+
+    fn writePretty(&self, width: usize, indent: usize, out: &mut String) {
+        // measuring stops at the end of the line, every level is not printed twice
+        if indent + self.serializedLenWithin(&PrintOptions::new(), width.saturating_sub(indent)) <= width {
+            return out.append(&self.toString())
+        }
+        match self {
+            Self::List(l) if l.len() > 1 => {
+                out.add(b'(');
+                Self::writeLines(&l.asArray().iter().collect::<std::vec::Vec<&Exp>>(), None, width, indent, 2, out);
+                out.add(b')');
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                out.add(b'(');
+                Self::writeLines(&items, tail, width, indent, 2, out);
+                out.add(b')');
+            },
+            Self::Vector(v) if v.len() > 1 => {
+                out.append("#(");
+                Self::writeLines(&v.asArray().iter().collect::<std::vec::Vec<&Exp>>(), None, width, indent, 2, out);
+                out.add(b')');
+            },
+            Self::Map(m) if m.len() > 0 => {
+                out.add(b'{');
+                for i in 0..m.len() {
+                    if i != 0 {
+                        out.add(b'\n');
+                        for _ in 0..indent + 1 { out.add(b' ') }
+                    }
+                    let key = m[i].0.toString();
+                    out.append(&key);
+                    out.add(b' ');
+                    m[i].1.writePretty(width, indent + 2 + key.asArray().len(), out);
+                }
+                out.add(b'}');
+            },
+            _ => out.append(&self.toString()),
+        }
+    }
+
+    // compact text of at most `budget` bytes, eliding what does not fit
+    fn writeBudgeted(&self, budget: usize, out: &mut String) {
+        let opts = PrintOptions::new();
+        if self.serializedLen(&opts) <= budget { return out.append(&self.toString()) }
+        let (open, items) = match self {
+            Self::List(l) => ("(", l.asArray()),
+            Self::Vector(v) => ("#(", v.asArray()),
+            _ => {
+                if budget >= 3 { out.append("...") }
+                return
+            }
+        };
+        // the shortest elided form is `(...)`
+        if budget < open.len() + 4 {
+            if budget >= 3 { out.append("...") }
+            return
+        }
+        out.append(open);
+        let mut rem = budget - open.len() - 1;
+        for i in 0..items.len() {
+            let sep = if i == 0 { 0 } else { 1 };
+            // room for ` ...` after this element unless it is the last one
+            let reserve = if i == items.len() - 1 { 0 } else { 4 };
+            let len = items[i].serializedLen(&opts);
+            if sep + len + reserve <= rem {
+                if sep == 1 { out.add(b' ') }
+                out.append(&items[i].toString());
+                rem -= sep + len;
+                continue
+            }
+            if sep == 1 { out.add(b' ') }
+            // part of a sublist, then ` ...` for the elements dropped after it
+            let nested = matches!(items[i], Self::List(_) | Self::Vector(_));
+            if nested && reserve == 0 && rem >= sep + 5 {
+                items[i].writeBudgeted(rem - sep, out);
+            } else if nested && rem >= sep + 5 + 4 {
+                items[i].writeBudgeted(rem - sep - 4, out);
+                out.append(" ...");
+            } else {
+                out.append("...");
+            }
+            break
+        }
+        out.add(b')');
+    }
+
+    /// pretty text (80 columns) of at most `maxBytes` bytes, compact then
+    /// with elided `...` elements when it does not fit, for bounded log lines
+    pub fn toPrettyStringBudgeted(&self, maxBytes: usize) -> String {
+        let pretty = self.toPrettyString(80);
+        if pretty.asArray().len() <= maxBytes { return pretty }
+        let mut s = String::new();
+        self.writeBudgeted(maxBytes, &mut s);
+        s
+    }
+
+    /// multi line rendering trying to keep lines within `width` columns
+    pub fn toPrettyString(&self, width: usize) -> String {
+        let mut s = String::new();
+        self.writePretty(width, 0, &mut s);
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testPrettyString() {
+        let sexp = String::from("(define (square x) (* x x))");
+        match Exp::fromSExp(sexp.asArray()) {
+            PROk(r) => {
+                assert!(r.toPrettyString(80).toStr() == "(define (square x) (* x x))");
+                assert!(r.toPrettyString(20).toStr() == "(define\n  (square x)\n  (* x x))");
+                assert!(r.toPrettyString(4).toStr() == "(define\n  (square\n    x)\n  (*\n    x\n    x))");
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        match Exp::fromSExp(b"{name \"srv\" ports (80 443) env {mode prod}}") {
+            PROk(r) => assert!(r.toPrettyString(30).toStr() == "{name \"srv\"\n ports (80 443)\n env {mode prod}}"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        match Exp::fromSExp(b"(config #(alpha beta (gamma delta)) (lambda x y . rest))") {
+            PROk(r) => assert!(r.toPrettyString(16).toStr() == "(config\n  #(alpha\n    beta\n    (gamma\n      delta))\n  (lambda\n    x\n    y\n    . rest))"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testPrettyBudgeted() {
+        let e = match Exp::fromSExp(b"(request (id 42) (path \"/a/b\") (headers (accept json) (agent curl)) #(1 2 3))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(e.toPrettyStringBudgeted(200) == e.toPrettyString(80));
+        assert!(e.toPrettyStringBudgeted(77).toStr() == "(request (id 42) (path \"/a/b\") (headers (accept json) (agent curl)) #(1 2 3))");
+        assert!(e.toPrettyStringBudgeted(76).toStr() == "(request (id 42) (path \"/a/b\") (headers (accept json) (agent curl)) #(...))");
+        for n in 0..77 {
+            let s = e.toPrettyStringBudgeted(n);
+            assert!(s.asArray().len() <= n, "{} over {}", s.toStr(), n);
+        }
+        assert!(e.toPrettyStringBudgeted(50).toStr() == "(request (id 42) (path \"/a/b\") (headers ...) ...)");
+        assert!(e.toPrettyStringBudgeted(60).toStr() == "(request (id 42) (path \"/a/b\") (headers (...) ...) ...)");
+        assert!(e.toPrettyStringBudgeted(13).toStr() == "(request ...)");
+        assert!(e.toPrettyStringBudgeted(4).toStr() == "...");
+    }
+}
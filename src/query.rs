@@ -0,0 +1,403 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// queries select entries of a config tree, either as a dotted path
+// (`services.**.timeout`, `\.` and `\\` escape a dot and a backslash in a key,
+// an escaped segment is always a key) or as an s-expression whose steps can filter on
+// the entry value (`(services * (port (? > 1000)))`):
+//  - `key` the `(key ...)` entries
+//  - `n` the n-th positional element
+//  - `*` any child, `**` any chain of entries (including none)
+//  - `(key (? op value))` the `key` entries whose value compares with `op`
+//    (one of `= != < <= > >=`, ordering only applies to numbers)
+//
+use alt_std::*;
+use core::cmp::Ordering;
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CmpOp { Eq, Ne, Lt, Le, Gt, Ge }
+
+#[derive(Clone)]
+pub struct Predicate {
+    pub op      : CmpOp,
+    pub value   : Exp,
+}
+
+#[derive(Clone)]
+pub enum Step {
+    Key(String),
+    Index(usize),
+    Any,
+    AnyDepth,
+    Filter(String, Predicate),
+}
+
+#[derive(Clone)]
+pub struct Query {
+    steps   : Vec<Step>,
+}
+
+impl Predicate {
+    // exact across the number types, NaN compares with nothing
+    fn cmpNumber(a: &Exp, b: &Exp) -> Option<Ordering> {
+        let nan = |e: &Exp| matches!(e, Exp::Float(f) if f.is_nan());
+        if nan(a) || nan(b) { return None }
+        a.cmpNumber(b)
+    }
+
+    pub fn test(&self, e: &Exp) -> bool {
+        let ord = Self::cmpNumber(e, &self.value);
+        match self.op {
+            CmpOp::Eq => ord == Some(Ordering::Equal) || (ord.is_none() && *e == self.value),
+            CmpOp::Ne => !(ord == Some(Ordering::Equal) || (ord.is_none() && *e == self.value)),
+            CmpOp::Lt => ord == Some(Ordering::Less),
+            CmpOp::Le => ord == Some(Ordering::Less) || ord == Some(Ordering::Equal),
+            CmpOp::Gt => ord == Some(Ordering::Greater),
+            CmpOp::Ge => ord == Some(Ordering::Greater) || ord == Some(Ordering::Equal),
+        }
+    }
+}
+
+impl Query {
+    fn invalid(offset: usize) -> ParseResult<Query> {
+        PRErr(ParseError { message: String::from("invalid query step"), offset, kind: ErrorKind::Syntax })
+    }
+
+    fn symbolStep(s: &str) -> Step {
+        match s {
+            "*" => Step::Any,
+            "**" => Step::AnyDepth,
+            s => match s.parse::<usize>() {
+                Ok(i) if s.bytes().all(|c| c.is_ascii_digit()) => Step::Index(i),
+                _ => Step::Key(String::from(s)),
+            }
+        }
+    }
+
+    fn filterStep(e: &Exp) -> Option<Step> {
+        let (l, p) = match e {
+            Exp::List(l) if l.len() == 2 => match (&l[0], &l[1]) {
+                (Exp::Symbol(k), Exp::List(p)) if p.len() == 3 => (k, p),
+                _ => return None
+            },
+            _ => return None
+        };
+
+        match (&p[0], &p[1]) {
+            (Exp::Symbol(q), Exp::Symbol(op)) if q.toStr() == "?" => {
+                let op = match op.toStr() {
+                    "=" => CmpOp::Eq,
+                    "!=" => CmpOp::Ne,
+                    "<" => CmpOp::Lt,
+                    "<=" => CmpOp::Le,
+                    ">" => CmpOp::Gt,
+                    ">=" => CmpOp::Ge,
+                    _ => return None
+                };
+                Some(Step::Filter(String::from(l.toStr()), Predicate { op, value: p[2].clone() }))
+            },
+            _ => None
+        }
+    }
+
+    /// query from its s-expression form, `None` on an invalid step
+    pub fn fromExp(e: &Exp) -> Option<Query> {
+        let l = match e {
+            Exp::List(l) => l,
+            _ => return None
+        };
+
+        let mut steps = Vec::new();
+        for s in l.asArray().iter() {
+            steps.pushBack(match s {
+                Exp::Symbol(s) => Self::symbolStep(s.toStr()),
+                Exp::Int(i) if *i >= 0 => Step::Index(*i as usize),
+                e => Self::filterStep(e)?,
+            });
+        }
+        Some(Query { steps })
+    }
+
+    /// dotted path or s-expression query
+    pub fn parse(src: &str) -> ParseResult<Query> {
+        if src.starts_with('(') {
+            return match Exp::fromSExp(src.as_bytes()) {
+                PROk(e) => match Self::fromExp(&e) {
+                    Some(q) => PROk(q),
+                    None => Self::invalid(0)
+                },
+                PRErr(err) => PRErr(err)
+            }
+        }
+
+        let mut steps = Vec::new();
+        let src = src.as_bytes();
+        let mut k = String::new();
+        let mut escaped = false;
+        let mut start = 0;
+        let mut i = 0;
+        loop {
+            if i == src.len() || src[i] == b'.' {
+                if i == start { return Self::invalid(start) }
+                steps.pushBack(if escaped { Step::Key(k) } else { Self::symbolStep(k.toStr()) });
+                if i == src.len() { return PROk(Query { steps }) }
+                k = String::new();
+                escaped = false;
+                start = i + 1;
+            } else if src[i] == b'\\' && i + 1 < src.len() {
+                escaped = true;
+                i += 1;
+                k.add(src[i]);
+            } else {
+                k.add(src[i]);
+            }
+            i += 1;
+        }
+    }
+
+    pub fn steps(&self) -> &[Step] { self.steps.asArray() }
+}
+
+impl Step {
+    // whether the i-th child is selected (`**` is handled by the traversal)
+    fn matches(&self, c: &Exp, i: usize) -> bool {
+        match self {
+            Step::Key(k) => c.entryKey() == Some(k),
+            Step::Filter(k, p) => c.entryKey() == Some(k) && p.test(c.entryValue()),
+            Step::Index(n) => *n == i,
+            Step::Any => true,
+            Step::AnyDepth => false,
+        }
+    }
+}
+
+impl Exp {
+    // what a leaf entry stands for, `(port 8080)` is 8080
+    pub(crate) fn entryValue(&self) -> &Exp {
+        match self {
+            Self::List(l) if l.len() == 2 && self.entryKey().is_some() && l[1].entryKey().is_none() => &l[1],
+            e => e
+        }
+    }
+
+    fn entryValueMut(&mut self) -> &mut Exp {
+        if core::ptr::eq(self.entryValue(), self) { return self }
+        match self {
+            Self::List(l) => &mut l[1],
+            e => e
+        }
+    }
+
+    // the children of a node, without the key of an entry
+    fn queryChildren(&self) -> &[Exp] {
+        match self {
+            Self::List(l) if self.entryKey().is_some() => &l.asArray()[1..],
+            Self::List(l) => l.asArray(),
+            _ => &[]
+        }
+    }
+
+    fn queryChildrenMut(&mut self) -> &mut [Exp] {
+        let start = if self.entryKey().is_some() { 1 } else { 0 };
+        match self {
+            Self::List(l) => &mut l.asMutArray()[start..],
+            _ => &mut []
+        }
+    }
+
+    fn childSeg(children: &[Exp], i: usize) -> PathSeg {
+        match children[i].entryKey() {
+            Some(k) => PathSeg::Key(k.clone()),
+            None => PathSeg::Index(i),
+        }
+    }
+
+    fn queryFrom<'a>(children: &'a [Exp], path: &Path, steps: &[Step], out: &mut Vec<(Path, &'a Exp)>) {
+        let (step, rest) = match steps.split_first() {
+            Some(s) => s,
+            None => return
+        };
+
+        let hit = |e: &'a Exp, p: Path, out: &mut Vec<(Path, &'a Exp)>| {
+            if rest.is_empty() {
+                out.pushBack((p, e.entryValue()));
+            } else {
+                Self::queryFrom(e.queryChildren(), &p, rest, out);
+            }
+        };
+
+        for i in 0..children.len() {
+            if step.matches(&children[i], i) {
+                let seg = match step {
+                    Step::Index(_) => PathSeg::Index(i),
+                    _ => Self::childSeg(children, i),
+                };
+                hit(&children[i], path.child(seg), out);
+            }
+        }
+
+        if let Step::AnyDepth = step {
+            // zero levels, then one more level down keeping the `**`
+            match rest.is_empty() {
+                true => for i in 0..children.len() {
+                    out.pushBack((path.child(Self::childSeg(children, i)), children[i].entryValue()));
+                },
+                false => Self::queryFrom(children, path, rest, out),
+            }
+            for i in 0..children.len() {
+                if children[i].entryKey().is_some() {
+                    Self::queryFrom(children[i].queryChildren(), &path.child(Self::childSeg(children, i)), steps, out);
+                }
+            }
+        }
+    }
+
+    /// the matching entries (their value for `(key value)` leaves) with their paths
+    pub fn query(&self, q: &Query) -> Vec<(Path, &Exp)> {
+        let mut out = Vec::new();
+        match self.entryKey() {
+            // a lone entry is the only top level node
+            Some(_) => Self::queryFrom(core::slice::from_ref(self), &Path::new(), q.steps(), &mut out),
+            None => Self::queryFrom(self.queryChildren(), &Path::new(), q.steps(), &mut out),
+        }
+        out
+    }
+}
+
+impl Exp {
+    fn updateLeaf(e: &mut Exp, f: &mut dyn FnMut(&mut Exp), changed: &mut usize) {
+        let v = e.entryValueMut();
+        let before = v.clone();
+        f(v);
+        if before.totalCmp(v) != Ordering::Equal { *changed += 1 }
+    }
+
+    fn updateFrom(children: &mut [Exp], steps: &[Step], f: &mut dyn FnMut(&mut Exp), changed: &mut usize) {
+        let (step, rest) = match steps.split_first() {
+            Some(s) => s,
+            None => return
+        };
+
+        for i in 0..children.len() {
+            if !step.matches(&children[i], i) { continue }
+            match rest.is_empty() {
+                true => Self::updateLeaf(&mut children[i], f, changed),
+                false => Self::updateFrom(children[i].queryChildrenMut(), rest, f, changed),
+            }
+        }
+
+        if let Step::AnyDepth = step {
+            match rest.is_empty() {
+                true => for c in children.iter_mut() { Self::updateLeaf(c, f, changed) },
+                false => Self::updateFrom(children, rest, f, changed),
+            }
+            for c in children.iter_mut() {
+                if c.entryKey().is_some() {
+                    Self::updateFrom(c.queryChildrenMut(), steps, f, changed);
+                }
+            }
+        }
+    }
+
+    /// applies `f` to every match of `q` (the same nodes `query` returns) and
+    /// returns how many of them it actually changed
+    pub fn updateWhere<F: FnMut(&mut Exp)>(&mut self, q: &Query, mut f: F) -> usize {
+        let mut changed = 0;
+        match self.entryKey() {
+            Some(_) => Self::updateFrom(core::slice::from_mut(self), q.steps(), &mut f, &mut changed),
+            None => Self::updateFrom(self.queryChildrenMut(), q.steps(), &mut f, &mut changed),
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(src: &str, q: &str) -> String {
+        let e = match Exp::fromSExp(src.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let q = match Query::parse(q) {
+            PROk(q) => q,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut s = String::new();
+        for (p, v) in e.query(&q).asArray().iter() {
+            s.append(&p.toString());
+            s.add(b'=');
+            s.append(&v.toString());
+            s.add(b' ');
+        }
+        s
+    }
+
+    #[test]
+    fn testQuery() {
+        let src = "(services (web (port 80) (timeout 5)) (db (port 5432) (pool (timeout 30))) (ports 1 2))";
+        assert!(run(src, "services.web.port") == "services.web.port=80 ");
+        assert!(run(src, "services.*.port") == "services.web.port=80 services.db.port=5432 ");
+        assert!(run(src, "services.**.timeout") == "services.web.timeout=5 services.db.pool.timeout=30 ");
+        assert!(run(src, "services.ports.1") == "services.ports.1=2 ");
+        assert!(run(src, "(services * (port (? > 1000)))") == "services.db.port=5432 ");
+        assert!(run(src, "(services ** (timeout (? <= 5)))") == "services.web.timeout=5 ");
+        assert!(run(src, "(** (port (? = 80)))") == "services.web.port=80 ");
+        assert!(match Query::parse("a..b") { PRErr(err) => err.offset() == 2, _ => false });
+        assert!(match Query::parse("(a (b (? ~ 1)))") { PRErr(_) => true, _ => false });
+    }
+
+    #[test]
+    fn testQueryExact() {
+        // 2^53 + 1 is above 2^53 although it rounds to it as a float
+        let src = "(v (n 9007199254740993) (h 0.5) (u 18446744073709551615) (x 1))";
+        assert!(run(src, "(v (n (? > 9007199254740992.0)))") == "v.n=9007199254740993 ");
+        assert!(run(src, "(v (h (? = 1/2)))") == "v.h=0.5 ");
+        assert!(run(src, "(v (u (? > 18446744073709551614)))") == "v.u=18446744073709551615 ");
+        assert!(run(src, "(v (x (? = 1.0)))") == "v.x=1 ");
+
+        let src = "(cfg (a.b 1) (a (b 2)) (* 3) (\\ 4) (|7| 5))";
+        assert!(run(src, "cfg.a\\.b") == "cfg.a\\.b=1 ");
+        assert!(run(src, "cfg.a.b") == "cfg.a.b=2 ");
+        assert!(run(src, "cfg.\\*") == "cfg.*=3 ");
+        assert!(run(src, "cfg.\\\\") == "cfg.\\\\=4 ");
+        assert!(run(src, "cfg.\\7") == "cfg.\\7=5 ");
+        assert!(match Query::parse("a.\\..") { PRErr(err) => err.offset() == 5, _ => false });
+    }
+
+    #[test]
+    fn testUpdateWhere() {
+        let src = "(app (log-level info) (db (log-level warn) (port 5432)) (web (log-level debug)))";
+        let mut e = match Exp::fromSExp(src.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let q = match Query::parse("app.**.log-level") {
+            PROk(q) => q,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let n = e.updateWhere(&q, |v| *v = Exp::Symbol(String::from("warn")));
+        assert!(n == 2);
+        assert!(e.toString() == "(app (log-level warn) (db (log-level warn) (port 5432)) (web (log-level warn)))");
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// exact rationals: `1/3`, `-22/7` read into `Exp::Ratio` kept in lowest terms with
+// a positive denominator, `4/2` reads as the Int 2. Ratios stay exact through
+// `Number`, compare by value with the other numbers and print back as `n/d`
+//
+use alt_std::*;
+use core::convert::TryFrom;
+use crate::*;
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        let t = a % b;
+        a = b;
+        b = t;
+    }
+    a
+}
+
+impl Exp {
+    /// `n/d` in lowest terms, an Int when `d` divides `n`, None when `d` is 0 or
+    /// the reduced terms do not fit an i64
+    pub fn ratio(n: i64, d: i64) -> Option<Exp> {
+        Self::reduced(n as i128, d as i128)
+    }
+
+    pub(crate) fn reduced(n: i128, d: i128) -> Option<Exp> {
+        if d == 0 { return None }
+        let g = gcd(n.unsigned_abs(), d.unsigned_abs()) as i128;
+        let (n, d) = if d < 0 { (-n / g, -d / g) } else { (n / g, d / g) };
+        match (i64::try_from(n), i64::try_from(d)) {
+            (Ok(n), Ok(1)) => Some(Exp::Int(n)),
+            (Ok(n), Ok(d)) => Some(Exp::Ratio(n, d)),
+            _ => None
+        }
+    }
+
+    // `s` is the number token read up to a separator, `start` its offset
+    pub(crate) fn readRatio(rd: &mut Reader, s: &str, start: usize) -> ParseResult<Exp> {
+        let invalid = || PRErr(ParseError { message: String::from("invalid number format"), offset: start, kind: ErrorKind::Number });
+        let (n, d) = match s.split_once('/') {
+            Some((n, d)) if Self::isIntLiteral(n.as_bytes()) && !d.is_empty() && d.bytes().all(Self::isDigit) => (n, d),
+            _ => return invalid()
+        };
+        if d.bytes().all(|c| c == b'0') {
+            return PRErr(ParseError { message: String::from("division by zero (ratio)"), offset: start, kind: ErrorKind::Number })
+        }
+        let exact = match (n.parse::<i128>(), d.parse::<i128>()) {
+            (Ok(n), Ok(d)) => Self::reduced(n, d),
+            _ => None
+        };
+        match (exact, rd.opts.intOverflow) {
+            (Some(e), _) => PROk(e),
+            (None, IntOverflow::Error) => PRErr(ParseError { message: String::from("integer overflow"), offset: start, kind: ErrorKind::Number }),
+            // there are no big ratios, they read as a Float too
+            (None, _) => {
+                rd.warnings.pushBack(ParseError { message: String::from("integer overflow, read as float"), offset: start, kind: ErrorKind::Number });
+                match (n.parse::<f64>(), d.parse::<f64>()) {
+                    (Ok(n), Ok(d)) => PROk(Exp::Float(n / d)),
+                    _ => invalid()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testRatio() {
+        match Exp::fromSExp(b"(1/3 -6/4 4/2 +0/5 #(2/3))") {
+            PROk(e) => {
+                let l = match &e { Exp::List(l) => l, _ => panic!("expected a list") };
+                assert!(l[0] == Exp::Ratio(1, 3) && l[1] == Exp::Ratio(-3, 2));
+                assert!(l[2] == Exp::Int(2) && l[3] == Exp::Int(0));
+                assert!(e.toString() == "(1/3 -3/2 2 0 #(2/3))");
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::ratio(3, -9) == Some(Exp::Ratio(-1, 3)) && Exp::ratio(1, 0).is_none());
+        assert!(Exp::ratio(i64::MIN, -1).is_none());
+        assert!(Exp::fromSExp(b"(a 1/0)") == PRErr(ParseError { message: String::from("division by zero (ratio)"), offset: 3, kind: ErrorKind::Number }));
+        assert!(Exp::fromSExp(b"1/-2") == PRErr(ParseError { message: String::from("invalid number format"), offset: 0, kind: ErrorKind::Number }));
+        assert!(Exp::fromSExp(b"1.5/2") == PRErr(ParseError { message: String::from("invalid number format"), offset: 0, kind: ErrorKind::Number }));
+
+        let mut v = [Exp::Float(0.5), Exp::Ratio(1, 3), Exp::Int(1), Exp::Ratio(1, 2), Exp::Int(0)];
+        v.sort_by(|a, b| a.totalCmp(b));
+        assert!(v[0] == Exp::Int(0) && v[1] == Exp::Ratio(1, 3) && v[2] == Exp::Ratio(1, 2) && v[3] == Exp::Float(0.5) && v[4] == Exp::Int(1));
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// record/replay: `Recorder` tees the forms read from a stream into a corpus of
+// `(at <microseconds> <form>)` entries, `Replayer` reads such a corpus back and
+// yields the forms at their original pace, or faster
+//
+use alt_std::*;
+use std::time::{Duration, Instant};
+use crate::*;
+
+pub struct Recorder<R: std::io::Read, W: std::io::Write> {
+    forms   : FormReader<R>,
+    corpus  : W,
+    start   : Instant,
+}
+
+impl<R: std::io::Read, W: std::io::Write> Recorder<R, W> {
+    pub fn new(forms: FormReader<R>, corpus: W) -> Self {
+        Self { forms, corpus, start: Instant::now() }
+    }
+
+    pub fn intoCorpus(self) -> W { self.corpus }
+
+    fn entry(at: u64, form: Exp) -> Exp {
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from("at")));
+        l.pushBack(Exp::Int(at as i64));
+        l.pushBack(form);
+        Exp::List(l)
+    }
+}
+
+impl<R: std::io::Read, W: std::io::Write> Iterator for Recorder<R, W> {
+    type Item = ParseResult<Exp>;
+
+    fn next(&mut self) -> Option<ParseResult<Exp>> {
+        let form = self.forms.next()?;
+        if let PROk(e) = &form {
+            let at = self.start.elapsed().as_micros() as u64;
+            let mut line = Self::entry(at, e.clone()).toString();
+            line.add(b'\n');
+            if self.corpus.write_all(line.asArray()).is_err() {
+                return Some(PRErr(ParseError { message: String::from("unable to write corpus"), offset: 0, kind: ErrorKind::Syntax }))
+            }
+        }
+        Some(form)
+    }
+}
+
+pub struct Replayer<R: std::io::Read> {
+    entries : FormReader<R>,
+    // 1.0 replays at the recorded pace, 0.0 (or infinity) without waiting
+    speed   : f64,
+    start   : Instant,
+}
+
+impl<R: std::io::Read> Replayer<R> {
+    pub fn new(corpus: R, speed: f64) -> Self {
+        Self { entries: FormReader::new(corpus), speed, start: Instant::now() }
+    }
+
+    // recorded time and form of a corpus entry
+    fn split(e: Exp) -> Option<(u64, Exp)> {
+        match e {
+            Exp::List(l) if l.len() == 3 => match (&l[0], &l[1]) {
+                (Exp::Symbol(s), Exp::Int(at)) if s.toStr() == "at" && *at >= 0 => Some((*at as u64, l[2].clone())),
+                _ => None
+            },
+            _ => None
+        }
+    }
+}
+
+impl<R: std::io::Read> Iterator for Replayer<R> {
+    type Item = ParseResult<Exp>;
+
+    fn next(&mut self) -> Option<ParseResult<Exp>> {
+        let (at, form) = match self.entries.next()? {
+            PROk(e) => match Self::split(e) {
+                Some(entry) => entry,
+                None => return Some(PRErr(ParseError { message: String::from("invalid corpus entry"), offset: 0, kind: ErrorKind::Syntax }))
+            },
+            PRErr(err) => return Some(PRErr(err))
+        };
+
+        if self.speed > 0.0 && self.speed.is_finite() {
+            let due = Duration::from_micros((at as f64 / self.speed) as u64);
+            let elapsed = self.start.elapsed();
+            if due > elapsed {
+                std::thread::sleep(due - elapsed);
+            }
+        }
+        Some(PROk(form))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testRecordReplay() {
+        let live = b"(tick 1) (tick 2)\n(quit)";
+        let mut rec = Recorder::new(FormReader::new(&live[..]), std::vec::Vec::new());
+        assert!(rec.by_ref().all(|f| match f { PROk(_) => true, PRErr(_) => false }));
+        let corpus = rec.intoCorpus();
+
+        let mut forms = std::vec::Vec::new();
+        for f in Replayer::new(&corpus[..], 0.0) {
+            match f {
+                PROk(e) => forms.push(e.toString().toStr().to_string()),
+                PRErr(err) => panic!("{}", err.message.toStr())
+            }
+        }
+        assert!(forms == ["(tick 1)", "(tick 2)", "(quit)"]);
+
+        // a form recorded 20ms in is not replayed before 10ms at double speed
+        let corpus = b"(at 20000 (late))";
+        let start = Instant::now();
+        assert!(Replayer::new(&corpus[..], 2.0).count() == 1);
+        assert!(start.elapsed() >= Duration::from_millis(10));
+        assert!(match Replayer::new(&b"(tick)"[..], 0.0).next() { Some(PRErr(err)) => err.message() == "invalid corpus entry", _ => false });
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// recovery: the complete top level forms at the start of a buffer that may stop
+// anywhere (an append-only log after a crash). A form counts when it is closed
+// and, for atoms, followed by a separator, `12` at the very end may be `123` cut
+// short. Reading stops at the first incomplete or broken form
+//
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    pub fn fromSExpPrefix(src: &[u8]) -> (Vec<Exp>, usize) {
+        Self::fromSExpPrefixWithOptions(src, &ReaderOptions::new())
+    }
+
+    /// the complete forms of `src` and the offset where they end (past the trailing
+    /// comments when those are complete too), truncating the input there leaves a
+    /// valid document
+    pub fn fromSExpPrefixWithOptions(src: &[u8], opts: &ReaderOptions) -> (Vec<Exp>, usize) {
+        let mut warnings = Vec::new();
+        let mut rd = Reader::new(opts, &mut warnings);
+        let mut forms = Vec::new();
+        let mut valid = Self::documentStart(src, opts);
+        while let Some(start) = Self::wsEnd(src, valid, false) {
+            if start == src.len() {
+                valid = start;
+                break
+            }
+            let end = match Self::formEnd(src, start, false) {
+                Ok(Some(end)) => end,
+                _ => break
+            };
+            let mut offset = start;
+            match Self::parseToken(&mut rd, &src[..end], &mut offset) {
+                PROk(e) if offset == end => forms.pushBack(e),
+                _ => break
+            }
+            valid = end;
+        }
+        (forms, valid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testFromSExpPrefix() {
+        let log = b"(put a 1)\n(put b \"x)\") ; ok\n(put c 12";
+        // the string holds a `)` and the comment stops without its newline
+        for (cut, count, end) in [(5, 0, 0), (9, 1, 9), (10, 1, 10), (18, 1, 9), (22, 2, 22), (25, 2, 22), (28, 2, 28), (log.len(), 2, 22)].iter() {
+            let (forms, valid) = Exp::fromSExpPrefix(&log[..*cut]);
+            assert!(forms.len() == *count && valid == *end);
+        }
+        let (forms, end) = Exp::fromSExpPrefix(b"(a) 12 (b) \n");
+        assert!(forms.len() == 3 && end == 12);
+        assert!(Exp::fromSExpPrefix(b"(a) 12").1 == 3);
+        // a broken form stops the reading like a truncated one
+        let (forms, end) = Exp::fromSExpPrefix(b"(a) (b #\\bogus) (c)");
+        assert!(forms.len() == 1 && forms[0].toString() == "(a)" && end == 3);
+    }
+}
@@ -0,0 +1,292 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+//
+// the scoped rename knows the Scheme binders: `lambda`, `let` (named too),
+// `let*`, `letrec` and `define` of a function, with dotted rest parameters as
+// in `(lambda (a . rest) ...)`. Occurrences bound by them are local
+// variables and are left alone, renaming a free symbol under a binder of its
+// new name would capture it and is reported as a collision.
+//
+impl Exp {
+    fn boundNames(e: &Exp, out: &mut Vec<String>) {
+        match e {
+            Self::Symbol(s) => out.pushBack(s.clone()),
+            Self::List(l) => for b in l.asArray().iter() {
+                match b {
+                    Self::Symbol(s) => out.pushBack(s.clone()),
+                    // (name init) bindings
+                    Self::List(nb) if nb.len() != 0 => if let Self::Symbol(s) = &nb[0] { out.pushBack(s.clone()) },
+                    _ => ()
+                }
+            },
+            // (a b . rest) parameters
+            Self::Pair(car, cdr) => {
+                if let Self::Symbol(s) = &**car { out.pushBack(s.clone()) }
+                Self::boundNames(cdr, out)
+            },
+            _ => ()
+        }
+    }
+
+    // the inits of `(name init)` bindings
+    fn visitInits(bs: &mut Exp, scoped: bool, bound: &mut Vec<String>, f: &mut dyn FnMut(&mut String, &Vec<String>)) {
+        if let Self::List(bs) = bs {
+            for b in bs.asMutArray().iter_mut() {
+                if let Self::List(nb) = b {
+                    for init in nb.asMutArray()[1..].iter_mut() { Self::visitFree(init, scoped, bound, f) }
+                }
+            }
+        }
+    }
+
+    fn isBound(bound: &Vec<String>, s: &str) -> bool {
+        bound.asArray().iter().any(|b| b.toStr() == s)
+    }
+
+    // calls `f` on every symbol that names a free variable (all of them when not `scoped`)
+    fn visitFree(e: &mut Exp, scoped: bool, bound: &mut Vec<String>, f: &mut dyn FnMut(&mut String, &Vec<String>)) {
+        let l = match e {
+            Self::Symbol(s) => {
+                if !scoped || !Self::isBound(bound, s.toStr()) { f(s, bound) }
+                return
+            },
+            Self::List(l) => l,
+            Self::Map(m) => {
+                for (k, v) in m.asMutArray().iter_mut() {
+                    Self::visitFree(k, scoped, bound, f);
+                    Self::visitFree(v, scoped, bound, f);
+                }
+                return
+            },
+            Self::Vector(v) => {
+                for c in v.asMutArray().iter_mut() { Self::visitFree(c, scoped, bound, f) }
+                return
+            },
+            Self::Pair(car, cdr) => {
+                Self::visitFree(car, scoped, bound, f);
+                Self::visitFree(cdr, scoped, bound, f);
+                return
+            },
+            _ => return
+        };
+
+        let binder = match (scoped, l.len() >= 2, l.asArray().first()) {
+            (true, true, Some(Self::Symbol(h))) => match h.toStr() {
+                "lambda" | "let" | "let*" | "letrec" | "define" => Some(String::from(h.toStr())),
+                _ => None
+            },
+            _ => None
+        };
+        let binder = match binder {
+            Some(b) => b,
+            None => {
+                for c in l.asMutArray().iter_mut() { Self::visitFree(c, scoped, bound, f) }
+                return
+            }
+        };
+
+        let mut names = Vec::new();
+        let depth = bound.len();
+        // the inits of a let are evaluated outside of it, the names are bound after them
+        let late = binder.toStr() == "let" || binder.toStr() == "define";
+        let mut body = 2;
+        match binder.toStr() {
+            "define" => match &mut l[1] {
+                // (define (f args...) body), f belongs to the outer scope
+                Self::List(sig) if sig.len() != 0 => {
+                    Self::visitFree(&mut sig[0], scoped, bound, f);
+                    for a in sig.asArray()[1..].iter() { Self::boundNames(a, &mut names) }
+                },
+                // (define (f . args) body)
+                Self::Pair(car, cdr) => {
+                    Self::visitFree(car, scoped, bound, f);
+                    Self::boundNames(cdr, &mut names);
+                },
+                other => Self::visitFree(other, scoped, bound, f),
+            },
+            // (let loop ((name init)...) body), the loop name is only bound in the body
+            "let" if l.len() >= 3 && matches!(l[1], Self::Symbol(_)) => {
+                Self::boundNames(&l[1], &mut names);
+                Self::boundNames(&l[2], &mut names);
+                Self::visitInits(&mut l[2], scoped, bound, f);
+                body = 3;
+            },
+            _ => {
+                Self::boundNames(&l[1], &mut names);
+                if !late {
+                    for n in names.asArray().iter() { bound.pushBack(n.clone()) }
+                }
+                if binder.toStr() != "lambda" { Self::visitInits(&mut l[1], scoped, bound, f) }
+            },
+        }
+
+        if late {
+            for n in names.asArray().iter() { bound.pushBack(n.clone()) }
+        }
+        for c in l.asMutArray()[body..].iter_mut() { Self::visitFree(c, scoped, bound, f) }
+        while bound.len() > depth { bound.pop(); }
+    }
+
+    fn renamedTo<'a>(map: &[(&str, &'a str)], s: &str) -> Option<&'a str> {
+        map.iter().find(|(from, _)| *from == s).map(|(_, to)| *to)
+    }
+
+    fn addCollision(collisions: &mut Vec<String>, name: &str) {
+        if !collisions.asArray().iter().any(|c| c.toStr() == name) {
+            collisions.pushBack(String::from(name));
+        }
+    }
+
+    fn rename(&mut self, map: &[(&str, &str)], scoped: bool) -> Result<usize, Vec<String>> {
+        let mut free = Vec::new();
+        let mut scratch = self.clone();
+        Self::visitFree(&mut scratch, scoped, &mut Vec::new(), &mut |s, _| {
+            if !Self::isBound(&free, s.toStr()) { free.pushBack(s.clone()) }
+        });
+
+        // a target already in use (and not renamed away), or shared by two renamed symbols, merges names
+        let mut collisions = Vec::new();
+        for (from, to) in map.iter() {
+            if !Self::isBound(&free, from) { continue }
+            let taken = Self::isBound(&free, to) && Self::renamedTo(map, to).is_none();
+            let shared = map.iter().any(|(f, t)| f != from && t == to && Self::isBound(&free, f));
+            if taken || shared { Self::addCollision(&mut collisions, to) }
+        }
+
+        let mut count = 0;
+        Self::visitFree(&mut scratch, scoped, &mut Vec::new(), &mut |s, bound| {
+            if let Some(to) = Self::renamedTo(map, s.toStr()) {
+                if Self::isBound(bound, to) { Self::addCollision(&mut collisions, to) }
+                *s = String::from(to);
+                count += 1;
+            }
+        });
+
+        if collisions.len() != 0 { return Err(collisions) }
+        *self = scratch;
+        Ok(count)
+    }
+
+    /// renames every symbol of `map` (`(from, to)` pairs) and returns the number of
+    /// occurrences renamed, or leaves the tree untouched and returns the colliding targets
+    pub fn renameSymbols(&mut self, map: &[(&str, &str)]) -> Result<usize, Vec<String>> {
+        self.rename(map, false)
+    }
+
+    /// like `renameSymbols` for free variables only, see above for the binder forms
+    pub fn renameSymbolsScoped(&mut self, map: &[(&str, &str)]) -> Result<usize, Vec<String>> {
+        self.rename(map, true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Exp {
+        match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testRenameSymbols() {
+        let mut e = parse("(config (host a) (port b) (hostname c))");
+        assert!(e.renameSymbols(&[("host", "address"), ("port", "tcp-port")]).ok() == Some(2));
+        assert!(e.toString() == "(config (address a) (tcp-port b) (hostname c))");
+
+        match e.renameSymbols(&[("address", "hostname")]) {
+            Err(c) => assert!(c.len() == 1 && c[0] == "hostname"),
+            Ok(_) => panic!("expected a collision")
+        }
+        // swaps are fine
+        assert!(e.renameSymbols(&[("a", "b"), ("b", "a")]).ok() == Some(2));
+        assert!(e.toString() == "(config (address b) (tcp-port a) (hostname c))");
+    }
+
+    #[test]
+    fn testRenameSymbolsScoped() {
+        let mut e = parse("(define (f x) (let ((y x) (z total)) (+ x y z total (lambda (total) total))))");
+        assert!(e.renameSymbolsScoped(&[("x", "arg"), ("total", "sum")]).ok() == Some(2));
+        assert!(e.toString() == "(define (f x) (let ((y x) (z sum)) (+ x y z sum (lambda (total) total))))");
+
+        // `sum` would be captured by the lambda
+        let mut e = parse("(lambda (sum) (+ sum total))");
+        match e.renameSymbolsScoped(&[("total", "sum")]) {
+            Err(c) => assert!(c.len() == 1 && c[0] == "sum"),
+            Ok(_) => panic!("expected a capture")
+        }
+        assert!(e.toString() == "(lambda (sum) (+ sum total))");
+    }
+
+    #[test]
+    fn testRenameDottedParams() {
+        let mut e = parse("(lambda (a . rest) (f a rest other))");
+        assert!(e.renameSymbolsScoped(&[("a", "x"), ("rest", "r"), ("other", "o")]).ok() == Some(1));
+        assert!(e.toString() == "(lambda (a . rest) (f a rest o))");
+
+        let mut e = parse("(lambda (a . rest) (g total))");
+        assert!(matches!(e.renameSymbolsScoped(&[("total", "rest")]), Err(c) if c.len() == 1 && c[0] == "rest"));
+    }
+
+    #[test]
+    fn testRenameDefineRest() {
+        let mut e = parse("(define (f . args) (apply g args f))");
+        assert!(e.renameSymbolsScoped(&[("f", "h"), ("args", "xs"), ("g", "k")]).ok() == Some(3));
+        assert!(e.toString() == "(define (h . args) (apply k args h))");
+
+        let mut e = parse("(define (f a . more) (cons a more))");
+        assert!(e.renameSymbolsScoped(&[("a", "b"), ("more", "m")]).ok() == Some(0));
+    }
+
+    #[test]
+    fn testRenameNamedLet() {
+        // the inits see the outer `i` and `loop`, the body the bound ones
+        let mut e = parse("(let loop ((i i) (n loop)) (if (< i n) (loop (+ i 1) n) done))");
+        assert!(e.renameSymbolsScoped(&[("i", "j"), ("loop", "go"), ("n", "m"), ("done", "end")]).ok() == Some(3));
+        assert!(e.toString() == "(let loop ((i j) (n go)) (if (< i n) (loop (+ i 1) n) end))");
+
+        let mut e = parse("(let loop ((i 0)) (loop total))");
+        assert!(matches!(e.renameSymbolsScoped(&[("total", "loop")]), Err(c) if c.len() == 1 && c[0] == "loop"));
+    }
+
+    #[test]
+    fn testRenameInContainers() {
+        // (source, renames, scoped, renamed count, result)
+        type Case = (&'static str, &'static [(&'static str, &'static str)], bool, usize, &'static str);
+        let cases : &[Case] = &[
+            ("(config {host a port {b host}})", &[("host", "address")], false, 2, "(config {address a port {b address}})"),
+            ("(lambda (x) {key x other y})", &[("x", "z"), ("y", "w")], true, 1, "(lambda (x) {key x other w})"),
+            ("(list #(host #(host port)) host)", &[("host", "address")], false, 3, "(list #(address #(address port)) address)"),
+            ("(let ((x 1)) #(x y))", &[("x", "z"), ("y", "w")], true, 1, "(let ((x 1)) #(x w))"),
+            ("((host . port) (a b . host))", &[("host", "address")], false, 2, "((address . port) (a b . address))"),
+            ("(lambda (x) (x . y))", &[("x", "z"), ("y", "w")], true, 1, "(lambda (x) (x . w))"),
+        ];
+        for (src, map, scoped, count, res) in cases.iter() {
+            let mut e = parse(src);
+            let n = if *scoped { e.renameSymbolsScoped(map) } else { e.renameSymbols(map) };
+            assert!(n.ok() == Some(*count) && e.toString() == *res, "{}", src);
+        }
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// routing: top level forms are delimited by a light scan (balanced parens,
+// strings, character literals and comments) and handed as raw bytes to the handler
+// registered for their head symbol, the bodies are never parsed
+//
+use alt_std::*;
+use crate::*;
+
+type Handler<'a> = std::boxed::Box<dyn FnMut(&[u8], usize) + 'a>;
+
+impl Exp {
+    // end of the form starting at `start`, None when `src` stops before it does
+    pub(crate) fn formEnd(src: &[u8], start: usize, eof: bool) -> Result<Option<usize>, ParseError> {
+        Self::formScan(src, start, eof, &mut None)
+    }
+
+    // `formEnd`, when `src` stops inside a string literal or a plain atom `open`
+    // is set to its start and whether it is a string
+    pub(crate) fn formScan(src: &[u8], start: usize, eof: bool, open: &mut Option<(usize, bool)>) -> Result<Option<usize>, ParseError> {
+        let mut depth = 0;
+        // the datum of 'x, `x, ,x and ,@x starts after the prefix
+        let mut start = start;
+        while let Some(b'\'' | b'`' | b',') = src.get(start) {
+            start += if src[start] == b',' && src.get(start + 1) == Some(&b'@') { 2 } else { 1 };
+        }
+        let mut i = start;
+        while i < src.len() {
+            match src[i] {
+                b'"' if src[i..].starts_with(b"\"\"\"") => match Self::textBlockEnd(src, i + 3) {
+                    Some(end) => i = end + 2,
+                    None if eof => return Err(ParseError { message: String::from("unexpected end of stream (string)"), offset: src.len(), kind: ErrorKind::Eof }),
+                    None => return Ok(None),
+                },
+                b'"' => {
+                    let quote = i;
+                    i += 1;
+                    loop {
+                        match src.get(i) {
+                            None if eof => return Err(ParseError { message: String::from("unexpected end of stream (string)"), offset: src.len(), kind: ErrorKind::Eof }),
+                            None => { *open = Some((quote, true)); return Ok(None) },
+                            Some(b'"') => break,
+                            Some(b'\\') => i += 2,
+                            Some(_) => i += 1,
+                        }
+                    }
+                },
+                b'#' if src.get(i + 1) == Some(&b'\\') => i += 2,
+                // the string of `#u8"..."` is part of the form
+                b'#' if src[i..].starts_with(b"#u8\"") => i += 2,
+                b'#' if src.get(i + 1) == Some(&b';') => i += 1,
+                b'#' if depth > 0 && src.get(i + 1) == Some(&b'|') => match Self::wsEnd(src, i, eof) {
+                    Some(end) if end == i => return Err(ParseError { message: String::from("unterminated block comment"), offset: i, kind: ErrorKind::Eof }),
+                    Some(end) => i = end - 1,
+                    None => return Ok(None),
+                },
+                b';' if depth > 0 => match Self::wsEnd(src, i, eof) {
+                    Some(end) => i = end - 1,
+                    None => return Ok(None),
+                },
+                // a |...| symbol, or a :|...| keyword
+                b'|' if (Self::tokenStart(src, start, i) || (src[i - 1] == b':' && Self::tokenStart(src, start, i - 1))) && Self::isPipeSymbolStart(src, i) => loop {
+                    i += 1;
+                    match src.get(i) {
+                        None => return if eof { Err(ParseError { message: String::from("unexpected end of stream (symbol)"), offset: src.len(), kind: ErrorKind::Eof }) } else { Ok(None) },
+                        Some(b'|') => break,
+                        Some(b'\\') => i += 1,
+                        Some(_) => (),
+                    }
+                },
+                b'(' | b'[' | b'{' => depth += 1,
+                // an atom ends before the list closing
+                b')' | b']' | b'}' if depth == 0 && i > start => return Ok(Some(i)),
+                b')' | b']' | b'}' if depth == 0 => return Err(ParseError { message: String::from("unexpected character (list)"), offset: i, kind: ErrorKind::Syntax }),
+                b')' | b']' | b'}' => {
+                    depth -= 1;
+                    if depth == 0 { return Ok(Some(i + 1)) }
+                },
+                c if depth == 0 && i > start && Self::isSeparator(c) => return Ok(Some(i)),
+                _ => (),
+            }
+            i += 1;
+        }
+
+        match (depth, eof) {
+            (0, true) if i > start => Ok(Some(src.len())),
+            (_, true) => Err(ParseError { message: String::from("unexpected end of stream (list)"), offset: src.len(), kind: ErrorKind::Eof }),
+            (_, false) => {
+                let mut j = src.len();
+                while j > start && !Self::isSeparator(src[j - 1]) { j -= 1 }
+                if j < src.len() { *open = Some((j, false)) }
+                Ok(None)
+            },
+        }
+    }
+
+    fn tokenStart(src: &[u8], start: usize, i: usize) -> bool {
+        i == start || Self::isSeparator(src[i - 1])
+    }
+
+    // head symbol of a raw list form
+    pub(crate) fn rawHead(form: &[u8]) -> Option<&[u8]> {
+        if form.first() != Some(&b'(') { return None }
+        let mut start = 1;
+        Self::skipWS(form, &mut start);
+        let mut end = start;
+        while end < form.len() && !Self::isSeparator(form[end]) {
+            end += 1;
+        }
+        match form.get(start) {
+            Some(c) if (Self::isAlpha(*c) || Self::isOp(*c) || Self::symbolCharLen(form, start) > 0) && end > start => Some(&form[start..end]),
+            _ => None
+        }
+    }
+}
+
+pub struct Router<'a> {
+    routes      : Vec<(String, Handler<'a>)>,
+    fallback    : Option<Handler<'a>>,
+}
+
+impl<'a> Router<'a> {
+    pub fn new() -> Self {
+        Self { routes: Vec::new(), fallback: None }
+    }
+
+    /// `handler(raw form, offset)` receives the forms headed by `head`
+    pub fn on<F: FnMut(&[u8], usize) + 'a>(&mut self, head: &str, handler: F) -> &mut Self {
+        self.routes.pushBack((String::from(head), std::boxed::Box::new(handler)));
+        self
+    }
+
+    /// receives the forms no route matched, they are dropped otherwise
+    pub fn otherwise<F: FnMut(&[u8], usize) + 'a>(&mut self, handler: F) -> &mut Self {
+        self.fallback = Some(std::boxed::Box::new(handler));
+        self
+    }
+
+    fn dispatch(&mut self, form: &[u8], offset: usize) {
+        if let Some(head) = Exp::rawHead(form) {
+            for (h, handler) in self.routes.asMutArray().iter_mut() {
+                if h.asArray() == head {
+                    handler(form, offset);
+                    return
+                }
+            }
+        }
+        if let Some(handler) = &mut self.fallback {
+            handler(form, offset)
+        }
+    }
+
+    /// routes every top level form of `input`, returns how many were read
+    pub fn route<R: std::io::Read>(&mut self, mut input: R) -> Result<usize, ParseError> {
+        let mut buff = Vec::new();
+        let mut chunk = [0u8; 64 * 1024];
+        let (mut pos, mut base, mut eof, mut forms) = (0, 0, false, 0);
+        let mut preamble = true;
+        loop {
+            if preamble {
+                match Exp::preambleEnd(buff.asArray(), eof, true) {
+                    Some(end) => { pos = end; preamble = false },
+                    None => {
+                        match input.read(&mut chunk) {
+                            Ok(0) => eof = true,
+                            Ok(n) => for c in chunk[..n].iter() { buff.pushBack(*c) },
+                            Err(_) => return Err(ParseError { message: String::from("unable to read input"), offset: buff.len(), kind: ErrorKind::Syntax })
+                        }
+                        continue
+                    }
+                }
+            }
+            let end = match Exp::wsEnd(buff.asArray(), pos, eof) {
+                Some(end) if end == buff.len() && eof => return Ok(forms),
+                Some(end) => { pos = end; Exp::formEnd(buff.asArray(), pos, eof) },
+                None => Ok(None),
+            };
+            match end {
+                Ok(Some(end)) => {
+                    self.dispatch(&buff.asArray()[pos..end], base + pos);
+                    forms += 1;
+                    pos = end;
+                },
+                Ok(None) => {
+                    let mut rest = Vec::new();
+                    for c in buff.asArray()[pos..].iter() { rest.pushBack(*c) }
+                    base += pos;
+                    pos = 0;
+                    buff = rest;
+                    match input.read(&mut chunk) {
+                        Ok(0) => eof = true,
+                        Ok(n) => for c in chunk[..n].iter() { buff.pushBack(*c) },
+                        Err(_) => return Err(ParseError { message: String::from("unable to read input"), offset: base + buff.len(), kind: ErrorKind::Syntax })
+                    }
+                },
+                Err(err) => return Err(ParseError { message: err.message, offset: err.offset + base, kind: err.kind }),
+            }
+        }
+    }
+}
+
+impl<'a> Default for Router<'a> {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testRouter() {
+        let src = b"(order 1 \"a)\") (ping) ( order #\\) 2)\n42 #u8\"0a ff\" (quote x #| ) |#) \"\"\"\n)\n\"\"\" (broken";
+        let mut orders = std::vec::Vec::new();
+        let mut others = std::vec::Vec::new();
+        let res = {
+            let mut r = Router::new();
+            r.on("order", |f, at| orders.push((std::str::from_utf8(f).unwrap().to_string(), at)))
+             .on("ping", |_, _| ())
+             .otherwise(|f, _| others.push(std::str::from_utf8(f).unwrap().to_string()));
+            r.route(&src[..])
+        };
+        assert!(orders == [("(order 1 \"a)\")".to_string(), 0), ("( order #\\) 2)".to_string(), 22)]);
+        assert!(others == ["42", "#u8\"0a ff\"", "(quote x #| ) |#)", "\"\"\"\n)\n\"\"\""]);
+        assert!(match res { Err(err) => err.offset() == src.len(), Ok(_) => false });
+    }
+}
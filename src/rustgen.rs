@@ -0,0 +1,240 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// rust bindings: `Schema::toRust` writes a struct per record and map of the
+// schema, with the `FromExp`/`ToExp` impls reading and writing them. Other
+// shapes map to plain types, unions and `map-of` stay `Exp`. Optional fields
+// are `Option`s, fields with a default read it when their entry is missing.
+//
+use alt_std::*;
+use crate::*;
+
+const RESERVED : [&str; 46] = [
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn", "else", "enum",
+    "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move",
+    "mut", "override", "priv", "pub", "ref", "return", "static", "struct", "trait", "true", "try", "type", "typeof",
+    "unsafe", "unsized", "use", "virtual", "where", "while",
+];
+
+// keywords that cannot be raw identifiers, they get a trailing `_`
+const NOT_RAW : [&str; 4] = ["crate", "self", "Self", "super"];
+
+struct RustGen {
+    out     : String,
+    names   : Vec<String>,
+}
+
+// `max-conns` is `max_conns`, `type` is `r#type` and `self` is `self_`, the
+// entries keep their original key
+fn rustIdent(name: &str) -> String {
+    let mut s = String::new();
+    if NOT_RAW.contains(&name) { return alt_std::format!("{}_", name) }
+    if RESERVED.contains(&name) { s.append("r#") }
+    if name.bytes().next().map_or(true, |c| c.is_ascii_digit()) { s.add(b'_') }
+    for c in name.bytes() {
+        s.add(if c.is_ascii_alphanumeric() { c } else { b'_' });
+    }
+    s
+}
+
+// `max-conns` is `MaxConns`
+fn camelCase(name: &str) -> String {
+    let mut s = String::new();
+    let mut upper = true;
+    for c in name.bytes() {
+        if !c.is_ascii_alphanumeric() { upper = true; continue }
+        if s.asArray().is_empty() && c.is_ascii_digit() { s.add(b'T') }
+        s.add(if upper { c.to_ascii_uppercase() } else { c });
+        upper = false;
+    }
+    if s.asArray().is_empty() { s.append("Anon") }
+    s
+}
+
+impl RustGen {
+    fn structName(&mut self, hint: &str, parent: &str) -> String {
+        let base = camelCase(hint);
+        let mut name = base.clone();
+        if self.names.asArray().contains(&name) {
+            name = String::from(parent);
+            name.append(&base);
+        }
+        let mut n = 2;
+        while self.names.asArray().contains(&name) {
+            name = alt_std::format!("{}{}", base.toStr(), n);
+            n += 1;
+        }
+        self.names.pushBack(name.clone());
+        name
+    }
+
+    fn rustType(&mut self, s: &Schema, hint: &str, parent: &str) -> String {
+        match s {
+            Schema::Bool => String::from("bool"),
+            Schema::Char => String::from("char"),
+            Schema::UInt => String::from("u64"),
+            Schema::Int => String::from("i64"),
+            Schema::Float => String::from("f64"),
+            Schema::String => String::from("::alt_std::String"),
+            Schema::Symbol => String::from("::s_exp::Sym"),
+            Schema::Keyword => String::from("::s_exp::Kw"),
+            Schema::List(e) => {
+                let t = self.rustType(e, hint, parent);
+                alt_std::format!("::alt_std::Vec<{}>", t.toStr())
+            },
+            Schema::Record(..) | Schema::Map(_) => {
+                let name = self.structName(hint, parent);
+                self.emitStruct(&name, s);
+                name
+            },
+            Schema::Any | Schema::BigInt | Schema::Ratio | Schema::Bytes | Schema::Vector(_) | Schema::Pair(..) | Schema::MapOf(..) | Schema::Or(_) | Schema::Form(..) => String::from("::s_exp::Exp"),
+        }
+    }
+
+    fn emitStruct(&mut self, name: &String, s: &Schema) {
+        let (tag, fields, map) = match s {
+            Schema::Record(tag, fields) => (tag.as_ref(), fields, false),
+            Schema::Map(fields) => (None, fields, true),
+            _ => return
+        };
+
+        let mut decl = String::new();
+        let mut from = String::new();
+        let mut to = String::new();
+        for f in fields.asArray().iter() {
+            let ident = rustIdent(f.name.toStr());
+            let mut ty = self.rustType(&f.schema, f.name.toStr(), name.toStr());
+            // a default fills the missing entry
+            let default = f.default.as_ref().map(|d| d.toRustTokens());
+            if !f.required() && default.is_none() { ty = alt_std::format!("Option<{}>", ty.toStr()) }
+            // list and record values follow the key of their entry
+            let spread = matches!(f.schema, Schema::List(_) | Schema::Record(None, _));
+            decl.append(&alt_std::format!("    pub {}: {},\n", ident.toStr(), ty.toStr()));
+            let (i, n) = (ident.toStr(), f.name.toStr());
+            from.append(&match (&default, map) {
+                (Some(d), true) => alt_std::format!("            {}: e.mapFieldOr({:?}, &{})?,\n", i, n, d.toStr()),
+                (Some(d), false) => alt_std::format!("            {}: ::s_exp::Exp::recordFieldOr(entries, {:?}, {}, &{})?,\n", i, n, spread, d.toStr()),
+                (None, true) => alt_std::format!("            {}: e.mapField({:?})?,\n", i, n),
+                (None, false) => alt_std::format!("            {}: ::s_exp::Exp::recordField(entries, {:?}, {})?,\n", i, n, spread),
+            });
+            to.append(&match map {
+                true => alt_std::format!("        m.setMapField({:?}, &self.{});\n", n, i),
+                false => alt_std::format!("        ::s_exp::Exp::pushRecordField(&mut v, {:?}, &self.{}, {});\n", n, i, spread),
+            });
+        }
+
+        let out = &mut self.out;
+        out.append(&alt_std::format!("#[derive(Clone)]\npub struct {} {{\n", name.toStr()));
+        out.append(&decl);
+        out.append("}\n\n");
+
+        out.append(&alt_std::format!("impl ::s_exp::FromExp for {} {{\n", name.toStr()));
+        out.append("    fn fromExp(e: &::s_exp::Exp) -> Result<Self, ::alt_std::String> {\n");
+        if !map {
+            match tag {
+                Some(t) => out.append(&alt_std::format!("        let entries = e.recordEntries(Some({:?}))?;\n", t.toStr())),
+                None => out.append("        let entries = e.recordEntries(None)?;\n"),
+            }
+        }
+        out.append("        Ok(Self {\n");
+        out.append(&from);
+        out.append("        })\n    }\n}\n\n");
+
+        out.append(&alt_std::format!("impl ::s_exp::ToExp for {} {{\n", name.toStr()));
+        out.append("    fn toExp(&self) -> ::s_exp::Exp {\n");
+        if map {
+            out.append("        let mut m = ::s_exp::Exp::Map(::alt_std::Vec::new());\n");
+            out.append(&to);
+            out.append("        m\n");
+        } else {
+            out.append("        let mut v = ::alt_std::Vec::new();\n");
+            if let Some(t) = tag {
+                out.append(&alt_std::format!("        v.pushBack(::s_exp::Exp::Symbol(::alt_std::String::from({:?})));\n", t.toStr()));
+            }
+            out.append(&to);
+            out.append("        ::s_exp::Exp::List(v)\n");
+        }
+        out.append("    }\n}\n\n");
+    }
+}
+
+impl Schema {
+    /// rust source of the bindings, the root type is `name`, nested records
+    /// and maps are named after their field
+    pub fn toRust(&self, name: &str) -> String {
+        let mut g = RustGen { out: String::from("// generated from an s-expression schema, do not edit\n\n"), names: Vec::new() };
+        match self {
+            Schema::Record(..) | Schema::Map(_) => {
+                g.names.pushBack(String::from(name));
+                g.emitStruct(&String::from(name), self);
+            },
+            s => {
+                let t = g.rustType(s, name, "");
+                g.out.append(&alt_std::format!("pub type {} = {};\n", name, t.toStr()));
+            }
+        }
+        g.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testToRust() {
+        let schema = match Exp::fromSExp(b"(record server (max-conns int) (type symbol optional) (limits (map (rps float))))") {
+            PROk(e) => Schema::fromExp(&e).ok().unwrap(),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let src = schema.toRust("Config");
+        assert!(src.toStr().contains("pub struct Config {\n    pub max_conns: i64,\n    pub r#type: Option<::s_exp::Sym>,\n    pub limits: Limits,\n}"));
+        assert!(src.toStr().contains("pub struct Limits {\n    pub rps: f64,\n}"));
+        assert!(src.toStr().contains("let entries = e.recordEntries(Some(\"server\"))?;"));
+        assert!(src.toStr().contains("max_conns: ::s_exp::Exp::recordField(entries, \"max-conns\", false)?,"));
+        assert!(src.toStr().contains("m.setMapField(\"rps\", &self.rps);"));
+        assert!(Schema::Int.toRust("Port").toStr().ends_with("pub type Port = i64;\n"));
+    }
+
+    // the bindings of FIXTURE_SCHEMA as generated, compiled with the tests
+    mod generated {
+        include!("testdata/rustgen_config.rs");
+    }
+
+    #[test]
+    fn testGeneratedBindings() {
+        let schema = match Exp::fromSExp(FIXTURE_SCHEMA.as_bytes()) {
+            PROk(e) => Schema::fromExp(&e).ok().unwrap(),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(schema.toRust("Config").toStr() == include_str!("testdata/rustgen_config.rs"));
+
+        let e = match Exp::fromSExp(b"(server (max-conns 64) (self \"me\") (tags web api) (limits {rps 2.5}))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let c = match generated::Config::fromExp(&e) { Ok(c) => c, Err(m) => panic!("{}", m.toStr()) };
+        assert!(c.max_conns == 64 && c.r#type.is_none() && c.self_ == "me" && c.crate_.is_none());
+        assert!(c.port == 80 && c.tags.len() == 2 && c.limits.rps == 2.5 && c.limits.burst == 10);
+        assert!(c.toExp().toString() == "(server (max-conns 64) (self \"me\") (port 80) (tags web api) (limits {rps 2.5 burst 10}))");
+    }
+
+    const FIXTURE_SCHEMA : &str = "(record server (max-conns int) (type symbol optional) (self string) (crate keyword optional) (port int (default 80)) (tags (list symbol)) (limits (map (rps float) (burst int (default 10)))))";
+}
@@ -0,0 +1,598 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// schemas: structural types of documents, written as s-expressions
+//
+//  any bool char uint int bigint ratio float string symbol keyword bytes
+//  (list T)                    homogeneous list
+//  (vector T)                  homogeneous vector
+//  (pair A D)                  `(car . cdr)`
+//  (record [tag] field...)     list of `(key value...)` entries, after a head symbol when tagged
+//  (map field...)              map with symbol keys
+//  (map-of K V)                any other map
+//  (form [head] param...)      positional arguments after a head symbol when given
+//  (or T...)                   one of
+//
+// a field is `(name T)` followed by any of `optional`, `(default value)` and
+// `(doc "text")`, a field with a default is not required. The value of an entry is its
+// single element, or the list of its elements otherwise (so `(port 80)` is an
+// int and `(server (port 80))` a record).
+//
+// form parameters are fields matched by position, an optional one can only be
+// followed by optional ones and the last can be a variadic tail `(name T ...)`
+// taking the remaining arguments, as in
+// `(form defrule (name symbol) (priority int (default 0)) (body any ...))`.
+//
+// each number type also accepts the ones before it in uint int bigint ratio float.
+//
+use std::boxed::Box;
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone)]
+pub enum Schema {
+    // nothing known (elements of empty lists), merges into anything
+    Any,
+    Bool,
+    Char,
+    UInt,
+    Int,
+    BigInt,
+    Ratio,
+    Float,
+    String,
+    Symbol,
+    Keyword,
+    Bytes,
+    List(Box<Schema>),
+    Vector(Box<Schema>),
+    Pair(Box<Schema>, Box<Schema>),
+    Record(Option<String>, Vec<Field>),
+    Map(Vec<Field>),
+    MapOf(Box<Schema>, Box<Schema>),
+    Or(Vec<Schema>),
+    Form(Option<String>, Vec<Field>, Option<Box<Field>>),
+}
+
+#[derive(Clone, PartialEq)]
+pub struct Field {
+    pub name        : String,
+    pub schema      : Schema,
+    pub optional    : bool,
+    pub default     : Option<Exp>,
+    pub doc         : Option<String>,
+}
+
+impl Field {
+    pub fn new(name: String, schema: Schema) -> Self {
+        Self { name, schema, optional: false, default: None, doc: None }
+    }
+
+    pub fn required(&self) -> bool { !self.optional && self.default.is_none() }
+}
+
+impl PartialEq for Schema {
+    fn eq(&self, other: &Schema) -> bool {
+        match (self, other) {
+            (Self::List(a), Self::List(b)) | (Self::Vector(a), Self::Vector(b)) => a == b,
+            (Self::Pair(a0, d0), Self::Pair(a1, d1)) => a0 == a1 && d0 == d1,
+            (Self::Record(t0, f0), Self::Record(t1, f1)) => t0 == t1 && f0.asArray() == f1.asArray(),
+            (Self::Map(f0), Self::Map(f1)) => f0.asArray() == f1.asArray(),
+            (Self::MapOf(k0, v0), Self::MapOf(k1, v1)) => k0 == k1 && v0 == v1,
+            (Self::Or(a), Self::Or(b)) => a.asArray() == b.asArray(),
+            (Self::Form(h0, p0, r0), Self::Form(h1, p1, r1)) => h0 == h1 && p0.asArray() == p1.asArray() && r0 == r1,
+            (a, b) => a.atomName().is_some() && a.atomName() == b.atomName(),
+        }
+    }
+}
+
+const ATOMS : [(&str, Schema); 12] = [
+    ("any", Schema::Any), ("bool", Schema::Bool), ("char", Schema::Char), ("uint", Schema::UInt), ("int", Schema::Int),
+    ("bigint", Schema::BigInt), ("ratio", Schema::Ratio), ("float", Schema::Float), ("string", Schema::String), ("symbol", Schema::Symbol), ("keyword", Schema::Keyword),
+    ("bytes", Schema::Bytes),
+];
+
+impl Exp {
+    // the value of a `(key value...)` entry
+    pub(crate) fn fieldValue(&self) -> Exp {
+        match self {
+            Self::List(l) if l.len() == 2 && l[1].entryKey().is_none() => l[1].clone(),
+            _ => self.fieldItems(),
+        }
+    }
+
+    // the elements of an entry after its key
+    pub(crate) fn fieldItems(&self) -> Exp {
+        let mut v = Vec::new();
+        if let Self::List(l) = self {
+            for e in l.asArray().iter().skip(1) { v.pushBack(e.clone()) }
+        }
+        Self::List(v)
+    }
+
+    // `(key value...)` entries with distinct keys, None otherwise
+    fn entries(l: &[Exp]) -> Option<&[Exp]> {
+        for i in 0..l.len() {
+            let k = l[i].entryKey()?;
+            if l[..i].iter().any(|e| e.entryKey() == Some(k)) { return None }
+        }
+        Some(l)
+    }
+
+    /// schema of this document, see `Schema::infer`
+    pub fn inferShape(&self) -> Exp {
+        Schema::infer(core::slice::from_ref(self)).toExp()
+    }
+}
+
+impl Schema {
+    pub(crate) fn atomName(&self) -> Option<&'static str> {
+        ATOMS.iter().find(|(_, s)| core::mem::discriminant(s) == core::mem::discriminant(self)).map(|(n, _)| *n)
+    }
+
+    // position in uint int bigint ratio float, a number type accepts the ones before it
+    pub(crate) fn numberRank(&self) -> Option<usize> {
+        match self {
+            Self::UInt => Some(0),
+            Self::Int => Some(1),
+            Self::BigInt => Some(2),
+            Self::Ratio => Some(3),
+            Self::Float => Some(4),
+            _ => None
+        }
+    }
+
+    fn fieldsOf(entries: &[Exp]) -> Vec<Field> {
+        let mut fields = Vec::new();
+        for e in entries.iter() {
+            let name = match e.entryKey() { Some(k) => k.clone(), None => continue };
+            fields.pushBack(Field::new(name, Self::shapeOf(&e.fieldValue())));
+        }
+        fields
+    }
+
+    fn shapeOf(e: &Exp) -> Schema {
+        match e {
+            Exp::Bool(_) => Self::Bool,
+            Exp::Char(_) => Self::Char,
+            Exp::UInt(_) => Self::UInt,
+            Exp::Int(_) => Self::Int,
+            #[cfg(feature = "bigint")]
+            Exp::BigInt(_) => Self::BigInt,
+            Exp::Ratio(..) => Self::Ratio,
+            Exp::Float(_) => Self::Float,
+            Exp::String(_) | Exp::Spilled(_) => Self::String,
+            Exp::Symbol(_) => Self::Symbol,
+            Exp::Keyword(_) => Self::Keyword,
+            Exp::Bytes(_) => Self::Bytes,
+            Exp::Pair(a, d) => Self::Pair(Box::new(Self::shapeOf(a)), Box::new(Self::shapeOf(d))),
+            Exp::List(l) if l.len() > 0 && Exp::entries(l.asArray()).is_some() => Self::Record(None, Self::fieldsOf(l.asArray())),
+            Exp::List(l) if l.len() > 1 && Exp::entries(&l.asArray()[1..]).is_some() => match &l[0] {
+                Exp::Symbol(tag) => Self::Record(Some(tag.clone()), Self::fieldsOf(&l.asArray()[1..])),
+                _ => Self::List(Box::new(Self::mergeAll(l.asArray().iter().map(Self::shapeOf)))),
+            },
+            Exp::List(l) => Self::List(Box::new(Self::mergeAll(l.asArray().iter().map(Self::shapeOf)))),
+            // a vector is an array of data, it never holds entries
+            Exp::Vector(v) => Self::Vector(Box::new(Self::mergeAll(v.asArray().iter().map(Self::shapeOf)))),
+            Exp::Map(m) if m.asArray().iter().all(|(k, _)| matches!(k, Exp::Symbol(_))) => {
+                let mut fields = Vec::new();
+                for (k, v) in m.asArray().iter() {
+                    if let Exp::Symbol(name) = k {
+                        fields.pushBack(Field::new(name.clone(), Self::shapeOf(v)));
+                    }
+                }
+                Self::Map(fields)
+            },
+            Exp::Map(m) => Self::MapOf(Box::new(Self::mergeAll(m.asArray().iter().map(|(k, _)| Self::shapeOf(k)))),
+                                       Box::new(Self::mergeAll(m.asArray().iter().map(|(_, v)| Self::shapeOf(v))))),
+        }
+    }
+
+    fn mergeAll<I: Iterator<Item = Schema>>(it: I) -> Schema {
+        it.fold(Self::Any, |a, b| a.merge(&b))
+    }
+
+    // an entry with a single element may be a list of one
+    fn mergeValues(a: &Schema, b: &Schema) -> Schema {
+        match (a, b) {
+            (Self::List(l), s) | (s, Self::List(l)) if !matches!(s, Self::List(_)) && l.sameKind(s) => Self::List(Box::new(l.merge(s))),
+            _ => a.merge(b)
+        }
+    }
+
+    // fields missing on one side become optional
+    fn mergeFields(a: &Vec<Field>, b: &Vec<Field>) -> Vec<Field> {
+        let mut fields = Vec::new();
+        for f in a.asArray().iter() {
+            match b.asArray().iter().find(|g| g.name == f.name) {
+                Some(g) => fields.pushBack(Field { schema: Self::mergeValues(&f.schema, &g.schema), optional: f.optional || g.optional, ..f.clone() }),
+                None => fields.pushBack(Field { optional: true, ..f.clone() }),
+            }
+        }
+        for g in b.asArray().iter() {
+            if !a.asArray().iter().any(|f| f.name == g.name) {
+                fields.pushBack(Field { optional: true, ..g.clone() });
+            }
+        }
+        fields
+    }
+
+    // same kind of value, so `merge` does not need an `or`
+    pub(crate) fn sameKind(&self, other: &Schema) -> bool {
+        match (self, other) {
+            (a, b) if a.numberRank().is_some() && b.numberRank().is_some() => true,
+            (Self::Record(t0, _), Self::Record(t1, _)) => t0 == t1,
+            (Self::Form(..), b) => self == b,
+            (a, b) => core::mem::discriminant(a) == core::mem::discriminant(b),
+        }
+    }
+
+    /// smallest schema accepting what either accepts
+    pub fn merge(&self, other: &Schema) -> Schema {
+        match (self, other) {
+            (Self::Any, s) | (s, Self::Any) => s.clone(),
+            (Self::Or(a), Self::Or(b)) => b.asArray().iter().fold(Self::Or(a.clone()), |u, s| u.merge(s)),
+            (Self::Or(u), s) | (s, Self::Or(u)) => {
+                let mut v = Vec::new();
+                let mut merged = false;
+                for m in u.asArray().iter() {
+                    if !merged && m.sameKind(s) { v.pushBack(m.merge(s)); merged = true } else { v.pushBack(m.clone()) }
+                }
+                if !merged { v.pushBack(s.clone()) }
+                Self::Or(v)
+            },
+            (a, b) if a.numberRank().is_some() && b.numberRank().is_some() => if a.numberRank() < b.numberRank() { b.clone() } else { a.clone() },
+            (Self::List(a), Self::List(b)) => Self::List(Box::new(a.merge(b))),
+            (Self::Vector(a), Self::Vector(b)) => Self::Vector(Box::new(a.merge(b))),
+            (Self::Pair(a0, d0), Self::Pair(a1, d1)) => Self::Pair(Box::new(a0.merge(a1)), Box::new(d0.merge(d1))),
+            (Self::Record(t0, a), Self::Record(t1, b)) if t0 == t1 => Self::Record(t0.clone(), Self::mergeFields(a, b)),
+            (Self::Map(a), Self::Map(b)) => Self::Map(Self::mergeFields(a, b)),
+            (Self::MapOf(k0, v0), Self::MapOf(k1, v1)) => Self::MapOf(Box::new(k0.merge(k1)), Box::new(v0.merge(v1))),
+            (a, b) if a.sameKind(b) => a.clone(),
+            (a, b) => {
+                let mut v = Vec::new();
+                v.pushBack(a.clone());
+                v.pushBack(b.clone());
+                Self::Or(v)
+            }
+        }
+    }
+
+    /// schema accepting every example, fields absent from some are optional
+    pub fn infer(docs: &[Exp]) -> Schema {
+        Self::mergeAll(docs.iter().map(Self::shapeOf))
+    }
+
+    fn fieldsToExp(fields: &Vec<Field>, out: &mut Vec<Exp>) {
+        for f in fields.asArray().iter() {
+            let mut e = Vec::new();
+            e.pushBack(Exp::Symbol(f.name.clone()));
+            e.pushBack(f.schema.toExp());
+            if f.optional { e.pushBack(Exp::Symbol(String::from("optional"))) }
+            let mut attr = |name: &str, v: Exp| {
+                let mut a = Vec::new();
+                a.pushBack(Exp::Symbol(String::from(name)));
+                a.pushBack(v);
+                e.pushBack(Exp::List(a));
+            };
+            if let Some(d) = &f.default { attr("default", d.clone()) }
+            if let Some(d) = &f.doc { attr("doc", Exp::String(d.clone())) }
+            out.pushBack(Exp::List(e));
+        }
+    }
+
+    pub fn toExp(&self) -> Exp {
+        if let Some(name) = self.atomName() {
+            return Exp::Symbol(String::from(name))
+        }
+        let mut v = Vec::new();
+        let head = match self {
+            Self::List(_) => "list",
+            Self::Vector(_) => "vector",
+            Self::Pair(..) => "pair",
+            Self::Record(..) => "record",
+            Self::Map(_) => "map",
+            Self::MapOf(..) => "map-of",
+            Self::Form(..) => "form",
+            _ => "or",
+        };
+        v.pushBack(Exp::Symbol(String::from(head)));
+        match self {
+            Self::List(s) | Self::Vector(s) => v.pushBack(s.toExp()),
+            Self::Pair(a, d) => { v.pushBack(a.toExp()); v.pushBack(d.toExp()) },
+            Self::Record(tag, fields) => {
+                if let Some(t) = tag { v.pushBack(Exp::Symbol(t.clone())) }
+                Self::fieldsToExp(fields, &mut v);
+            },
+            Self::Map(fields) => Self::fieldsToExp(fields, &mut v),
+            Self::MapOf(k, s) => { v.pushBack(k.toExp()); v.pushBack(s.toExp()) },
+            Self::Or(u) => for s in u.asArray().iter() { v.pushBack(s.toExp()) },
+            Self::Form(head, params, rest) => {
+                if let Some(h) = head { v.pushBack(Exp::Symbol(h.clone())) }
+                Self::fieldsToExp(params, &mut v);
+                if let Some(r) = rest {
+                    let mut tail = Vec::new();
+                    tail.pushBack((**r).clone());
+                    let mut out = Vec::new();
+                    Self::fieldsToExp(&tail, &mut out);
+                    if let Some(Exp::List(l)) = out.pop() {
+                        let mut items = Vec::new();
+                        for (i, e) in l.asArray().iter().enumerate() {
+                            items.pushBack(e.clone());
+                            if i == 1 { items.pushBack(Exp::Symbol(String::from("..."))) }
+                        }
+                        v.pushBack(Exp::List(items));
+                    }
+                }
+            },
+            _ => ()
+        }
+        Exp::List(v)
+    }
+
+    fn fieldsFromExp(forms: &[Exp]) -> Result<Vec<Field>, String> {
+        let mut fields = Vec::new();
+        for f in forms.iter() {
+            let invalid = || Err(alt_std::format!("invalid field {}", f.toString().toStr()));
+            let l = match f { Exp::List(l) if l.len() >= 2 => l, _ => return invalid() };
+            let mut field = match &l[0] { Exp::Symbol(n) => Field::new(n.clone(), Self::fromExp(&l[1])?), _ => return invalid() };
+            for a in l.asArray()[2..].iter() {
+                match a {
+                    Exp::Symbol(o) if o.toStr() == "optional" => field.optional = true,
+                    Exp::List(a) if a.len() == 2 && a[0] == Exp::Symbol(String::from("default")) => field.default = Some(a[1].clone()),
+                    Exp::List(a) if a.len() == 2 && a[0] == Exp::Symbol(String::from("doc")) => match &a[1] {
+                        Exp::String(d) => field.doc = Some(d.clone()),
+                        _ => return invalid()
+                    },
+                    _ => return invalid()
+                }
+            }
+            fields.pushBack(field);
+        }
+        Ok(fields)
+    }
+
+    fn paramsFromExp(forms: &[Exp]) -> Result<(Vec<Field>, Option<Box<Field>>), String> {
+        let dots = Exp::Symbol(String::from("..."));
+        let mut params = Vec::new();
+        let mut rest = None;
+        for (i, f) in forms.iter().enumerate() {
+            let items = match f { Exp::List(l) => l.asArray(), _ => core::slice::from_ref(f) };
+            if items.contains(&dots) {
+                if i != forms.len() - 1 { return Err(alt_std::format!("variadic parameter {} is not the last", f.toString().toStr())) }
+                let mut v = Vec::new();
+                for e in items.iter().filter(|e| **e != dots) { v.pushBack(e.clone()) }
+                rest = Self::fieldsFromExp(&[Exp::List(v)])?.pop().map(Box::new);
+                continue
+            }
+            let p = Self::fieldsFromExp(core::slice::from_ref(f))?.pop();
+            if let Some(p) = p {
+                if p.required() && params.asArray().last().is_some_and(|q: &Field| !q.required()) {
+                    return Err(alt_std::format!("required parameter {} after an optional one", p.name.toStr()))
+                }
+                params.pushBack(p);
+            }
+        }
+        Ok((params, rest))
+    }
+
+    /// reads back the form written by `toExp`
+    pub fn fromExp(e: &Exp) -> Result<Schema, String> {
+        let invalid = || Err(alt_std::format!("invalid schema {}", e.toString().toStr()));
+        let l = match e {
+            Exp::Symbol(s) => return match ATOMS.iter().find(|(n, _)| s.toStr() == *n) { Some((_, a)) => Ok(a.clone()), None => invalid() },
+            Exp::List(l) if l.len() > 0 => l.asArray(),
+            _ => return invalid()
+        };
+        let head = match &l[0] { Exp::Symbol(h) => h.toStr(), _ => return invalid() };
+        match (head, l.len()) {
+            ("list", 2) => Ok(Self::List(Box::new(Self::fromExp(&l[1])?))),
+            ("vector", 2) => Ok(Self::Vector(Box::new(Self::fromExp(&l[1])?))),
+            ("pair", 3) => Ok(Self::Pair(Box::new(Self::fromExp(&l[1])?), Box::new(Self::fromExp(&l[2])?))),
+            ("record", _) => match l.get(1) {
+                Some(Exp::Symbol(tag)) => Ok(Self::Record(Some(tag.clone()), Self::fieldsFromExp(&l[2..])?)),
+                _ => Ok(Self::Record(None, Self::fieldsFromExp(&l[1..])?)),
+            },
+            ("map", _) => Ok(Self::Map(Self::fieldsFromExp(&l[1..])?)),
+            ("map-of", 3) => Ok(Self::MapOf(Box::new(Self::fromExp(&l[1])?), Box::new(Self::fromExp(&l[2])?))),
+            ("form", _) => {
+                let (head, params) = match l.get(1) {
+                    Some(Exp::Symbol(h)) => (Some(h.clone()), &l[2..]),
+                    _ => (None, &l[1..]),
+                };
+                let (params, rest) = Self::paramsFromExp(params)?;
+                Ok(Self::Form(head, params, rest))
+            },
+            ("or", _) => {
+                let mut u = Vec::new();
+                for s in l[1..].iter() { u.pushBack(Self::fromExp(s)?) }
+                Ok(Self::Or(u))
+            },
+            _ => invalid()
+        }
+    }
+
+    fn checkFields(fields: &Vec<Field>, values: &[(&String, Exp)], path: &Path, errs: &mut Vec<String>) {
+        for f in fields.asArray().iter() {
+            match values.iter().find(|(k, _)| **k == f.name) {
+                Some((_, v)) => f.schema.check(v, &path.child(PathSeg::Key(f.name.clone())), errs),
+                None if !f.required() => (),
+                None => errs.pushBack(alt_std::format!("{}: missing field {}", path.toString().toStr(), f.name.toStr())),
+            }
+        }
+        for (k, _) in values.iter() {
+            if !fields.asArray().iter().any(|f| f.name == **k) {
+                errs.pushBack(alt_std::format!("{}: unexpected field {}", path.toString().toStr(), k.toStr()));
+            }
+        }
+    }
+
+    fn check(&self, e: &Exp, path: &Path, errs: &mut Vec<String>) {
+        match (self, e) {
+            (Self::Any, _) | (Self::Bool, Exp::Bool(_)) | (Self::Char, Exp::Char(_)) | (Self::String, Exp::String(_)) | (Self::String, Exp::Spilled(_)) | (Self::Symbol, Exp::Symbol(_)) |
+            (Self::Keyword, Exp::Keyword(_)) | (Self::Bytes, Exp::Bytes(_)) => (),
+            (s, e) if s.numberRank().is_some() && Self::shapeOf(e).numberRank().is_some_and(|r| Some(r) <= s.numberRank()) => (),
+            (Self::List(s), Exp::List(l)) | (Self::Vector(s), Exp::Vector(l)) => for i in 0..l.len() { s.check(&l[i], &path.child(PathSeg::Index(i)), errs) },
+            (Self::Pair(a, d), Exp::Pair(car, cdr)) => {
+                a.check(car, &path.child(PathSeg::Index(0)), errs);
+                d.check(cdr, &path.child(PathSeg::Index(1)), errs);
+            },
+            (Self::Record(tag, fields), Exp::List(l)) => {
+                let entries = match (tag, l.asArray().first()) {
+                    (None, _) => l.asArray(),
+                    (Some(t), Some(Exp::Symbol(h))) if h == t => &l.asArray()[1..],
+                    (Some(t), _) => return errs.pushBack(alt_std::format!("{}: expected ({} ...)", path.toString().toStr(), t.toStr())),
+                };
+                let mut values = std::vec::Vec::new();
+                for en in entries.iter() {
+                    match en.entryKey() {
+                        // `(tags a)` is a list of one element when a list is expected
+                        Some(k) => values.push((k, match fields.asArray().iter().find(|f| f.name == *k) {
+                            Some(Field { schema: Self::List(_), .. }) => en.fieldItems(),
+                            _ => en.fieldValue(),
+                        })),
+                        None => return errs.pushBack(alt_std::format!("{}: expected (key value...) entries", path.toString().toStr())),
+                    }
+                }
+                Self::checkFields(fields, &values, path, errs)
+            },
+            (Self::Map(fields), Exp::Map(m)) => {
+                let mut values = std::vec::Vec::new();
+                for (k, v) in m.asArray().iter() {
+                    match k {
+                        Exp::Symbol(k) => values.push((k, v.clone())),
+                        _ => return errs.pushBack(alt_std::format!("{}: expected symbol keys", path.toString().toStr())),
+                    }
+                }
+                Self::checkFields(fields, &values, path, errs)
+            },
+            (Self::Form(head, params, rest), Exp::List(l)) => {
+                let skip = match (head, l.asArray().first()) {
+                    (None, _) => 0,
+                    (Some(h), Some(Exp::Symbol(s))) if s == h => 1,
+                    (Some(h), _) => return errs.pushBack(alt_std::format!("{}: expected ({} ...)", path.toString().toStr(), h.toStr())),
+                };
+                let args = &l.asArray()[skip..];
+                for (i, p) in params.asArray().iter().enumerate() {
+                    match args.get(i) {
+                        Some(a) => p.schema.check(a, &path.child(PathSeg::Index(i + skip)), errs),
+                        None if !p.required() => (),
+                        None => errs.pushBack(alt_std::format!("{}: missing argument {}", path.toString().toStr(), p.name.toStr())),
+                    }
+                }
+                for i in params.len()..args.len() {
+                    match rest {
+                        Some(r) => r.schema.check(&args[i], &path.child(PathSeg::Index(i + skip)), errs),
+                        None => return errs.pushBack(alt_std::format!("{}: unexpected argument {}", path.toString().toStr(), args[i].toString().toStr())),
+                    }
+                }
+            },
+            (Self::MapOf(ks, vs), Exp::Map(m)) => for (k, v) in m.asArray().iter() {
+                ks.check(k, path, errs);
+                vs.check(v, path, errs);
+            },
+            (Self::Or(u), e) if u.asArray().iter().any(|s| { let mut none = Vec::new(); s.check(e, path, &mut none); none.len() == 0 }) => (),
+            (s, e) => errs.pushBack(alt_std::format!("{}: expected {}, found {}", path.toString().toStr(), s.toExp().toString().toStr(), e.toString().toStr())),
+        }
+    }
+
+    /// every mismatch between `e` and the schema, as `path: message`
+    pub fn validate(&self, e: &Exp) -> Result<(), Vec<String>> {
+        let mut errs = Vec::new();
+        self.check(e, &Path::new(), &mut errs);
+        if errs.len() == 0 { Ok(()) } else { Err(errs) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(s: &str) -> Exp {
+        match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+    }
+
+    #[test]
+    fn testInferShape() {
+        let docs = [
+            parse("(server (port 80) (host \"a\") (tags web edge) (limits {rps 10}))"),
+            parse("(server (port 8080.5) (tags) (tls #t) (limits {rps 20 burst 5}))"),
+            parse("(server (port 81) (tags web) (limits {rps 30}))"),
+        ];
+        assert!(docs[0].inferShape().toString() == "(record server (port int) (host string) (tags (list symbol)) (limits (map (rps int))))");
+        let s = Schema::infer(&docs);
+        let e = s.toExp();
+        assert!(e.toString() == "(record server (port float) (host string optional) (tags (list symbol)) \
+                                 (limits (map (rps int) (burst int optional))) (tls bool optional))");
+        assert!(Schema::fromExp(&e) == Ok(s.clone()));
+        assert!(docs.iter().all(|d| s.validate(d).is_ok()));
+
+        let errs = match s.validate(&parse("(server (port \"x\") (tags 1) (mode fast))")) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs.len() == 4);
+        assert!(errs[0] == "port: expected float, found \"x\"");
+        assert!(errs[1] == "tags.0: expected symbol, found 1");
+        assert!(errs[2] == ": missing field limits" && errs[3] == ": unexpected field mode");
+
+        assert!(parse("(1 a 2.5 \"s\")").inferShape().toString() == "(list (or float symbol string))");
+        assert!(Schema::fromExp(&parse("(list nope)")).is_err());
+        let s = parse("(map (port int (default 80) (doc \"listening port\")) (tls bool optional))");
+        let m = Schema::fromExp(&s).ok().unwrap();
+        assert!(m.toExp() == s && m.validate(&parse("{tls #t}")).is_ok());
+    }
+
+    #[test]
+    fn testInferExactTypes() {
+        let d = parse("((id 18446744073709551615) (scale 1/3) (dims #(1 2)) (range (0 . 10)))");
+        let s = Schema::infer(core::slice::from_ref(&d));
+        assert!(s.toExp().toString() == "(record (id uint) (scale ratio) (dims (vector int)) (range (pair int int)))");
+        assert!(Schema::fromExp(&s.toExp()) == Ok(s.clone()) && s.validate(&d).is_ok());
+        let errs = match s.validate(&parse("((id 1) (scale 0.5) (dims (1 2)) (range (0 10)))")) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs.len() == 4 && errs[2] == "dims: expected (vector int), found (1 2)");
+        assert!(parse("(1 1/2 18446744073709551615)").inferShape().toString() == "(list ratio)");
+        assert!(Schema::Float.validate(&parse("1/2")).is_ok() && Schema::Int.validate(&parse("1/2")).is_err());
+        #[cfg(feature = "bigint")]
+        {
+            let big = Exp::bigInt(match BigInt::parse("100000000000000000000") { Some(b) => b, None => panic!("bigint") });
+            assert!(Schema::infer(&[Exp::Int(1), big.clone()]).toExp().toString() == "bigint");
+            assert!(Schema::BigInt.validate(&big).is_ok() && Schema::Int.validate(&big).is_err());
+        }
+    }
+
+    #[test]
+    fn testFormSchema() {
+        let s = parse("(form define (name symbol) (value any) (doc string optional) (meta symbol ...))");
+        let f = Schema::fromExp(&s).ok().unwrap();
+        assert!(f.toExp() == s);
+        assert!(f.validate(&parse("(define x 1)")).is_ok());
+        assert!(f.validate(&parse("(define x 1 \"doc\" a b)")).is_ok());
+        let errs = match f.validate(&parse("(define 1)")) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs.len() == 2 && errs[0] == "1: expected symbol, found 1" && errs[1] == ": missing argument value");
+        let errs = match f.validate(&parse("(let x 1)")) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs[0] == ": expected (define ...)");
+        let g = Schema::fromExp(&parse("(form (a int) (b int optional))")).ok().unwrap();
+        let errs = match g.validate(&parse("(1 2 3)")) { Err(e) => e, Ok(()) => panic!("expected errors") };
+        assert!(errs[0] == ": unexpected argument 3");
+        assert!(Schema::fromExp(&parse("(form f (a int ...) (b int))")).is_err());
+        assert!(Schema::fromExp(&parse("(form f (a int optional) (b int))")).is_err());
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// schema documentation: a section per record and map of the schema with a
+// table of its fields, and an example document built from the defaults,
+// rendered as Markdown or HTML
+//
+use alt_std::*;
+use crate::*;
+
+struct Row {
+    name        : String,
+    ty          : String,
+    // section the type links to
+    link        : Option<String>,
+    required    : bool,
+    default     : Option<String>,
+    doc         : Option<String>,
+}
+
+struct Section {
+    title       : String,
+    form        : String,
+    rows        : Vec<Row>,
+}
+
+// `config.limits` is `config-limits`
+fn anchor(title: &str) -> String {
+    let mut s = String::new();
+    for c in title.bytes() {
+        s.add(if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { b'-' });
+    }
+    s
+}
+
+impl Schema {
+    fn typeText(&self, title: &str, sections: &mut Vec<Section>) -> (String, Option<String>) {
+        match self {
+            Self::List(e) => {
+                let (t, link) = e.typeText(title, sections);
+                (alt_std::format!("list of {}", t.toStr()), link)
+            },
+            Self::Vector(e) => {
+                let (t, link) = e.typeText(title, sections);
+                (alt_std::format!("vector of {}", t.toStr()), link)
+            },
+            Self::Pair(a, d) => {
+                let (at, _) = a.typeText(title, sections);
+                let (dt, link) = d.typeText(title, sections);
+                (alt_std::format!("pair of {} and {}", at.toStr(), dt.toStr()), link)
+            },
+            Self::Record(..) | Self::Map(_) | Self::Form(..) => {
+                self.collectSections(title, sections);
+                let kind = match self { Self::Map(_) => "map", Self::Form(..) => "form", _ => "record" };
+                (String::from(kind), Some(String::from(title)))
+            },
+            Self::MapOf(k, v) => {
+                let (kt, _) = k.typeText(title, sections);
+                let (vt, link) = v.typeText(title, sections);
+                (alt_std::format!("map of {} to {}", kt.toStr(), vt.toStr()), link)
+            },
+            Self::Or(u) => {
+                let mut s = String::new();
+                for i in 0..u.len() {
+                    if i != 0 { s.append(" or ") }
+                    s.append(&u[i].typeText(title, sections).0);
+                }
+                (s, None)
+            },
+            s => (s.toExp().toString(), None),
+        }
+    }
+
+    fn collectSections(&self, title: &str, sections: &mut Vec<Section>) {
+        if let Self::Form(..) = self { return self.collectFormSection(title, sections) }
+        let (form, fields) = match self {
+            Self::Record(Some(tag), fields) => (alt_std::format!("({} (field value...)...)", tag.toStr()), fields),
+            Self::Record(None, fields) => (String::from("((field value...)...)"), fields),
+            Self::Map(fields) => (String::from("{field value ...}"), fields),
+            _ => return
+        };
+        let at = sections.len();
+        sections.pushBack(Section { title: String::from(title), form, rows: Vec::new() });
+        for f in fields.asArray().iter() {
+            let (ty, link) = f.schema.typeText(alt_std::format!("{}.{}", title, f.name.toStr()).toStr(), sections);
+            sections[at].rows.pushBack(Row {
+                name        : f.name.clone(),
+                ty,
+                link,
+                required    : f.required(),
+                default     : f.default.as_ref().map(|d| d.toString()),
+                doc         : f.doc.clone(),
+            });
+        }
+    }
+
+    // `(head name [opt] rest...)` and a row per parameter
+    fn collectFormSection(&self, title: &str, sections: &mut Vec<Section>) {
+        let (head, params, rest) = match self { Self::Form(h, p, r) => (h, p, r), _ => return };
+        let mut form = String::from("(");
+        if let Some(h) = head { form.append(h) }
+        for p in params.asArray().iter() {
+            if form.asArray().len() > 1 { form.add(b' ') }
+            form.append(&if p.required() { p.name.clone() } else { alt_std::format!("[{}]", p.name.toStr()) });
+        }
+        if let Some(r) = rest {
+            if form.asArray().len() > 1 { form.add(b' ') }
+            form.append(&alt_std::format!("{}...", r.name.toStr()));
+        }
+        form.add(b')');
+        let at = sections.len();
+        sections.pushBack(Section { title: String::from(title), form, rows: Vec::new() });
+        for p in params.asArray().iter().chain(rest.as_deref()) {
+            let (ty, link) = p.schema.typeText(alt_std::format!("{}.{}", title, p.name.toStr()).toStr(), sections);
+            let variadic = rest.as_deref() == Some(p);
+            sections[at].rows.pushBack(Row {
+                name        : if variadic { alt_std::format!("{}...", p.name.toStr()) } else { p.name.clone() },
+                ty,
+                link,
+                required    : p.required() && !variadic,
+                default     : p.default.as_ref().map(|d| d.toString()),
+                doc         : p.doc.clone(),
+            });
+        }
+    }
+
+    fn sections(&self, title: &str) -> Vec<Section> {
+        let mut sections = Vec::new();
+        match self {
+            Self::Record(..) | Self::Map(_) | Self::Form(..) => self.collectSections(title, &mut sections),
+            s => {
+                let form = s.typeText(title, &mut sections).0;
+                sections.pushBack(Section { title: String::from(title), form, rows: Vec::new() });
+            }
+        }
+        sections
+    }
+
+    /// a document the schema accepts, fields take their default when they have one
+    pub fn example(&self) -> Exp {
+        let field = |f: &Field| f.default.clone().unwrap_or_else(|| f.schema.example());
+        match self {
+            Self::Any => Exp::List(Vec::new()),
+            Self::Bool => Exp::Bool(false),
+            Self::Char => Exp::Char('a'),
+            Self::UInt => Exp::UInt(u64::MAX),
+            Self::Int | Self::BigInt => Exp::Int(0),
+            Self::Ratio => Exp::Ratio(1, 2),
+            Self::Float => Exp::Float(0.0),
+            Self::String => Exp::String(String::new()),
+            Self::Symbol => Exp::Symbol(String::from("x")),
+            Self::Keyword => Exp::Keyword(String::from("x")),
+            Self::Bytes => Exp::Bytes(Vec::new()),
+            Self::List(e) => {
+                let mut v = Vec::new();
+                v.pushBack(e.example());
+                Exp::List(v)
+            },
+            Self::Vector(e) => {
+                let mut v = Vec::new();
+                v.pushBack(e.example());
+                Exp::Vector(v)
+            },
+            Self::Pair(a, d) => Exp::Pair(std::boxed::Box::new(a.example()), std::boxed::Box::new(d.example())),
+            Self::Record(tag, fields) => {
+                let mut v = Vec::new();
+                if let Some(t) = tag { v.pushBack(Exp::Symbol(t.clone())) }
+                for f in fields.asArray().iter() {
+                    let spread = matches!(f.schema, Self::List(_) | Self::Record(None, _));
+                    Exp::pushRecordField(&mut v, f.name.toStr(), &field(f), spread);
+                }
+                Exp::List(v)
+            },
+            Self::Map(fields) => {
+                let mut m = Exp::Map(Vec::new());
+                for f in fields.asArray().iter() { m.setMapField(f.name.toStr(), &field(f)) }
+                m
+            },
+            Self::MapOf(k, v) => {
+                let mut m = Exp::Map(Vec::new());
+                m.insert(k.example(), v.example());
+                m
+            },
+            Self::Form(head, params, rest) => {
+                let mut v = Vec::new();
+                if let Some(h) = head { v.pushBack(Exp::Symbol(h.clone())) }
+                for p in params.asArray().iter() { v.pushBack(field(p)) }
+                if let Some(r) = rest { v.pushBack(r.schema.example()) }
+                Exp::List(v)
+            },
+            Self::Or(u) => u.asArray().first().map_or(Exp::List(Vec::new()), |s| s.example()),
+        }
+    }
+
+    pub fn toMarkdown(&self, title: &str) -> String {
+        let cell = |s: &str| {
+            let mut out = String::new();
+            for c in s.bytes() {
+                if c == b'|' { out.add(b'\\') }
+                out.add(if c == b'\n' { b' ' } else { c });
+            }
+            out
+        };
+        let mut out = String::new();
+        for sec in self.sections(title).asArray().iter() {
+            let level = if sec.title == title { "#" } else { "##" };
+            out.append(&alt_std::format!("{} {}\n\n`{}`\n\n", level, sec.title.toStr(), sec.form.toStr()));
+            if sec.rows.len() == 0 { continue }
+            out.append("| field | type | required | default | description |\n");
+            out.append("|-------|------|----------|---------|-------------|\n");
+            for r in sec.rows.asArray().iter() {
+                let ty = match &r.link {
+                    Some(l) => alt_std::format!("[{}](#{})", cell(r.ty.toStr()).toStr(), anchor(l.toStr()).toStr()),
+                    None => cell(r.ty.toStr()),
+                };
+                out.append(&alt_std::format!("| `{}` | {} | {} | {} | {} |\n",
+                    r.name.toStr(), ty.toStr(), if r.required { "yes" } else { "no" },
+                    r.default.as_ref().map_or(String::new(), |d| alt_std::format!("`{}`", cell(d.toStr()).toStr())).toStr(),
+                    r.doc.as_ref().map_or(String::new(), |d| cell(d.toStr())).toStr()));
+            }
+            out.add(b'\n');
+        }
+        out.append("## Example\n\n```\n");
+        out.append(&self.example().toPrettyString(72));
+        out.append("\n```\n");
+        out
+    }
+
+    pub fn toHtmlDoc(&self, title: &str) -> String {
+        let text = |out: &mut String, s: &str| Exp::escapeHtml(out, s.as_bytes());
+        let mut out = String::new();
+        for sec in self.sections(title).asArray().iter() {
+            let h = if sec.title == title { "h1" } else { "h2" };
+            out.append(&alt_std::format!("<{} id=\"{}\">", h, anchor(sec.title.toStr()).toStr()));
+            text(&mut out, sec.title.toStr());
+            out.append(&alt_std::format!("</{}>\n<p><code>", h));
+            text(&mut out, sec.form.toStr());
+            out.append("</code></p>\n");
+            if sec.rows.len() == 0 { continue }
+            out.append("<table>\n<tr><th>field</th><th>type</th><th>required</th><th>default</th><th>description</th></tr>\n");
+            for r in sec.rows.asArray().iter() {
+                out.append("<tr><td><code>");
+                text(&mut out, r.name.toStr());
+                out.append("</code></td><td>");
+                match &r.link {
+                    Some(l) => {
+                        out.append(&alt_std::format!("<a href=\"#{}\">", anchor(l.toStr()).toStr()));
+                        text(&mut out, r.ty.toStr());
+                        out.append("</a>");
+                    },
+                    None => text(&mut out, r.ty.toStr()),
+                }
+                out.append(if r.required { "</td><td>yes</td><td>" } else { "</td><td>no</td><td>" });
+                if let Some(d) = &r.default {
+                    out.append("<code>");
+                    text(&mut out, d.toStr());
+                    out.append("</code>");
+                }
+                out.append("</td><td>");
+                if let Some(d) = &r.doc { text(&mut out, d.toStr()) }
+                out.append("</td></tr>\n");
+            }
+            out.append("</table>\n");
+        }
+        out.append("<h2>Example</h2>\n<pre>");
+        text(&mut out, self.example().toPrettyString(72).toStr());
+        out.append("</pre>\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSchemaDocs() {
+        let e = match Exp::fromSExp(b"(record server (port int (default 80) (doc \"listening | port\")) (tags (list symbol) optional) (limits (map (rps float))))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let s = Schema::fromExp(&e).ok().unwrap();
+        assert!(s.example().toString() == "(server (port 80) (tags x) (limits {rps 0.0}))");
+        assert!(s.validate(&s.example()).is_ok());
+
+        let md = s.toMarkdown("config");
+        assert!(md.toStr().starts_with("# config\n\n`(server (field value...)...)`\n\n| field |"));
+        assert!(md.toStr().contains("| `port` | int | no | `80` | listening \\| port |\n"));
+        assert!(md.toStr().contains("| `tags` | list of symbol | no |  |  |\n"));
+        assert!(md.toStr().contains("| `limits` | [map](#config-limits) | yes |  |  |\n\n## config.limits\n\n`{field value ...}`\n"));
+        assert!(md.toStr().ends_with("## Example\n\n```\n(server (port 80) (tags x) (limits {rps 0.0}))\n```\n"));
+
+        let html = s.toHtmlDoc("config");
+        assert!(html.toStr().starts_with("<h1 id=\"config\">config</h1>\n"));
+        assert!(html.toStr().contains("<td><a href=\"#config-limits\">map</a></td>"));
+        assert!(html.toStr().contains("<h2 id=\"config-limits\">config.limits</h2>"));
+    }
+}
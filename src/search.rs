@@ -0,0 +1,139 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+//
+// structural search, positions are paths of plain child indices
+// (`PathSeg::Index`) from the root, the root itself is the empty path. The
+// children of a pair are its car and cdr, those of a map the key then the value
+// of each entry (entry `i` is at `2i` and `2i + 1`)
+//
+impl Exp {
+    /// whether `needle` is this expression or one of its subtrees
+    pub fn contains(&self, needle: &Exp) -> bool {
+        self.findFirst(|e| e == needle).is_some()
+    }
+
+    /// first subtree (pre-order) matching `pred`
+    pub fn findFirst<F: Fn(&Exp) -> bool>(&self, pred: F) -> Option<&Exp> {
+        self.findFirstBy(&pred)
+    }
+
+    fn findFirstBy(&self, pred: &dyn Fn(&Exp) -> bool) -> Option<&Exp> {
+        if pred(self) { return Some(self) }
+        match self {
+            Self::List(l) => l.asArray().iter().find_map(|e| e.findFirstBy(pred)),
+            Self::Vector(v) => v.asArray().iter().find_map(|e| e.findFirstBy(pred)),
+            Self::Pair(a, d) => a.findFirstBy(pred).or_else(|| d.findFirstBy(pred)),
+            Self::Map(m) => m.asArray().iter().find_map(|(k, v)| k.findFirstBy(pred).or_else(|| v.findFirstBy(pred))),
+            _ => None
+        }
+    }
+
+    pub(crate) fn childCount(&self) -> usize {
+        match self {
+            Self::List(l) | Self::Vector(l) => l.len(),
+            Self::Pair(..) => 2,
+            Self::Map(m) => 2 * m.len(),
+            _ => 0
+        }
+    }
+
+    pub(crate) fn child(&self, i: usize) -> Option<&Exp> {
+        match self {
+            Self::List(l) | Self::Vector(l) => l.asArray().get(i),
+            Self::Pair(a, _) if i == 0 => Some(a),
+            Self::Pair(_, d) if i == 1 => Some(d),
+            Self::Map(m) => m.asArray().get(i / 2).map(|(k, v)| if i % 2 == 0 { k } else { v }),
+            _ => None
+        }
+    }
+
+    fn positionsIn(&self, needle: &Exp, path: &mut Path, out: &mut Vec<Path>) {
+        if self == needle {
+            out.pushBack(path.clone());
+        }
+        for i in 0..self.childCount() {
+            if let Some(c) = self.child(i) {
+                path.push(PathSeg::Index(i));
+                c.positionsIn(needle, path, out);
+                path.pop();
+            }
+        }
+    }
+
+    /// every occurrence of `needle`, outer ones first
+    pub fn positionsOf(&self, needle: &Exp) -> Vec<Path> {
+        let mut out = Vec::new();
+        self.positionsIn(needle, &mut Path::new(), &mut out);
+        out
+    }
+
+    /// subtree at a path of indices
+    pub fn at(&self, path: &Path) -> Option<&Exp> {
+        let mut e = self;
+        for seg in path.segments().iter() {
+            e = match seg {
+                PathSeg::Index(i) => e.child(*i)?,
+                _ => return None
+            };
+        }
+        Some(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSearch() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let e = parse("(let ((x (f 1))) (g (f 1) x) (f 2))");
+        let needle = parse("(f 1)");
+        assert!(e.contains(&needle));
+        assert!(!e.contains(&parse("(f 3)")));
+
+        let ps = e.positionsOf(&needle);
+        assert!(ps.len() == 2);
+        assert!(ps[0].toString() == "1.0.1" && ps[1].toString() == "2.1");
+        assert!(e.at(&ps[1]) == Some(&needle));
+
+        let big = e.findFirst(|x| match x { Exp::Int(i) => *i > 1, _ => false });
+        assert!(big == Some(&Exp::Int(2)));
+
+        let mut opts = ReaderOptions::new();
+        opts.braces = true;
+        let e = match Exp::fromSExpWithOptions(b"(a #(1 (f 1)) {(f 1) (f 1)} ((f 1) . b))", &opts) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let ps = e.positionsOf(&needle);
+        let mut s = String::new();
+        for p in ps.asArray().iter() { s.append(&p.toString()); s.add(b' ') }
+        assert!(s == "1.1 2.0 2.1 3.0 ");
+        assert!(ps.asArray().iter().all(|p| e.at(p) == Some(&needle)));
+        assert!(e.at(&Path::parse("2.2")).is_none() && e.at(&Path::parse("3.2")).is_none());
+    }
+}
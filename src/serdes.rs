@@ -0,0 +1,223 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// serde support: booleans, numbers, strings and lists map to the native types of
+// the format, what it has no type for is a single entry map tagged with its kind
+// so that it comes back the same:
+//
+//  {"Sym": "name"}  {"Kw": "name"}  {"Char": "c"}  {"Vec": [...]}  {"Bytes": [0, 255]}  {"Ratio": [1, 3]}
+//  {"BigInt": "123456789012345678901"}
+//  {"Pair": [car, cdr]}  {"Map": [[key, value]...]}
+//
+use core::fmt;
+use alt_std::*;
+use serde::ser::{Serialize, Serializer, SerializeSeq};
+use serde::de::{self, Deserialize, Deserializer, Visitor, SeqAccess, MapAccess};
+use crate::*;
+
+#[cfg(not(feature = "bigint"))]
+const TAGS : [&str; 8] = ["Sym", "Kw", "Char", "Pair", "Map", "Vec", "Bytes", "Ratio"];
+#[cfg(feature = "bigint")]
+const TAGS : [&str; 9] = ["Sym", "Kw", "Char", "Pair", "Map", "Vec", "Bytes", "Ratio", "BigInt"];
+
+// serialized as `[key, value]`
+struct Entry<'a>(&'a Exp, &'a Exp);
+
+impl<'a> Serialize for Entry<'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        let mut seq = s.serialize_seq(Some(2))?;
+        seq.serialize_element(self.0)?;
+        seq.serialize_element(self.1)?;
+        seq.end()
+    }
+}
+
+struct Entries<'a>(&'a [(Exp, Exp)]);
+
+impl<'a> Serialize for Entries<'a> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.collect_seq(self.0.iter().map(|(k, v)| Entry(k, v)))
+    }
+}
+
+impl Serialize for Exp {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Bool(b) => s.serialize_bool(*b),
+            Self::Char(c) => s.serialize_newtype_variant("Exp", 2, "Char", c),
+            Self::Int(i) => s.serialize_i64(*i),
+            Self::UInt(u) => s.serialize_u64(*u),
+            Self::Float(f) => s.serialize_f64(*f),
+            Self::String(v) => s.serialize_str(v.toStr()),
+            Self::Spilled(v) => s.serialize_str(&std::string::String::from_utf8_lossy(&Self::spilledBytes(v))),
+            Self::Symbol(v) => s.serialize_newtype_variant("Exp", 0, "Sym", v.toStr()),
+            Self::Keyword(v) => s.serialize_newtype_variant("Exp", 1, "Kw", v.toStr()),
+            Self::List(l) => s.collect_seq(l.asArray().iter()),
+            Self::Pair(a, d) => s.serialize_newtype_variant("Exp", 3, "Pair", &Entry(a, d)),
+            Self::Map(m) => s.serialize_newtype_variant("Exp", 4, "Map", &Entries(m.asArray())),
+            Self::Vector(v) => s.serialize_newtype_variant("Exp", 5, "Vec", v.asArray()),
+            Self::Bytes(b) => s.serialize_newtype_variant("Exp", 6, "Bytes", b.asArray()),
+            Self::Ratio(n, d) => s.serialize_newtype_variant("Exp", 7, "Ratio", &[*n, *d]),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => s.serialize_newtype_variant("Exp", 8, "BigInt", &alt_std::format!("{}", b).toStr()),
+        }
+    }
+}
+
+impl Serialize for Sym {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_variant("Exp", 0, "Sym", self.0.toStr())
+    }
+}
+
+impl Serialize for Kw {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_newtype_variant("Exp", 1, "Kw", self.0.toStr())
+    }
+}
+
+struct ExpVisitor;
+
+impl ExpVisitor {
+    fn pair<E: de::Error>(v: std::vec::Vec<Exp>) -> Result<(Exp, Exp), E> {
+        let mut it = v.into_iter();
+        match (it.next(), it.next(), it.next()) {
+            (Some(a), Some(b), None) => Ok((a, b)),
+            _ => Err(E::custom("expected a [car, cdr] pair")),
+        }
+    }
+}
+
+impl<'de> Visitor<'de> for ExpVisitor {
+    type Value = Exp;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an s-expression")
+    }
+
+    fn visit_bool<E: de::Error>(self, b: bool) -> Result<Exp, E> { Ok(Exp::Bool(b)) }
+    fn visit_i64<E: de::Error>(self, i: i64) -> Result<Exp, E> { Ok(Exp::Int(i)) }
+    fn visit_u64<E: de::Error>(self, u: u64) -> Result<Exp, E> {
+        Ok(Exp::uint(u))
+    }
+    fn visit_f64<E: de::Error>(self, f: f64) -> Result<Exp, E> { Ok(Exp::Float(f)) }
+    fn visit_char<E: de::Error>(self, c: char) -> Result<Exp, E> { Ok(Exp::Char(c)) }
+    fn visit_str<E: de::Error>(self, s: &str) -> Result<Exp, E> { Ok(Exp::String(String::from(s))) }
+    fn visit_unit<E: de::Error>(self) -> Result<Exp, E> { Ok(Exp::List(Vec::new())) }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Exp, A::Error> {
+        let mut l = Vec::new();
+        while let Some(e) = seq.next_element()? { l.pushBack(e) }
+        Ok(Exp::List(l))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Exp, A::Error> {
+        let tag : std::string::String = match map.next_key()? {
+            Some(t) => t,
+            None => return Err(de::Error::invalid_length(0, &"a single tagged entry")),
+        };
+        let e = match tag.as_str() {
+            "Sym" => Exp::Symbol(String::from(map.next_value::<std::string::String>()?.as_str())),
+            "Kw" => Exp::Keyword(String::from(map.next_value::<std::string::String>()?.as_str())),
+            "Char" => Exp::Char(map.next_value()?),
+            "Pair" => {
+                let (a, d) = Self::pair(map.next_value()?)?;
+                Exp::Pair(std::boxed::Box::new(a), std::boxed::Box::new(d))
+            },
+            "Vec" => {
+                let mut v = Vec::new();
+                for e in map.next_value::<std::vec::Vec<Exp>>()? { v.pushBack(e) }
+                Exp::Vector(v)
+            },
+            "Bytes" => {
+                let mut v = Vec::new();
+                for c in map.next_value::<std::vec::Vec<u8>>()? { v.pushBack(c) }
+                Exp::Bytes(v)
+            },
+            "Ratio" => {
+                let (n, d) = map.next_value::<(i64, i64)>()?;
+                Exp::ratio(n, d).ok_or_else(|| de::Error::custom("invalid ratio"))?
+            },
+            #[cfg(feature = "bigint")]
+            "BigInt" => {
+                let b = BigInt::parse(&map.next_value::<std::string::String>()?);
+                Exp::bigInt(b.ok_or_else(|| de::Error::custom("invalid big integer"))?)
+            },
+            "Map" => {
+                let mut m = Vec::new();
+                for e in map.next_value::<std::vec::Vec<std::vec::Vec<Exp>>>()? {
+                    m.pushBack(Self::pair(e)?);
+                }
+                Exp::Map(m)
+            },
+            t => return Err(de::Error::unknown_variant(t, &TAGS)),
+        };
+        match map.next_key::<std::string::String>()? {
+            None => Ok(e),
+            Some(_) => Err(de::Error::custom("more than one entry in a tagged value")),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Exp {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Exp, D::Error> {
+        d.deserialize_any(ExpVisitor)
+    }
+}
+
+impl<'de> Deserialize<'de> for Sym {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Sym, D::Error> {
+        match Exp::deserialize(d)? {
+            Exp::Symbol(s) => Ok(Sym(s)),
+            e => Err(de::Error::custom(alt_std::format!("expected a symbol, found {}", e.toString().toStr()).toStr())),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Kw {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Kw, D::Error> {
+        match Exp::deserialize(d)? {
+            Exp::Keyword(k) => Ok(Kw(k)),
+            e => Err(de::Error::custom(alt_std::format!("expected a keyword, found {}", e.toString().toStr()).toStr())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSerde() {
+        let e = match Exp::fromSExp(b"(define x \"x\" :x #\\x 1.5 -2 (a . b) {k (1)} () #(1) #u8(7))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let json = serde_json::to_string(&e).unwrap();
+        assert!(json == r#"[{"Sym":"define"},{"Sym":"x"},"x",{"Kw":"x"},{"Char":"x"},1.5,-2,{"Pair":[{"Sym":"a"},{"Sym":"b"}]},{"Map":[[{"Sym":"k"},[1]]]},[],{"Vec":[1]},{"Bytes":[7]}]"#);
+        assert!(serde_json::from_str::<Exp>(&json).unwrap() == e);
+
+        let (s, k) : (Sym, Kw) = serde_json::from_str(r#"[{"Sym":"a"},{"Kw":"b"}]"#).unwrap();
+        assert!(s.0 == "a" && k.0 == "b");
+        assert!(serde_json::to_string(&s).unwrap() == r#"{"Sym":"a"}"#);
+        assert!(serde_json::from_str::<Sym>(r#""a""#).is_err());
+        assert!(serde_json::from_str::<Exp>(r#"{"Set":[]}"#).is_err());
+    }
+}
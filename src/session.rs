@@ -0,0 +1,124 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+/// tree with an undo/redo history of the edits made through it
+pub struct EditSession {
+    exp     : Exp,
+    // (edit, inverse) pairs
+    undos   : Vec<(PatchOp, PatchOp)>,
+    redos   : Vec<(PatchOp, PatchOp)>,
+}
+
+impl EditSession {
+    pub fn new(exp: Exp) -> Self {
+        Self { exp, undos: Vec::new(), redos: Vec::new() }
+    }
+
+    pub fn exp(&self) -> &Exp { &self.exp }
+    pub fn intoExp(self) -> Exp { self.exp }
+    pub fn canUndo(&self) -> bool { self.undos.len() != 0 }
+    pub fn canRedo(&self) -> bool { self.redos.len() != 0 }
+
+    fn inverse(&self, op: &PatchOp) -> Option<PatchOp> {
+        let p = op.path().clone();
+        match op {
+            PatchOp::Replace(..) => Some(PatchOp::Replace(p.clone(), self.exp.at(&p)?.clone())),
+            PatchOp::Insert(..) => Some(PatchOp::Remove(p)),
+            PatchOp::Remove(_) => Some(PatchOp::Insert(p.clone(), self.exp.at(&p)?.clone())),
+        }
+    }
+
+    fn run(&mut self, op: &PatchOp) -> bool {
+        match PatchOp::indices(op.path()) {
+            Some(ix) => self.exp.applyAt(&ix, op),
+            None => false
+        }
+    }
+
+    /// applies `op`, false (and nothing recorded) when its path does not exist
+    pub fn apply(&mut self, op: PatchOp) -> bool {
+        let inv = match self.inverse(&op) {
+            Some(inv) => inv,
+            None => return false
+        };
+        if !self.run(&op) { return false }
+        self.undos.pushBack((op, inv));
+        self.redos = Vec::new();
+        true
+    }
+
+    pub fn replace(&mut self, path: Path, e: Exp) -> bool { self.apply(PatchOp::Replace(path, e)) }
+    pub fn insert(&mut self, path: Path, e: Exp) -> bool { self.apply(PatchOp::Insert(path, e)) }
+    pub fn remove(&mut self, path: Path) -> bool { self.apply(PatchOp::Remove(path)) }
+
+    pub fn undo(&mut self) -> bool {
+        match self.undos.pop() {
+            Some((op, inv)) => {
+                self.run(&inv);
+                self.redos.pushBack((op, inv));
+                true
+            },
+            None => false
+        }
+    }
+
+    pub fn redo(&mut self) -> bool {
+        match self.redos.pop() {
+            Some((op, inv)) => {
+                self.run(&op);
+                self.undos.pushBack((op, inv));
+                true
+            },
+            None => false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testUndoRedo() {
+        let e = match Exp::fromSExp(b"(server (port 80) (host \"a\"))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut s = EditSession::new(e.clone());
+        assert!(s.replace(Path::parse("1.1"), Exp::Int(8080)));
+        assert!(s.remove(Path::parse("2")));
+        assert!(s.insert(Path::parse("1"), Exp::Symbol(String::from("x"))));
+        assert!(!s.remove(Path::parse("7")));
+        assert!(s.exp().toString() == "(server x (port 8080))");
+
+        assert!(s.undo() && s.undo());
+        assert!(s.exp().toString() == "(server (port 8080) (host \"a\"))");
+        assert!(s.redo());
+        assert!(s.exp().toString() == "(server (port 8080))");
+        assert!(s.undo() && s.undo() && !s.undo());
+        assert!(*s.exp() == e);
+
+        assert!(s.redo());
+        assert!(s.remove(Path::parse("1")));
+        assert!(!s.canRedo());
+    }
+}
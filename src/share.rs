@@ -0,0 +1,271 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// shared subtrees: with `PrintOptions::shareAbove` set, a subtree of at least that
+// many nodes written more than once is printed as `(def $n ...)` the first time
+// and `(ref $n)` after, `ReaderOptions::expandRefs` reads them back into copies.
+// The scheme datum labels `#n=...` and `#n#` spell the same thing
+//
+use std::collections::HashMap;
+use alt_std::*;
+use crate::*;
+
+struct Seen<'a> {
+    exp     : &'a Exp,
+    times   : usize,
+    // once defined
+    label   : Option<usize>,
+}
+
+struct Shared<'a> {
+    // node -> (hash, size)
+    nodes   : HashMap<*const Exp, (u64, usize)>,
+    // subtrees by hash
+    seen    : HashMap<u64, std::vec::Vec<Seen<'a>>>,
+    min     : usize,
+    labels  : usize,
+}
+
+impl<'a> Shared<'a> {
+    fn entry(&mut self, e: &'a Exp) -> Option<&mut Seen<'a>> {
+        let (h, size) = self.nodes[&(e as *const Exp)];
+        if size < self.min { return None }
+        let bucket = self.seen.entry(h).or_default();
+        match bucket.iter().position(|s| s.exp == e) {
+            Some(i) => Some(&mut bucket[i]),
+            None => { bucket.push(Seen { exp: e, times: 0, label: None }); bucket.last_mut() },
+        }
+    }
+
+    // how many times each subtree is written, copies after the first are not walked
+    // since they are going to be refs
+    fn count(&mut self, e: &'a Exp) {
+        if let Some(en) = self.entry(e) {
+            en.times += 1;
+            if en.times > 1 { return }
+        }
+        match e {
+            Exp::List(l) | Exp::Vector(l) => for c in l.asArray().iter() { self.count(c) },
+            Exp::Pair(..) => {
+                let (items, tail) = e.dottedItems();
+                for c in items.into_iter().chain(tail) { self.count(c) }
+            },
+            Exp::Map(m) => for (k, v) in m.asArray().iter() { self.count(k); self.count(v) },
+            _ => ()
+        }
+    }
+
+    fn print(&mut self, e: &'a Exp, opts: &PrintOptions, out: &mut String) {
+        let label = match self.entry(e).filter(|en| en.times > 1).map(|en| en.label) {
+            Some(Some(n)) if opts.datumLabels => return out.append(&alt_std::format!("#{}#", n)),
+            Some(Some(n)) => return out.append(&alt_std::format!("(ref ${})", n)),
+            Some(None) => {
+                self.labels += 1;
+                let n = self.labels;
+                if let Some(en) = self.entry(e) { en.label = Some(n) }
+                Some(n)
+            },
+            None => None
+        };
+        match label {
+            Some(n) if opts.datumLabels => out.append(&alt_std::format!("#{}=", n)),
+            Some(n) => out.append(&alt_std::format!("(def ${} ", n)),
+            None => ()
+        }
+        match e {
+            Exp::List(l) | Exp::Vector(l) => {
+                out.append(if let Exp::Vector(_) = e { "#(" } else { "(" });
+                for i in 0..l.len() {
+                    if i != 0 { out.add(b' ') }
+                    self.print(&l[i], opts, out);
+                }
+                out.add(b')');
+            },
+            Exp::Pair(..) => {
+                let (items, tail) = e.dottedItems();
+                out.add(b'(');
+                for i in 0..items.len() {
+                    if i != 0 { out.add(b' ') }
+                    self.print(items[i], opts, out);
+                }
+                if let Some(t) = tail {
+                    out.append(" . ");
+                    self.print(t, opts, out);
+                }
+                out.add(b')');
+            },
+            Exp::Map(m) => {
+                out.add(b'{');
+                for i in 0..m.len() {
+                    if i != 0 { out.add(b' ') }
+                    self.print(&m[i].0, opts, out);
+                    out.add(b' ');
+                    self.print(&m[i].1, opts, out);
+                }
+                out.add(b'}');
+            },
+            e => out.append(&e.toStringWithOptions(&PrintOptions { shareAbove: None, ..opts.clone() })),
+        }
+        if label.is_some() && !opts.datumLabels { out.add(b')') }
+    }
+}
+
+impl Exp {
+    pub(crate) fn printShared(&self, min: usize, opts: &PrintOptions) -> String {
+        let mut nodes = HashMap::new();
+        self.hashSubtrees(&mut |e, h, size| { nodes.insert(e as *const Exp, (h, size)); });
+        let mut sh = Shared { nodes, seen: HashMap::new(), min: usize::max(min, 2), labels: 0 };
+        sh.count(self);
+        let mut out = String::new();
+        sh.print(self, opts, &mut out);
+        out
+    }
+
+    // `(def $n e)` binds and reads as e, `(ref $n)` as a copy of what $n is bound to
+    pub(crate) fn expandRef(rd: &mut Reader, cells: Vec<Exp>, start: usize) -> ParseResult<Exp> {
+        let label = |e: &Exp| match e {
+            Exp::Symbol(s) if s.asArray().len() > 1 && s.asArray()[0] == b'$' => Some(s.clone()),
+            _ => None
+        };
+        match cells.asArray() {
+            [Exp::Symbol(d), l, e] if d.toStr() == "def" && label(l).is_some() => {
+                if let Some(name) = label(l) { rd.refs.pushBack((name, e.clone(), e.nodeCount())) }
+                PROk(e.clone())
+            },
+            [Exp::Symbol(r), l] if r.toStr() == "ref" && label(l).is_some() => {
+                let name = label(l).unwrap_or_else(String::new);
+                match rd.refs.asArray().iter().rposition(|(n, _, _)| *n == name) {
+                    Some(i) => match Self::charge(rd, rd.refs[i].2, start, "expansion too large (ref)") {
+                        Ok(()) => PROk(rd.refs[i].1.clone()),
+                        Err(err) => PRErr(err)
+                    },
+                    None => PRErr(ParseError { message: String::from("undefined reference (ref)"), offset: start, kind: ErrorKind::Syntax }),
+                }
+            },
+            _ => PROk(Exp::List(cells))
+        }
+    }
+
+    pub(crate) fn nodeCount(&self) -> usize {
+        match self {
+            Exp::List(l) | Exp::Vector(l) => 1 + l.asArray().iter().map(|e| e.nodeCount()).sum::<usize>(),
+            Exp::Pair(a, d) => 1 + a.nodeCount() + d.nodeCount(),
+            Exp::Map(m) => 1 + m.asArray().iter().map(|(k, v)| k.nodeCount() + v.nodeCount()).sum::<usize>(),
+            _ => 1
+        }
+    }
+
+    // accounts for a copy of `size` nodes, an error past `ReaderOptions::maxExpansion`
+    fn charge(rd: &mut Reader, size: usize, start: usize, message: &str) -> Result<(), ParseError> {
+        rd.expanded = rd.expanded.saturating_add(size);
+        if rd.expanded > rd.opts.maxExpansion {
+            return Err(ParseError { message: String::from(message), offset: start, kind: ErrorKind::Syntax })
+        }
+        Ok(())
+    }
+
+    // end of the `#n=` or `#n#` at `start`
+    pub(crate) fn datumLabelEnd(src: &[u8], start: usize) -> Option<usize> {
+        let digits = src.get(start + 1..)?.iter().take_while(|c| Self::isDigit(**c)).count();
+        match src.get(start + 1 + digits) {
+            Some(b'=') | Some(b'#') if digits > 0 => Some(start + digits + 2),
+            _ => None
+        }
+    }
+
+    // `#n=x` binds and reads as x, `#n#` as a copy of what n is bound to
+    pub(crate) fn parseDatumLabel(rd: &mut Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let start = *offset;
+        let end = Self::datumLabelEnd(src, start).unwrap_or(start + 1);
+        let name = String::from(core::str::from_utf8(&src[start + 1..end - 1]).unwrap_or(""));
+        *offset = end;
+        if src[end - 1] == b'#' {
+            let found = rd.labels.asArray().iter().rposition(|(n, _)| *n == name);
+            return match found.map(|i| (i, rd.labels[i].1.as_ref().map(|(_, size)| *size))) {
+                Some((i, Some(size))) => match Self::charge(rd, size, start, "expansion too large (label)") {
+                    Ok(()) => PROk(rd.labels[i].1.as_ref().map_or(Exp::Bool(false), |(e, _)| e.clone())),
+                    Err(err) => PRErr(err)
+                },
+                Some((_, None)) => PRErr(ParseError { message: String::from("cyclic datum label (label)"), offset: start, kind: ErrorKind::Syntax }),
+                None => PRErr(ParseError { message: String::from("undefined datum label (label)"), offset: start, kind: ErrorKind::Syntax }),
+            }
+        }
+        Self::skipWS(src, offset);
+        match Self::peek(src, *offset) {
+            None | Some(b')') | Some(b']') | Some(b'}') =>
+                return PRErr(ParseError { message: String::from("missing datum after label (label)"), offset: start, kind: ErrorKind::Syntax }),
+            _ => ()
+        }
+        rd.labels.pushBack((name, None));
+        let at = rd.labels.len() - 1;
+        let res = Self::parseToken(rd, src, offset);
+        if let PROk(e) = &res { rd.labels[at].1 = Some((e.clone(), e.nodeCount())) }
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSharedSubtrees() {
+        let src = "(scene (mesh (v 1 2 3) (v 4 5 6)) (mesh (v 1 2 3) (v 4 5 6)) (v 1 2 3) (x 1) (x 1) (mesh (v 1 2 3) (v 4 5 6)) (p (v 1 2 3) . q))";
+        let e = match Exp::fromSExp(src.as_bytes()) { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let mut opts = PrintOptions::new();
+        opts.shareAbove = Some(4);
+        let text = e.toStringWithOptions(&opts);
+        assert!(text == "(scene (def $1 (mesh (def $2 (v 1 2 3)) (v 4 5 6))) (ref $1) (ref $2) (x 1) (x 1) (ref $1) (p (ref $2) . q))");
+        assert!(e.serializedLen(&opts) == text.asArray().len());
+
+        let mut ropts = ReaderOptions::new();
+        ropts.expandRefs = true;
+        assert!(match Exp::fromSExpWithOptions(text.asArray(), &ropts) { PROk(r) => r == e, _ => false });
+        assert!(Exp::fromSExpWithOptions(b"(a (ref $3))", &ropts) == PRErr(ParseError { message: String::from("undefined reference (ref)"), offset: 3, kind: ErrorKind::Syntax }));
+
+        opts.datumLabels = true;
+        let text = e.toStringWithOptions(&opts);
+        assert!(text == "(scene #1=(mesh #2=(v 1 2 3) (v 4 5 6)) #1# #2# (x 1) (x 1) #1# (p #2# . q))");
+        assert!(e.serializedLen(&opts) == text.asArray().len());
+        assert!(match Exp::fromSExp(text.asArray()) { PROk(r) => r == e, _ => false });
+        assert!(Exp::fromSExp(b"(a #0=(b #0#))") == PRErr(ParseError { message: String::from("cyclic datum label (label)"), offset: 9, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"(a #7#)") == PRErr(ParseError { message: String::from("undefined datum label (label)"), offset: 3, kind: ErrorKind::Syntax }));
+        let mut ropts = ReaderOptions::new();
+        ropts.datumLabels = false;
+        assert!(match Exp::fromSExpWithOptions(b"#0=", &ropts) { PROk(Exp::Symbol(s)) => s.toStr() == "#0=", _ => false });
+
+        // copies of copies double at every level
+        let mut labels = std::string::String::from("(#0=(x x)");
+        let mut refs = std::string::String::from("((def $0 (x x))");
+        for i in 1..40 {
+            labels.push_str(&std::format!(" #{}=(#{}# #{}#)", i, i - 1, i - 1));
+            refs.push_str(&std::format!(" (def ${} ((ref ${}) (ref ${})))", i, i - 1, i - 1));
+        }
+        labels.push(')');
+        refs.push(')');
+        let mut ropts = ReaderOptions::new();
+        ropts.maxExpansion = 10_000;
+        assert!(match Exp::fromSExpWithOptions(labels.as_bytes(), &ropts) { PRErr(err) => err.message.toStr() == "expansion too large (label)", _ => false });
+        ropts.expandRefs = true;
+        assert!(match Exp::fromSExpWithOptions(refs.as_bytes(), &ropts) { PRErr(err) => err.message.toStr() == "expansion too large (ref)", _ => false });
+        ropts.maxExpansion = 4;
+        assert!(matches!(Exp::fromSExpWithOptions(b"(#0=(a b) #0#)", &ropts), PROk(_)) && matches!(Exp::fromSExpWithOptions(b"(#0=(a b) #0# #0#)", &ropts), PRErr(_)));
+    }
+}
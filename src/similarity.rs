@@ -0,0 +1,99 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    fn subtreeHashes(&self) -> Vec<u64> {
+        let mut hs = Vec::new();
+        self.hashSubtrees(&mut |_, h, _| hs.pushBack(h));
+        hs.asMutArray().sort_unstable();
+        hs
+    }
+
+    /// Dice coefficient of the subtree multisets: 1.0 for equal trees, 0.0
+    /// when no subtree (atoms included) is shared
+    pub fn similarity(&self, other: &Exp) -> f64 {
+        let a = self.subtreeHashes();
+        let b = other.subtreeHashes();
+        let (mut i, mut j, mut common) = (0, 0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                common += 1;
+                i += 1;
+                j += 1;
+            } else if a[i] < b[j] {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+        2.0 * common as f64 / (a.len() + b.len()) as f64
+    }
+
+    /// biggest (in nodes) subtree of `self` also found in `other`, the first one on ties
+    pub fn largestCommonSubtree<'a>(&'a self, other: &Exp) -> Option<&'a Exp> {
+        let mut theirs = Vec::new();
+        other.hashSubtrees(&mut |e, h, _| theirs.pushBack((h, e)));
+        theirs.asMutArray().sort_unstable_by_key(|(h, _)| *h);
+
+        let mut best : Option<(&'a Exp, usize)> = None;
+        self.hashSubtrees(&mut |e, h, n| {
+            if let Some((_, bn)) = best {
+                if n <= bn { return }
+            }
+            let t = theirs.asArray();
+            let mut k = t.partition_point(|(th, _)| *th < h);
+            while k < t.len() && t[k].0 == h {
+                if *t[k].1 == *e {
+                    best = Some((e, n));
+                    return
+                }
+                k += 1;
+            }
+        });
+        best.map(|(e, _)| e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSimilarity() {
+        let parse = |s: &str| match Exp::fromSExp(s.as_bytes()) {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let a = parse("(server (port 8080) (tls (cert \"a.pem\") (key \"a.key\")))");
+        let b = parse("(server (port 9090) (tls (cert \"a.pem\") (key \"a.key\")))");
+        let c = parse("(1 2 3)");
+
+        assert!(a.similarity(&a) == 1.0);
+        assert!(a.similarity(&c) == 0.0);
+        let s = a.similarity(&b);
+        assert!(s > 0.7 && s < 1.0 && s == b.similarity(&a));
+
+        let t = a.largestCommonSubtree(&b).unwrap();
+        assert!(t.toString() == "(tls (cert \"a.pem\") (key \"a.key\"))");
+        assert!(a.largestCommonSubtree(&c).is_none());
+    }
+}
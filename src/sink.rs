@@ -0,0 +1,125 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// async output: `ExpSink` pretty prints one top level form at a time into a
+// `futures_io::AsyncWrite`, waiting on the writer between chunks and flushing
+// after every form, so at most one rendered form is ever held in memory
+//
+use core::future::poll_fn;
+use core::pin::Pin;
+use futures_io::AsyncWrite;
+use crate::*;
+
+pub struct ExpSink<W: AsyncWrite + Unpin> {
+    writer  : W,
+    width   : usize,
+    forms   : usize,
+}
+
+impl<W: AsyncWrite + Unpin> ExpSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::withWidth(writer, 80)
+    }
+
+    pub fn withWidth(writer: W, width: usize) -> Self {
+        Self { writer, width, forms: 0 }
+    }
+
+    /// number of forms written so far
+    pub fn forms(&self) -> usize { self.forms }
+
+    pub fn intoInner(self) -> W { self.writer }
+
+    /// writes `e` and a newline, resolves once the writer flushed them
+    pub async fn send(&mut self, e: &Exp) -> std::io::Result<()> {
+        let mut form = e.toPrettyString(self.width);
+        form.add(b'\n');
+        let mut bytes = form.asArray();
+        while !bytes.is_empty() {
+            let n = poll_fn(|cx| Pin::new(&mut self.writer).poll_write(cx, bytes)).await?;
+            if n == 0 {
+                return Err(std::io::Error::new(std::io::ErrorKind::WriteZero, "sink closed"))
+            }
+            bytes = &bytes[n..];
+        }
+        poll_fn(|cx| Pin::new(&mut self.writer).poll_flush(cx)).await?;
+        self.forms += 1;
+        Ok(())
+    }
+
+    pub async fn close(&mut self) -> std::io::Result<()> {
+        poll_fn(|cx| Pin::new(&mut self.writer).poll_close(cx)).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::task::{Context, Poll, Waker};
+
+    // takes 3 bytes per write and is busy every other call
+    struct Slow { data: std::vec::Vec<u8>, busy: bool, flushed: std::vec::Vec<usize> }
+
+    impl AsyncWrite for Slow {
+        fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<std::io::Result<usize>> {
+            self.busy = !self.busy;
+            if self.busy {
+                cx.waker().wake_by_ref();
+                return Poll::Pending
+            }
+            let n = usize::min(3, buf.len());
+            self.data.extend_from_slice(&buf[..n]);
+            Poll::Ready(Ok(n))
+        }
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+            let n = self.data.len();
+            self.flushed.push(n);
+            Poll::Ready(Ok(()))
+        }
+        fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<std::io::Result<()>> { Poll::Ready(Ok(())) }
+    }
+
+    struct Noop;
+    impl std::task::Wake for Noop {
+        fn wake(self: std::sync::Arc<Self>) {}
+    }
+
+    fn blockOn<F: core::future::Future>(f: F) -> F::Output {
+        let mut f = core::pin::pin!(f);
+        let waker = Waker::from(std::sync::Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        loop {
+            if let Poll::Ready(r) = f.as_mut().poll(&mut cx) { return r }
+        }
+    }
+
+    #[test]
+    fn testExpSink() {
+        let mut sink = ExpSink::withWidth(Slow { data: std::vec::Vec::new(), busy: false, flushed: std::vec::Vec::new() }, 20);
+        for src in ["(event (level info) (msg \"up\"))", "(tick 1)"].iter() {
+            let e = match Exp::fromSExp(src.as_bytes()) { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+            assert!(blockOn(sink.send(&e)).is_ok());
+        }
+        assert!(sink.forms() == 2);
+        let w = sink.intoInner();
+        assert!(w.data == b"(event\n  (level info)\n  (msg \"up\"))\n(tick 1)\n");
+        assert!(w.flushed == [36, 45]);
+    }
+}
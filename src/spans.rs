@@ -0,0 +1,126 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// source spans: a `Document` read from text remembers where each node was written
+// so that its exact original text (spacing, comments, spelling) can be quoted back
+//
+use std::collections::HashMap;
+use crate::*;
+
+// node address -> (start, end) in the source, the nodes below the root are on the
+// heap and keep their address while the document moves
+pub(crate) type Spans = HashMap<usize, (usize, usize)>;
+
+impl Exp {
+    // records the span of `e` written at `start` and of its children, returns where
+    // it ends. Children are matched to the text by walking both in order, a node
+    // whose text does not look like it (dispatch handlers, expanded refs) keeps its
+    // own span but not the ones of its children
+    pub(crate) fn collectSpans(&self, src: &[u8], start: usize, out: &mut Spans) -> Option<usize> {
+        let mut start = start;
+        if let Some(end) = Self::datumLabelEnd(src, start).filter(|end| src[end - 1] == b'=') {
+            start = Self::wsEnd(src, end, true)?;
+        }
+        let end = Self::formEnd(src, start, true).ok()??;
+        out.insert(self as *const Exp as usize, (start, end));
+
+        let open = |at: usize| match (self, src.get(at)) {
+            (Exp::Vector(_), Some(b'#')) if src.get(at + 1) == Some(&b'(') => Some(at + 2),
+            (Exp::List(_) | Exp::Pair(..) | Exp::Map(_), Some(b'(' | b'[' | b'{')) => Some(at + 1),
+            _ => None
+        };
+        let prefix = match (self, &src[start..end]) {
+            (Exp::List(_), [b',', b'@', ..]) => 2,
+            (Exp::List(_), [b'\'' | b'`' | b',', ..]) => 1,
+            _ => 0
+        };
+        let mut pos = match open(start) {
+            Some(pos) => pos,
+            None if prefix > 0 => {
+                if let Exp::List(l) = self {
+                    if l.len() == 2 { Self::wsEnd(src, start + prefix, true).and_then(|at| l[1].collectSpans(src, at, out)); }
+                }
+                return Some(end)
+            },
+            None => return Some(end)
+        };
+        let mut child = |e: &Exp, pos: &mut usize| -> Option<()> {
+            *pos = e.collectSpans(src, Self::wsEnd(src, *pos, true)?, out)?;
+            Some(())
+        };
+        match self {
+            Exp::List(l) | Exp::Vector(l) => for e in l.asArray().iter() { child(e, &mut pos)? },
+            Exp::Map(m) => for (k, v) in m.asArray().iter() { child(k, &mut pos)?; child(v, &mut pos)? },
+            Exp::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                for e in items.into_iter() { child(e, &mut pos)? }
+                if let Some(t) = tail {
+                    pos = Self::wsEnd(src, pos, true)? + 1;
+                    child(t, &mut pos)?
+                }
+            },
+            _ => ()
+        }
+        Some(end)
+    }
+}
+
+impl Document {
+    /// where `node`, a node of this document's tree, was written. None for documents
+    /// not read from text, after `rootMut` and for nodes the text does not show
+    /// (the quote of 'x, the inner pairs of (a b . c))
+    pub fn spanOf(&self, node: &Exp) -> Option<(usize, usize)> {
+        let key = if core::ptr::eq(node, &self.root) { 0 } else { node as *const Exp as usize };
+        self.spans.get(&key).copied()
+    }
+
+    /// the original text of `node`, see `spanOf`
+    pub fn sourceOf(&self, node: &Exp) -> Option<&str> {
+        let (start, end) = self.spanOf(node)?;
+        core::str::from_utf8(&self.src[start..end]).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSourceOf() {
+        let src = "\u{feff}; config\n(server  #;(old 1) [port  8080]\n  'x\n  #(1  2) {:a  \"b\"}\n  (p  q . #x1F) #1=(s) #1#)";
+        let doc = match Document::parse(src.as_bytes(), &ReaderOptions::new()) { PROk(d) => d, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let root = doc.root();
+        let at = |p: &str| root.at(&Path::parse(p));
+        assert!(doc.sourceOf(root).is_some_and(|s| s.starts_with("(server  #;(old 1)") && s.ends_with("#1#)")));
+        assert!(doc.sourceOf(at("1").unwrap()) == Some("[port  8080]"));
+        assert!(doc.sourceOf(at("1.1").unwrap()) == Some("8080"));
+        assert!(doc.sourceOf(at("2").unwrap()) == Some("'x"));
+        assert!(doc.sourceOf(at("2.0").unwrap()).is_none());
+        assert!(doc.sourceOf(at("2.1").unwrap()) == Some("x"));
+        assert!(doc.sourceOf(at("3").unwrap()) == Some("#(1  2)"));
+        assert!(doc.sourceOf(at("4").unwrap()) == Some("{:a  \"b\"}"));
+        assert!(doc.sourceOf(at("5").unwrap()) == Some("(p  q . #x1F)"));
+        assert!(doc.sourceOf(at("6").unwrap()) == Some("(s)"));
+        assert!(doc.sourceOf(at("7").unwrap()) == Some("#1#"));
+        assert!(doc.sourceOf(&Exp::Int(1)).is_none());
+        let port = src.find("8080").unwrap();
+        assert!(doc.spanOf(at("1.1").unwrap()) == Some((port, port + 4)));
+    }
+}
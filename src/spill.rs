@@ -0,0 +1,284 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// atom length limit: strings longer than `ReaderOptions::maxAtomLen` are either
+// rejected or decoded straight into a temporary file instead of memory, read
+// as `Exp::Spilled` which removes the file when its last copy is dropped. A
+// `FormReader` checks the limit as it reads and writes the string out chunk by
+// chunk, so it is never buffered whole. Spilled strings compare and hash by content
+//
+use alt_std::*;
+use alt_std::{format};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::io::Write;
+use crate::*;
+
+static SPILLS : AtomicUsize = AtomicUsize::new(0);
+
+/// a string kept in a temporary file, the file is removed with the last copy
+pub struct SpilledString {
+    path    : std::string::String,
+    len     : usize,
+}
+
+impl SpilledString {
+    pub fn path(&self) -> &str { &self.path }
+
+    /// decoded length in bytes
+    pub fn len(&self) -> usize { self.len }
+
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+
+    pub fn read(&self) -> std::io::Result<std::vec::Vec<u8>> {
+        std::fs::read(&self.path)
+    }
+}
+
+impl Drop for SpilledString {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+// a spill file being written, removed when dropped before `finish`
+pub(crate) struct SpillWriter {
+    path    : std::path::PathBuf,
+    out     : Option<std::io::BufWriter<std::fs::File>>,
+    len     : usize,
+    failed  : bool,
+}
+
+impl SpillWriter {
+    pub(crate) fn create() -> Option<Self> {
+        let name = format!("s-exp-{}-{}.spill", std::process::id(), SPILLS.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(name.toStr());
+        let file = std::fs::File::create(&path).ok()?;
+        Some(Self { path, out: Some(std::io::BufWriter::new(file)), len: 0, failed: false })
+    }
+
+    fn push(&mut self, c: u8) {
+        if let Some(out) = &mut self.out {
+            self.failed |= out.write_all(&[c]).is_err();
+        }
+        self.len += 1;
+    }
+
+    // decodes the string literal at `offset`
+    pub(crate) fn decode(&mut self, src: &[u8], offset: &mut usize) -> ParseResult<()> {
+        Exp::decodeString(src, offset, &mut |c| self.push(c))
+    }
+
+    // decodes `body`, a piece of a string literal not ending inside an escape
+    // and found at `offset` in the input
+    pub(crate) fn decodePiece(&mut self, body: &[u8], offset: usize) -> Result<(), ParseError> {
+        let mut quoted = std::vec::Vec::with_capacity(body.len() + 2);
+        quoted.push(b'"');
+        quoted.extend_from_slice(body);
+        quoted.push(b'"');
+        match self.decode(&quoted, &mut 0) {
+            PROk(()) => Ok(()),
+            PRErr(err) => Err(ParseError { message: err.message, offset: err.offset + offset - 1, kind: err.kind })
+        }
+    }
+
+    pub(crate) fn finish(mut self) -> Option<SpilledString> {
+        let mut out = self.out.take()?;
+        if self.failed || out.flush().is_err() { return None }
+        drop(out);
+        let path = core::mem::take(&mut self.path);
+        Some(SpilledString { path: path.to_string_lossy().into_owned(), len: self.len })
+    }
+}
+
+impl Drop for SpillWriter {
+    fn drop(&mut self) {
+        drop(self.out.take());
+        if !self.path.as_os_str().is_empty() { let _ = std::fs::remove_file(&self.path); }
+    }
+}
+
+impl Exp {
+    // decoded length of the string literal at `offset`, counting stops past `max`
+    pub(crate) fn stringLen(src: &[u8], offset: usize, max: usize) -> usize {
+        let mut i = offset + 1;
+        let mut len = 0;
+        while i < src.len() && len <= max {
+            match src[i] {
+                b'"' => break,
+                b'\\' => match Self::unicodeEscape(src, i + 1) {
+                    Some((c, end)) => { i = end; len += c.len_utf8() - 1 },
+                    None => i += 2,
+                },
+                _ => i += 1,
+            }
+            len += 1;
+        }
+        len
+    }
+
+    pub(crate) fn overlongString(rd: &Reader, src: &[u8], offset: &mut usize) -> ParseResult<Exp> {
+        let start = *offset;
+        if rd.opts.atomOverflow == AtomOverflow::Error {
+            return PRErr(ParseError { message: String::from("atom too long"), offset: start, kind: ErrorKind::Syntax })
+        }
+
+        let mut w = match SpillWriter::create() {
+            Some(w) => w,
+            None => return PRErr(Self::spillError(start))
+        };
+        if let PRErr(err) = w.decode(src, offset) { return PRErr(err) }
+        match w.finish() {
+            Some(s) => PROk(Exp::Spilled(std::sync::Arc::new(s))),
+            None => PRErr(Self::spillError(start))
+        }
+    }
+
+    pub(crate) fn spillError(offset: usize) -> ParseError {
+        ParseError { message: String::from("unable to spill string"), offset, kind: ErrorKind::Syntax }
+    }
+
+    /// path and length of a string spilled by `AtomOverflow::Spill`
+    pub fn spilledString(&self) -> Option<(&str, usize)> {
+        match self {
+            Exp::Spilled(s) => Some((s.path(), s.len())),
+            _ => None
+        }
+    }
+
+    // the content of a spilled string, empty when the file can no longer be read
+    pub(crate) fn spilledBytes(s: &SpilledString) -> std::vec::Vec<u8> {
+        s.read().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testMaxAtomLen() {
+        let mut opts = ReaderOptions::new();
+        opts.maxAtomLen = Some(4);
+        assert!(match Exp::fromSExpWithOptions(b"(abcd \"a\\\"cd\" 1234)", &opts) { PROk(_) => true, _ => false });
+        for (src, at) in [("(abcde)", 1), ("(a 12345)", 3), ("(a \"abcde\")", 3), ("(a \"abcdefgh", 3)].iter() {
+            match Exp::fromSExpWithOptions(src.as_bytes(), &opts) {
+                PRErr(err) => assert!(err.message() == "atom too long" && err.offset() == *at),
+                PROk(_) => panic!("{} accepted", src)
+            }
+        }
+
+        opts.atomOverflow = AtomOverflow::Spill;
+        match Exp::fromSExpWithOptions(b"(a \"ab\\ncdef\" \"xy\")", &opts) {
+            PROk(Exp::List(l)) => {
+                let (path, len) = match l[1].spilledString() { Some(s) => s, None => panic!("not spilled") };
+                let path = std::string::String::from(path);
+                let content = std::fs::read(&path).unwrap();
+                assert!(len == 7 && content == b"ab\ncdef");
+                assert!(l[2].spilledString().is_none());
+                assert!(Exp::List(l.clone()).toString().toStr() == "(a \"ab\\ncdef\" \"xy\")");
+                // not a list any more
+                assert!(l[1] != Exp::String(String::from("ab\ncdef")) && !matches!(l[1], Exp::List(_)));
+                let copy = l[1].clone();
+                drop(l);
+                assert!(std::path::Path::new(&path).exists());
+                drop(copy);
+                assert!(!std::path::Path::new(&path).exists());
+            },
+            _ => panic!("spill failed")
+        }
+        assert!(match Exp::fromSExpWithOptions(b"(a \"abcdefgh", &opts) { PRErr(err) => err.offset() == 12, _ => false });
+
+        // a string split over many refills is still spilled once
+        struct Trickle<'a>(&'a [u8]);
+        impl<'a> std::io::Read for Trickle<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                let n = usize::min(3, usize::min(buf.len(), self.0.len()));
+                buf[..n].copy_from_slice(&self.0[..n]);
+                self.0 = &self.0[n..];
+                Ok(n)
+            }
+        }
+        let before = SPILLS.load(Ordering::Relaxed);
+        let forms : std::vec::Vec<_> = FormReader::withOptions(Trickle(b"(x \"0123456789abcdef0123456789\") y"), opts).collect();
+        assert!(forms.len() == 2 && SPILLS.load(Ordering::Relaxed) - before == 1);
+    }
+
+    // `head`, then `n` bytes of `fill`, then `tail`, counting what was read
+    struct Gen<'a> { head: &'a [u8], n: usize, fill: u8, tail: &'a [u8], read: std::rc::Rc<core::cell::Cell<usize>> }
+    impl<'a> std::io::Read for Gen<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let mut k = 0;
+            while k < buf.len() {
+                if !self.head.is_empty() { buf[k] = self.head[0]; self.head = &self.head[1..] }
+                else if self.n > 0 { buf[k] = self.fill; self.n -= 1 }
+                else if !self.tail.is_empty() { buf[k] = self.tail[0]; self.tail = &self.tail[1..] }
+                else { break }
+                k += 1;
+            }
+            self.read.set(self.read.get() + k);
+            Ok(k)
+        }
+    }
+
+    #[test]
+    fn testMaxAtomLenStreaming() {
+        let read = std::rc::Rc::new(core::cell::Cell::new(0));
+        let gen = |head, n, fill, tail| Gen { head, n, fill, tail, read: read.clone() };
+        let mut opts = ReaderOptions::new();
+        opts.maxAtomLen = Some(4);
+
+        // an overlong atom fails as soon as it is seen, not at the end of the input
+        for head in [&b"(x \"abc"[..], &b"(x abc"[..], &b"(x 123"[..]].iter() {
+            read.set(0);
+            let mut r = FormReader::withOptions(gen(head, 1 << 24, b'1', b")"), opts.clone());
+            assert!(match r.next() { Some(PRErr(err)) => err.message() == "atom too long" && err.offset() == 3, _ => false });
+            assert!(read.get() < 1 << 20);
+        }
+
+        // a spilled string is streamed to its file, offsets still count its bytes
+        opts.atomOverflow = AtomOverflow::Spill;
+        let n = 1 << 20;
+        let mut r = FormReader::withOptions(gen(b"(x \"a\\u{41}", n, b'b', b"\\n\\u{42}\" y) (z \"\" q\"\\q\")"), opts.clone());
+        match r.next() {
+            Some(PROk(Exp::List(l))) => {
+                let (_, len) = match l[1].spilledString() { Some(s) => s, None => panic!("not spilled") };
+                let text = Exp::spilledBytes(match &l[1] { Exp::Spilled(s) => s, _ => unreachable!() });
+                assert!(len == n + 4 && text.len() == len && &text[..2] == b"aA" && &text[n + 2..] == b"\nB");
+                assert!(text[2..n + 2].iter().all(|c| *c == b'b') && l[2] == Exp::Symbol(String::from("y")));
+                assert!(r.offset() == n + 23);
+            },
+            _ => panic!("spill failed")
+        }
+        assert!(match r.next() { Some(PRErr(err)) => err.message() == "invalid escape (string)" && err.offset() == n + 32, _ => false });
+
+        let mut r = FormReader::withOptions(gen(b"(x \"", n, b'b', b""), opts.clone());
+        assert!(match r.next() { Some(PRErr(err)) => err.kind() == ErrorKind::Eof && err.offset() == n + 4, _ => false });
+
+        // spills of the same text are the same value
+        let parse = || match Exp::fromSExpWithOptions(b"(\"abcdef\" \"abcdeg\")", &opts) {
+            PROk(Exp::List(l)) => l,
+            _ => panic!("spill failed")
+        };
+        let (a, b) = (parse(), parse());
+        assert!(a[0] == b[0] && a[0] != a[1] && a[0].totalCmp(&b[0]) == core::cmp::Ordering::Equal);
+        assert!(a[0].totalCmp(&a[1]) == core::cmp::Ordering::Less);
+        assert!(HashedExp::new(a[0].clone()).hash() == HashedExp::new(b[0].clone()).hash());
+    }
+}
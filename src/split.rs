@@ -0,0 +1,82 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// splitting: cuts a document into ranges of whole top level forms of about the
+// same size, each one reads on its own (a worker per range). Boundaries come from
+// the light scan of the router, the forms are not parsed
+//
+use alt_std::*;
+use core::ops::Range;
+use crate::*;
+
+impl Exp {
+    /// consecutive ranges covering `src`, each cut at the form boundary nearest to
+    /// `chunkSize` bytes past its start, a form longer than that is a range alone
+    pub fn splitBalanced(src: &[u8], chunkSize: usize) -> Result<Vec<Range<usize>>, ParseError> {
+        let mut ranges = Vec::new();
+        // a byte order mark and a shebang line go with the first range
+        let (mut start, mut last, mut pos) = (0, 0, Self::preambleEnd(src, true, true).unwrap_or(0));
+        loop {
+            pos = match Self::wsEnd(src, pos, true) {
+                Some(end) if end == src.len() => break,
+                Some(end) => end,
+                None => break,
+            };
+            let end = match Self::formEnd(src, pos, true)? {
+                Some(end) => end,
+                None => break,
+            };
+            let target = start + chunkSize;
+            // the boundary before this form may be nearer
+            if end >= target && last > start && target - last < end - target {
+                ranges.pushBack(start..last);
+                start = last;
+            }
+            if end >= start + chunkSize {
+                ranges.pushBack(start..end);
+                start = end;
+            }
+            last = end;
+            pos = end;
+        }
+        if start < src.len() || ranges.len() == 0 { ranges.pushBack(start..src.len()) }
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSplitBalanced() {
+        let src = b"(a 1) (b \"))\") (c 3)\n(d (4 5 6 7 8 9)) ; tail\n(e)";
+        let ranges = Exp::splitBalanced(src, 12).ok().unwrap();
+        let parts : std::vec::Vec<&str> = ranges.asArray().iter().map(|r| std::str::from_utf8(&src[r.clone()]).unwrap()).collect();
+        // `(d ...)` is longer than a chunk and goes alone
+        assert!(parts == ["(a 1) (b \"))\")", " (c 3)", "\n(d (4 5 6 7 8 9))", " ; tail\n(e)"]);
+        assert!(ranges.asArray().windows(2).all(|w| w[0].end == w[1].start) && ranges[3].end == src.len());
+        for r in ranges.asArray().iter() {
+            assert!(Exp::fromSExpPrefix(&src[r.clone()]).0.len() > 0);
+        }
+        assert!(Exp::splitBalanced(b"", 8).ok().unwrap().len() == 1);
+        assert!(Exp::splitBalanced(b"(a) (b", 2).is_err());
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+/// heap free expression that can live in a `const`/`static`
+#[derive(Clone, Copy)]
+pub enum StaticExp {
+    Bool(bool),
+    Char(char),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Ratio(i64, i64),
+    // sign and base 10^9 limbs, see `BigInt::fromLimbs`
+    #[cfg(feature = "bigint")]
+    BigInt(bool, &'static [u32]),
+    String(&'static str),
+    Symbol(&'static str),
+    Keyword(&'static str),
+    List(&'static [StaticExp]),
+    Vector(&'static [StaticExp]),
+    Bytes(&'static [u8]),
+    Pair(&'static StaticExp, &'static StaticExp),
+    Map(&'static [(StaticExp, StaticExp)]),
+}
+
+impl StaticExp {
+    pub fn toExp(&self) -> Exp {
+        match self {
+            Self::Bool(b) => Exp::Bool(*b),
+            Self::Char(c) => Exp::Char(*c),
+            Self::Int(i) => Exp::Int(*i),
+            Self::UInt(u) => Exp::UInt(*u),
+            Self::Float(f) => Exp::Float(*f),
+            Self::Ratio(n, d) => Exp::Ratio(*n, *d),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(negative, limbs) => Exp::BigInt(BigInt::fromLimbs(*negative, limbs)),
+            Self::String(s) => Exp::String(String::from(s)),
+            Self::Symbol(s) => Exp::Symbol(String::from(s)),
+            Self::Keyword(k) => Exp::Keyword(String::from(k)),
+            Self::List(l) => {
+                let mut v = Vec::new();
+                for e in l.iter() {
+                    v.pushBack(e.toExp());
+                }
+                Exp::List(v)
+            },
+            Self::Vector(l) => {
+                let mut v = Vec::new();
+                for e in l.iter() { v.pushBack(e.toExp()) }
+                Exp::Vector(v)
+            },
+            Self::Bytes(b) => {
+                let mut v = Vec::new();
+                for c in b.iter() { v.pushBack(*c) }
+                Exp::Bytes(v)
+            },
+            Self::Pair(a, d) => Exp::Pair(std::boxed::Box::new(a.toExp()), std::boxed::Box::new(d.toExp())),
+            Self::Map(m) => {
+                let mut v = Vec::new();
+                for (k, e) in m.iter() {
+                    v.pushBack((k.toExp(), e.toExp()));
+                }
+                Exp::Map(v)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG : StaticExp = StaticExp::List(&[StaticExp::Symbol("abcd"), StaticExp::Int(123), StaticExp::Symbol("abc")]);
+
+    #[test]
+    fn testStaticToExp() {
+        let s = CONFIG.toExp().toString();
+        assert!(s == "(abcd 123 abc)");
+    }
+}
@@ -0,0 +1,376 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+
+//
+// streaming: top level forms are read one at a time from any `std::io::Read`,
+// only the form being parsed is kept in memory
+//
+use alt_std::*;
+use core::cmp::Ordering;
+use crate::*;
+
+const CHUNK : usize = 64 * 1024;
+
+pub struct FormReader<R: std::io::Read> {
+    input       : R,
+    buff        : Vec<u8>,
+    pos         : usize,
+    // offset of buff[0] in the input
+    base        : usize,
+    eof         : bool,
+    failed      : bool,
+    // the start of the input is still to be checked for a byte order mark and a shebang line
+    preamble    : bool,
+    opts        : ReaderOptions,
+    warnings    : Vec<ParseError>,
+    // (buffer position, count) of the bytes of spilled strings taken out of the buffer
+    shifts      : std::vec::Vec<(usize, usize)>,
+    // strings over `maxAtomLen` streamed to spill files, by the position of the `""` left in their place
+    spills      : std::vec::Vec<(usize, std::sync::Arc<SpilledString>)>,
+}
+
+impl<R: std::io::Read> FormReader<R> {
+    pub fn new(input: R) -> Self {
+        Self::withOptions(input, ReaderOptions::new())
+    }
+
+    pub fn withOptions(input: R, opts: ReaderOptions) -> Self {
+        Self { input, buff: Vec::new(), pos: 0, base: 0, eof: false, failed: false, preamble: true, opts, warnings: Vec::new(), shifts: std::vec::Vec::new(), spills: std::vec::Vec::new() }
+    }
+
+    pub fn warnings(&self) -> &[ParseError] { self.warnings.asArray() }
+
+    /// input offset right after the last form read
+    pub fn offset(&self) -> usize { self.inputOffset(self.pos) }
+
+    // input offset of a buffer position
+    fn inputOffset(&self, at: usize) -> usize {
+        self.base + at + self.shifts.iter().filter(|(p, _)| *p <= at).map(|(_, n)| n).sum::<usize>()
+    }
+
+    // drop the consumed bytes and read some more, at least as much as is left buffered
+    // so that a form spanning many chunks is scanned a logarithmic number of times
+    fn fill(&mut self) -> Result<(), ParseError> {
+        if self.pos > 0 {
+            let mut buff = Vec::new();
+            for c in self.buff.asArray()[self.pos..].iter() {
+                buff.pushBack(*c);
+            }
+            let pos = self.pos;
+            self.base = self.inputOffset(pos);
+            self.shifts.retain(|(p, _)| *p > pos);
+            for (p, _) in self.shifts.iter_mut() { *p -= pos }
+            self.spills.retain(|(p, _)| *p >= pos);
+            for (p, _) in self.spills.iter_mut() { *p -= pos }
+            self.pos = 0;
+            self.buff = buff;
+        }
+
+        let want = self.buff.len();
+        let mut read = 0;
+        let mut chunk = [0u8; CHUNK];
+        loop {
+            match self.input.read(&mut chunk) {
+                Ok(0) => { self.eof = true; break },
+                Ok(n) => { for c in chunk[..n].iter() { self.buff.pushBack(*c) } read += n },
+                Err(_) => return Err(ParseError { message: String::from("unable to read input"), offset: self.inputOffset(self.buff.len()), kind: ErrorKind::Syntax })
+            }
+            if read >= want { break }
+        }
+        Ok(())
+    }
+
+    // streams the body of the string literal opening at `at` to a spill file as
+    // it is read, leaving `""` in its place
+    fn spillString(&mut self, mut at: usize) -> Result<(), ParseError> {
+        let mut w = match SpillWriter::create() {
+            Some(w) => w,
+            None => return Err(Exp::spillError(self.inputOffset(at)))
+        };
+        loop {
+            let src = self.buff.asArray();
+            let mut i = at + 1;
+            let mut close = None;
+            let mut escape = None;
+            while i < src.len() {
+                match src[i] {
+                    b'"' => { close = Some(i); break },
+                    b'\\' => { escape = Some(i); i += 2 },
+                    _ => i += 1,
+                }
+            }
+            // an escape that may go on in the next read (`\u{10ffff}` at most) waits for it
+            let cut = match (close, escape) {
+                (Some(end), _) => end,
+                (None, Some(e)) if src.len() - e < 10 => e,
+                _ => src.len(),
+            };
+            w.decodePiece(&src[at + 1..cut], self.inputOffset(at + 1))?;
+
+            let mut buff = Vec::new();
+            for c in src[..at + 1].iter().chain(src[cut..].iter()) { buff.pushBack(*c) }
+            self.buff = buff;
+            if cut > at + 1 {
+                match self.shifts.iter_mut().find(|(p, _)| *p == at + 1) {
+                    Some((_, n)) => *n += cut - at - 1,
+                    None => self.shifts.push((at + 1, cut - at - 1)),
+                }
+            }
+
+            if close.is_some() {
+                match w.finish() {
+                    Some(s) => { self.spills.push((at, std::sync::Arc::new(s))); return Ok(()) },
+                    None => return Err(Exp::spillError(self.inputOffset(at)))
+                }
+            }
+            if self.eof {
+                let offset = self.inputOffset(self.buff.len());
+                return Err(ParseError { message: String::from("Unexpected end of stream (string)"), offset, kind: ErrorKind::Eof })
+            }
+            let pos = self.pos;
+            self.fill()?;
+            at -= pos;
+        }
+    }
+
+    fn fail(&mut self, err: ParseError) -> Option<ParseResult<Exp>> {
+        self.failed = true;
+        Some(PRErr(err))
+    }
+
+    /// `fold` of the query matches over all the remaining forms
+    pub fn aggregate(&mut self, q: &Query, fold: Fold) -> ParseResult<Option<Exp>> {
+        let mut agg = Aggregator::new(fold);
+        for form in self {
+            match form {
+                PROk(e) => for (_, v) in e.query(q).asArray().iter() { agg.feed(v) },
+                PRErr(err) => return PRErr(err)
+            }
+        }
+        PROk(agg.result())
+    }
+}
+
+impl<R: std::io::Read> Iterator for FormReader<R> {
+    type Item = ParseResult<Exp>;
+
+    fn next(&mut self) -> Option<ParseResult<Exp>> {
+        if self.failed { return None }
+        loop {
+            let len = self.buff.len();
+            if self.preamble {
+                match Exp::preambleEnd(self.buff.asArray(), self.eof, self.opts.skipShebang) {
+                    Some(end) => { self.pos = end; self.preamble = false },
+                    None => {
+                        if let Err(err) = self.fill() { return self.fail(err) }
+                        continue
+                    }
+                }
+            }
+            match Exp::wsEnd(self.buff.asArray(), self.pos, self.eof) {
+                Some(end) => self.pos = end,
+                None => {
+                    if let Err(err) = self.fill() { return self.fail(err) }
+                    continue
+                }
+            }
+
+            if self.pos == len {
+                if self.eof { return None }
+                if let Err(err) = self.fill() { return self.fail(err) }
+                continue
+            }
+
+            // only a complete form is parsed, a broken one is parsed as far as it goes
+            // for the reader's own error
+            let mut open = None;
+            let end = match Exp::formScan(self.buff.asArray(), self.pos, self.eof, &mut open) {
+                Ok(Some(end)) => end,
+                Ok(None) => {
+                    // an atom over the limit is dealt with before the rest of it is buffered
+                    if let Some(max) = self.opts.maxAtomLen {
+                        let src = self.buff.asArray();
+                        match open {
+                            Some((at, true)) if !src[..at].ends_with(b"#u8") && Exp::stringLen(src, at, max) > max => {
+                                if self.opts.atomOverflow == AtomOverflow::Error {
+                                    let offset = self.inputOffset(at);
+                                    return self.fail(ParseError { message: String::from("atom too long"), offset, kind: ErrorKind::Syntax })
+                                }
+                                if let Err(err) = self.spillString(at) { return self.fail(err) }
+                                continue
+                            },
+                            Some((at, false)) if len - at > max && (src[at] != b'#' || Exp::radixPrefix(src, at).is_some()) => {
+                                let offset = self.inputOffset(at);
+                                return self.fail(ParseError { message: String::from("atom too long"), offset, kind: ErrorKind::Syntax })
+                            },
+                            _ => (),
+                        }
+                    }
+                    if let Err(err) = self.fill() { return self.fail(err) }
+                    continue
+                },
+                Err(_) => len,
+            };
+            let mut warnings = Vec::new();
+            let mut offset = self.pos;
+            let mut rd = Reader::new(&self.opts, &mut warnings);
+            rd.spilled = &self.spills;
+            let res = Exp::parseToken(&mut rd, &self.buff.asArray()[..end], &mut offset);
+            let stop = match &res { PROk(_) => offset, PRErr(err) => err.offset };
+            rd.record(self.inputOffset(stop) - self.inputOffset(self.pos), &res);
+            return match res {
+                PROk(e) => {
+                    self.pos = offset;
+                    for w in warnings.asArray().iter() {
+                        self.warnings.pushBack(ParseError { message: w.message.clone(), offset: self.inputOffset(w.offset), kind: w.kind });
+                    }
+                    Some(PROk(e))
+                },
+                PRErr(err) => {
+                    let offset = self.inputOffset(err.offset);
+                    self.fail(ParseError { message: err.message, offset, kind: err.kind })
+                },
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fold {
+    // number of values
+    Count,
+    // sum of the numeric values (others are skipped)
+    Sum,
+    // smallest/largest value in `totalCmp` order
+    Min,
+    Max,
+    // all the values as a list
+    Collect,
+}
+
+pub struct Aggregator {
+    fold    : Fold,
+    count   : i64,
+    sum     : Number,
+    best    : Option<Exp>,
+    items   : Vec<Exp>,
+}
+
+impl Aggregator {
+    pub fn new(fold: Fold) -> Self {
+        Self { fold, count: 0, sum: Number::Int(0), best: None, items: Vec::new() }
+    }
+
+    pub fn feed(&mut self, e: &Exp) {
+        self.count += 1;
+        match self.fold {
+            Fold::Count => (),
+            Fold::Sum => if let Some(n) = e.asNumber() {
+                // overflowing ints carry on as floats
+                self.sum = match self.sum.checkedAdd(&n) {
+                    Some(s) => s,
+                    None => Number::Float(self.sum.toFloat() + n.toFloat())
+                }
+            },
+            Fold::Min | Fold::Max => {
+                let keep = match &self.best {
+                    None => true,
+                    Some(b) => e.totalCmp(b) == if self.fold == Fold::Min { Ordering::Less } else { Ordering::Greater },
+                };
+                if keep { self.best = Some(e.clone()) }
+            },
+            Fold::Collect => self.items.pushBack(e.clone()),
+        }
+    }
+
+    /// `None` for the min/max of nothing
+    pub fn result(&self) -> Option<Exp> {
+        match self.fold {
+            Fold::Count => Some(Exp::Int(self.count)),
+            Fold::Sum => Some(self.sum.toExp()),
+            Fold::Min | Fold::Max => self.best.clone(),
+            Fold::Collect => Some(Exp::List(self.items.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hands out a few bytes at a time to split forms across reads
+    struct Trickle<'a>(&'a [u8]);
+
+    impl<'a> std::io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            let n = usize::min(3, usize::min(buf.len(), self.0.len()));
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    const LOG : &str = "; access log\n(req (ms 12) (path \"/a\"))\n(req (ms 250) (path \"/b\"))\n(req (ms 7.5) (path \"/a\"))\n12345";
+
+    #[test]
+    fn testFormReader() {
+        let mut r = FormReader::new(Trickle(LOG.as_bytes()));
+        let mut s = String::new();
+        for f in &mut r {
+            match f {
+                PROk(e) => { s.append(&e.toString()); s.add(b'|') },
+                PRErr(err) => panic!("{}", err.message.toStr())
+            }
+        }
+        assert!(s == "(req (ms 12) (path \"/a\"))|(req (ms 250) (path \"/b\"))|(req (ms 7.5) (path \"/a\"))|12345|");
+
+        let mut r = FormReader::new(Trickle(b"(a 1) (b \"x"));
+        assert!(match r.next() { Some(PROk(_)) => true, _ => false });
+        assert!(match r.next() { Some(PRErr(err)) => err.offset() == 11, _ => false });
+        assert!(r.next().is_none());
+
+        let mut r = FormReader::new(Trickle(b"#!/usr/bin/env sexp run\n(a) (b)"));
+        assert!(match r.next() { Some(PROk(e)) => e.toString() == "(a)" && r.offset() == 27, _ => false });
+
+        // valid forms cut anywhere by the chunking
+        for src in ["(x |a b c d|)", "(a #;(b c) d)", "(x #\\space)", "(a #| b |# \"c d\" 12.5)"].iter() {
+            let mut r = FormReader::new(Trickle(src.as_bytes()));
+            assert!(match (r.next(), r.next()) { (Some(PROk(e)), None) => Exp::fromSExp(src.as_bytes()) == PROk(e), _ => false }, "{}", src);
+        }
+    }
+
+    #[test]
+    fn testFold() {
+        let run = |q: &str, f: Fold| {
+            let q = match Query::parse(q) { PROk(q) => q, PRErr(err) => panic!("{}", err.message.toStr()) };
+            match FormReader::new(Trickle(LOG.as_bytes())).aggregate(&q, f) {
+                PROk(r) => r,
+                PRErr(err) => panic!("{}", err.message.toStr())
+            }
+        };
+        assert!(run("req.ms", Fold::Count) == Some(Exp::Int(3)));
+        assert!(run("req.ms", Fold::Sum) == Some(Exp::Float(269.5)));
+        assert!(run("req.ms", Fold::Min) == Some(Exp::Float(7.5)));
+        assert!(run("req.ms", Fold::Max) == Some(Exp::Int(250)));
+        assert!(run("(req (ms (? > 10)))", Fold::Count) == Some(Exp::Int(2)));
+        assert!(run("(req (path (? = \"/a\")))", Fold::Collect).unwrap().toString() == "(\"/a\" \"/a\")");
+        assert!(run("nope", Fold::Max).is_none());
+    }
+}
@@ -0,0 +1,124 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// symbol tables: symbols numbered in the order they are first interned. A table
+// exported with `toExp` and read back with `fromExp` in another process gives the
+// same ids, interning more symbols there only appends, so the producers and the
+// consumers of a pipeline agree on the ids they exchange
+//
+use alt_std::*;
+use std::collections::HashMap;
+use crate::*;
+
+#[derive(Clone)]
+pub struct SymbolTable {
+    names   : Vec<String>,
+    ids     : HashMap<std::string::String, u32>,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self { names: Vec::new(), ids: HashMap::new() }
+    }
+
+    /// the id of `name`, a new one after the existing ones when it is not in the table
+    pub fn intern(&mut self, name: &str) -> u32 {
+        if let Some(id) = self.ids.get(name) { return *id }
+        let id = self.names.len() as u32;
+        self.names.pushBack(String::from(name));
+        self.ids.insert(std::string::String::from(name), id);
+        id
+    }
+
+    /// interns the symbols of `e` in reading order
+    pub fn internAll(&mut self, e: &Exp) {
+        match e {
+            Exp::Symbol(s) => { self.intern(s.toStr()); },
+            Exp::List(l) | Exp::Vector(l) => for e in l.asArray().iter() { self.internAll(e) },
+            Exp::Pair(a, d) => { self.internAll(a); self.internAll(d) },
+            Exp::Map(m) => for (k, v) in m.asArray().iter() { self.internAll(k); self.internAll(v) },
+            _ => ()
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Option<u32> { self.ids.get(name).copied() }
+
+    pub fn name(&self, id: u32) -> Option<&str> {
+        self.names.asArray().get(id as usize).map(|n| n.toStr())
+    }
+
+    pub fn len(&self) -> usize { self.names.len() }
+
+    pub fn is_empty(&self) -> bool { self.names.len() == 0 }
+
+    /// `(symbols "name"...)`, the name of id i in position i
+    pub fn toExp(&self) -> Exp {
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from("symbols")));
+        for n in self.names.asArray().iter() { l.pushBack(Exp::String(n.clone())) }
+        Exp::List(l)
+    }
+
+    /// reads back the form written by `toExp`
+    pub fn fromExp(e: &Exp) -> Result<SymbolTable, String> {
+        let l = match e {
+            Exp::List(l) if l.len() > 0 && l[0] == Exp::Symbol(String::from("symbols")) => l.asArray(),
+            _ => return Err(alt_std::format!("invalid symbol table {}", e.toString().toStr()))
+        };
+        let mut t = Self::new();
+        for n in l[1..].iter() {
+            match n {
+                Exp::String(s) if t.id(s.toStr()).is_none() => { t.intern(s.toStr()); },
+                Exp::String(s) => return Err(alt_std::format!("symbol {} listed twice", s.toStr())),
+                n => return Err(alt_std::format!("expected a string, found {}", n.toString().toStr())),
+            }
+        }
+        Ok(t)
+    }
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testSymbolTable() {
+        let mut producer = SymbolTable::new();
+        if let PROk(e) = Exp::fromSExp(b"(define (f x) {:k x} #(y f) \"s\")") { producer.internAll(&e) }
+        assert!(producer.len() == 4 && producer.id("f") == Some(1) && producer.name(3) == Some("y") && producer.id("s").is_none());
+
+        // the consumer reads the exported table and keeps the ids while interning more
+        let text = producer.toExp().toString();
+        assert!(text == "(symbols \"define\" \"f\" \"x\" \"y\")");
+        let mut consumer = match Exp::fromSExp(text.asArray()) {
+            PROk(e) => match SymbolTable::fromExp(&e) { Ok(t) => t, Err(err) => panic!("{}", err.toStr()) },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        assert!(consumer.intern("z") == 4 && consumer.intern("x") == 2);
+
+        let twice = match Exp::fromSExp(b"(symbols \"a\" \"a\")") { PROk(e) => SymbolTable::fromExp(&e).err(), _ => None };
+        assert!(twice == Some(String::from("symbol a listed twice")));
+        assert!(SymbolTable::fromExp(&Exp::Int(1)).is_err());
+    }
+}
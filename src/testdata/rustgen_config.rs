@@ -0,0 +1,67 @@
+// generated from an s-expression schema, do not edit
+
+#[derive(Clone)]
+pub struct Limits {
+    pub rps: f64,
+    pub burst: i64,
+}
+
+impl ::s_exp::FromExp for Limits {
+    fn fromExp(e: &::s_exp::Exp) -> Result<Self, ::alt_std::String> {
+        Ok(Self {
+            rps: e.mapField("rps")?,
+            burst: e.mapFieldOr("burst", &::s_exp::Exp::Int(10))?,
+        })
+    }
+}
+
+impl ::s_exp::ToExp for Limits {
+    fn toExp(&self) -> ::s_exp::Exp {
+        let mut m = ::s_exp::Exp::Map(::alt_std::Vec::new());
+        m.setMapField("rps", &self.rps);
+        m.setMapField("burst", &self.burst);
+        m
+    }
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub max_conns: i64,
+    pub r#type: Option<::s_exp::Sym>,
+    pub self_: ::alt_std::String,
+    pub crate_: Option<::s_exp::Kw>,
+    pub port: i64,
+    pub tags: ::alt_std::Vec<::s_exp::Sym>,
+    pub limits: Limits,
+}
+
+impl ::s_exp::FromExp for Config {
+    fn fromExp(e: &::s_exp::Exp) -> Result<Self, ::alt_std::String> {
+        let entries = e.recordEntries(Some("server"))?;
+        Ok(Self {
+            max_conns: ::s_exp::Exp::recordField(entries, "max-conns", false)?,
+            r#type: ::s_exp::Exp::recordField(entries, "type", false)?,
+            self_: ::s_exp::Exp::recordField(entries, "self", false)?,
+            crate_: ::s_exp::Exp::recordField(entries, "crate", false)?,
+            port: ::s_exp::Exp::recordFieldOr(entries, "port", false, &::s_exp::Exp::Int(80))?,
+            tags: ::s_exp::Exp::recordField(entries, "tags", true)?,
+            limits: ::s_exp::Exp::recordField(entries, "limits", false)?,
+        })
+    }
+}
+
+impl ::s_exp::ToExp for Config {
+    fn toExp(&self) -> ::s_exp::Exp {
+        let mut v = ::alt_std::Vec::new();
+        v.pushBack(::s_exp::Exp::Symbol(::alt_std::String::from("server")));
+        ::s_exp::Exp::pushRecordField(&mut v, "max-conns", &self.max_conns, false);
+        ::s_exp::Exp::pushRecordField(&mut v, "type", &self.r#type, false);
+        ::s_exp::Exp::pushRecordField(&mut v, "self", &self.self_, false);
+        ::s_exp::Exp::pushRecordField(&mut v, "crate", &self.crate_, false);
+        ::s_exp::Exp::pushRecordField(&mut v, "port", &self.port, false);
+        ::s_exp::Exp::pushRecordField(&mut v, "tags", &self.tags, true);
+        ::s_exp::Exp::pushRecordField(&mut v, "limits", &self.limits, false);
+        ::s_exp::Exp::List(v)
+    }
+}
+
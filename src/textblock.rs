@@ -0,0 +1,117 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// text blocks: `"""` then a newline opens a multi-line string closed by a `"""`
+// on its own line. The indentation of the closing line is stripped from every
+// line, the newlines after the opening and before the closing quotes are not part
+// of the string. Escapes (`\n`, `\u{E9}`, ...) are read as in strings, a lone `"`
+// needs none
+//
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    pub(crate) fn isTextBlockStart(src: &[u8], offset: usize) -> bool {
+        src.get(offset..).is_some_and(|s| s.starts_with(b"\"\"\""))
+    }
+
+    // offset of the closing `"""`
+    pub(crate) fn textBlockEnd(src: &[u8], start: usize) -> Option<usize> {
+        let mut i = start;
+        while i < src.len() {
+            match src[i] {
+                b'\\' => i += 2,
+                b'"' if src[i..].starts_with(b"\"\"\"") => return Some(i),
+                _ => i += 1,
+            }
+        }
+        None
+    }
+
+    pub(crate) fn parseTextBlock(src: &[u8], offset: &mut usize) -> ParseResult<String> {
+        let start = *offset;
+        let mut body = start + 3;
+        while let Some(b' ' | b'\t' | b'\r') = src.get(body) { body += 1 }
+        if src.get(body) != Some(&b'\n') {
+            return PRErr(ParseError { message: String::from("expected a newline after \"\"\" (string)"), offset: body, kind: ErrorKind::Syntax })
+        }
+        body += 1;
+        let close = match Self::textBlockEnd(src, body) {
+            Some(close) => close,
+            None => return PRErr(ParseError { message: String::from("Unexpected end of stream (string)"), offset: src.len(), kind: ErrorKind::Eof }),
+        };
+        let lineStart = src[..close].iter().rposition(|c| *c == b'\n').map_or(0, |i| i + 1);
+        if lineStart < body || src[lineStart..close].iter().any(|c| !Self::isWS(*c)) {
+            return PRErr(ParseError { message: String::from("closing \"\"\" must be on its own line (string)"), offset: close, kind: ErrorKind::Syntax })
+        }
+        let indent = &src[lineStart..close];
+
+        let mut s = String::new();
+        let mut at = body;
+        while at < lineStart {
+            let end = at + src[at..].iter().position(|c| *c == b'\n').unwrap_or(0);
+            let line = match &src[at..end] { [l @ .., b'\r'] => l, l => l };
+            if at > body { s.add(b'\n') }
+            if !line.iter().all(|c| Self::isWS(*c)) {
+                if !line.starts_with(indent) {
+                    return PRErr(ParseError { message: String::from("insufficient indentation (string)"), offset: at, kind: ErrorKind::Syntax })
+                }
+                let mut i = at + indent.len();
+                while i < at + line.len() {
+                    match src[i] {
+                        b'\\' if src.get(i + 1) == Some(&b'u') => match Self::unicodeEscape(src, i + 1) {
+                            Some((c, end)) if end <= at + line.len() => { Self::encodeUtf8(c, &mut |b| s.add(b)); i = end },
+                            _ => return PRErr(ParseError { message: String::from("invalid unicode escape (string)"), offset: i, kind: ErrorKind::Escape }),
+                        },
+                        b'\\' => match src.get(i + 1).and_then(|c| Self::unescape(*c)) {
+                            Some(c) if i + 1 < at + line.len() => { s.add(c); i += 2 },
+                            _ => return PRErr(ParseError { message: String::from("invalid escape (string)"), offset: i, kind: ErrorKind::Escape }),
+                        },
+                        c => { s.add(c); i += 1 },
+                    }
+                }
+            }
+            at = end + 1;
+        }
+        *offset = close + 3;
+        Self::utf8Checked(s, start, "string")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testTextBlock() {
+        let src = b"(help \"\"\"\n    usage: \"tool\" [options]\n\n      -v\\tverbose \\u{E9}\n    \"\"\" x)";
+        match Exp::fromSExp(src) {
+            PROk(Exp::List(l)) => {
+                assert!(l[1] == Exp::String(String::from("usage: \"tool\" [options]\n\n  -v\tverbose \u{E9}")));
+                assert!(l[2] == Exp::Symbol(String::from("x")));
+            },
+            _ => panic!("expected a list")
+        }
+        assert!(Exp::fromSExp(b"\"\"\"\n\"\"\"") == PROk(Exp::String(String::new())));
+        assert!(Exp::fromSExp(b"\"\"\"a\n\"\"\"") == PRErr(ParseError { message: String::from("expected a newline after \"\"\" (string)"), offset: 3, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"\"\"\"\n  a\n b\n  \"\"\"") == PRErr(ParseError { message: String::from("insufficient indentation (string)"), offset: 8, kind: ErrorKind::Syntax }));
+        assert!(Exp::fromSExp(b"\"\"\"\n  a \"\"\"") == PRErr(ParseError { message: String::from("closing \"\"\" must be on its own line (string)"), offset: 8, kind: ErrorKind::Syntax }));
+    }
+}
@@ -0,0 +1,247 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// parse traces: `fromSExpTraced` records every tokenizer decision (the rule
+// taken, where it started and how many bytes it consumed) so that a surprising
+// parse can be inspected or replayed step by step after the fact. The opening
+// step of a list, vector or map spans its opener (`(`, `[`, `#(`, `{`) and a
+// dispatch step (quote, bytes, label, `#c` handler) the whole datum, so replay
+// reads every completed value back from its source span with the options of
+// the parse. Values that only make sense in the whole document do not replay:
+// a `#n#` reference to a label outside of its span is dropped, a `$n` ref reads
+// as the symbol
+//
+use alt_std::*;
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rule {
+    String,
+    Number,
+    Char,
+    // symbols, including #t/#f and the special floats
+    Symbol,
+    ListOpen,
+    ListClose,
+    // a `#c` dispatch handler, replayed as nothing
+    Dispatch,
+    // the parse failed at `offset`
+    Error,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::String => "string",
+            Self::Number => "number",
+            Self::Char => "char",
+            Self::Symbol => "symbol",
+            Self::ListOpen => "list-open",
+            Self::ListClose => "list-close",
+            Self::Dispatch => "dispatch",
+            Self::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct TraceStep {
+    pub rule        : Rule,
+    pub offset      : usize,
+    pub consumed    : usize,
+}
+
+pub struct ParseTrace {
+    steps   : Vec<TraceStep>,
+    // the options of the traced parse, the defaults for a trace read back with `fromExp`
+    opts    : ReaderOptions,
+}
+
+impl ParseTrace {
+    pub fn new() -> Self { Self { steps: Vec::new(), opts: ReaderOptions::new() } }
+
+    pub(crate) fn push(&mut self, rule: Rule, offset: usize, consumed: usize) {
+        self.steps.pushBack(TraceStep { rule, offset, consumed })
+    }
+
+    pub fn steps(&self) -> &[TraceStep] { self.steps.asArray() }
+
+    /// `(trace (rule offset consumed) ...)`
+    pub fn toExp(&self) -> Exp {
+        let mut l = Vec::new();
+        l.pushBack(Exp::Symbol(String::from("trace")));
+        for s in self.steps.asArray().iter() {
+            let mut step = Vec::new();
+            step.pushBack(Exp::Symbol(String::from(s.rule.name())));
+            step.pushBack(Exp::Int(s.offset as i64));
+            step.pushBack(Exp::Int(s.consumed as i64));
+            l.pushBack(Exp::List(step));
+        }
+        Exp::List(l)
+    }
+
+    /// reads back a trace written by `toExp`
+    pub fn fromExp(e: &Exp) -> Option<Self> {
+        let l = match e { Exp::List(l) => l.asArray(), _ => return None };
+        match l.first() {
+            Some(Exp::Symbol(s)) if s.toStr() == "trace" => (),
+            _ => return None
+        }
+        let mut t = Self::new();
+        for s in l[1..].iter() {
+            match s {
+                Exp::List(s) if s.len() == 3 => {
+                    let rule = match &s[0] {
+                        Exp::Symbol(n) => [Rule::String, Rule::Number, Rule::Char, Rule::Symbol, Rule::ListOpen, Rule::ListClose, Rule::Dispatch, Rule::Error]
+                            .iter().find(|r| r.name() == n.toStr()).copied()?,
+                        _ => return None
+                    };
+                    match (&s[1], &s[2]) {
+                        (Exp::Int(o), Exp::Int(c)) if *o >= 0 && *c >= 0 => t.push(rule, *o as usize, *c as usize),
+                        _ => return None
+                    }
+                },
+                _ => return None
+            }
+        }
+        Some(t)
+    }
+
+    /// replays the first `upTo` steps over `src`: the lists, vectors and maps
+    /// still open at that point, outermost first, each holding the elements
+    /// completed so far (a map as a list of its keys and values). The first one
+    /// is the top level list, replaying a whole successful trace leaves it
+    /// holding the result.
+    pub fn replay(&self, src: &[u8], upTo: usize) -> Vec<Exp> {
+        self.replayWithOptions(src, upTo, &self.opts)
+    }
+
+    /// `replay` reading the values with `opts`, for a trace read back by `fromExp`
+    pub fn replayWithOptions(&self, src: &[u8], upTo: usize, opts: &ReaderOptions) -> Vec<Exp> {
+        let read = |start: usize, len: usize| {
+            let end = usize::min(start + len, src.len());
+            match Exp::fromSExpWithOptions(&src[usize::min(start, end)..end], opts) {
+                PROk(e) => Some((start, e)),
+                PRErr(_) => None
+            }
+        };
+        // the offset of each open list with its elements and where they start
+        let mut stack : std::vec::Vec<(usize, std::vec::Vec<(usize, Exp)>)> = std::vec::Vec::new();
+        stack.push((0, std::vec::Vec::new()));
+        for s in self.steps.asArray().iter().take(upTo) {
+            let done = match s.rule {
+                Rule::ListOpen => { stack.push((s.offset, std::vec::Vec::new())); None },
+                Rule::ListClose if stack.len() > 1 => match stack.pop() {
+                    Some((start, _)) => read(start, s.offset + s.consumed - start),
+                    None => None
+                },
+                Rule::ListClose | Rule::Error => None,
+                // the datum under a quote or a label was replayed on its own first
+                Rule::Dispatch => {
+                    if let Some((_, top)) = stack.last_mut() { top.retain(|(at, _)| *at < s.offset) }
+                    read(s.offset, s.consumed)
+                },
+                _ => read(s.offset, s.consumed),
+            };
+            if let (Some(e), Some((_, top))) = (done, stack.last_mut()) { top.push(e) }
+        }
+
+        let mut out = Vec::new();
+        for (i, (start, l)) in stack.iter().enumerate() {
+            let mut items = Vec::new();
+            for (_, e) in l.iter() { items.pushBack(e.clone()) }
+            out.pushBack(if i > 0 && src.get(*start..).is_some_and(|s| s.starts_with(b"#(")) { Exp::Vector(items) } else { Exp::List(items) });
+        }
+        out
+    }
+}
+
+impl Default for ParseTrace {
+    fn default() -> Self { Self::new() }
+}
+
+impl Exp {
+    /// like `fromSExpWithOptions`, also returns the trace of the tokenizer decisions
+    pub fn fromSExpTraced(src: &[u8], opts: &ReaderOptions) -> (ParseResult<Exp>, ParseTrace) {
+        let mut trace = ParseTrace::new();
+        let mut warnings = Vec::new();
+        let res = {
+            let mut rd = Reader::new(opts, &mut warnings);
+            trace.opts = opts.clone();
+            rd.trace = Some(&mut trace);
+            let mut offset = Self::documentStart(src, opts);
+            Self::skipWS(src, &mut offset);
+            Self::parseToken(&mut rd, src, &mut offset)
+        };
+        (res, trace)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testParseTrace() {
+        let src = b"(a (12 \"x\") #\\b)";
+        let (res, trace) = Exp::fromSExpTraced(src, &ReaderOptions::new());
+        let rules : std::vec::Vec<Rule> = trace.steps().iter().map(|s| s.rule).collect();
+        assert!(rules == [Rule::ListOpen, Rule::Symbol, Rule::ListOpen, Rule::Number, Rule::String, Rule::ListClose, Rule::Char, Rule::ListClose]);
+        assert!(trace.steps()[4] == TraceStep { rule: Rule::String, offset: 7, consumed: 3 });
+
+        let dumped = match ParseTrace::fromExp(&trace.toExp()) { Some(t) => t, None => panic!("trace did not read back") };
+        let partial = dumped.replay(src, 4);
+        assert!(partial.len() == 3);
+        assert!(partial[2].toString().toStr() == "(12)");
+        let whole = dumped.replay(src, dumped.steps().len());
+        match (res, &whole[0]) {
+            (PROk(e), Exp::List(top)) => assert!(top.len() == 1 && top[0] == e),
+            _ => panic!("replay mismatch")
+        }
+
+        let (res, trace) = Exp::fromSExpTraced(b"(a \"b", &ReaderOptions::new());
+        assert!(match res { PRErr(_) => true, PROk(_) => false });
+        assert!(trace.steps().iter().filter(|s| s.rule == Rule::Error).count() == 1);
+        assert!(trace.steps().last().map(|s| s.rule) == Some(Rule::Error));
+        let (_, trace) = Exp::fromSExpTraced(b"(a (b)", &ReaderOptions::new());
+        assert!(trace.steps().last() == Some(&TraceStep { rule: Rule::Error, offset: 6, consumed: 0 }));
+    }
+
+    #[test]
+    fn testReplayKinds() {
+        let mut opts = ReaderOptions::new();
+        opts.braces = true;
+        opts.keywords = true;
+        opts.datumLabels = true;
+        let src = b"(#(1 :k) {a (b . c)} 'x '(y z) #u8(1 2) #0=(q) [w])";
+        let (res, trace) = Exp::fromSExpTraced(src, &opts);
+        let e = match res { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let whole = trace.replay(src, trace.steps().len());
+        assert!(whole.len() == 1 && matches!(&whole[0], Exp::List(top) if top.len() == 1 && top[0] == e));
+
+        // read back without the options, they are passed in
+        let dumped = match ParseTrace::fromExp(&trace.toExp()) { Some(t) => t, None => panic!("trace did not read back") };
+        assert!(dumped.replayWithOptions(src, dumped.steps().len(), &opts)[0] == whole[0]);
+
+        // the vector still open after `1`
+        let open = trace.replay(src, 3);
+        assert!(open.len() == 3 && matches!(&open[2], Exp::Vector(v) if v.len() == 1));
+    }
+}
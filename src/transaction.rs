@@ -0,0 +1,73 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+use alt_std::*;
+use crate::*;
+
+impl Exp {
+    /// runs `edit` on a copy and keeps the result only when `validate` accepts it,
+    /// otherwise the tree is left untouched and the diagnostics are returned
+    pub fn transaction<E, V>(&mut self, edit: E, validate: V) -> Result<(), Vec<String>>
+        where E: FnOnce(&mut Exp), V: FnOnce(&Exp) -> Result<(), Vec<String>> {
+        let mut tx = self.clone();
+        edit(&mut tx);
+        let phase = Phase::begin(PhaseKind::Validate, 0);
+        let res = validate(&tx);
+        phase.end(res.is_ok());
+        res?;
+        *self = tx;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn portInRange(e: &Exp) -> Result<(), Vec<String>> {
+        let q = match Query::parse("server.port") { PROk(q) => q, PRErr(_) => panic!("bad query") };
+        let mut errs = Vec::new();
+        for (p, v) in e.query(&q).asArray().iter() {
+            match v {
+                Exp::Int(i) if *i > 0 && *i < 65536 => (),
+                _ => errs.pushBack(alt_std::format!("{}: invalid port", p.toString().toStr())),
+            }
+        }
+        if errs.len() == 0 { Ok(()) } else { Err(errs) }
+    }
+
+    #[test]
+    fn testTransaction() {
+        let mut e = match Exp::fromSExp(b"(server (port 80) (host \"a\"))") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let q = match Query::parse("server.port") { PROk(q) => q, PRErr(_) => panic!("bad query") };
+
+        let r = e.transaction(|tx| { tx.updateWhere(&q, |v| *v = Exp::Int(70000)); }, portInRange);
+        match r {
+            Err(errs) => assert!(errs.len() == 1 && errs[0] == "server.port: invalid port"),
+            Ok(_) => panic!("expected a rollback")
+        }
+        assert!(e.toString() == "(server (port 80) (host \"a\"))");
+
+        assert!(e.transaction(|tx| { tx.updateWhere(&q, |v| *v = Exp::Int(8080)); }, portInRange).is_ok());
+        assert!(e.toString() == "(server (port 8080) (host \"a\"))");
+    }
+}
@@ -0,0 +1,203 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// transcoding: non UTF-8 sources are converted before lexing, keeping for every
+// produced byte the offset of the source byte it came from so that errors can
+// be reported against the original input
+//
+use alt_std::*;
+use crate::*;
+
+pub struct Transcoded {
+    utf8    : Vec<u8>,
+    // source offset of every utf8 byte, plus the end of the source
+    map     : Vec<usize>,
+}
+
+impl Transcoded {
+    fn new() -> Self { Self { utf8: Vec::new(), map: Vec::new() } }
+
+    fn pushChar(&mut self, c: char, at: usize) {
+        let mut buff = [0u8; 4];
+        for b in c.encode_utf8(&mut buff).as_bytes().iter() {
+            self.utf8.pushBack(*b);
+            self.map.pushBack(at);
+        }
+    }
+
+    fn finish(mut self, end: usize) -> Self {
+        self.map.pushBack(end);
+        self
+    }
+
+    pub fn asUtf8(&self) -> &[u8] { self.utf8.asArray() }
+
+    /// offset in the original source of a utf8 offset
+    pub fn sourceOffset(&self, offset: usize) -> usize {
+        let m = self.map.asArray();
+        m[usize::min(offset, m.len() - 1)]
+    }
+
+    /// parses the transcoded text, error and warning offsets refer to the source
+    pub fn parse(&self, opts: &ReaderOptions, warnings: &mut Vec<ParseError>) -> ParseResult<Exp> {
+        let mut w = Vec::new();
+        let res = Exp::fromSExpWithWarnings(self.utf8.asArray(), opts, &mut w);
+        for e in w.asArray().iter() {
+            warnings.pushBack(ParseError { message: e.message.clone(), offset: self.sourceOffset(e.offset), kind: e.kind });
+        }
+        match res {
+            PROk(e) => PROk(e),
+            PRErr(err) => PRErr(ParseError { offset: self.sourceOffset(err.offset), message: err.message, kind: err.kind })
+        }
+    }
+}
+
+impl Transcoded {
+    /// UTF-16 with a byte order mark, offsets are counted in code units (the
+    /// mark being unit 0), None without a mark. Unpaired surrogates and a
+    /// dangling odd byte read as U+FFFD
+    pub fn fromUtf16(src: &[u8]) -> Option<Self> {
+        let le = match src {
+            [0xFF, 0xFE, ..] => true,
+            [0xFE, 0xFF, ..] => false,
+            _ => return None
+        };
+        let units = (src.len() - 2) / 2;
+        let unit = |i: usize| {
+            let (a, b) = (src[2 + i * 2], src[3 + i * 2]);
+            if le { u16::from_le_bytes([a, b]) } else { u16::from_be_bytes([a, b]) }
+        };
+
+        let mut t = Self::new();
+        let mut i = 0;
+        for c in char::decode_utf16((0..units).map(unit)) {
+            let c = c.unwrap_or(char::REPLACEMENT_CHARACTER);
+            t.pushChar(c, i + 1);
+            i += c.len_utf16();
+        }
+        if src.len() % 2 == 1 {
+            t.pushChar(char::REPLACEMENT_CHARACTER, units + 1);
+        }
+        Some(t.finish(units + 1 + src.len() % 2))
+    }
+}
+
+impl Exp {
+    /// parses UTF-16LE/BE input announced by its byte order mark, error offsets are in code units
+    pub fn fromSExpUtf16(src: &[u8], opts: &ReaderOptions) -> ParseResult<Exp> {
+        let mut warnings = Vec::new();
+        match Transcoded::fromUtf16(src) {
+            Some(t) => t.parse(opts, &mut warnings),
+            None => PRErr(ParseError { message: String::from("missing UTF-16 byte order mark"), offset: 0, kind: ErrorKind::Syntax })
+        }
+    }
+}
+
+#[cfg(feature = "legacy-encodings")]
+#[derive(Clone, Copy, PartialEq)]
+pub enum Legacy {
+    // ISO-8859-1, every byte is the code point
+    Latin1,
+    // Latin-1 with printable characters in 0x80..0x9F
+    Windows1252,
+}
+
+// 0x80..0x9F, the 5 unassigned bytes map to the C1 control like Latin-1
+#[cfg(feature = "legacy-encodings")]
+const CP1252 : [u16; 32] = [
+    0x20AC, 0x0081, 0x201A, 0x0192, 0x201E, 0x2026, 0x2020, 0x2021, 0x02C6, 0x2030, 0x0160, 0x2039, 0x0152, 0x008D, 0x017D, 0x008F,
+    0x0090, 0x2018, 0x2019, 0x201C, 0x201D, 0x2022, 0x2013, 0x2014, 0x02DC, 0x2122, 0x0161, 0x203A, 0x0153, 0x009D, 0x017E, 0x0178,
+];
+
+#[cfg(feature = "legacy-encodings")]
+impl Transcoded {
+    pub fn fromLegacy(src: &[u8], enc: Legacy) -> Self {
+        let mut t = Self::new();
+        for (i, b) in src.iter().enumerate() {
+            let c = match (enc, *b) {
+                (Legacy::Windows1252, b) if (0x80..0xA0).contains(&b) => CP1252[(b - 0x80) as usize] as u32,
+                (_, b) => b as u32,
+            };
+            t.pushChar(char::from_u32(c).unwrap_or('\u{FFFD}'), i);
+        }
+        t.finish(src.len())
+    }
+}
+
+#[cfg(feature = "legacy-encodings")]
+impl Exp {
+    /// parses a Latin-1/Windows-1252 source, error offsets refer to its bytes
+    pub fn fromSExpLegacy(src: &[u8], enc: Legacy, opts: &ReaderOptions) -> ParseResult<Exp> {
+        let mut warnings = Vec::new();
+        Transcoded::fromLegacy(src, enc).parse(opts, &mut warnings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utf16(s: &str, le: bool) -> std::vec::Vec<u8> {
+        let mut out = std::vec::Vec::new();
+        for u in core::iter::once(0xFEFF).chain(s.encode_utf16()) {
+            out.extend_from_slice(&if le { u.to_le_bytes() } else { u.to_be_bytes() });
+        }
+        out
+    }
+
+    #[test]
+    fn testUtf16() {
+        for le in [true, false].iter() {
+            match Exp::fromSExpUtf16(&utf16("(a \"\u{1F600}λ\" 12)", *le), &ReaderOptions::new()) {
+                PROk(e) => assert!(e.toString().toStr() == "(a \"\u{1F600}λ\" 12)"),
+                PRErr(err) => panic!("{}", err.message.toStr())
+            }
+        }
+        // the surrogate pair takes two units, the backslash is unit 8
+        match Exp::fromSExpUtf16(&utf16("(a \"\u{1F600}λ\\q\")", true), &ReaderOptions::new()) {
+            PRErr(err) => assert!(err.offset() == 8),
+            PROk(_) => panic!("bad escape accepted")
+        }
+        match Exp::fromSExpUtf16(&utf16("(a", false), &ReaderOptions::new()) {
+            PRErr(err) => assert!(err.offset() == 3),
+            PROk(_) => panic!("unterminated list accepted")
+        }
+        assert!(match Exp::fromSExpUtf16(b"(a)", &ReaderOptions::new()) { PRErr(err) => err.offset() == 0, _ => false });
+    }
+
+    #[cfg(feature = "legacy-encodings")]
+    #[test]
+    fn testLegacy() {
+        let src = b"(cafe \"\x93caf\xE9\x94\")";
+        match Exp::fromSExpLegacy(src, Legacy::Windows1252, &ReaderOptions::new()) {
+            PROk(e) => assert!(e.toString().toStr() == "(cafe \"\u{201C}café\u{201D}\")"),
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        match Exp::fromSExpLegacy(src, Legacy::Latin1, &ReaderOptions::new()) {
+            PROk(Exp::List(l)) => assert!(match &l[1] { Exp::String(s) => s.asArray() == "\u{93}café\u{94}".as_bytes(), _ => false }),
+            _ => panic!("latin-1 parse failed")
+        }
+        // the bad escape is at byte 7 of the source, byte 10 of the utf8
+        match Exp::fromSExpLegacy(b"(a \"\xE9\xE9\xE9\\q\")", Legacy::Latin1, &ReaderOptions::new()) {
+            PRErr(err) => assert!(err.offset() == 7),
+            PROk(_) => panic!("bad escape accepted")
+        }
+    }
+}
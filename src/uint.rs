@@ -0,0 +1,66 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// unsigned integers: literals from i64::MAX + 1 to u64::MAX (hashes, file sizes)
+// read exactly into `Exp::UInt`, smaller ones stay an Int so that a value has a
+// single representation
+//
+use core::convert::TryFrom;
+use crate::*;
+
+impl Exp {
+    /// an Int when `u` fits an i64, a UInt otherwise
+    pub fn uint(u: u64) -> Exp {
+        match i64::try_from(u) {
+            Ok(i) => Exp::Int(i),
+            Err(_) => Exp::UInt(u),
+        }
+    }
+
+    // `digits` (no sign) in `radix` when they fit a u64
+    pub(crate) fn parseU64(digits: &[u8], radix: u32) -> Option<u64> {
+        core::str::from_utf8(digits).ok().and_then(|s| u64::from_str_radix(s, radix).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testUInt() {
+        match Exp::fromSExp(b"(9223372036854775808 18446744073709551615 #xffffffffffffffff 0x8000000000000000 +9223372036854775807)") {
+            PROk(e) => {
+                let l = match &e { Exp::List(l) => l, _ => panic!("expected a list") };
+                assert!(l[0] == Exp::UInt(1 << 63) && l[1] == Exp::UInt(u64::MAX) && l[2] == Exp::UInt(u64::MAX));
+                assert!(l[3] == Exp::UInt(1 << 63) && l[4] == Exp::Int(i64::MAX));
+                assert!(e.toString() == "(9223372036854775808 18446744073709551615 18446744073709551615 9223372036854775808 9223372036854775807)");
+            },
+            PRErr(err) => panic!("{}", err.message.toStr())
+        }
+        assert!(Exp::uint(7) == Exp::Int(7) && u64::fromExp(&Exp::Int(7)) == Ok(7) && u64::fromExp(&Exp::Int(-1)).is_err());
+        assert!(u64::MAX.toExp() == Exp::UInt(u64::MAX));
+
+        let mut v = [Exp::Float(1e19), Exp::UInt(u64::MAX), Exp::Int(i64::MAX), Exp::Float(9223372036854775808.0), Exp::UInt(1 << 63)];
+        v.sort_by(|a, b| a.totalCmp(b));
+        assert!(v[0] == Exp::Int(i64::MAX) && v[1] == Exp::UInt(1 << 63) && v[2] == Exp::Float(9223372036854775808.0));
+        assert!(v[3] == Exp::Float(1e19) && v[4] == Exp::UInt(u64::MAX));
+    }
+}
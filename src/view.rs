@@ -0,0 +1,153 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// views: `Exp::view` borrows an expression as an atom or one of the compound
+// shapes. Both enums are `#[non_exhaustive]`, a match outside this crate needs a
+// `_` arm and keeps compiling when a variant is added, where a match on `Exp`
+// itself would break
+//
+use crate::*;
+
+/// borrowed atom
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub enum AtomRef<'a> {
+    Bool(bool),
+    Char(char),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Ratio(i64, i64),
+    #[cfg(feature = "bigint")]
+    BigInt(&'a BigInt),
+    String(&'a str),
+    Symbol(&'a str),
+    Keyword(&'a str),
+    Bytes(&'a [u8]),
+    // a string kept in a file, see `AtomOverflow::Spill`
+    Spilled(&'a std::sync::Arc<SpilledString>),
+}
+
+/// borrowed expression, see `Exp::view`
+#[non_exhaustive]
+#[derive(Clone, Copy)]
+pub enum ExpView<'a> {
+    Atom(AtomRef<'a>),
+    List(&'a [Exp]),
+    Vector(&'a [Exp]),
+    // car and cdr
+    Pair(&'a Exp, &'a Exp),
+    Map(&'a [(Exp, Exp)]),
+}
+
+impl<'a> AtomRef<'a> {
+    /// the text of a string, symbol or keyword (without its colon)
+    pub fn text(&self) -> Option<&'a str> {
+        match self {
+            Self::String(s) | Self::Symbol(s) | Self::Keyword(s) => Some(s),
+            _ => None
+        }
+    }
+
+    /// the value of a number, rounded for the exact ones that have no f64
+    pub fn toF64(&self) -> Option<f64> {
+        match self {
+            Self::Int(i) => Some(*i as f64),
+            Self::UInt(u) => Some(*u as f64),
+            Self::Float(f) => Some(*f),
+            Self::Ratio(n, d) => Some(*n as f64 / *d as f64),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => Some(b.toF64()),
+            _ => None
+        }
+    }
+}
+
+impl<'a> ExpView<'a> {
+    pub fn atom(&self) -> Option<AtomRef<'a>> {
+        match self {
+            Self::Atom(a) => Some(*a),
+            _ => None
+        }
+    }
+
+    /// the elements of a list or a vector
+    pub fn items(&self) -> Option<&'a [Exp]> {
+        match self {
+            Self::List(l) | Self::Vector(l) => Some(l),
+            _ => None
+        }
+    }
+}
+
+impl Exp {
+    pub fn view(&self) -> ExpView<'_> {
+        match self {
+            Self::Bool(b) => ExpView::Atom(AtomRef::Bool(*b)),
+            Self::Char(c) => ExpView::Atom(AtomRef::Char(*c)),
+            Self::Int(i) => ExpView::Atom(AtomRef::Int(*i)),
+            Self::UInt(u) => ExpView::Atom(AtomRef::UInt(*u)),
+            Self::Float(f) => ExpView::Atom(AtomRef::Float(*f)),
+            Self::Ratio(n, d) => ExpView::Atom(AtomRef::Ratio(*n, *d)),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => ExpView::Atom(AtomRef::BigInt(b)),
+            Self::String(s) => ExpView::Atom(AtomRef::String(s.toStr())),
+            Self::Symbol(s) => ExpView::Atom(AtomRef::Symbol(s.toStr())),
+            Self::Keyword(k) => ExpView::Atom(AtomRef::Keyword(k.toStr())),
+            Self::Bytes(b) => ExpView::Atom(AtomRef::Bytes(b.asArray())),
+            Self::Spilled(s) => ExpView::Atom(AtomRef::Spilled(s)),
+            Self::List(l) => ExpView::List(l.asArray()),
+            Self::Vector(v) => ExpView::Vector(v.asArray()),
+            Self::Pair(a, d) => ExpView::Pair(a, d),
+            Self::Map(m) => ExpView::Map(m.asArray()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // symbols of a tree, the way a caller outside the crate would walk it
+    fn symbols<'a>(e: &'a Exp, out: &mut std::vec::Vec<&'a str>) {
+        match e.view() {
+            ExpView::Atom(AtomRef::Symbol(s)) => out.push(s),
+            ExpView::Pair(a, d) => { symbols(a, out); symbols(d, out) },
+            ExpView::Map(m) => for (k, v) in m.iter() { symbols(k, out); symbols(v, out) },
+            v => for e in v.items().unwrap_or(&[]).iter() { symbols(e, out) },
+        }
+    }
+
+    #[test]
+    fn testView() {
+        let e = match Exp::fromSExp(b"(define (f x) #(y \"s\") {:k z} (a . b) 1/2)") {
+            PROk(e) => e,
+            PRErr(err) => panic!("{}", err.message.toStr())
+        };
+        let mut out = std::vec::Vec::new();
+        symbols(&e, &mut out);
+        assert!(out == ["define", "f", "x", "y", "z", "a", "b"]);
+
+        let l = e.view().items().unwrap();
+        assert!(matches!(l[0].view().atom(), Some(AtomRef::Symbol("define"))));
+        assert!(l[2].view().items().unwrap()[1].view().atom().and_then(|a| a.text()) == Some("s"));
+        assert!(l[5].view().atom().and_then(|a| a.toF64()) == Some(0.5) && l[1].view().atom().is_none());
+    }
+}
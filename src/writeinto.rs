@@ -0,0 +1,189 @@
+// Copyright 2020(c) Wael El Oraiby
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in
+// all copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN
+// THE SOFTWARE.
+//
+// allocation free output: `writeInto` renders like `toString` straight into a
+// caller supplied buffer, for senders packing small expressions into fixed frames
+//
+use core::fmt::Write;
+use crate::*;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BufferTooSmall;
+
+struct SliceWriter<'a> {
+    buf : &'a mut [u8],
+    len : usize,
+}
+
+impl<'a> SliceWriter<'a> {
+    fn put(&mut self, s: &[u8]) -> Result<(), BufferTooSmall> {
+        if self.len + s.len() > self.buf.len() { return Err(BufferTooSmall) }
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s);
+        self.len += s.len();
+        Ok(())
+    }
+}
+
+impl<'a> Write for SliceWriter<'a> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        self.put(s.as_bytes()).map_err(|_| core::fmt::Error)
+    }
+}
+
+impl Exp {
+    fn writeSlice(&self, w: &mut SliceWriter) -> Result<(), BufferTooSmall> {
+        match self {
+            Self::Bool(b) => w.put(if *b { b"#t" } else { b"#f" }),
+            Self::Char(c) => {
+                w.put(b"#\\")?;
+                match Self::CHAR_NAMES.iter().find(|(_, n)| n == c) {
+                    Some((name, _)) => w.put(name.as_bytes()),
+                    None if Self::isHexChar(*c) => write!(w, "x{:x}", *c as u32).map_err(|_| BufferTooSmall),
+                    None => w.put(c.encode_utf8(&mut [0u8; 4]).as_bytes()),
+                }
+            },
+            Self::Int(i) => write!(w, "{}", i).map_err(|_| BufferTooSmall),
+            Self::UInt(u) => write!(w, "{}", u).map_err(|_| BufferTooSmall),
+            Self::Ratio(n, d) => write!(w, "{}/{}", n, d).map_err(|_| BufferTooSmall),
+            #[cfg(feature = "bigint")]
+            Self::BigInt(b) => write!(w, "{}", b).map_err(|_| BufferTooSmall),
+            Self::Float(f) if f.is_nan() => w.put(b"+nan.0"),
+            Self::Float(f) if f.is_infinite() => w.put(if *f > 0.0 { b"+inf.0" } else { b"-inf.0" }),
+            Self::Float(f) => {
+                let start = w.len;
+                write!(w, "{}", f).map_err(|_| BufferTooSmall)?;
+                if !w.buf[start..w.len].iter().any(|c| *c == b'.' || *c == b'e') {
+                    w.put(b".0")?;
+                }
+                Ok(())
+            },
+            Self::String(_) | Self::Spilled(_) => {
+                let spilled;
+                let s = match self {
+                    Self::Spilled(s) => { spilled = Self::spilledBytes(s); &spilled[..] },
+                    Self::String(s) => s.asArray(),
+                    _ => &[],
+                };
+                w.put(b"\"")?;
+                for c in s.iter() {
+                    match c {
+                        b'"' => w.put(b"\\\"")?,
+                        b'\\' => w.put(b"\\\\")?,
+                        b'\n' => w.put(b"\\n")?,
+                        b'\t' => w.put(b"\\t")?,
+                        b'\r' => w.put(b"\\r")?,
+                        c => w.put(&[*c])?,
+                    }
+                }
+                w.put(b"\"")
+            },
+            Self::Symbol(s) if !Self::readsAsSymbol(s.asArray(), Dialect::Scheme) => {
+                w.put(b"|")?;
+                for c in s.asArray().iter() {
+                    if *c == b'|' || *c == b'\\' { w.put(b"\\")? }
+                    w.put(&[*c])?;
+                }
+                w.put(b"|")
+            },
+            Self::Symbol(s) => w.put(s.asArray()),
+            Self::Keyword(k) if !Self::readsAsKeyword(k.asArray()) => {
+                w.put(b":|")?;
+                for c in k.asArray().iter() {
+                    if *c == b'|' || *c == b'\\' { w.put(b"\\")? }
+                    w.put(&[*c])?;
+                }
+                w.put(b"|")
+            },
+            Self::Keyword(k) => { w.put(b":")?; w.put(k.asArray()) },
+            Self::List(l) => {
+                w.put(b"(")?;
+                for i in 0..l.len() {
+                    if i != 0 { w.put(b" ")? }
+                    l[i].writeSlice(w)?;
+                }
+                w.put(b")")
+            },
+            Self::Bytes(b) => {
+                w.put(b"#u8(")?;
+                for i in 0..b.len() {
+                    if i != 0 { w.put(b" ")? }
+                    write!(w, "{}", b[i]).map_err(|_| BufferTooSmall)?;
+                }
+                w.put(b")")
+            },
+            Self::Vector(v) => {
+                w.put(b"#(")?;
+                for i in 0..v.len() {
+                    if i != 0 { w.put(b" ")? }
+                    v[i].writeSlice(w)?;
+                }
+                w.put(b")")
+            },
+            Self::Pair(..) => {
+                let (items, tail) = self.dottedItems();
+                w.put(b"(")?;
+                for i in 0..items.len() {
+                    if i != 0 { w.put(b" ")? }
+                    items[i].writeSlice(w)?;
+                }
+                if let Some(t) = tail {
+                    w.put(b" . ")?;
+                    t.writeSlice(w)?;
+                }
+                w.put(b")")
+            },
+            Self::Map(m) => {
+                w.put(b"{")?;
+                for i in 0..m.len() {
+                    if i != 0 { w.put(b" ")? }
+                    m[i].0.writeSlice(w)?;
+                    w.put(b" ")?;
+                    m[i].1.writeSlice(w)?;
+                }
+                w.put(b"}")
+            }
+        }
+    }
+
+    /// writes the `toString` text into `buf` without allocating, returns its length
+    pub fn writeInto(&self, buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+        let mut w = SliceWriter { buf, len: 0 };
+        self.writeSlice(&mut w)?;
+        Ok(w.len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn testWriteInto() {
+        let src = "(tx 12 -1.5 2.0 #t #\\space #\\λ \"a\\\"b\\n\" (+inf.0))";
+        let e = match Exp::fromSExp(src.as_bytes()) { PROk(e) => e, PRErr(err) => panic!("{}", err.message.toStr()) };
+        let text = e.toString();
+        let n = text.asArray().len();
+        let mut frame = [0u8; 64];
+        assert!(e.writeInto(&mut frame) == Ok(n));
+        assert!(&frame[..n] == text.asArray());
+        assert!(e.writeInto(&mut frame[..n - 1]) == Err(BufferTooSmall));
+        assert!(Exp::Float(1e300).writeInto(&mut frame[..4]) == Err(BufferTooSmall));
+    }
+}